@@ -1,19 +1,4258 @@
-use crate::{Args, Config};
-use anyhow::Result;
+use crate::{
+    commands::{self, typed, CharArgument, Operator},
+    debugger, diff, grep, language_server, metadata, motion, search, Args, Assoc, Buffer,
+    BufferLine, Clipboard, ColorDepth, Config, Context, DebugRequest, DebugSession, DiffMark,
+    Direction, Environment, GrepHandle, GrepMatch, GutterColumnKind, JumpList, JumpPoint, Key,
+    KeymapMode, KeymapResult, Keymaps, LanguageConfig, LineNumber, Loader, LocalSettings, Mode,
+    Prompt, PromptEvent, PromptKind, Registers, Search, Selection, Session, SessionBuffer,
+    SessionView, SettingScope, StatusLineElement, Substitution, Symbol, Syntax, TextObject, Theme,
+    ThemeLoader, Transaction, View, VisualKind, MAX_GREP_MATCHES,
+};
+use anyhow::{anyhow, bail, Context as _, Result};
+use crossterm::{
+    cursor::Show,
+    event::{
+        DisableMouseCapture, EnableMouseCapture, Event, EventStream, KeyCode, KeyEvent,
+        MouseButton, MouseEvent, MouseEventKind,
+    },
+    execute,
+    terminal::{self, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use futures_util::StreamExt;
+use ropey::Rope;
+use std::{
+    collections::{hash_map::Entry, BTreeMap, HashMap},
+    future::Future,
+    io::{self, Stdout},
+    ops::{Range, RangeInclusive},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+use tissue_dap::{
+    self as dap,
+    types::{StackFrame, Variable},
+};
+use tissue_lsp::{lsp, Client, Notification};
+use tissue_tui::{
+    BufferLine as TabLine, CrosstermBackend, Gutter, GutterColumn, Layout, LineNumbers, Picker,
+    PickerEvent, PickerStyle, Preview, Rect, Side, Span, Split, StatusLine, Style, Surface, ViewId,
+};
+use tokio::{
+    sync::mpsc::{self, UnboundedReceiver, UnboundedSender},
+    time::{self, Instant},
+};
+use unicode_width::UnicodeWidthStr;
+
+type Terminal = tissue_tui::Terminal<CrosstermBackend<Stdout>>;
+
+// What picking an item of the picker does.
+#[derive(Debug)]
+enum PickerItem {
+    // Opens the file at the line.
+    Location { path: PathBuf, line: usize },
+    // Opens the file.
+    File(PathBuf),
+    // Focuses the open buffer.
+    Buffer(usize),
+    // Switches to the theme.
+    Theme(String),
+    // Runs the command.
+    Command(&'static commands::Command),
+}
+
+// Files larger than this are not previewed.
+const MAX_PREVIEW_SIZE: u64 = 1024 * 1024;
+
+// Completions of the word before the end of the command line, cycled through with tab.
+#[derive(Debug)]
+struct Completion {
+    // Where the completed word starts in the line.
+    start: usize,
+    candidates: Vec<String>,
+    // The candidate in the line.
+    index: usize,
+}
+
+// A substitution asking before each replacement.
+#[derive(Debug)]
+struct Confirm {
+    // The matches left to confirm with their replacements, the current one last.
+    pending: Vec<(Range<usize>, String)>,
+    accepted: Vec<(Range<usize>, String)>,
+}
+
+/// Exit code when the editor quits normally.
+pub const EXIT_OK: i32 = 0;
+
+/// Exit code when the editor is forced to quit while buffers have unsaved changes.
+pub const EXIT_UNSAVED: i32 = 1;
+
+// How long a status message stays visible.
+const STATUS_TIMEOUT: Duration = Duration::from_secs(5);
+
+// How long the lines that differ from the diff base may lag behind edits of a buffer.
+const DIFF_DELAY: Duration = Duration::from_millis(200);
+
+// Clicks on the same cell closer together than this make double and triple clicks.
+const MULTI_CLICK_TIMEOUT: Duration = Duration::from_millis(500);
+
+// How many lines the view scrolls by per turn of the mouse wheel.
+const SCROLL_LINES: usize = 3;
+
+// A press of the left mouse button.
+#[derive(Debug, Clone, Copy)]
+struct Click {
+    at: Instant,
+    x: u16,
+    y: u16,
+    // 1 for a single click, 2 for a double click and 3 for a triple click.
+    count: u8,
+}
+
+/// Messages sent to the application by background tasks.
+#[derive(Debug)]
+pub enum Message {
+    /// Shows a message in the status line.
+    Status(String),
+    /// A language server finished starting.
+    LanguageServerStarted {
+        /// The language the server is for.
+        language: String,
+        /// The initialized client, or why the server could not be started.
+        result: Result<Arc<Client>>,
+    },
+    /// A language server sent a notification.
+    LanguageServer(Notification),
+    /// Jumps to the first of the locations.
+    Locations(Vec<lsp::Location>),
+    /// Completes the word before the cursor.
+    Completion(Vec<lsp::CompletionItem>),
+    /// Applies edits to one or more documents.
+    WorkspaceEdit(lsp::WorkspaceEdit),
+    /// A debug adapter finished starting, or the debug session could not be started.
+    DebuggerStarted(Result<Arc<dap::Client>>),
+    /// The debug adapter sent an event.
+    Debugger(dap::Event),
+    /// Jumps to the innermost of the stack frames of a stopped thread.
+    StackFrames {
+        /// The thread the stack belongs to.
+        thread: i64,
+        /// The stack, innermost frame first.
+        frames: Vec<StackFrame>,
+    },
+    /// Shows the variables in the status line.
+    Variables(Vec<Variable>),
+    /// Lines of a file matching a workspace search.
+    GrepMatches {
+        /// The id of the search.
+        id: usize,
+        /// The matching lines.
+        matches: Vec<GrepMatch>,
+    },
+    /// Files of the workspace, listed for the file picker.
+    Files {
+        /// The id of the listing.
+        id: usize,
+        /// The paths of the files.
+        paths: Vec<PathBuf>,
+    },
+    /// Lists the symbols in a picker.
+    Symbols(Vec<Symbol>),
+    /// A workspace search searched every file, or stopped at [`MAX_GREP_MATCHES`].
+    GrepFinished {
+        /// The id of the search.
+        id: usize,
+        /// Whether the search stopped before searching every file.
+        truncated: bool,
+    },
+}
 
 #[derive(Debug)]
 pub struct App {
     pub args: Args,
     pub config: Config,
+    /// Open buffers, one per file passed on the command line.
+    pub buffers: Vec<Buffer>,
+    /// Index of the buffer being edited.
+    pub focus: usize,
+    /// The current editing mode.
+    pub mode: Mode,
+    /// The shape of the selection in Visual mode.
+    pub visual: VisualKind,
+    pub(crate) count: Option<usize>,
+    pub(crate) operator: Option<(Operator, Option<usize>)>,
+    pub(crate) pending_char: Option<(CharArgument, Option<usize>)>,
+    pub(crate) last_find: Option<(char, Direction, bool)>,
+    /// Yanked and deleted text.
+    pub registers: Registers,
+    // The environment the clipboard provider is detected in.
+    environment: Environment,
+    // The register picked for the next command with `"`.
+    pub(crate) register: Option<char>,
+    /// Diagnostics published by language servers, by document.
+    pub diagnostics: HashMap<lsp::Url, Vec<lsp::Diagnostic>>,
+    // Work in progress reported by language servers, as its title and latest message, the most
+    // recently begun last.
+    progress: Vec<(lsp::ProgressToken, String, String)>,
+    // Language servers by language. `None` while a server starts, or if it failed to.
+    language_servers: HashMap<String, Option<Arc<Client>>>,
+    // The documents opened in language servers, with the language whose server has them.
+    lsp_documents: HashMap<lsp::Url, String>,
+    /// The debug session, once its adapter has started.
+    pub debugger: Option<DebugSession>,
+    /// Styles for the scopes of syntax highlighting.
+    pub theme: Theme,
+    syntax_loader: Loader,
+    // The line typed in the status line.
+    pub(crate) prompt: Option<Prompt>,
+    // The completions of the command line being typed.
+    completion: Option<Completion>,
+    // The pattern typed in the prompt, whose matches are highlighted.
+    highlight: Option<Search>,
+    // The replacements of a substitution waiting for confirmation.
+    confirm: Option<Confirm>,
+    // The direction of the last search, which `n` repeats.
+    pub(crate) search_direction: Direction,
+    picker: Option<Picker<PickerItem>>,
+    // The running workspace search or file listing, whose results go to the picker.
+    grep: Option<GrepHandle>,
+    grep_count: usize,
+    // The file previewed next to the picker, unless it is open in a buffer.
+    preview: Option<(PathBuf, Rope)>,
+    // The arrangement of the views on screen, and what each of them shows.
+    layout: Layout,
+    views: HashMap<ViewId, View>,
+    // The view with focus, which shows the buffer being edited.
+    focused_view: ViewId,
+    // The area the editor was last drawn in, which the views are laid out in and mouse events
+    // are mapped through.
+    area: Rect,
+    // Whether the terminal reports mouse events, which follows `EditorConfig::mouse`.
+    mouse_capture: bool,
+    last_click: Option<Click>,
+    keymaps: Keymaps,
+    status: Option<(String, Instant)>,
+    // When the buffers whose text changed are diffed again.
+    diff_deadline: Option<Instant>,
+    exit_code: Option<i32>,
+    sender: UnboundedSender<Message>,
+    receiver: UnboundedReceiver<Message>,
 }
 
 impl App {
-    pub fn new(args: Args, config: Config) -> Result<Self> {
-        Ok(Self { args, config })
+    pub fn new(args: Args, config: Config, environment: Environment) -> Result<Self> {
+        let mut buffers: Vec<_> = args
+            .files
+            .iter()
+            .map(|path| {
+                Buffer::open(path).with_context(|| format!("unable to open {}", path.display()))
+            })
+            .collect::<Result<_>>()?;
+
+        if buffers.is_empty() {
+            buffers.push(Buffer::new());
+        }
+        for buffer in &mut buffers {
+            detect_language(&config, buffer);
+            load_history(buffer);
+            load_diff_base(buffer);
+        }
+
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let (clipboard, _) = Clipboard::detect(&config.editor.clipboard, &environment);
+        log::info!("using the {} clipboard", clipboard.name());
+
+        let layout = Layout::new();
+        let focused_view = layout.views()[0];
+        let mut app = Self {
+            args,
+            config,
+            buffers,
+            focus: 0,
+            mode: Mode::Normal,
+            visual: VisualKind::default(),
+            count: None,
+            operator: None,
+            pending_char: None,
+            last_find: None,
+            registers: Registers::new(clipboard),
+            environment,
+            register: None,
+            diagnostics: HashMap::new(),
+            progress: Vec::new(),
+            language_servers: HashMap::new(),
+            lsp_documents: HashMap::new(),
+            debugger: None,
+            theme: Theme::default(),
+            syntax_loader: Loader::default(),
+            prompt: None,
+            completion: None,
+            highlight: None,
+            confirm: None,
+            search_direction: Direction::Forward,
+            picker: None,
+            grep: None,
+            grep_count: 0,
+            preview: None,
+            views: HashMap::from([(focused_view, View::new(0))]),
+            layout,
+            focused_view,
+            area: Rect::default(),
+            mouse_capture: false,
+            last_click: None,
+            keymaps: Keymaps::default(),
+            status: None,
+            diff_deadline: None,
+            exit_code: None,
+            sender,
+            receiver,
+        };
+
+        for index in 0..app.buffers.len() {
+            app.load_syntax(index);
+        }
+        if let Some(name) = app.config.theme.clone() {
+            app.set_theme(&name);
+        }
+
+        Ok(app)
+    }
+
+    /// Runs the editor until it quits and returns the process exit code.
+    ///
+    /// The terminal is put into raw mode on the alternate screen for the duration of the call and
+    /// restored afterwards, even if the editor panics.
+    pub async fn run(mut self) -> Result<i32> {
+        let registers_file = registers_file();
+        if let Err(err) = self.registers.load(&registers_file) {
+            log::error!("failed to load {}: {err:#}", registers_file.display());
+        }
+        self.restore_startup_session()?;
+
+        let mut terminal = setup_terminal().context("failed to setup terminal")?;
+        let result = self.event_loop(&mut terminal).await;
+        let restored = restore_terminal().context("failed to restore terminal");
+        // The error of the event loop is why the editor stopped, so it is kept, with the failure
+        // to restore the terminal attached.
+        let result = match (result, restored) {
+            (Err(err), Err(restore_err)) => Err(err.context(format!("{restore_err:#}"))),
+            (Ok(_), Err(restore_err)) => Err(restore_err),
+            (result, Ok(())) => result,
+        };
+
+        if let Err(err) = self.registers.save(&registers_file) {
+            log::error!("failed to save {}: {err:#}", registers_file.display());
+        }
+        if let Some(file) = self.workspace_session_file() {
+            if let Err(err) = self.session().save(&file) {
+                log::error!("failed to save {}: {err:#}", file.display());
+            }
+        }
+
+        if result.as_ref().map_or(false, |&code| code == EXIT_UNSAVED) {
+            for path in self.modified_buffers() {
+                eprintln!("Unsaved changes discarded: {path}");
+            }
+        }
+
+        result
+    }
+
+    // Restores the session given with `--session`, or the session of the workspace if it is
+    // restored automatically. Files given on the command line are opened on top of the session.
+    fn restore_startup_session(&mut self) -> Result<()> {
+        let file = match &self.args.session {
+            Some(name) => {
+                let file = Session::file(name)?;
+                if !file.exists() {
+                    bail!("no session named '{name}'");
+                }
+                file
+            }
+            None => match self.workspace_session_file() {
+                Some(file) if file.exists() => file,
+                _ => return Ok(()),
+            },
+        };
+        let session = Session::load(&file)
+            .with_context(|| format!("failed to load the session {}", file.display()))?;
+        self.restore_session(session);
+
+        for path in self.args.files.clone() {
+            self.open(&path)?;
+        }
+        Ok(())
+    }
+
+    // Returns the file of the session of the workspace if it is restored and saved
+    // automatically: with `editor.auto-session`, when started without files or `--session`.
+    fn workspace_session_file(&self) -> Option<PathBuf> {
+        let args = &self.args;
+        if !self.config.editor.auto_session || !args.files.is_empty() || args.session.is_some() {
+            return None;
+        }
+        let dir = std::env::current_dir().ok()?;
+        Session::workspace_file(&dir)
+            .map_err(|err| log::error!("no workspace session for {}: {err:#}", dir.display()))
+            .ok()
+    }
+
+    /// Returns the open buffers, the views and the registers as a session.
+    pub fn session(&self) -> Session {
+        let current_dir = std::env::current_dir().unwrap_or_default();
+        let buffers = self.buffers.iter().map(|buffer| SessionBuffer {
+            path: buffer.path().map(|path| current_dir.join(path)),
+            selection: buffer.selection().clone(),
+        });
+        let views = self.layout.views();
+        let session_views = views.iter().map(|&view| {
+            let buffer = self.view_buffer(view);
+            let selection = match view == self.focused_view {
+                true => Some(self.buffers[buffer].selection()),
+                false => self.buffers[buffer].view_selection(view),
+            };
+            SessionView {
+                buffer,
+                scroll: self.views[&view].scroll,
+                selection: selection.cloned().unwrap_or_default(),
+                jumps: self.views[&view].jumps.points().to_vec(),
+            }
+        });
+
+        Session {
+            focus: views
+                .iter()
+                .position(|&view| view == self.focused_view)
+                .unwrap_or(0),
+            buffers: buffers.collect(),
+            layout: self.layout.tree(),
+            views: session_views.collect(),
+            registers: self.registers.saved().clone(),
+        }
+    }
+
+    /// Replaces the open buffers, the views and the registers with those of `session`.
+    ///
+    /// Scratch buffers come back empty, and so do files that cannot be read any more. Cursors
+    /// past the end of files that got shorter move to the end.
+    pub fn restore_session(&mut self, session: Session) {
+        self.buffers = session
+            .buffers
+            .into_iter()
+            .map(|saved| {
+                let mut buffer = match &saved.path {
+                    Some(path) => Buffer::open(path).unwrap_or_else(|err| {
+                        log::error!("failed to open {}: {err:#}", path.display());
+                        Buffer::new()
+                    }),
+                    None => Buffer::new(),
+                };
+                detect_language(&self.config, &mut buffer);
+                load_history(&mut buffer);
+                load_diff_base(&mut buffer);
+                let selection = clamp_selection(&buffer, saved.selection);
+                buffer.set_selection(selection);
+                buffer
+            })
+            .collect();
+        if self.buffers.is_empty() {
+            self.buffers.push(Buffer::new());
+        }
+
+        self.layout = Layout::from_tree(&session.layout);
+        let ids = self.layout.views();
+        let last = self.buffers.len() - 1;
+        self.views = ids
+            .iter()
+            .zip(
+                session
+                    .views
+                    .iter()
+                    .map(Some)
+                    .chain(std::iter::repeat(None)),
+            )
+            .map(|(&id, saved)| {
+                let view = saved.map_or(View::new(0), |saved| View {
+                    buffer: saved.buffer.min(last),
+                    scroll: saved.scroll,
+                    jumps: JumpList::new(saved.jumps.clone()),
+                });
+                let buffer = &mut self.buffers[view.buffer];
+                let selection = saved.map(|saved| clamp_selection(buffer, saved.selection.clone()));
+                buffer.set_view_selection(id, selection.unwrap_or_default());
+                (id, view)
+            })
+            .collect();
+        self.focused_view = ids.get(session.focus).copied().unwrap_or(ids[0]);
+        self.focus = self.views[&self.focused_view].buffer;
+        let view = self.focused_view;
+        if let Some(selection) = self.buffer_mut().take_view_selection(view) {
+            self.buffer_mut().set_selection(selection);
+        }
+
+        self.registers.restore(session.registers);
+        self.mode = Mode::Normal;
+        for index in 0..self.buffers.len() {
+            self.load_syntax(index);
+        }
+        self.start_language_servers();
+    }
+
+    /// Returns a handle for background tasks to send messages to the application.
+    pub fn sender(&self) -> UnboundedSender<Message> {
+        self.sender.clone()
+    }
+
+    /// Returns the buffer being edited.
+    pub fn buffer(&self) -> &Buffer {
+        &self.buffers[self.focus]
+    }
+
+    /// Returns the buffer being edited mutably.
+    pub fn buffer_mut(&mut self) -> &mut Buffer {
+        &mut self.buffers[self.focus]
+    }
+
+    /// Returns the view with focus.
+    pub fn view(&self) -> &View {
+        &self.views[&self.focused_view]
+    }
+
+    /// Returns the view with focus mutably.
+    pub fn view_mut(&mut self) -> &mut View {
+        self.views
+            .get_mut(&self.focused_view)
+            .expect("the focused view exists")
+    }
+
+    /// Returns the arrangement of the views on screen.
+    pub fn layout(&self) -> &Layout {
+        &self.layout
+    }
+
+    /// Splits the view with focus in two. The new view shows the same buffer with the same
+    /// cursors, and takes focus.
+    pub fn split(&mut self, split: Split) {
+        let Some(view) = self.layout.split(self.focused_view, split) else {
+            return;
+        };
+        let View { scroll, jumps, .. } = self.view().clone();
+        self.views.insert(
+            view,
+            View {
+                buffer: self.focus,
+                scroll,
+                jumps,
+            },
+        );
+        let selection = self.buffer().selection().clone();
+        self.buffer_mut().set_view_selection(view, selection);
+        self.focus_view(view);
+    }
+
+    /// Records the cursors in the buffer being edited as a place to jump back to, in the
+    /// jumplist of the view with focus.
+    pub fn save_jump(&mut self) {
+        let point = JumpPoint {
+            buffer: self.focus,
+            selection: self.buffer().selection().clone(),
+        };
+        self.view_mut().jumps.push(point);
+    }
+
+    /// Moves `count` places back or forward in the jumplist of the view with focus.
+    pub fn jump(&mut self, direction: Direction, count: usize) {
+        let here = JumpPoint {
+            buffer: self.focus,
+            selection: self.buffer().selection().clone(),
+        };
+        let jumps = &mut self.view_mut().jumps;
+        let point = match direction {
+            Direction::Backward => jumps.backward(here, count),
+            Direction::Forward => jumps.forward(count),
+        };
+        let Some(point) = point.cloned() else {
+            return;
+        };
+        self.focus = point.buffer.min(self.buffers.len() - 1);
+        let selection = clamp_selection(self.buffer(), point.selection);
+        self.buffer_mut().set_selection(selection);
+        if self.mode == Mode::Visual {
+            self.mode = Mode::Normal;
+        }
+    }
+
+    /// Moves focus to `view`, whose buffer becomes the one being edited.
+    pub fn focus_view(&mut self, view: ViewId) {
+        if view == self.focused_view || !self.views.contains_key(&view) {
+            return;
+        }
+        let (previous, focus) = (self.focused_view, self.focus);
+        let selection = self.buffer().selection().clone();
+        self.buffer_mut().set_view_selection(previous, selection);
+        self.view_mut().buffer = focus;
+
+        self.focused_view = view;
+        self.focus = self.views[&view].buffer;
+        if let Some(selection) = self.buffer_mut().take_view_selection(view) {
+            self.buffer_mut().set_selection(selection);
+        }
+        if self.mode == Mode::Visual {
+            self.mode = Mode::Normal;
+        }
+    }
+
+    /// Moves focus to the view on `side` of the one with focus, if there is one.
+    pub fn focus_neighbor(&mut self, side: Side) {
+        if let Some(view) =
+            self.layout
+                .neighbor(self.views_area(self.area), self.focused_view, side)
+        {
+            self.focus_view(view);
+        }
+    }
+
+    /// Swaps the view with focus with the one on its `side`, if there is one.
+    pub fn swap_view(&mut self, side: Side) {
+        if let Some(view) =
+            self.layout
+                .neighbor(self.views_area(self.area), self.focused_view, side)
+        {
+            self.layout.swap(self.focused_view, view);
+        }
+    }
+
+    /// Grows the view with focus by `delta` rows or columns of the `split` it is part of, or
+    /// shrinks it if `delta` is negative.
+    pub fn resize_view(&mut self, split: Split, delta: i32) {
+        let area = self.views_area(self.area);
+        self.layout.resize(area, self.focused_view, split, delta);
+    }
+
+    /// Gives every view the same share of the split it is part of.
+    pub fn equalize_views(&mut self) {
+        self.layout.equalize();
+    }
+
+    /// Closes the view with focus and moves focus to the view that takes its place. Returns
+    /// `false` if it is the last view, which stays open.
+    pub fn close_view(&mut self) -> bool {
+        let closed = self.focused_view;
+        let Some(next) = self.layout.close(closed) else {
+            return false;
+        };
+        self.focus_view(next);
+        self.remove_view(closed);
+        true
+    }
+
+    /// Closes every view but the one with focus.
+    pub fn close_other_views(&mut self) {
+        self.layout.only(self.focused_view);
+        let closed: Vec<_> = self
+            .views
+            .keys()
+            .copied()
+            .filter(|&view| view != self.focused_view)
+            .collect();
+        for view in closed {
+            self.remove_view(view);
+        }
+    }
+
+    // Forgets `view`, which has no focus, and its selection.
+    fn remove_view(&mut self, view: ViewId) {
+        if let Some(removed) = self.views.remove(&view) {
+            self.buffers[removed.buffer].take_view_selection(view);
+        }
+    }
+
+    // Returns the index of the buffer that `view` shows.
+    fn view_buffer(&self, view: ViewId) -> usize {
+        match view == self.focused_view {
+            true => self.focus,
+            false => self.views[&view].buffer,
+        }
+    }
+
+    // Returns the area of `view` in the area the editor was last drawn in, its status line
+    // included.
+    fn view_area(&self, view: ViewId) -> Rect {
+        let mut areas = self.layout.areas(self.views_area(self.area)).into_iter();
+        let area = areas.find(|&(id, _)| id == view);
+        area.map_or(Rect::default(), |(_, area)| area)
+    }
+
+    /// Shows `message` in the status line for a while.
+    pub fn set_status(&mut self, message: impl Into<String>) {
+        self.status = Some((message.into(), Instant::now() + STATUS_TIMEOUT));
+    }
+
+    /// Returns the message shown in the status line.
+    pub fn status(&self) -> Option<&str> {
+        self.status.as_ref().map(|(message, _)| message.as_str())
+    }
+
+    /// Returns the exit code once the editor has been asked to quit.
+    pub fn exit_code(&self) -> Option<i32> {
+        self.exit_code
+    }
+
+    /// Closes the view with focus, or quits the editor if it is the last view.
+    ///
+    /// Unless `force` is set, quitting is refused while any buffer has unsaved changes.
+    pub fn quit(&mut self, force: bool) {
+        if self.close_view() {
+            return;
+        }
+        let modified = self.modified_buffers();
+
+        match (modified.is_empty(), force) {
+            (true, _) => self.exit_code = Some(EXIT_OK),
+            (false, true) => self.exit_code = Some(EXIT_UNSAVED),
+            (false, false) => self.set_status(format!(
+                "{} unsaved buffer(s) remaining: {}",
+                modified.len(),
+                modified.join(", ")
+            )),
+        }
+    }
+
+    /// Saves the buffer being edited. Returns `false` and reports the error in the status line if
+    /// saving failed.
+    pub fn write(&mut self) -> bool {
+        match self.buffer_mut().save() {
+            Ok(()) => {
+                if let Err(err) = self.buffer().save_history(&history_dir()) {
+                    log::error!("failed to save the undo history: {err:#}");
+                }
+                let uri = self.buffer().path().and_then(language_server::uri);
+                if let (Some(client), Some(uri)) = (self.language_server(), uri) {
+                    client.text_document_did_save(uri).ok();
+                }
+                true
+            }
+            Err(err) => {
+                self.set_status(format!("Failed to save: {err}"));
+                false
+            }
+        }
+    }
+
+    /// Focuses the buffer of the file at `path`, opening it if needed, and returns its index.
+    pub fn open(&mut self, path: &Path) -> Result<usize> {
+        let index = match self.find_buffer(path) {
+            Some(index) => index,
+            None => {
+                let mut buffer = Buffer::open(path)
+                    .with_context(|| format!("unable to open {}", path.display()))?;
+                detect_language(&self.config, &mut buffer);
+                load_history(&mut buffer);
+                load_diff_base(&mut buffer);
+                self.buffers.push(buffer);
+                self.load_syntax(self.buffers.len() - 1);
+                self.start_language_servers();
+                self.buffers.len() - 1
+            }
+        };
+
+        self.focus = index;
+        Ok(index)
+    }
+
+    // Opens the file at `path` like `open`, first recording the cursors as a place to jump back
+    // to.
+    fn save_jump_and_open(&mut self, path: &Path) -> Result<usize> {
+        self.save_jump();
+        self.open(path)
+    }
+
+    /// Opens a prompt in the status line with `line` already typed.
+    pub fn open_prompt(&mut self, kind: PromptKind, line: impl Into<String>) {
+        self.prompt = Some(Prompt::new(kind, line));
+        self.completion = None;
+        self.update_highlight();
+    }
+
+    // Edits the prompt with `event`, and runs it once submitted.
+    fn handle_prompt_key(&mut self, event: KeyEvent) {
+        let Some(prompt) = self.prompt.as_mut() else {
+            return;
+        };
+        let history = match prompt.kind() {
+            PromptKind::Search(_) | PromptKind::Grep => self.registers.search_history(),
+            PromptKind::Command { .. } => self.registers.command_history(),
+        };
+        let command = matches!(prompt.kind(), PromptKind::Command { .. });
+        if command && matches!(event.code, KeyCode::Tab | KeyCode::BackTab) {
+            return self.complete_command(event.code == KeyCode::Tab);
+        }
+        self.completion = None;
+
+        match prompt.handle_key(event, history) {
+            PromptEvent::Update => self.update_highlight(),
+            PromptEvent::Submit => {
+                let prompt = self.prompt.take().expect("prompt is open");
+                self.highlight = None;
+                match prompt.kind().clone() {
+                    PromptKind::Search(direction) => self.search(prompt.line(), direction),
+                    PromptKind::Command { lines } => self.run_command_line(prompt.line(), lines),
+                    PromptKind::Grep => self.grep(prompt.line()),
+                }
+            }
+            PromptEvent::Abort => {
+                self.prompt = None;
+                self.highlight = None;
+                if command {
+                    self.mode = Mode::Normal;
+                }
+            }
+            PromptEvent::Ignored => {}
+        }
+    }
+
+    // Runs the line typed in Command mode, going back to Normal mode first so that the command
+    // may switch to another mode.
+    fn run_command_line(&mut self, line: &str, lines: RangeInclusive<usize>) {
+        self.mode = Mode::Normal;
+        if !line.trim().is_empty() {
+            self.registers.set_command(line);
+        }
+        if let Err(err) = typed::execute(self, line, lines) {
+            self.set_status(format!("{err:#}"));
+        }
+    }
+
+    // Replaces the word before the end of the command line with its next completion, or the
+    // previous one going `forward`.
+    fn complete_command(&mut self, forward: bool) {
+        let Some(prompt) = self.prompt.as_ref() else {
+            return;
+        };
+        let completion = match self.completion.take() {
+            Some(mut completion) => {
+                let len = completion.candidates.len();
+                completion.index = match forward {
+                    true => (completion.index + 1) % len,
+                    false => (completion.index + len - 1) % len,
+                };
+                completion
+            }
+            None => {
+                let (start, candidates) = typed::complete(self, prompt.line());
+                if candidates.is_empty() {
+                    return;
+                }
+                let index = if forward { 0 } else { candidates.len() - 1 };
+                Completion {
+                    start,
+                    candidates,
+                    index,
+                }
+            }
+        };
+
+        let prompt = self.prompt.as_mut().expect("prompt is open");
+        let line = format!(
+            "{}{}",
+            &prompt.line()[..completion.start],
+            completion.candidates[completion.index]
+        );
+        prompt.set_line(line);
+        self.completion = Some(completion);
+    }
+
+    // Compiles the pattern typed in the prompt so far to highlight its matches.
+    fn update_highlight(&mut self) {
+        let smart_case = self.config.editor.search.smart_case;
+        self.highlight = self.prompt.as_ref().and_then(|prompt| match prompt.kind() {
+            PromptKind::Search(_) if !prompt.line().is_empty() => {
+                Search::new(prompt.line(), smart_case).ok()
+            }
+            PromptKind::Search(_) | PromptKind::Grep => None,
+            PromptKind::Command { .. } => {
+                let substitution: Substitution = prompt.line().parse().ok()?;
+                let pattern = match substitution.pattern.is_empty() {
+                    true => self.last_search()?,
+                    false => substitution.pattern,
+                };
+                match substitution.case_sensitive {
+                    Some(case_sensitive) => Search::with_case(&pattern, case_sensitive).ok(),
+                    None => Search::new(&pattern, smart_case).ok(),
+                }
+            }
+        });
+    }
+
+    // Returns the last search pattern.
+    fn last_search(&self) -> Option<String> {
+        let yank = self.registers.get('/', None).ok().flatten()?;
+        Some(yank.text)
+    }
+
+    /// Searches for `pattern` in `direction` from the primary cursor, and remembers it for
+    /// [`App::search_next`]. An empty pattern repeats the last search.
+    pub fn search(&mut self, pattern: &str, direction: Direction) {
+        if !pattern.is_empty() {
+            self.registers.set_search(pattern);
+        }
+        self.search_direction = direction;
+        self.search_next(direction, 1);
+    }
+
+    /// Moves the primary cursor to the `count`th match of the last search in `direction`.
+    pub fn search_next(&mut self, direction: Direction, count: usize) {
+        let Some(pattern) = self.last_search() else {
+            return self.set_status("No previous search pattern");
+        };
+        let config = self.config.editor.search;
+        let search = match Search::new(&pattern, config.smart_case) {
+            Ok(search) => search,
+            Err(err) => return self.set_status(format!("{err:#}")),
+        };
+
+        let buffer = self.buffer();
+        let primary = buffer.selection().primary();
+        let mut pos = primary.head;
+        let mut wrapped = false;
+        for _ in 0..count {
+            let found = match direction {
+                Direction::Forward => search.find_next(buffer.text(), pos, config.wrap_around),
+                Direction::Backward => search.find_prev(buffer.text(), pos, config.wrap_around),
+            };
+            match found {
+                Ok(Some((found, wrap))) => {
+                    pos = found.start;
+                    wrapped |= wrap;
+                }
+                Ok(None) => return self.set_status(format!("Pattern not found: {pattern}")),
+                Err(err) => return self.set_status(format!("{err:#}")),
+            }
+        }
+
+        let selection = match self.mode {
+            Mode::Visual => Selection::single(primary.put_head(pos)),
+            _ => Selection::point(pos),
+        };
+        self.save_jump();
+        self.buffer_mut().set_selection(selection);
+        if wrapped {
+            self.set_status(match direction {
+                Direction::Forward => "search hit BOTTOM, continuing at TOP",
+                Direction::Backward => "search hit TOP, continuing at BOTTOM",
+            });
+        }
+    }
+
+    /// Runs the substitution `command`, such as `s/foo/bar/g`, over `lines` or, if it starts
+    /// with `%`, over the whole buffer.
+    ///
+    /// With the `c` flag, each replacement waits for `y`, `n`, `a` (all), `l` (last) or `q`.
+    pub fn substitute(&mut self, command: &str, lines: RangeInclusive<usize>) {
+        let substitution: Substitution = match command.parse() {
+            Ok(substitution) => substitution,
+            Err(err) => return self.set_status(format!("{err:#}")),
+        };
+        let pattern = match substitution.pattern.is_empty() {
+            true => match self.last_search() {
+                Some(pattern) => pattern,
+                None => return self.set_status("No previous search pattern"),
+            },
+            false => substitution.pattern.clone(),
+        };
+        self.registers.set_search(pattern.as_str());
+
+        let smart_case = self.config.editor.search.smart_case;
+        let search = match substitution.case_sensitive {
+            Some(case_sensitive) => Search::with_case(&pattern, case_sensitive),
+            None => Search::new(&pattern, smart_case),
+        };
+        let lines = match substitution.every_line {
+            true => 0..=self.buffer().len_lines() - 1,
+            false => lines,
+        };
+        let replacements = match search
+            .and_then(|search| replacements(self.buffer(), &search, &substitution, lines))
+        {
+            Ok(replacements) => replacements,
+            Err(err) => return self.set_status(format!("{err:#}")),
+        };
+
+        if self.mode == Mode::Visual {
+            self.mode = Mode::Normal;
+        }
+        if replacements.is_empty() {
+            return self.set_status(format!("Pattern not found: {pattern}"));
+        }
+        match substitution.confirm {
+            true => {
+                let mut pending = replacements;
+                pending.reverse();
+                self.confirm = Some(Confirm {
+                    pending,
+                    accepted: Vec::new(),
+                });
+                self.show_confirm();
+            }
+            false => self.replace(replacements),
+        }
+    }
+
+    // Answers the confirmation of the current replacement with `key`.
+    fn handle_confirm_key(&mut self, key: Key) {
+        let Some(confirm) = self.confirm.as_mut() else {
+            return;
+        };
+        let Some(current) = confirm.pending.pop() else {
+            return;
+        };
+
+        match (key.code, key.char()) {
+            (_, Some('y')) => confirm.accepted.push(current),
+            (_, Some('n')) => {}
+            (_, Some('a')) => {
+                confirm.accepted.push(current);
+                confirm.accepted.extend(confirm.pending.drain(..).rev());
+            }
+            (_, Some('l')) => {
+                confirm.accepted.push(current);
+                confirm.pending.clear();
+            }
+            (_, Some('q')) | (KeyCode::Esc, _) => confirm.pending.clear(),
+            _ => return confirm.pending.push(current),
+        }
+
+        if !confirm.pending.is_empty() {
+            return self.show_confirm();
+        }
+        let accepted = std::mem::take(&mut confirm.accepted);
+        self.confirm = None;
+        match accepted.is_empty() {
+            true => self.set_status("No substitutions"),
+            false => self.replace(accepted),
+        }
     }
 
-    pub async fn run(self) -> Result<i32> {
-        println!("Hello\rWorld\n");
-        Ok(0)
+    // Moves the cursor to the replacement waiting for confirmation.
+    fn show_confirm(&mut self) {
+        let Some((found, _)) = self
+            .confirm
+            .as_ref()
+            .and_then(|confirm| confirm.pending.last())
+        else {
+            return;
+        };
+        let pos = found.start;
+        self.buffer_mut().set_selection(Selection::point(pos));
+    }
+
+    // Applies `replacements`, sorted by position, as a single change, and moves the cursor to the
+    // start of the last line replaced.
+    fn replace(&mut self, replacements: Vec<(Range<usize>, String)>) {
+        let buffer = self.buffer();
+        let count = replacements.len();
+        let mut lines: Vec<_> = replacements
+            .iter()
+            .map(|(found, _)| buffer.byte_to_pos(found.start).line)
+            .collect();
+        lines.dedup();
+        let last = buffer.line_start(lines[lines.len() - 1]);
+
+        let transaction = Transaction::change(
+            replacements
+                .into_iter()
+                .map(|(found, replacement)| (found.start, found.end, replacement)),
+        );
+        let buffer = self.buffer_mut();
+        buffer.apply(&transaction);
+        let pos = transaction.map_pos(last, Assoc::Before);
+        buffer.set_selection(Selection::point(pos));
+        self.set_status(format!(
+            "{count} substitution(s) on {} line(s)",
+            lines.len()
+        ));
+    }
+
+    /// Returns the root of the workspace of the buffer being edited, found from its directory or
+    /// the current one by [`metadata::workspace_root`] with the root markers of its language.
+    pub fn workspace_root(&self) -> PathBuf {
+        let current_dir = std::env::current_dir().unwrap_or_default();
+        let dir = match self.buffer().path() {
+            Some(path) => language_server::absolute(path)
+                .parent()
+                .map_or_else(|| current_dir.clone(), Path::to_path_buf),
+            None => current_dir.clone(),
+        };
+        let markers = self
+            .language_config()
+            .map(|language| language.roots.as_slice())
+            .unwrap_or_default();
+        metadata::workspace_root(&dir, markers).unwrap_or(current_dir)
+    }
+
+    /// Searches the files of the workspace for `pattern` and lists the matching lines in a
+    /// picker as they are found.
+    pub fn grep(&mut self, pattern: &str) {
+        if pattern.is_empty() {
+            return;
+        }
+        self.registers.set_search(pattern);
+        let smart_case = self.config.editor.search.smart_case;
+        let regex = regex::bytes::RegexBuilder::new(pattern)
+            .case_insensitive(smart_case && !search::has_uppercase(pattern))
+            .build();
+        let regex = match regex {
+            Ok(regex) => regex,
+            Err(err) => return self.set_status(format!("invalid pattern: {err}")),
+        };
+
+        let root = self.workspace_root();
+        self.open_picker(Picker::default());
+        self.grep_count += 1;
+        self.grep = Some(grep::grep(&root, regex, self.grep_count, self.sender()));
+        self.set_status(format!("Searching {}", root.display()));
+    }
+
+    /// Lists the files of the workspace in a picker as they are found.
+    pub fn file_picker(&mut self) {
+        let root = self.workspace_root();
+        self.open_picker(Picker::default());
+        self.grep_count += 1;
+        self.grep = Some(grep::list_files(&root, self.grep_count, self.sender()));
+    }
+
+    // Adds the files of the running listing to the picker.
+    fn add_files(&mut self, id: usize, paths: Vec<PathBuf>) {
+        let (Some(grep), Some(picker)) = (&self.grep, &mut self.picker) else {
+            return;
+        };
+        if grep.id() != id {
+            return;
+        }
+        let current_dir = std::env::current_dir().unwrap_or_default();
+        picker.extend(paths.into_iter().map(|path| {
+            let label = path.strip_prefix(&current_dir).unwrap_or(&path);
+            (label.display().to_string(), PickerItem::File(path))
+        }));
+        self.update_preview();
+    }
+
+    /// Lists the open buffers in a picker.
+    pub fn buffer_picker(&mut self) {
+        let items = self.buffers.iter().enumerate().map(|(index, buffer)| {
+            let name = buffer
+                .path()
+                .map(|path| path.display().to_string())
+                .unwrap_or_else(|| "[scratch]".into());
+            let modified = if buffer.is_modified() { " [+]" } else { "" };
+            (
+                format!("{} {name}{modified}", index + 1),
+                PickerItem::Buffer(index),
+            )
+        });
+        self.open_picker(Picker::new(items));
+    }
+
+    /// Asks the language server for the symbols of the buffer, and lists them in a picker.
+    pub fn symbol_picker(&mut self) {
+        self.request_language_server(|client, uri, _| async move {
+            let symbols = match client.document_symbols(uri.clone()).await? {
+                Some(response) => language_server::document_symbols(&uri, response),
+                None => Vec::new(),
+            };
+            Ok(Message::Symbols(symbols))
+        });
+    }
+
+    /// Asks the language server for the symbols of the workspace, and lists them in a picker.
+    pub fn workspace_symbol_picker(&mut self) {
+        self.request_language_server(|client, _, _| async move {
+            let symbols = client
+                .workspace_symbols(String::new())
+                .await?
+                .map(language_server::workspace_symbols)
+                .unwrap_or_default();
+            Ok(Message::Symbols(symbols))
+        });
+    }
+
+    fn show_symbols(&mut self, symbols: Vec<Symbol>) {
+        if symbols.is_empty() {
+            return self.set_status("No symbols found");
+        }
+        let current_dir = std::env::current_dir().unwrap_or_default();
+        let focused = self.buffer().path().and_then(language_server::uri);
+        let items = symbols.into_iter().filter_map(|symbol| {
+            let path = symbol.uri.to_file_path().ok()?;
+            let mut label = match &symbol.container {
+                Some(container) => format!("{container}::{}", symbol.name),
+                None => symbol.name.clone(),
+            };
+            label = format!("{label} ({})", symbol.kind_name());
+            if focused.as_ref() != Some(&symbol.uri) {
+                let relative = path.strip_prefix(&current_dir).unwrap_or(&path);
+                label = format!("{label} {}:{}", relative.display(), symbol.line + 1);
+            }
+            let line = symbol.line;
+            Some((label, PickerItem::Location { path, line }))
+        });
+        self.open_picker(Picker::new(items));
+    }
+
+    /// Lists the diagnostics of every document in a picker.
+    pub fn diagnostics_picker(&mut self) {
+        let current_dir = std::env::current_dir().unwrap_or_default();
+        let mut items = Vec::new();
+        for (uri, diagnostics) in &self.diagnostics {
+            let Ok(path) = uri.to_file_path() else {
+                continue;
+            };
+            let relative = path.strip_prefix(&current_dir).unwrap_or(&path);
+            for diagnostic in diagnostics {
+                let line = diagnostic.range.start.line as usize;
+                let severity = match diagnostic.severity {
+                    Some(lsp::DiagnosticSeverity::ERROR) => "error",
+                    Some(lsp::DiagnosticSeverity::WARNING) => "warning",
+                    Some(lsp::DiagnosticSeverity::INFORMATION) => "info",
+                    Some(lsp::DiagnosticSeverity::HINT) => "hint",
+                    _ => "diagnostic",
+                };
+                let message = diagnostic.message.lines().next().unwrap_or_default();
+                let label = format!("{}:{}: {severity}: {message}", relative.display(), line + 1);
+                let item = PickerItem::Location {
+                    path: path.clone(),
+                    line,
+                };
+                items.push((label, item));
+            }
+        }
+        if items.is_empty() {
+            return self.set_status("No diagnostics");
+        }
+        items.sort_by(|(a, _), (b, _)| a.cmp(b));
+        self.open_picker(Picker::new(items));
+    }
+
+    /// Lists the built-in and installed themes in a picker.
+    pub fn theme_picker(&mut self) {
+        let items = ThemeLoader::default()
+            .names()
+            .into_iter()
+            .map(|name| (name.clone(), PickerItem::Theme(name)));
+        self.open_picker(Picker::new(items));
+    }
+
+    /// Lists every command with its description in a picker.
+    pub fn command_palette(&mut self) {
+        let items = commands::Command::all().iter().map(|command| {
+            let label = format!("{} - {}", command.name, command.doc);
+            (label, PickerItem::Command(command))
+        });
+        self.open_picker(Picker::new(items));
+    }
+
+    // Shows `picker`, closing the one open and stopping what fills it.
+    fn open_picker(&mut self, picker: Picker<PickerItem>) {
+        self.grep = None;
+        self.picker = Some(picker);
+        self.update_preview();
+    }
+
+    // Adds the matches of the running workspace search to the picker.
+    fn add_grep_matches(&mut self, id: usize, matches: Vec<GrepMatch>) {
+        let (Some(grep), Some(picker)) = (&self.grep, &mut self.picker) else {
+            return;
+        };
+        if grep.id() != id {
+            return;
+        }
+        let current_dir = std::env::current_dir().unwrap_or_default();
+        picker.extend(matches.into_iter().map(|found| {
+            let path = found.path.strip_prefix(&current_dir).unwrap_or(&found.path);
+            let label = format!("{}:{}: {}", path.display(), found.line + 1, found.text);
+            let item = PickerItem::Location {
+                path: found.path,
+                line: found.line,
+            };
+            (label, item)
+        }));
+        self.update_preview();
+    }
+
+    fn finish_grep(&mut self, id: usize, truncated: bool) {
+        let (Some(grep), Some(picker)) = (&self.grep, &self.picker) else {
+            return;
+        };
+        if grep.id() != id {
+            return;
+        }
+        let count = picker.len();
+        self.grep = None;
+        self.set_status(match truncated {
+            true => format!("{count} match(es), stopped at {MAX_GREP_MATCHES}"),
+            false => format!("{count} match(es)"),
+        });
+    }
+
+    // Edits the query of the picker with `event`, and runs the item once picked.
+    fn handle_picker_key(&mut self, event: KeyEvent) {
+        let Some(picker) = self.picker.as_mut() else {
+            return;
+        };
+        match picker.handle_key(event) {
+            PickerEvent::Submit => {
+                let picker = self.picker.take().expect("picker is open");
+                self.grep = None;
+                if let Some(item) = picker.selected() {
+                    self.pick(item);
+                }
+            }
+            PickerEvent::Abort => {
+                self.picker = None;
+                self.grep = None;
+            }
+            PickerEvent::Update => self.update_preview(),
+            PickerEvent::Ignored => {}
+        }
+    }
+
+    // Loads the file of the selected item of the picker for the preview, unless it is loaded
+    // already or open in a buffer.
+    fn update_preview(&mut self) {
+        let path = match self.picker.as_ref().and_then(Picker::selected) {
+            Some(PickerItem::Location { path, .. } | PickerItem::File(path)) => path,
+            _ => return,
+        };
+        if self
+            .preview
+            .as_ref()
+            .map_or(false, |(loaded, _)| loaded == path)
+            || self.find_buffer(path).is_some()
+        {
+            return;
+        }
+        let path = path.clone();
+        let text = std::fs::metadata(&path)
+            .ok()
+            .filter(|metadata| metadata.len() <= MAX_PREVIEW_SIZE)
+            .and_then(|_| std::fs::read(&path).ok())
+            .filter(|bytes| !bytes.iter().take(8192).any(|&byte| byte == 0))
+            .and_then(|bytes| String::from_utf8(bytes).ok());
+        self.preview = text.map(|text| (path, Rope::from(text)));
+    }
+
+    // Returns the lines around the location of the selected item of the picker, enough to fill
+    // `height` rows.
+    fn picker_preview(&self, height: usize) -> Option<Preview> {
+        let (path, line) = match self.picker.as_ref()?.selected()? {
+            PickerItem::Location { path, line } => (path, Some(*line)),
+            PickerItem::File(path) => (path, None),
+            PickerItem::Buffer(index) => {
+                let buffer = self.buffers.get(*index)?;
+                let head = buffer.selection().primary().head;
+                return Some(preview_lines(
+                    buffer.text(),
+                    Some(buffer.byte_to_pos(head).line),
+                    height,
+                ));
+            }
+            PickerItem::Theme(_) | PickerItem::Command(_) => return None,
+        };
+        let text = match self.find_buffer(path) {
+            Some(index) => self.buffers[index].text(),
+            None => match &self.preview {
+                Some((loaded, text)) if loaded == path => text,
+                _ => return None,
+            },
+        };
+        Some(preview_lines(text, line, height))
+    }
+    fn pick(&mut self, item: &PickerItem) {
+        match item {
+            PickerItem::Location { path, line } => match self.save_jump_and_open(path) {
+                Ok(_) => {
+                    let buffer = self.buffer();
+                    let pos = buffer.line_start((*line).min(buffer.len_lines() - 1));
+                    self.buffer_mut().set_selection(Selection::point(pos));
+                    self.mode = Mode::Normal;
+                }
+                Err(err) => self.set_status(format!("{err:#}")),
+            },
+            PickerItem::File(path) => {
+                if let Err(err) = self.save_jump_and_open(path) {
+                    self.set_status(format!("{err:#}"));
+                }
+            }
+            PickerItem::Buffer(index) => {
+                if *index < self.buffers.len() {
+                    self.save_jump();
+                    self.focus = *index;
+                }
+            }
+            PickerItem::Theme(name) => self.set_theme(name),
+            PickerItem::Command(command) => command.execute(&mut Context {
+                app: self,
+                count: None,
+            }),
+        }
+    }
+
+    /// Sets the language of the buffer being edited to the one called `name`, and highlights it
+    /// and starts its language server accordingly.
+    pub fn set_language(&mut self, name: &str) -> Result<()> {
+        if self.config.syntax.language(name).is_none() {
+            return Err(anyhow!("unknown language '{name}'"));
+        }
+        let buffer = self.buffer_mut();
+        buffer.set_language(Some(name.to_string()));
+        buffer.set_syntax(None);
+        self.load_syntax(self.focus);
+        self.start_language_servers();
+        Ok(())
+    }
+
+    /// Returns the settings of the `[editor]` section that the buffer being edited overrides,
+    /// itself or through its language.
+    pub fn local_settings(&self) -> LocalSettings {
+        self.buffer_settings(self.buffer())
+    }
+
+    // Returns the settings that `buffer` overrides, itself or through its language.
+    fn buffer_settings(&self, buffer: &Buffer) -> LocalSettings {
+        let language = buffer
+            .language()
+            .and_then(|name| self.config.syntax.language(name));
+        let language = language.map(|language| language.editor);
+        buffer.settings().or(language.unwrap_or_default())
+    }
+
+    /// Returns how line numbers are shown in the buffer being edited.
+    pub fn line_number(&self) -> LineNumber {
+        self.buffer_line_number(self.buffer())
+    }
+
+    fn buffer_line_number(&self, buffer: &Buffer) -> LineNumber {
+        let line_number = self.buffer_settings(buffer).line_number;
+        line_number.unwrap_or(self.config.editor.line_number)
+    }
+
+    /// Returns whether brackets and quotes are closed as they are typed in the buffer being
+    /// edited.
+    pub fn auto_pairs(&self) -> bool {
+        let auto_pairs = self.local_settings().auto_pairs;
+        auto_pairs.unwrap_or(self.config.editor.auto_pairs)
+    }
+
+    /// Returns the value of the setting called `key` in `scope`, for the buffer being edited and
+    /// its language: the value that applies there, whichever scope it comes from.
+    pub fn get_setting(&self, scope: SettingScope, key: &str) -> Result<String> {
+        let local = match scope {
+            SettingScope::Global => None,
+            SettingScope::Language => match self.language_config() {
+                Some(language) => language.editor.get(key)?,
+                None => return Err(anyhow!("the buffer has no language")),
+            },
+            SettingScope::Buffer => self.local_settings().get(key)?,
+        };
+        match local {
+            Some(value) => Ok(value),
+            None => self.config.editor.get(key),
+        }
+    }
+
+    /// Sets the setting called `key` to `value` in `scope`, for the buffer being edited and its
+    /// language.
+    pub fn set_setting(&mut self, scope: SettingScope, key: &str, value: &str) -> Result<()> {
+        match scope {
+            SettingScope::Global => {
+                self.config.editor.set(key, value)?;
+                if key == "clipboard.provider" {
+                    let (clipboard, _) =
+                        Clipboard::detect(&self.config.editor.clipboard, &self.environment);
+                    log::info!("using the {} clipboard", clipboard.name());
+                    self.registers.set_clipboard(clipboard);
+                }
+                Ok(())
+            }
+            SettingScope::Language => {
+                let name = self.buffer().language().map(str::to_string);
+                let language = self
+                    .config
+                    .syntax
+                    .languages
+                    .iter_mut()
+                    .find(|language| Some(&language.name) == name.as_ref());
+                match language {
+                    Some(language) => language.editor.set(key, value),
+                    None => Err(anyhow!("the buffer has no language")),
+                }
+            }
+            SettingScope::Buffer => self.buffer_mut().settings_mut().set(key, value),
+        }
+    }
+
+    /// Returns the configuration of the language of the buffer being edited.
+    pub fn language_config(&self) -> Option<&LanguageConfig> {
+        let language = self.buffer().language()?;
+        self.config.syntax.language(language)
+    }
+
+    /// Switches to the theme called `name`, keeping the current one if it cannot be loaded.
+    ///
+    /// Problems with the theme are logged, and the first of them is shown in the status line.
+    pub fn set_theme(&mut self, name: &str) {
+        let (theme, warnings) = match Theme::load(name) {
+            Ok(loaded) => loaded,
+            Err(err) => {
+                log::error!("{err:#}");
+                self.set_status(format!("{err:#}"));
+                return;
+            }
+        };
+        for warning in &warnings {
+            log::warn!("theme '{name}': {warning}");
+        }
+        if let Some(warning) = warnings.first() {
+            let more = match warnings.len() {
+                1 => String::new(),
+                n => format!(" (and {} more, see the log)", n - 1),
+            };
+            self.set_status(format!("theme '{name}': {warning}{more}"));
+        }
+        self.theme = theme.with_color_depth(ColorDepth::detect());
+    }
+
+    // Parses the buffer at `index` if its language has a grammar.
+    fn load_syntax(&mut self, index: usize) {
+        let buffer = &self.buffers[index];
+        let Some(language) = buffer
+            .language()
+            .and_then(|language| self.config.syntax.language(language))
+        else {
+            return;
+        };
+        let Some(config) = self.syntax_loader.highlight_config(language) else {
+            return;
+        };
+
+        match Syntax::new(buffer.text(), config) {
+            Ok(syntax) => self.buffers[index].set_syntax(Some(syntax)),
+            Err(err) => log::error!("failed to parse {}: {err:#}", language.name),
+        }
+    }
+
+    fn find_buffer(&self, path: &Path) -> Option<usize> {
+        let uri = language_server::uri(path);
+        self.buffers.iter().position(|buffer| {
+            buffer.path().map_or(false, |other| {
+                other == path || (uri.is_some() && language_server::uri(other) == uri)
+            })
+        })
+    }
+
+    /// Returns the language server of the buffer being edited, if it is running.
+    pub fn language_server(&self) -> Option<&Arc<Client>> {
+        self.language_server_for(self.buffer())
+    }
+
+    fn language_server_for(&self, buffer: &Buffer) -> Option<&Arc<Client>> {
+        self.language_servers.get(buffer.language()?)?.as_ref()
+    }
+
+    /// Sends a request to the language server of the buffer being edited for the position of the
+    /// primary cursor. The message `f` resolves to is handled once it arrives; errors are shown in
+    /// the status line.
+    pub fn request_language_server<F, Fut>(&mut self, f: F)
+    where
+        F: FnOnce(Arc<Client>, lsp::Url, lsp::Position) -> Fut,
+        Fut: Future<Output = Result<Message>> + Send + 'static,
+    {
+        let Some(client) = self.language_server().cloned() else {
+            return self.set_status("No language server is running for this buffer");
+        };
+        let buffer = self.buffer();
+        let Some(uri) = buffer.path().and_then(language_server::uri) else {
+            return self.set_status("The buffer has no file");
+        };
+        let pos = tissue_lsp::pos_to_lsp_pos(
+            buffer.text(),
+            buffer.selection().primary().head,
+            client.offset_encoding(),
+        );
+
+        let sender = self.sender();
+        let request = f(client, uri, pos);
+        tokio::spawn(async move {
+            let message = request
+                .await
+                .unwrap_or_else(|err| Message::Status(format!("{err:#}")));
+            sender.send(message).ok();
+        });
+    }
+
+    /// Asks the language server to rename the symbol under the cursor to `new_name`.
+    pub fn rename_symbol(&mut self, new_name: String) {
+        self.request_language_server(|client, uri, pos| async move {
+            match client.rename(uri, pos, new_name).await? {
+                Some(edit) => Ok(Message::WorkspaceEdit(edit)),
+                None => Ok(Message::Status("Nothing to rename".into())),
+            }
+        });
+    }
+
+    // Starts the language servers of open buffers that do not have one yet.
+    fn start_language_servers(&mut self) {
+        for buffer in &self.buffers {
+            let (Some(language), Some(path)) = (buffer.language(), buffer.path()) else {
+                continue;
+            };
+            let Some(config) = self.config.syntax.language(language) else {
+                continue;
+            };
+            if config.language_server.is_none() || self.language_servers.contains_key(language) {
+                continue;
+            }
+
+            self.language_servers.insert(language.to_string(), None);
+
+            let (config, path, sender) = (config.clone(), path.to_path_buf(), self.sender.clone());
+            tokio::spawn(async move {
+                let result = language_server::start(&config, &path, sender.clone()).await;
+                let language = config.name;
+                sender
+                    .send(Message::LanguageServerStarted { language, result })
+                    .ok();
+            });
+        }
+    }
+
+    // Tells language servers about the buffers opened, edited or closed since the last call.
+    //
+    // Documents are opened in a server once their buffer has a path and the server of its language
+    // is running, and closed once the buffer is gone or has a different path or language.
+    fn sync_language_servers(&mut self) {
+        let documents: HashMap<_, _> = self
+            .buffers
+            .iter()
+            .filter_map(|buffer| {
+                let uri = buffer.path().and_then(language_server::uri)?;
+                Some((uri, buffer.language()?))
+            })
+            .collect();
+        self.lsp_documents.retain(|uri, language| {
+            if documents.get(uri) == Some(&language.as_str()) {
+                return true;
+            }
+            if let Some(Some(client)) = self.language_servers.get(language.as_str()) {
+                if let Err(err) = client.text_document_did_close(uri.clone()) {
+                    log::error!("failed to close document in {}: {err}", client.name());
+                }
+            }
+            false
+        });
+
+        for index in 0..self.buffers.len() {
+            let changes = self.buffers[index].take_changes();
+            let buffer = &self.buffers[index];
+            let (Some(client), Some(uri)) = (
+                self.language_server_for(buffer).cloned(),
+                buffer.path().and_then(language_server::uri),
+            ) else {
+                continue;
+            };
+
+            if let Entry::Vacant(entry) = self.lsp_documents.entry(uri.clone()) {
+                // The whole text is sent on open, so earlier changes are moot.
+                let language = buffer.language().unwrap_or_default().to_string();
+                let text = buffer.text().to_string();
+                match client.text_document_did_open(uri, &language, buffer.version(), text) {
+                    Ok(()) => {
+                        entry.insert(language);
+                    }
+                    Err(err) => log::error!("failed to open document in {}: {err}", client.name()),
+                }
+                continue;
+            }
+            if changes.is_empty() {
+                continue;
+            }
+
+            let encoding = client.offset_encoding();
+            let events = match client.sync_kind() {
+                lsp::TextDocumentSyncKind::INCREMENTAL => changes
+                    .iter()
+                    .flat_map(|(text, transaction)| {
+                        language_server::content_changes(text, transaction, encoding)
+                    })
+                    .collect(),
+                _ => vec![lsp::TextDocumentContentChangeEvent {
+                    range: None,
+                    range_length: None,
+                    text: buffer.text().to_string(),
+                }],
+            };
+
+            if let Err(err) = client.text_document_did_change(uri, buffer.version(), events) {
+                log::error!("failed to send changes to {}: {err}", client.name());
+            }
+        }
+    }
+
+    fn handle_language_server_started(&mut self, language: String, result: Result<Arc<Client>>) {
+        let client = match result {
+            Ok(client) => client,
+            Err(err) => {
+                log::error!("failed to start language server for {language}: {err:#}");
+                return self.set_status(format!("{err:#}"));
+            }
+        };
+
+        // Documents opened in an earlier server of the language are opened again in this one.
+        self.lsp_documents.retain(|_, opened| *opened != language);
+        self.language_servers.insert(language, Some(client));
+        self.sync_language_servers();
+    }
+
+    fn handle_notification(&mut self, notification: Notification) {
+        match notification {
+            Notification::PublishDiagnostics(params) => {
+                self.diagnostics.insert(params.uri, params.diagnostics);
+            }
+            Notification::ShowMessage(params) => self.set_status(params.message),
+            Notification::LogMessage(params) => log::info!("language server: {}", params.message),
+            Notification::Progress(params) => {
+                let lsp::ProgressParamsValue::WorkDone(progress) = params.value;
+                let index = self
+                    .progress
+                    .iter()
+                    .position(|(token, ..)| *token == params.token);
+                let (message, percentage) = match (progress, index) {
+                    (lsp::WorkDoneProgress::Begin(begin), _) => {
+                        if let Some(index) = index {
+                            self.progress.remove(index);
+                        }
+                        self.progress
+                            .push((params.token.clone(), begin.title, String::new()));
+                        (begin.message, begin.percentage)
+                    }
+                    (lsp::WorkDoneProgress::Report(report), Some(_)) => {
+                        (report.message, report.percentage)
+                    }
+                    (lsp::WorkDoneProgress::End(_), Some(index)) => {
+                        self.progress.remove(index);
+                        return;
+                    }
+                    _ => return,
+                };
+                let percentage = percentage.map(|percentage| format!("{percentage}%"));
+                let parts = [message, percentage].into_iter().flatten();
+                let message = parts.collect::<Vec<_>>().join(" ");
+                let token = &params.token;
+                if let Some(entry) = self.progress.iter_mut().find(|(t, ..)| t == token) {
+                    entry.2 = message;
+                }
+            }
+        }
+    }
+
+    // Converts a protocol position in `buffer`, using the encoding of its language server.
+    fn lsp_pos_to_pos(&self, buffer: &Buffer, pos: lsp::Position) -> Option<usize> {
+        let encoding = self
+            .language_server_for(buffer)
+            .map(|client| client.offset_encoding())
+            .unwrap_or_default();
+        tissue_lsp::lsp_pos_to_pos(buffer.text(), pos, encoding)
+    }
+
+    fn goto_locations(&mut self, locations: Vec<lsp::Location>) {
+        let Some(location) = locations.first() else {
+            return self.set_status("No locations found");
+        };
+        let Ok(path) = location.uri.to_file_path() else {
+            return self.set_status(format!("Cannot open {}", location.uri));
+        };
+
+        match self.save_jump_and_open(&path) {
+            Ok(index) => {
+                let buffer = &self.buffers[index];
+                let pos = self.lsp_pos_to_pos(buffer, location.range.start);
+                if let Some(pos) = pos {
+                    self.buffer_mut().set_selection(Selection::point(pos));
+                }
+                self.mode = Mode::Normal;
+                if locations.len() > 1 {
+                    self.set_status(format!("{} locations found", locations.len()));
+                }
+            }
+            Err(err) => self.set_status(format!("{err:#}")),
+        }
+    }
+
+    fn complete(&mut self, items: Vec<lsp::CompletionItem>) {
+        if self.mode != Mode::Insert {
+            return;
+        }
+
+        let buffer = self.buffer();
+        let head = buffer.selection().primary().head;
+        let start = buffer
+            .chars_before(head)
+            .take_while(|&(_, c)| motion::same_word(c, 'a', false))
+            .last()
+            .map_or(head, |(i, _)| i);
+        let prefix = buffer.slice(start..head).to_string();
+
+        let candidates: Vec<_> = items
+            .iter()
+            .map(|item| item.insert_text.as_deref().unwrap_or(&item.label))
+            .filter(|text| text.starts_with(&prefix))
+            .collect();
+        let Some(first) = candidates.first() else {
+            return self.set_status("No completions");
+        };
+
+        // Insert as much as all candidates have in common.
+        let common = candidates.iter().fold(first.len(), |len, candidate| {
+            first
+                .char_indices()
+                .zip(candidate.chars())
+                .take_while(|((i, a), b)| *i < len && a == b)
+                .last()
+                .map_or(0, |((i, a), _)| i + a.len_utf8())
+        });
+        commands::insert_text(self, &first[prefix.len()..common]);
+
+        if candidates.len() > 1 {
+            self.set_status(candidates.join(" "));
+        }
+    }
+
+    fn apply_workspace_edit(&mut self, edit: lsp::WorkspaceEdit) {
+        let mut documents: Vec<(lsp::Url, Vec<lsp::TextEdit>)> =
+            edit.changes.unwrap_or_default().into_iter().collect();
+
+        match edit.document_changes {
+            Some(lsp::DocumentChanges::Edits(edits)) => {
+                documents.extend(edits.into_iter().map(|edit| {
+                    let edits = edit
+                        .edits
+                        .into_iter()
+                        .map(|edit| match edit {
+                            lsp::OneOf::Left(edit) => edit,
+                            lsp::OneOf::Right(edit) => edit.text_edit,
+                        })
+                        .collect();
+                    (edit.text_document.uri, edits)
+                }));
+            }
+            Some(lsp::DocumentChanges::Operations(_)) => {
+                return self.set_status("File operations are not supported");
+            }
+            None => {}
+        }
+
+        // Opening the documents moves focus, which goes back whether they were edited or not.
+        let focus = self.focus;
+        let count = documents.len();
+        let result = self.apply_document_edits(documents);
+        self.focus = focus;
+
+        match result {
+            Ok(()) => self.set_status(format!("Edited {count} file(s)")),
+            Err(err) => self.set_status(format!("{err:#}")),
+        }
+    }
+
+    // Opens each document and applies its edits, stopping at the first document that cannot be
+    // opened or whose edits are out of bounds or overlap.
+    fn apply_document_edits(
+        &mut self,
+        documents: Vec<(lsp::Url, Vec<lsp::TextEdit>)>,
+    ) -> Result<()> {
+        for (uri, mut edits) in documents {
+            let path = uri
+                .to_file_path()
+                .map_err(|()| anyhow!("Cannot open {uri}"))?;
+            let index = self.open(&path)?;
+
+            edits.sort_by_key(|edit| (edit.range.start.line, edit.range.start.character));
+            let buffer = &self.buffers[index];
+            let changes: Option<Vec<_>> = edits
+                .into_iter()
+                .map(|edit| {
+                    let from = self.lsp_pos_to_pos(buffer, edit.range.start)?;
+                    let to = self.lsp_pos_to_pos(buffer, edit.range.end)?;
+                    Some((from, to, edit.new_text))
+                })
+                .collect();
+            let changes = changes.filter(|changes| {
+                let mut end = 0;
+                changes.iter().all(|&(from, to, _)| {
+                    let valid = end <= from && from <= to;
+                    end = to;
+                    valid
+                })
+            });
+
+            match changes {
+                Some(changes) => self.buffers[index].apply(&Transaction::change(changes)),
+                None => bail!("Invalid edit for {uri}"),
+            }
+        }
+        Ok(())
+    }
+
+    /// Starts a debug session for the buffer being edited, with its language's debug
+    /// configuration called `name`, or the first one. `params` fill the placeholders of the
+    /// configuration's arguments.
+    pub fn start_debugger(&mut self, name: Option<&str>, params: &[String]) {
+        if self.debugger.is_some() {
+            return self.set_status("A debug session is already running");
+        }
+
+        match self.debug_launch(name, params) {
+            Ok((language, path, request, arguments)) => {
+                self.set_status(format!(
+                    "Starting {}",
+                    language.debugger.as_deref().unwrap_or("")
+                ));
+                tokio::spawn(debugger::start(
+                    language,
+                    path,
+                    request,
+                    arguments,
+                    self.sender(),
+                ));
+            }
+            Err(err) => self.set_status(format!("{err:#}")),
+        }
+    }
+
+    // Resolves what `start_debugger` starts.
+    fn debug_launch(
+        &self,
+        name: Option<&str>,
+        params: &[String],
+    ) -> Result<(LanguageConfig, PathBuf, DebugRequest, serde_json::Value)> {
+        let buffer = self.buffer();
+        let language = self
+            .language_config()
+            .filter(|language| language.debugger.is_some())
+            .ok_or_else(|| anyhow!("No debugger is configured for this buffer"))?;
+        let config = match name {
+            Some(name) => language
+                .debug_configs
+                .iter()
+                .find(|config| config.name == name)
+                .ok_or_else(|| anyhow!("No debug configuration named '{name}'"))?,
+            None => language
+                .debug_configs
+                .first()
+                .ok_or_else(|| anyhow!("No debug configurations for {}", language.name))?,
+        };
+        let arguments = config
+            .arguments(params, buffer.path())
+            .with_context(|| format!("Debug configuration '{}'", config.name))?;
+        let path = buffer.path().map(Path::to_path_buf).unwrap_or_default();
+
+        Ok((language.clone(), path, config.request, arguments))
+    }
+
+    /// Ends the debug session and terminates the debuggee.
+    pub fn stop_debugger(&mut self) {
+        let Some(session) = self.debugger.take() else {
+            return self.set_status("No debug session is running");
+        };
+        tokio::spawn(async move {
+            if let Err(err) = session.client.disconnect(true).await {
+                log::warn!("failed to disconnect from debug adapter: {err:#}");
+            }
+        });
+        self.set_status("Debug session ended");
+    }
+
+    /// Resumes the stopped thread with `f`, which continues or steps it.
+    pub fn resume_debuggee<F, Fut>(&mut self, f: F)
+    where
+        F: FnOnce(Arc<dap::Client>, i64) -> Fut,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        let Some(session) = self.debugger.as_mut() else {
+            return self.set_status("No debug session is running");
+        };
+        let Some(thread) = session.thread.filter(|_| session.stopped) else {
+            return self.set_status("The debuggee is running");
+        };
+
+        session.stopped = false;
+        session.frames.clear();
+        let request = f(session.client.clone(), thread);
+        self.spawn_debugger_request(async move {
+            request.await?;
+            Ok(Message::Status("Running".into()))
+        });
+    }
+
+    /// Suspends the debuggee.
+    pub fn pause_debuggee(&mut self) {
+        let Some(session) = &self.debugger else {
+            return self.set_status("No debug session is running");
+        };
+        if session.stopped {
+            return self.set_status("The debuggee is stopped");
+        }
+
+        let (client, thread) = (session.client.clone(), session.thread);
+        self.spawn_debugger_request(async move {
+            let thread = match thread {
+                Some(thread) => thread,
+                None => client
+                    .threads()
+                    .await?
+                    .first()
+                    .map(|thread| thread.id)
+                    .ok_or_else(|| anyhow!("The debuggee has no threads"))?,
+            };
+            client.pause(thread).await?;
+            Ok(Message::Status("Pausing".into()))
+        });
+    }
+
+    /// Sets or clears a breakpoint on the line of the primary cursor.
+    pub fn toggle_breakpoint(&mut self) {
+        let buffer = self.buffer_mut();
+        let line = buffer.byte_to_pos(buffer.selection().primary().head).line;
+        buffer.toggle_breakpoint(line);
+
+        let Some(path) = buffer.path().map(Path::to_path_buf) else {
+            return;
+        };
+        let breakpoints = vec![(path, debugger::breakpoints(buffer))];
+        if let Some(session) = self.debugger.as_ref().filter(|session| session.initialized) {
+            let client = session.client.clone();
+            self.spawn_debugger_request(async move {
+                debugger::set_breakpoints(&client, breakpoints).await?;
+                Ok(Message::Status("Breakpoints updated".into()))
+            });
+        }
+    }
+
+    /// Shows the variables of the innermost frame of the stopped thread in the status line.
+    pub fn request_variables(&mut self) {
+        let Some(session) = &self.debugger else {
+            return self.set_status("No debug session is running");
+        };
+        let Some(frame) = session.frames.first() else {
+            return self.set_status("The debuggee is running");
+        };
+
+        let (client, frame) = (session.client.clone(), frame.id);
+        self.spawn_debugger_request(async move {
+            let scopes = client.scopes(frame).await?;
+            let Some(scope) = scopes
+                .iter()
+                .find(|scope| !scope.expensive)
+                .or(scopes.first())
+            else {
+                return Ok(Message::Variables(Vec::new()));
+            };
+            Ok(Message::Variables(
+                client.variables(scope.variables_reference).await?,
+            ))
+        });
+    }
+
+    // Sends the message `request` resolves to, or shows its error in the status line.
+    fn spawn_debugger_request<Fut>(&self, request: Fut)
+    where
+        Fut: Future<Output = Result<Message>> + Send + 'static,
+    {
+        let sender = self.sender();
+        tokio::spawn(async move {
+            let message = request
+                .await
+                .unwrap_or_else(|err| Message::Status(format!("{err:#}")));
+            sender.send(message).ok();
+        });
+    }
+
+    fn handle_debugger_started(&mut self, result: Result<Arc<dap::Client>>) {
+        match result {
+            Ok(client) => self.debugger = Some(DebugSession::new(client)),
+            Err(err) => {
+                log::error!("failed to start debug session: {err:#}");
+                self.debugger = None;
+                self.set_status(format!("{err:#}"));
+            }
+        }
+    }
+
+    fn handle_debugger_event(&mut self, event: dap::Event) {
+        let Some(session) = self.debugger.as_mut() else {
+            return;
+        };
+
+        match event {
+            dap::Event::Initialized => {
+                session.initialized = true;
+                let client = session.client.clone();
+                let breakpoints: Vec<_> = self
+                    .buffers
+                    .iter()
+                    .filter(|buffer| !buffer.breakpoints().is_empty())
+                    .filter_map(|buffer| {
+                        Some((buffer.path()?.to_path_buf(), debugger::breakpoints(buffer)))
+                    })
+                    .collect();
+                self.spawn_debugger_request(async move {
+                    debugger::set_breakpoints(&client, breakpoints).await?;
+                    client.configuration_done().await?;
+                    Ok(Message::Status("Debug session started".into()))
+                });
+            }
+            dap::Event::Stopped(stopped) => {
+                session.stopped = true;
+                session.thread = stopped.thread_id.or(session.thread);
+                let (client, thread) = (session.client.clone(), session.thread);
+                self.set_status(format!(
+                    "Stopped ({})",
+                    stopped.description.unwrap_or(stopped.reason)
+                ));
+                self.spawn_debugger_request(async move {
+                    let thread = match thread {
+                        Some(thread) => thread,
+                        None => client
+                            .threads()
+                            .await?
+                            .first()
+                            .map(|thread| thread.id)
+                            .ok_or_else(|| anyhow!("The debuggee has no threads"))?,
+                    };
+                    let frames = client.stack_trace(thread).await?;
+                    Ok(Message::StackFrames { thread, frames })
+                });
+            }
+            dap::Event::Continued(_) => {
+                session.stopped = false;
+                session.frames.clear();
+            }
+            dap::Event::Exited(exited) => session.exit_code = Some(exited.exit_code),
+            dap::Event::Terminated => {
+                let exit_code = session.exit_code;
+                self.debugger = None;
+                self.set_status(match exit_code {
+                    Some(code) => format!("Debuggee exited with code {code}"),
+                    None => "Debug session ended".into(),
+                });
+            }
+            dap::Event::Output(output) => {
+                log::info!("debuggee: {}", output.output.trim_end());
+            }
+            dap::Event::Breakpoint(event) => {
+                log::debug!("breakpoint {}: {:?}", event.reason, event.breakpoint);
+            }
+        }
+    }
+
+    fn goto_stack_frames(&mut self, thread: i64, frames: Vec<StackFrame>) {
+        let Some(frame) = frames.first() else {
+            return;
+        };
+        let path = frame.source.as_ref().and_then(|source| source.path.clone());
+        let (line, column) = (frame.line, frame.column);
+
+        if let Some(session) = self.debugger.as_mut() {
+            session.thread = Some(thread);
+            session.frames = frames;
+        }
+
+        let Some(path) = path else {
+            return;
+        };
+        match self.open(&path) {
+            Ok(_) => {
+                let pos = debugger::frame_pos(self.buffer(), line, column);
+                self.buffer_mut().set_selection(Selection::point(pos));
+                self.mode = Mode::Normal;
+            }
+            Err(err) => self.set_status(format!("{err:#}")),
+        }
+    }
+
+    fn show_variables(&mut self, variables: Vec<Variable>) {
+        if variables.is_empty() {
+            return self.set_status("No variables");
+        }
+        let variables: Vec<_> = variables
+            .iter()
+            .map(|variable| format!("{} = {}", variable.name, variable.value))
+            .collect();
+        self.set_status(variables.join(", "));
+    }
+
+    // Returns the names of buffers with unsaved changes.
+    fn modified_buffers(&self) -> Vec<String> {
+        self.buffers
+            .iter()
+            .filter(|buffer| buffer.is_modified())
+            .map(|buffer| match buffer.path() {
+                Some(path) => path.display().to_string(),
+                None => "[scratch]".into(),
+            })
+            .collect()
+    }
+
+    async fn event_loop(&mut self, terminal: &mut Terminal) -> Result<i32> {
+        let mut events = EventStream::new();
+
+        self.start_language_servers();
+        self.update_mouse_capture()?;
+        self.draw(terminal)?;
+
+        loop {
+            if let Some(code) = self.exit_code {
+                return Ok(code);
+            }
+
+            let deadline = self.next_deadline();
+
+            tokio::select! {
+                event = events.next() => match event {
+                    Some(event) => self.handle_terminal_event(event?, terminal)?,
+                    // The terminal went away, so there is nobody left to save the buffers.
+                    None => self.quit(true),
+                },
+                Some(message) = self.receiver.recv() => self.handle_message(message),
+                _ = time::sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => {
+                    self.handle_timeout();
+                }
+            }
+
+            self.sync_language_servers();
+            self.schedule_diffs();
+            self.update_mouse_capture()?;
+            self.draw(terminal)?;
+        }
+    }
+
+    // Turns mouse capture on or off when `EditorConfig::mouse` changed.
+    fn update_mouse_capture(&mut self) -> Result<()> {
+        if self.mouse_capture != self.config.editor.mouse {
+            self.mouse_capture = self.config.editor.mouse;
+            match self.mouse_capture {
+                true => execute!(io::stdout(), EnableMouseCapture)?,
+                false => execute!(io::stdout(), DisableMouseCapture)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_terminal_event(&mut self, event: Event, terminal: &mut Terminal) -> Result<()> {
+        match event {
+            Event::Key(key) => self.handle_key(key),
+            Event::Mouse(mouse) => self.handle_mouse(mouse),
+            Event::Resize(width, height) => terminal.resize(Rect::new(0, 0, width, height))?,
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    /// Handles a key press by running the command bound to it in the current mode.
+    ///
+    /// Outside of Insert mode, a command may be preceded by a count, and an operator waits for the
+    /// motion or text object that follows it.
+    pub fn handle_key(&mut self, event: KeyEvent) {
+        let key = Key::from(event);
+
+        if self.confirm.is_some() {
+            self.handle_confirm_key(key);
+            return self.finish_command();
+        }
+        if self.prompt.is_some() {
+            self.handle_prompt_key(event);
+            return self.finish_command();
+        }
+        if self.picker.is_some() {
+            self.handle_picker_key(event);
+            return self.finish_command();
+        }
+
+        if let Some((argument, count)) = self.pending_char.take() {
+            self.count = None;
+            if let Some(ch) = key.char() {
+                commands::char_argument(&mut Context { app: self, count }, argument, ch);
+            } else {
+                self.operator = None;
+            }
+            return self.finish_command();
+        }
+
+        if self.mode != Mode::Insert && self.keymaps.pending().is_empty() {
+            if let Some(digit) = key.char().and_then(|c| c.to_digit(10)) {
+                if digit != 0 || self.count.is_some() {
+                    let count = self.count.unwrap_or(0).saturating_mul(10);
+                    self.count = Some(count.saturating_add(digit as usize));
+                    return;
+                }
+            }
+        }
+
+        let mode = match self.mode {
+            Mode::Normal if self.operator.is_some() => KeymapMode::Operator,
+            mode => KeymapMode::from(mode),
+        };
+        match self.keymaps.get(&self.config.keys, mode, key) {
+            KeymapResult::Matched(command) => {
+                let count = self.count.take();
+                command.execute(&mut Context { app: self, count });
+            }
+            KeymapResult::Pending => return,
+            KeymapResult::NotFound if self.mode == Mode::Insert => {
+                if let Some(ch) = key.char() {
+                    commands::insert_char(self, ch);
+                }
+            }
+            KeymapResult::NotFound | KeymapResult::Cancelled(_) => {
+                self.count = None;
+                self.operator = None;
+            }
+        }
+
+        self.finish_command();
+    }
+
+    /// Handles a mouse event, unless [`EditorConfig::mouse`](crate::EditorConfig::mouse) is off.
+    ///
+    /// A click places the cursor, a double click selects the word and a triple click the line
+    /// under it, and dragging selects in Visual mode. Clicking the gutter picks the whole line.
+    /// The wheel scrolls the view under the pointer, and a middle click pastes the primary
+    /// selection. Pressing any button in a view that has no focus focuses it first.
+    pub fn handle_mouse(&mut self, event: MouseEvent) {
+        if !self.config.editor.mouse
+            || self.prompt.is_some()
+            || self.picker.is_some()
+            || self.confirm.is_some()
+        {
+            return;
+        }
+        let (x, y) = (event.column, event.row);
+        let pointed = self.layout.view_at(self.views_area(self.area), x, y);
+        if let (MouseEventKind::Down(_), Some(view)) = (event.kind, pointed) {
+            self.focus_view(view);
+        }
+        let text_area = self.text_area(self.buffer(), self.view_area(self.focused_view));
+
+        match event.kind {
+            MouseEventKind::ScrollDown | MouseEventKind::ScrollUp => {
+                let Some(view) = pointed else {
+                    return;
+                };
+                let direction = match event.kind {
+                    MouseEventKind::ScrollDown => Direction::Forward,
+                    _ => Direction::Backward,
+                };
+                self.scroll_view(view, direction);
+            }
+            MouseEventKind::Down(MouseButton::Left) => {
+                let Some(pos) = self.text_pos(text_area, x, y) else {
+                    return;
+                };
+                let count = match self.last_click {
+                    Some(click)
+                        if (click.x, click.y) == (x, y)
+                            && click.at.elapsed() < MULTI_CLICK_TIMEOUT =>
+                    {
+                        click.count % 3 + 1
+                    }
+                    _ => 1,
+                };
+                self.last_click = Some(Click {
+                    at: Instant::now(),
+                    x,
+                    y,
+                    count,
+                });
+                self.operator = None;
+                self.count = None;
+
+                let buffer = self.buffer();
+                let word = TextObject::Word { big: false }.range(buffer, pos, true);
+                let (selection, visual) = match count {
+                    _ if x < text_area.x => (Selection::point(pos), Some(VisualKind::Line)),
+                    2 => match word {
+                        Some(word) => {
+                            let end = buffer.prev_grapheme_boundary(word.end);
+                            let range = crate::Range::new(word.start, end);
+                            (Selection::single(range), Some(VisualKind::Char))
+                        }
+                        None => (Selection::point(pos), None),
+                    },
+                    3 => (Selection::point(pos), Some(VisualKind::Line)),
+                    _ => (Selection::point(pos), None),
+                };
+                self.buffer_mut().set_selection(selection);
+                match visual {
+                    Some(kind) => {
+                        self.mode = Mode::Visual;
+                        self.visual = kind;
+                    }
+                    None if self.mode == Mode::Visual => self.mode = Mode::Normal,
+                    None => {}
+                }
+            }
+            MouseEventKind::Drag(MouseButton::Left) => {
+                let (Some(click), Some(pos)) = (self.last_click, self.text_pos(text_area, x, y))
+                else {
+                    return;
+                };
+                if self.mode != Mode::Visual {
+                    self.mode = Mode::Visual;
+                    self.visual = match click.x < text_area.x {
+                        true => VisualKind::Line,
+                        false => VisualKind::Char,
+                    };
+                }
+                let primary = self.buffer().selection().primary();
+                let selection = Selection::single(primary.put_head(pos));
+                self.buffer_mut().set_selection(selection);
+            }
+            MouseEventKind::Down(MouseButton::Middle) => {
+                let Some(pos) = self.text_pos(text_area, x, y) else {
+                    return;
+                };
+                self.buffer_mut().set_selection(Selection::point(pos));
+                if self.mode == Mode::Visual {
+                    self.mode = Mode::Normal;
+                }
+                self.register = Some('*');
+                if let Some(command) = commands::Command::find("paste_before") {
+                    command.execute(&mut Context {
+                        app: self,
+                        count: None,
+                    });
+                }
+            }
+            _ => return,
+        }
+
+        self.finish_command();
+    }
+
+    // Returns the position of the text drawn at `(x, y)` in `text_area`: the end of the line
+    // right of its end, and the start of the line left of the text.
+    fn text_pos(&self, text_area: Rect, x: u16, y: u16) -> Option<usize> {
+        if y < text_area.y || y >= text_area.bottom() || x >= text_area.right() {
+            return None;
+        }
+        let buffer = self.buffer();
+        let line = self.view().scroll + (y - text_area.y) as usize;
+        let line = line.min(buffer.len_lines() - 1);
+        let column = x.saturating_sub(text_area.x) as usize;
+
+        let (mut pos, end) = (buffer.line_start(line), buffer.line_end(line));
+        let mut width = 0;
+        while pos < end {
+            let next = buffer.next_grapheme_boundary(pos).min(end);
+            width += match buffer.slice(pos..next).to_string().as_str() {
+                "\t" => 4,
+                grapheme => grapheme.width(),
+            };
+            if width > column {
+                break;
+            }
+            pos = next;
+        }
+        Some(pos)
+    }
+
+    // Scrolls `view` by a few lines in `direction`. The cursor of the view with focus moves along
+    // if it would leave the lines in view.
+    fn scroll_view(&mut self, view: ViewId, direction: Direction) {
+        let height = self.view_area(view).height.saturating_sub(1);
+        let last = self.buffers[self.view_buffer(view)].len_lines() - 1;
+        let Some(View { scroll, .. }) = self.views.get_mut(&view) else {
+            return;
+        };
+        *scroll = match direction {
+            Direction::Forward => (*scroll + SCROLL_LINES).min(last),
+            Direction::Backward => scroll.saturating_sub(SCROLL_LINES),
+        };
+        if view != self.focused_view {
+            return;
+        }
+
+        let scroll = self.view().scroll;
+        let buffer = self.buffer();
+        let head = buffer.selection().primary().head;
+        let line = buffer.byte_to_pos(head).line;
+        let bottom = scroll + (height as usize).max(1) - 1;
+        let target = line.clamp(scroll, bottom.max(scroll));
+        if target != line {
+            let (direction, count) = match target > line {
+                true => (Direction::Forward, target - line),
+                false => (Direction::Backward, line - target),
+            };
+            let (pos, _) = buffer.move_vertically(head, direction, count, None);
+            self.buffer_mut().set_selection(Selection::point(pos));
+        }
+    }
+
+    // Clamps the cursors outside of Insert mode and ends the undo step of every buffer, but the
+    // one being inserted into so that a whole insertion is undone at once.
+    fn finish_command(&mut self) {
+        if self.mode != Mode::Insert {
+            commands::clamp_cursors(self);
+        }
+        for (index, buffer) in self.buffers.iter_mut().enumerate() {
+            if index != self.focus || self.mode != Mode::Insert {
+                buffer.commit();
+            }
+        }
+    }
+
+    fn handle_message(&mut self, message: Message) {
+        match message {
+            Message::Status(status) => self.set_status(status),
+            Message::LanguageServerStarted { language, result } => {
+                self.handle_language_server_started(language, result)
+            }
+            Message::LanguageServer(notification) => self.handle_notification(notification),
+            Message::Locations(locations) => self.goto_locations(locations),
+            Message::Completion(items) => self.complete(items),
+            Message::WorkspaceEdit(edit) => self.apply_workspace_edit(edit),
+            Message::DebuggerStarted(result) => self.handle_debugger_started(result),
+            Message::Debugger(event) => self.handle_debugger_event(event),
+            Message::StackFrames { thread, frames } => self.goto_stack_frames(thread, frames),
+            Message::Variables(variables) => self.show_variables(variables),
+            Message::GrepMatches { id, matches } => self.add_grep_matches(id, matches),
+            Message::GrepFinished { id, truncated } => self.finish_grep(id, truncated),
+            Message::Files { id, paths } => self.add_files(id, paths),
+            Message::Symbols(symbols) => self.show_symbols(symbols),
+        }
+
+        self.finish_command();
+    }
+
+    // Returns the earliest instant at which a timer fires.
+    fn next_deadline(&self) -> Option<Instant> {
+        let status = self.status.as_ref().map(|&(_, expiry)| expiry);
+        [status, self.keymaps.deadline(), self.diff_deadline]
+            .into_iter()
+            .flatten()
+            .min()
+    }
+
+    fn handle_timeout(&mut self) {
+        let now = Instant::now();
+
+        self.keymaps.expire(now);
+
+        if matches!(self.status, Some((_, expiry)) if expiry <= now) {
+            self.status = None;
+        }
+
+        if self.diff_deadline.map_or(false, |deadline| deadline <= now) {
+            self.diff_deadline = None;
+            self.buffers.iter_mut().for_each(Buffer::update_diff);
+        }
+    }
+
+    // Diffs the buffers whose text changed at most once per `DIFF_DELAY`, rather than on every
+    // edit.
+    fn schedule_diffs(&mut self) {
+        if self.diff_deadline.is_none() && self.buffers.iter().any(Buffer::diff_is_stale) {
+            self.diff_deadline = Some(Instant::now() + DIFF_DELAY);
+        }
+    }
+
+    fn draw(&mut self, terminal: &mut Terminal) -> Result<()> {
+        self.area = terminal.size();
+        // Keep the primary cursor of the view with focus in view.
+        let height = self.view_area(self.focused_view).height.saturating_sub(1);
+        let buffer = self.buffer();
+        let line = buffer.byte_to_pos(buffer.selection().primary().head).line;
+        self.view_mut().scroll_to(line, height as usize);
+        terminal.draw(|surface| self.render(surface))?;
+        Ok(())
+    }
+
+    // Returns the byte ranges of `buffer` drawn as selected by `selection`, the selection of a view
+    // that is `focused` or not.
+    fn selected_ranges(
+        &self,
+        buffer: &Buffer,
+        selection: &Selection,
+        focused: bool,
+    ) -> Vec<std::ops::Range<usize>> {
+        let primary = selection.primary();
+        let mode = match focused {
+            true => self.mode,
+            false => Mode::Normal,
+        };
+
+        match (mode, self.visual) {
+            (Mode::Visual, VisualKind::Char) => selection
+                .ranges()
+                .iter()
+                .map(|range| range.inclusive(buffer))
+                .collect(),
+            (Mode::Visual, VisualKind::Line) => selection
+                .ranges()
+                .iter()
+                .map(|range| {
+                    let first = buffer.byte_to_pos(range.from()).line;
+                    let last = buffer.byte_to_pos(range.to()).line;
+                    buffer.line_start(first)..buffer.line_end(last).max(range.to() + 1)
+                })
+                .collect(),
+            (Mode::Visual, VisualKind::Block) => selection
+                .ranges()
+                .iter()
+                .flat_map(|&range| commands::block_ranges(buffer, range))
+                .collect(),
+            // The terminal cursor shows the primary cursor of the view with focus; the others are
+            // drawn reversed.
+            _ => selection
+                .ranges()
+                .iter()
+                .filter(|&&range| !focused || range != primary)
+                .map(|range| {
+                    range.head
+                        ..buffer
+                            .next_grapheme_boundary(range.head)
+                            .max(range.head + 1)
+                })
+                .collect(),
+        }
+    }
+
+    // Returns the byte ranges within `visible` drawn as search matches.
+    fn searched_ranges(&self, visible: Range<usize>) -> Vec<Range<usize>> {
+        if let Some((found, _)) = self
+            .confirm
+            .as_ref()
+            .and_then(|confirm| confirm.pending.last())
+        {
+            return vec![found.clone()];
+        }
+        let Some(search) = &self.highlight else {
+            return Vec::new();
+        };
+        search
+            .find_iter(self.buffer().text(), visible)
+            .map_while(Result::ok)
+            .filter(|found| !found.is_empty())
+            .collect()
+    }
+
+    // Draws the completions of the command line on row `y`, scrolled so that the one in the line
+    // shows and highlighted.
+    fn render_completion(&self, surface: &mut Surface, completion: &Completion, y: u16) {
+        let area = Rect::new(surface.area().x, y, surface.area().width, 1);
+        let style = self.theme.get("ui.text");
+        let selected = style.patch(self.theme.get("ui.selection"));
+        surface.set_style(area, style);
+
+        // Each candidate takes its width and two columns before it.
+        let widths: Vec<_> = completion
+            .candidates
+            .iter()
+            .map(|candidate| candidate.width() + 2)
+            .collect();
+        let mut first = 0;
+        while first < completion.index
+            && widths[first..=completion.index].iter().sum::<usize>() > area.width as usize
+        {
+            first += 1;
+        }
+
+        let mut x = area.x + 1;
+        for (i, candidate) in completion.candidates.iter().enumerate().skip(first) {
+            let style = if i == completion.index {
+                selected
+            } else {
+                style
+            };
+            let width = area.right().saturating_sub(x) as usize;
+            x = surface.set_stringn(x, y, candidate, width, style).0 + 2;
+            if x >= area.right() {
+                break;
+            }
+        }
+    }
+
+    /// Returns the gutter of the buffer being edited, with the columns of
+    /// [`EditorConfig::gutter`](crate::EditorConfig::gutter).
+    pub fn gutter(&self) -> Gutter {
+        let buffer = self.buffer();
+        let cursor_line = buffer.byte_to_pos(buffer.selection().primary().head).line;
+        self.buffer_gutter(buffer, cursor_line)
+    }
+
+    // Returns the gutter of `buffer` with the cursor on `cursor_line`.
+    fn buffer_gutter(&self, buffer: &Buffer, cursor_line: usize) -> Gutter {
+        let mut gutter = Gutter::new(buffer.len_lines(), cursor_line);
+
+        for kind in &self.config.editor.gutter {
+            let column = match kind {
+                GutterColumnKind::Diagnostics => GutterColumn::Signs(self.diagnostic_signs(buffer)),
+                GutterColumnKind::Breakpoints => {
+                    let style = self.theme.get("ui.debug.breakpoint");
+                    let lines = buffer.breakpoints().iter();
+                    GutterColumn::Signs(lines.map(|&line| (line, ('●', style))).collect())
+                }
+                GutterColumnKind::LineNumbers => GutterColumn::LineNumbers {
+                    mode: match self.buffer_line_number(buffer) {
+                        LineNumber::Absolute => LineNumbers::Absolute,
+                        LineNumber::Relative => LineNumbers::Relative,
+                        LineNumber::Hybrid => LineNumbers::Hybrid,
+                        LineNumber::Off => continue,
+                    },
+                    style: self.theme.get("ui.linenr"),
+                    cursor_style: self.theme.get("ui.linenr.selected"),
+                },
+                GutterColumnKind::Diff => {
+                    let signs = buffer.diff().iter().map(|&(line, mark)| {
+                        let (sign, scope) = match mark {
+                            DiffMark::Added => ('▍', "diff.plus"),
+                            DiffMark::Modified => ('▍', "diff.delta"),
+                            DiffMark::Removed => ('▔', "diff.minus"),
+                        };
+                        (line, (sign, self.theme.get(scope)))
+                    });
+                    GutterColumn::Signs(signs.collect())
+                }
+                GutterColumnKind::Spacer => GutterColumn::Spacer,
+            };
+            gutter.push(column);
+        }
+        gutter
+    }
+
+    // Returns a sign for each line of `buffer` with a diagnostic, styled after the most severe
+    // one.
+    fn diagnostic_signs(&self, buffer: &Buffer) -> BTreeMap<usize, (char, Style)> {
+        let uri = buffer.path().and_then(language_server::uri);
+        let diagnostics = uri.and_then(|uri| self.diagnostics.get(&uri));
+        let mut signs = BTreeMap::new();
+        for diagnostic in diagnostics.into_iter().flatten() {
+            let severity = diagnostic
+                .severity
+                .unwrap_or(lsp::DiagnosticSeverity::ERROR);
+            let line = diagnostic.range.start.line as usize;
+            signs
+                .entry(line)
+                .and_modify(|most: &mut lsp::DiagnosticSeverity| *most = (*most).min(severity))
+                .or_insert(severity);
+        }
+        signs
+            .into_iter()
+            .map(|(line, severity)| {
+                let scope = match severity {
+                    lsp::DiagnosticSeverity::ERROR => "error",
+                    lsp::DiagnosticSeverity::WARNING => "warning",
+                    lsp::DiagnosticSeverity::INFORMATION => "info",
+                    _ => "hint",
+                };
+                (line, ('●', self.theme.get(scope)))
+            })
+            .collect()
+    }
+
+    // Returns the part of `area`, the area of a view showing `buffer`, that the text is drawn in:
+    // right of the gutter and above the status line.
+    fn text_area(&self, buffer: &Buffer, area: Rect) -> Rect {
+        let gutter = self.buffer_gutter(buffer, 0).width().min(area.width);
+        Rect::new(
+            area.x + gutter,
+            area.y,
+            area.width - gutter,
+            area.height.saturating_sub(1),
+        )
+    }
+
+    // Returns whether the open buffers are listed above the views.
+    fn shows_bufferline(&self) -> bool {
+        match self.config.editor.bufferline {
+            BufferLine::Never => false,
+            BufferLine::Always => true,
+            BufferLine::Multiple => self.buffers.len() > 1,
+        }
+    }
+
+    // Returns the part of `area`, the area of the editor, that the views are laid out in: below
+    // the bufferline, if it shows.
+    fn views_area(&self, area: Rect) -> Rect {
+        match self.shows_bufferline() {
+            true => Rect::new(
+                area.x,
+                area.y + 1,
+                area.width,
+                area.height.saturating_sub(1),
+            ),
+            false => area,
+        }
+    }
+
+    // Draws the name of every open buffer on the first row of `area`.
+    fn render_bufferline(&self, surface: &mut Surface, area: Rect) {
+        let active = self.theme.get("ui.bufferline.active");
+        let tabs = self.buffers.iter().enumerate().map(|(index, buffer)| {
+            let name = buffer.path().and_then(Path::file_name);
+            let name = name.map_or("[scratch]".into(), |name| name.to_string_lossy());
+            let modified = if buffer.is_modified() { " [+]" } else { "" };
+            let style = match index == self.focus {
+                true => active,
+                false => Style::default(),
+            };
+            Span::new(format!("{name}{modified}"), style)
+        });
+        let bufferline = TabLine::new(tabs.collect(), self.focus);
+        bufferline.render(surface, area, self.theme.get("ui.bufferline"));
+    }
+
+    /// Renders the editor into `surface` and returns the cursor position.
+    pub fn render(&self, surface: &mut Surface) -> Option<(u16, u16)> {
+        let area = surface.area();
+        let text_height = area.height.saturating_sub(1);
+        let mut cursor = None;
+
+        let views_area = self.views_area(area);
+        if views_area != area {
+            self.render_bufferline(surface, area);
+        }
+        for (view, view_area) in self.layout.areas(views_area) {
+            let view_cursor = self.render_view(surface, view, view_area);
+            if view == self.focused_view {
+                cursor = view_cursor;
+            }
+        }
+        let text_style = self.theme.get("ui.text");
+        let separator_style = text_style.patch(self.theme.get("ui.window"));
+        for separator in self.layout.separators(views_area) {
+            for y in separator.y..separator.bottom() {
+                surface.set_stringn(separator.x, y, "│", 1, separator_style);
+            }
+        }
+
+        if let Some(picker) = &self.picker {
+            let height = (text_height / 2).max(text_height.min(10));
+            let picker_area = Rect::new(area.x, area.y + text_height - height, area.width, height);
+            let style = PickerStyle {
+                prompt: self.theme.get("ui.statusline"),
+                text: text_style,
+                selected: text_style.patch(self.theme.get("ui.selection")),
+                matched: self.theme.get("ui.picker.matched"),
+            };
+            let preview = self.picker_preview(height as usize);
+            cursor = Some(picker.render(surface, picker_area, style, preview.as_ref()));
+        }
+
+        // Prompts and messages are shown over the status line of the bottom views.
+        let status_area = Rect::new(area.x, area.y + text_height, area.width, 1);
+        if let Some(completion) = self.completion.as_ref().filter(|c| c.candidates.len() > 1) {
+            self.render_completion(surface, completion, status_area.y.saturating_sub(1));
+        }
+        let confirm = self
+            .confirm
+            .as_ref()
+            .and_then(|confirm| confirm.pending.last());
+        let prompt = match (&self.prompt, confirm) {
+            (Some(prompt), _) => {
+                let before = &prompt.line()[..prompt.cursor()];
+                let x = status_area.x + 1 + before.width() as u16;
+                cursor = Some((x.min(status_area.right().saturating_sub(1)), status_area.y));
+                Some(format!("{}{}", prompt.prefix(), prompt.line()))
+            }
+            (None, Some((_, replacement))) => {
+                Some(format!("replace with {replacement} (y/n/a/q/l)?"))
+            }
+            (None, None) => None,
+        };
+        if let Some(status) = prompt.or_else(|| self.status().map(str::to_string)) {
+            let width = status_area.width as usize;
+            surface.set_style(status_area, self.theme.get("ui.statusline"));
+            surface.set_stringn(
+                status_area.x,
+                status_area.y,
+                &format!("{status:width$}"),
+                width,
+                Style::default(),
+            );
+        }
+
+        cursor
+    }
+
+    // Draws `view` into `area`, the text of its buffer above its status line, and returns the
+    // position of its primary cursor.
+    fn render_view(&self, surface: &mut Surface, view: ViewId, area: Rect) -> Option<(u16, u16)> {
+        let focused = view == self.focused_view;
+        let buffer = &self.buffers[self.view_buffer(view)];
+        let selection = match focused {
+            true => buffer.selection().clone(),
+            false => buffer
+                .view_selection(view)
+                .cloned()
+                .unwrap_or_else(|| Selection::point(0)),
+        };
+        let scroll = self.views[&view].scroll;
+        let text_height = area.height.saturating_sub(1);
+        let head = selection.primary().head;
+        let selected = self.selected_ranges(buffer, &selection, focused);
+        let is_selected = |byte: usize| selected.iter().any(|range| range.contains(&byte));
+        let mut cursor = None;
+
+        let last_line = (scroll + text_height as usize).min(buffer.len_lines());
+        let visible = buffer.line_start(scroll.min(last_line))..buffer.line_start(last_line);
+        let highlights = buffer
+            .syntax()
+            .map(|syntax| syntax.highlights(buffer.text(), visible.clone()))
+            .unwrap_or_default();
+        let mut highlights = highlights.iter().peekable();
+        let searched = match focused {
+            true => self.searched_ranges(visible.clone()),
+            false => Vec::new(),
+        };
+        let is_searched = |byte: usize| searched.iter().any(|range| range.contains(&byte));
+        let text_style = self.theme.get("ui.text");
+        let selection_style = self.theme.get("ui.selection");
+        let search_style = self.theme.get("ui.search");
+        surface.set_style(
+            Rect::new(area.x, area.y, area.width, text_height),
+            text_style,
+        );
+
+        let text_area = self.text_area(buffer, area);
+        let gutter_area = Rect::new(area.x, area.y, text_area.x - area.x, text_height);
+        surface.set_style(gutter_area, self.theme.get("ui.gutter"));
+        let cursor_line = buffer.byte_to_pos(head).line;
+        self.buffer_gutter(buffer, cursor_line)
+            .render(surface, gutter_area, scroll);
+
+        for (y, line) in (scroll..buffer.len_lines())
+            .take(text_height as usize)
+            .enumerate()
+        {
+            let y = text_area.y + y as u16;
+            let (start, end) = (buffer.line_start(line), buffer.line_end(line));
+            let mut x = text_area.x;
+            let mut pos = start;
+
+            loop {
+                if pos == head {
+                    cursor = Some((x, y));
+                }
+                // Highlight the line break so that selected empty lines stay visible.
+                let next = buffer.next_grapheme_boundary(pos).min(end);
+                let (symbol, width) = match next > pos {
+                    true => {
+                        let grapheme = buffer.slice(pos..next).to_string();
+                        match grapheme.as_str() {
+                            "\t" => ("    ".to_string(), 4),
+                            _ => {
+                                let width = grapheme.width() as u16;
+                                (grapheme, width)
+                            }
+                        }
+                    }
+                    false => (" ".to_string(), 1),
+                };
+
+                if x + width > text_area.right() {
+                    break;
+                }
+                while highlights.next_if(|(range, _)| range.end <= pos).is_some() {}
+                let style = match highlights.peek() {
+                    Some((range, scope)) if range.contains(&pos) => {
+                        text_style.patch(self.theme.get(scope))
+                    }
+                    _ => text_style,
+                };
+                let style = match is_searched(pos) {
+                    true => style.patch(search_style),
+                    false => style,
+                };
+                let style = match is_selected(pos) {
+                    true => style.patch(selection_style),
+                    false => style,
+                };
+                if next > pos || is_selected(pos) {
+                    surface.set_stringn(x, y, &symbol, width as usize, style);
+                }
+                x += width;
+
+                if next == pos {
+                    break;
+                }
+                pos = next;
+            }
+        }
+
+        if area.height == 0 {
+            return cursor;
+        }
+        let config = &self.config.editor.statusline;
+        let spans = |elements| self.statusline_spans(elements, buffer, head, focused);
+        let statusline = StatusLine {
+            left: spans(&config.left),
+            center: spans(&config.center),
+            right: spans(&config.right),
+        };
+        let style = match focused {
+            true => self.theme.get("ui.statusline"),
+            false => self.theme.get("ui.statusline.inactive"),
+        };
+        let status_area = Rect::new(area.x, area.y + text_height, area.width, 1);
+        statusline.render(surface, status_area, style);
+
+        cursor
+    }
+
+    // Returns the spans of the status line `elements` of a view of `buffer` with the primary
+    // cursor at `head`. The mode and pending keys only show in the view with focus.
+    fn statusline_spans(
+        &self,
+        elements: &[StatusLineElement],
+        buffer: &Buffer,
+        head: usize,
+        focused: bool,
+    ) -> Vec<Span> {
+        let mut spans = Vec::new();
+        for element in elements {
+            match element {
+                StatusLineElement::Mode if focused => {
+                    let scope = match self.mode {
+                        Mode::Normal | Mode::Command => "ui.statusline.normal",
+                        Mode::Insert => "ui.statusline.insert",
+                        Mode::Visual => "ui.statusline.visual",
+                    };
+                    spans.push(Span::new(self.mode.to_string(), self.theme.get(scope)));
+                }
+                StatusLineElement::FilePath => {
+                    let path = buffer.path().map(|path| path.display().to_string());
+                    spans.push(Span::raw(path.unwrap_or_else(|| "[scratch]".into())));
+                }
+                StatusLineElement::Modified if buffer.is_modified() => spans.push(Span::raw("[+]")),
+                StatusLineElement::Language => {
+                    let language = buffer
+                        .language()
+                        .and_then(|name| self.config.syntax.language(name));
+                    let name = language.map_or("text", |language| language.name.as_str());
+                    spans.push(Span::raw(name));
+                }
+                StatusLineElement::LspProgress => {
+                    if let Some((_, title, message)) = self.progress.last() {
+                        spans.push(Span::raw(format!("{title} {message}").trim_end()));
+                    }
+                }
+                StatusLineElement::Diagnostics => {
+                    let uri = buffer.path().and_then(language_server::uri);
+                    let diagnostics = uri.and_then(|uri| self.diagnostics.get(&uri));
+                    let diagnostics = diagnostics.into_iter().flatten();
+                    let count = |severity| {
+                        let severities = diagnostics.clone().map(|diagnostic| {
+                            diagnostic
+                                .severity
+                                .unwrap_or(lsp::DiagnosticSeverity::ERROR)
+                        });
+                        severities.filter(|&s| s == severity).count()
+                    };
+                    for (severity, scope) in [
+                        (lsp::DiagnosticSeverity::ERROR, "error"),
+                        (lsp::DiagnosticSeverity::WARNING, "warning"),
+                    ] {
+                        match count(severity) {
+                            0 => {}
+                            n => spans.push(Span::new(format!("●{n}"), self.theme.get(scope))),
+                        }
+                    }
+                }
+                StatusLineElement::Position => {
+                    let line = buffer.byte_to_pos(head).line;
+                    let column = buffer.grapheme_column(head);
+                    spans.push(Span::raw(format!("{}:{}", line + 1, column + 1)));
+                }
+                StatusLineElement::Encoding => spans.push(Span::raw("utf-8")),
+                StatusLineElement::LineEnding => spans.push(Span::raw(buffer.line_ending().name())),
+                StatusLineElement::PendingKeys if focused => {
+                    let count = self.count.map(|count| count.to_string());
+                    let pending: String =
+                        self.keymaps.pending().iter().map(Key::to_string).collect();
+                    spans.push(Span::raw(count.unwrap_or_default() + &pending));
+                }
+                _ => {}
+            }
+        }
+        spans
+    }
+}
+
+// Returns the matches of `search` that start on `lines`, with their replacements. Unless the
+// substitution is global, only the first match of each line is replaced.
+fn replacements(
+    buffer: &Buffer,
+    search: &Search,
+    substitution: &Substitution,
+    lines: RangeInclusive<usize>,
+) -> Result<Vec<(Range<usize>, String)>> {
+    let start = buffer.line_start(*lines.start());
+    let end = buffer.line_end(*lines.end());
+    let mut replacements = Vec::new();
+    let mut last_line = None;
+
+    for found in search.find_iter(buffer.text(), start..buffer.len_bytes()) {
+        let found = found?;
+        if found.start > end {
+            break;
+        }
+        let line = buffer.byte_to_pos(found.start).line;
+        if !substitution.global && last_line == Some(line) {
+            continue;
+        }
+        last_line = Some(line);
+        let replacement = search.expand(buffer.text(), found.clone(), &substitution.replacement);
+        replacements.push((found, replacement));
+    }
+
+    Ok(replacements)
+}
+
+// Returns the lines of `text` around `line` for a picker preview, `height` lines on either side of
+// it, or the first `height` lines without a line to show.
+fn preview_lines(text: &Rope, line: Option<usize>, height: usize) -> Preview {
+    let last = text.len_lines().saturating_sub(1);
+    let line = line.map(|line| line.min(last));
+    let first = line.map_or(0, |line| line.saturating_sub(height));
+    let lines = text
+        .lines_at(first)
+        .take(height * 2 + 1)
+        .map(|line| {
+            let line = line.to_string();
+            line.trim_end_matches(['\n', '\r']).replace('\t', "    ")
+        })
+        .collect();
+    Preview {
+        lines,
+        line: line.map(|line| line - first),
+    }
+}
+
+// Returns the file that registers persist in across sessions.
+fn registers_file() -> PathBuf {
+    metadata::cache_dir().join("registers.toml")
+}
+
+// Returns the directory that undo histories persist in across sessions.
+fn history_dir() -> PathBuf {
+    metadata::cache_dir().join("undo")
+}
+
+// Restores the undo history of `buffer`, discarding it if the file changed since it was saved.
+fn load_history(buffer: &mut Buffer) {
+    let Some(path) = buffer.path().map(|path| path.display().to_string()) else {
+        return;
+    };
+    match buffer.load_history(&history_dir()) {
+        Ok(true) => log::debug!("restored the undo history of {path}"),
+        Ok(false) => {}
+        Err(err) => log::warn!("discarding the undo history of {path}: {err:#}"),
+    }
+}
+
+// Marks the lines of `buffer` that changed since the file was last committed to git, if it was.
+fn load_diff_base(buffer: &mut Buffer) {
+    let Some(path) = buffer.path() else {
+        return;
+    };
+    match diff::diff_base(path) {
+        Ok(base) => buffer.set_diff_base(Some(base)),
+        Err(err) => log::debug!("no diff base for {}: {err:#}", path.display()),
+    }
+}
+
+// Moves the ends of the ranges of `selection` onto grapheme boundaries of `buffer`, the end of
+// the text at most, as a saved selection may not fit the file any more.
+fn clamp_selection(buffer: &Buffer, selection: Selection) -> Selection {
+    let clamp = |pos: usize| {
+        let pos = pos.min(buffer.len_bytes());
+        match buffer.is_grapheme_boundary(pos) {
+            true => pos,
+            false => buffer.prev_grapheme_boundary(pos),
+        }
+    };
+    let selection =
+        selection.transform(|range| crate::Range::new(clamp(range.anchor), clamp(range.head)));
+    selection.normalize()
+}
+
+// Sets the language of `buffer` from its path and contents.
+fn detect_language(config: &Config, buffer: &mut Buffer) {
+    let detection = config.syntax.detect(buffer.path(), Some(buffer.text()));
+    if let Some(detection) = detection {
+        log::debug!(
+            "detected {} from {} '{}'",
+            detection.language.name,
+            detection.by,
+            detection.matched
+        );
+    }
+    let language = detection.map(|detection| detection.language.name.clone());
+    buffer.set_language(language);
+}
+
+fn setup_terminal() -> Result<Terminal> {
+    // Restore the terminal before the default hook prints the panic message.
+    let hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        restore_terminal().ok();
+        hook(info);
+    }));
+
+    terminal::enable_raw_mode()?;
+    execute!(io::stdout(), EnterAlternateScreen)?;
+
+    Ok(Terminal::new(CrosstermBackend::new(io::stdout()))?)
+}
+
+fn restore_terminal() -> Result<()> {
+    execute!(
+        io::stdout(),
+        DisableMouseCapture,
+        LeaveAlternateScreen,
+        Show
+    )?;
+    terminal::disable_raw_mode()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{HighlightConfig, Range, Selection, StatusLineConfig, SyntaxConfig, Yank};
+    use crossterm::event::{KeyCode, KeyModifiers};
+    use tissue_tui::{Color, Modifier, TestBackend};
+
+    fn test_app(text: &str) -> App {
+        let mut app = App::new(Args::default(), Config::default(), Environment::new([])).unwrap();
+        app.buffers[0] = Buffer::from(text);
+        app
+    }
+
+    // Feeds `keys` to a buffer holding `before` and checks the result against `after`. `|` marks
+    // the cursors, the first being the primary, and named keys are written in angle brackets
+    // (`<esc>`, `<C-v>`).
+    #[track_caller]
+    fn assert_keys(before: &str, keys: &str, after: &str) {
+        let cursors: Vec<_> = before
+            .split('|')
+            .scan(0, |pos, part| {
+                *pos += part.len();
+                Some(Range::point(*pos))
+            })
+            .collect();
+        assert!(cursors.len() > 1, "missing cursor");
+        let mut app = test_app(&before.replace('|', ""));
+        let cursors = cursors[..cursors.len() - 1].to_vec();
+        app.buffer_mut().set_selection(Selection::new(cursors, 0));
+        type_keys(&mut app, keys);
+
+        let mut text = app.buffer().text().to_string();
+        for range in app.buffer().selection().ranges().iter().rev() {
+            text.insert(range.head, '|');
+        }
+        assert_eq!(text, after);
+    }
+
+    // Feeds `keys`, written as for `assert_keys`, to `app`.
+    fn type_keys(app: &mut App, keys: &str) {
+        let mut keys = keys.chars();
+        while let Some(c) = keys.next() {
+            let key: Key = match c {
+                '<' => keys.by_ref().take_while(|&c| c != '>').collect::<String>(),
+                c => c.to_string(),
+            }
+            .parse()
+            .unwrap();
+            app.handle_key(KeyEvent::new(key.code, key.modifiers));
+        }
+    }
+
+    #[test]
+    fn motions_take_counts() {
+        assert_keys("|one two three four", "2w", "one two |three four");
+        assert_keys("one two |three", "b", "one |two three");
+        assert_keys("|one two", "e", "on|e two");
+        assert_keys("|a\nb\nc\nd", "2j", "a\nb\n|c\nd");
+        assert_keys("ab|c\nd", "j", "abc\n|d");
+        assert_keys("|a,b,c", "2f,", "a,b|,c");
+        assert_keys("|a,b,c", "t,;;", "a,|b,c");
+        assert_keys("  |foo", "0", "|  foo");
+        assert_keys("|  foo", "$^", "  |foo");
+        assert_keys("|a\nb\nc", "G", "a\nb\n|c");
+        assert_keys("a\nb\n|c", "2gg", "a\n|b\nc");
+        assert_keys("|a\n\nb\n\nc", "2}", "a\n\nb\n|\nc");
+        assert_keys("|ab\n", "$$l", "a|b\n");
+    }
+
+    #[test]
+    fn registers_are_picked_with_quote() {
+        assert_keys("|one\ntwo\n", "\"ayyj\"Ayy\"aP", "one\n|one\ntwo\ntwo\n");
+        assert_keys("|a\nb\n", "yyj\"_ddP", "|a\na\n");
+        assert_keys("|a b\n", "\"qdw2\"qp", "ba a| \n");
+        assert_keys("|a b\n", "dwdw\"2P", "a| \n");
+        // Empty registers paste nothing.
+        assert_keys("|ab\n", "\"%p\"/p", "|ab\n");
+        // Nothing is deleted into a register that cannot hold it.
+        assert_keys("|a\nb\n", "\"%dd", "|a\nb\n");
+    }
+
+    #[test]
+    fn operators_combine_with_motions() {
+        assert_keys("|foo bar baz", "dw", "|bar baz");
+        assert_keys("|foo bar baz", "2dw", "|baz");
+        assert_keys("|foo bar baz", "d2w", "|baz");
+        assert_keys("foo |bar\nbaz", "dw", "foo| \nbaz");
+        assert_keys("|foo bar", "cwqux<esc>", "qu|x bar");
+        assert_keys("a|bc", "d$", "|a");
+        assert_keys("a(b|c)d", "di(", "a(|)d");
+        assert_keys("a \"b|c\" d", "da\"", "a |d");
+        assert_keys("|foo bar", "ciwx<esc>", "|x bar");
+        assert_keys("a|bcd", "dtd", "a|d");
+        assert_keys("abc|d", "dFa", "|d");
+    }
+
+    #[test]
+    fn linewise_operators() {
+        assert_keys("a\n|b\nc", "dd", "a\n|c");
+        assert_keys("a\nb\n|c", "dd", "a\n|b");
+        assert_keys("|a\nb\nc", "2dd", "|c");
+        assert_keys("|a\nb\nc", "dj", "|c");
+        assert_keys("a\n|b\nc", "yyp", "a\nb\n|b\nc");
+        assert_keys("a\n|b", "yyP", "a\n|b\nb");
+        assert_keys("a\n|b", "yyp", "a\nb\n|b");
+        assert_keys("  |a\nb", "ccx<esc>", "|x\nb");
+        assert_keys("|a\nb\nc", "dG", "|");
+    }
+
+    #[test]
+    fn editing_commands() {
+        assert_keys("|abc", "2x", "|c");
+        assert_keys("|abc", "xp", "b|ac");
+        assert_keys("|abc", "2rx", "x|xc");
+        assert_keys("a|bc", "D", "|a");
+        assert_keys("|ab", "ix<esc>", "|xab");
+        assert_keys("|ab", "ax<esc>", "a|xb");
+        assert_keys("|ab", "Ax<esc>", "ab|x");
+        assert_keys("  a|b", "Ix<esc>", "  |xab");
+        assert_keys("  |a", "ox<esc>", "  a\n  |x");
+        assert_keys("|a", "Ox<esc>", "|x\na");
+        assert_keys("a|b", "i<ret><esc>", "a\n|b");
+        assert_keys("ab|c", "a<backspace><backspace><esc>", "|a");
+    }
+
+    #[test]
+    fn undo_and_redo() {
+        // A whole insertion is undone at once, and puts the cursor back.
+        assert_keys("a|b", "ixyz<ret>w<esc>u", "a|b");
+        assert_keys("|ab", "xxuu", "|ab");
+        assert_keys("|ab", "xxuu<C-r>", "|b");
+        // A change after an undo starts a new branch, and `g-` goes back to the old one.
+        assert_keys("|abc", "xu$xg-", "|bc");
+    }
+
+    #[test]
+    fn search() {
+        assert_keys("|foo bar\nfoo bar", "/bar<ret>n", "foo bar\nfoo |bar");
+        assert_keys("|foo bar\nfoo bar", "?FOO<ret>", "|foo bar\nfoo bar");
+        assert_keys("|foo bar\nfoo bar", "?foo<ret>", "foo bar\n|foo bar");
+        assert_keys("|a b a b a", "/a<ret>2nN", "a b a b |a");
+        assert_keys("|ab", "/x<esc>", "|ab");
+        assert_keys("|a-b a-b", "/-b<ret>/<up><ret>", "a-b a|-b");
+
+        let mut app = test_app("one\ntwo\none");
+        app.buffer_mut().set_selection(Selection::point(10));
+        app.search("one", Direction::Forward);
+        assert_eq!(app.buffer().selection().primary().head, 0);
+        assert_eq!(app.status(), Some("search hit BOTTOM, continuing at TOP"));
+        app.search("three", Direction::Forward);
+        assert_eq!(app.status(), Some("Pattern not found: three"));
+        assert_eq!(app.registers.search_history(), ["one", "three"]);
+    }
+
+    #[tokio::test]
+    async fn grep_picker_opens_matches() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        std::fs::create_dir_all(dir.join(".git")).unwrap();
+        std::fs::write(dir.join("a.txt"), "one\ntwo needle\n").unwrap();
+        std::fs::write(dir.join("b.txt"), "three\n").unwrap();
+        let mut app = test_app("");
+        app.buffers[0] = Buffer::open(&dir.join("b.txt")).unwrap();
+        assert_eq!(app.workspace_root(), dir);
+
+        app.grep("Needle");
+        while app.grep.is_some() {
+            let message = app.receiver.recv().await.unwrap();
+            app.handle_message(message);
+        }
+        assert_eq!(app.status(), Some("0 match(es)"));
+
+        app.grep("needle");
+        while app.grep.is_some() {
+            let message = app.receiver.recv().await.unwrap();
+            app.handle_message(message);
+        }
+        assert_eq!(app.status(), Some("1 match(es)"));
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert!(app.picker.is_none());
+        assert_eq!(app.buffer().path(), Some(dir.join("a.txt").as_path()));
+        assert_eq!(app.buffer().selection().primary().head, 4);
+    }
+
+    #[tokio::test]
+    async fn file_picker_previews_and_opens_files() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        std::fs::create_dir_all(dir.join(".git")).unwrap();
+        std::fs::write(dir.join("alpha.txt"), "first\n\tsecond\n").unwrap();
+        std::fs::write(dir.join("beta.txt"), "").unwrap();
+        let mut app = test_app("");
+        app.buffers[0] = Buffer::open(&dir.join("beta.txt")).unwrap();
+
+        app.file_picker();
+        while app.picker.as_ref().unwrap().len() < 2 {
+            let message = app.receiver.recv().await.unwrap();
+            app.handle_message(message);
+        }
+        type_keys(&mut app, "alp");
+        let mut terminal = tissue_tui::Terminal::new(TestBackend::new(80, 6)).unwrap();
+        terminal.draw(|surface| app.render(surface)).unwrap();
+        let surface = terminal.backend().surface();
+        let row = |y| -> String {
+            (40..60)
+                .map(|x| surface.get(x, y).unwrap().symbol.as_str())
+                .collect()
+        };
+        assert_eq!(row(0).trim_end(), "│ first");
+        assert_eq!(row(1).trim_end(), "│     second");
+
+        type_keys(&mut app, "<ret>");
+        assert!(app.picker.is_none());
+        assert_eq!(app.buffer().path(), Some(dir.join("alpha.txt").as_path()));
+    }
+
+    // Waits for the next diagnostics the server publishes, and returns their document and count.
+    async fn next_diagnostics(
+        notifications: &mut UnboundedReceiver<Notification>,
+    ) -> (lsp::Url, usize) {
+        loop {
+            let notification = time::timeout(Duration::from_secs(5), notifications.recv());
+            if let Notification::PublishDiagnostics(params) =
+                notification.await.unwrap().expect("the server stopped")
+            {
+                return (params.uri, params.diagnostics.len());
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn files_open_in_running_language_servers() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        std::fs::write(dir.join("a.rs"), "fn main() {}\n").unwrap();
+        std::fs::write(dir.join("b.rs"), "// TODO\n").unwrap();
+        let (client, mut notifications) = tissue_lsp::mock::start(dir);
+        client.initialize().await.unwrap();
+        let uri = |name| language_server::uri(&dir.join(name)).unwrap();
+
+        let mut app = test_app("");
+        app.config.syntax = SyntaxConfig::load_default();
+        app.buffers[0] = Buffer::open(&dir.join("a.rs")).unwrap();
+        app.buffers[0].set_language(Some("rust".into()));
+        app.handle_message(Message::LanguageServerStarted {
+            language: "rust".into(),
+            result: Ok(Arc::new(client)),
+        });
+        assert_eq!(next_diagnostics(&mut notifications).await, (uri("a.rs"), 0));
+
+        app.open(&dir.join("b.rs")).unwrap();
+        app.sync_language_servers();
+        assert_eq!(next_diagnostics(&mut notifications).await, (uri("b.rs"), 1));
+        type_keys(&mut app, "OTODO<esc>");
+        app.sync_language_servers();
+        assert_eq!(next_diagnostics(&mut notifications).await, (uri("b.rs"), 2));
+
+        app.set_language("toml").unwrap();
+        app.sync_language_servers();
+        assert_eq!(next_diagnostics(&mut notifications).await, (uri("b.rs"), 0));
+        app.set_language("rust").unwrap();
+        app.sync_language_servers();
+        assert_eq!(next_diagnostics(&mut notifications).await, (uri("b.rs"), 2));
+    }
+
+    #[test]
+    fn pickers_run_the_picked_item() {
+        let mut app = test_app("a");
+        app.buffers.push(Buffer::from("b"));
+        type_keys(&mut app, "<space>b2<ret>");
+        assert_eq!(app.focus, 1);
+
+        type_keys(&mut app, "<space>?insert_mode<ret>");
+        assert_eq!(app.mode, Mode::Insert);
+
+        app.mode = Mode::Normal;
+        type_keys(&mut app, "<space>tnight<ret>");
+        assert_eq!(app.theme.name(), "night");
+
+        type_keys(&mut app, "<space>x");
+        assert!(app.picker.is_none());
+        assert_eq!(app.status(), Some("No diagnostics"));
+    }
+
+    #[test]
+    fn command_mode() {
+        assert_keys("|a1 a2\na3", ":%s/a/b/g<ret>", "b1 b2\n|b3");
+        assert_keys("a\n|a\na", "Vj:s/a/b<ret>", "a\nb\n|b");
+        assert_keys("|a", "ix<esc>:earlier<ret>", "|a");
+        assert_keys("|a", ":insert_mode<ret>x", "x|a");
+
+        let mut app = test_app("");
+        type_keys(&mut app, ":set mouse maybe<ret>");
+        assert_eq!(
+            app.status(),
+            Some("invalid value 'maybe' for mouse, expected one of true, false")
+        );
+        assert_eq!(app.mode, Mode::Normal);
+        type_keys(&mut app, ":set search.smart-case false<ret>");
+        assert!(!app.config.editor.search.smart_case);
+        type_keys(&mut app, ":lang rust<ret>");
+        assert_eq!(app.status(), Some("unknown language 'rust'"));
+        app.config.syntax = SyntaxConfig::load_default();
+        type_keys(&mut app, ":lang rust<ret>:lang<ret>");
+        assert_eq!(app.status(), Some("rust"));
+        type_keys(&mut app, ":nope<ret>");
+        assert_eq!(app.status(), Some("unknown command 'nope'"));
+        type_keys(&mut app, ":help wq<ret>");
+        assert_eq!(
+            app.status(),
+            Some(":wq - Save the current buffer and close the view or, from the last one, the editor")
+        );
+
+        // Tab cycles through the completions, and up browses the history.
+        type_keys(&mut app, ":the<tab> ni<tab>");
+        assert_eq!(app.prompt.as_ref().unwrap().line(), "theme night");
+        type_keys(&mut app, "<ret>");
+        assert_eq!(app.theme.name(), "night");
+        type_keys(&mut app, ":set search.w<tab> <tab><tab>");
+        assert_eq!(
+            app.prompt.as_ref().unwrap().line(),
+            "set search.wrap-around true"
+        );
+        type_keys(&mut app, "<esc>:<up><up>");
+        assert_eq!(app.prompt.as_ref().unwrap().line(), "help wq");
+        assert_eq!(app.mode, Mode::Command);
+    }
+
+    #[test]
+    fn auto_pairs() {
+        assert_keys("|", "i(x<esc>", "(|x)");
+        assert_keys("|", "i([])<esc>", "([]|)");
+        assert_keys("|a\n|b", "i{<esc>", "|{a\n|{b");
+        assert_keys("|", "i(<backspace>x<esc>", "|x");
+        assert_keys("(|)|x", "i<backspace><esc>", "|x");
+        assert_keys("|", "idon't \"<esc>", "don't |\"\"");
+        assert_keys("|", "i\"a\"<esc>", "\"a|\"");
+
+        let mut app = test_app("");
+        app.config.syntax = SyntaxConfig::load_default();
+        type_keys(&mut app, ":lang rust<ret>i'a<esc>");
+        assert_eq!(app.buffer().text(), "'a");
+        type_keys(&mut app, ":set auto-pairs false<ret>A(<esc>");
+        assert_eq!(app.buffer().text(), "'a(");
+
+        // Nothing is paired in strings and comments.
+        let config = HighlightConfig::new(tree_sitter_rust::language(), "").unwrap();
+        let mut app = test_app("");
+        let syntax = Syntax::new(app.buffer().text(), Arc::new(config)).unwrap();
+        app.buffer_mut().set_syntax(Some(syntax));
+        type_keys(&mut app, "i\"(<esc>o// [<esc>o(<esc>");
+        assert_eq!(app.buffer().text(), "\"(\"\n// [\n()");
+    }
+
+    #[test]
+    fn settings_apply_to_the_narrowest_scope() {
+        let mut app = test_app("");
+        app.config.syntax = SyntaxConfig::load_default();
+        type_keys(
+            &mut app,
+            ":set line-number relative<ret>:set line-number<ret>",
+        );
+        assert_eq!(app.status(), Some("line-number = relative"));
+        type_keys(&mut app, ":setlang line-number off<ret>");
+        assert_eq!(app.status(), Some("the buffer has no language"));
+
+        type_keys(&mut app, ":lang rust<ret>:setlang line-number off<ret>");
+        assert_eq!(app.line_number(), LineNumber::Off);
+        type_keys(&mut app, ":setlocal line-number hybrid<ret>");
+        assert_eq!(app.line_number(), LineNumber::Hybrid);
+        type_keys(&mut app, ":setlang line-number<ret>");
+        assert_eq!(app.status(), Some("line-number = off"));
+        assert_eq!(app.config.editor.line_number, LineNumber::Relative);
+
+        type_keys(&mut app, ":setlocal mouse false<ret>");
+        assert_eq!(
+            app.status(),
+            Some("mouse is a global setting, it cannot be set per language or buffer")
+        );
+        let err = app.set_setting(SettingScope::Global, "nope", "1");
+        assert!(err
+            .unwrap_err()
+            .to_string()
+            .starts_with("unknown setting 'nope'"));
+
+        type_keys(&mut app, ":setl <tab>");
+        assert_eq!(app.prompt.as_ref().unwrap().line(), "setl auto-pairs");
+    }
+
+    #[test]
+    fn clipboard_is_detected_in_the_environment_of_the_app() {
+        let mut app = test_app("");
+        assert_eq!(app.registers.clipboard().name(), "internal");
+
+        app.environment = Environment::new([("TERM".to_string(), "xterm".to_string())]);
+        type_keys(&mut app, ":set clipboard.provider osc52<ret>");
+        assert_eq!(app.registers.clipboard().name(), "osc52");
+        type_keys(&mut app, ":set clipboard.provider internal<ret>");
+        assert_eq!(app.registers.clipboard().name(), "internal");
+    }
+
+    #[test]
+    fn substitute() {
+        assert_keys(
+            "|a1 a2\na3",
+            r"<space>sa(\d)/b\1&/g<ret>",
+            "b1a1 b2a2\n|b3a3",
+        );
+        assert_keys("|a a\na", "<space>sa/x<ret>", "x a\n|x");
+        assert_keys("a\n|a\na\na", "Vj<space>sa/b<ret>", "a\nb\n|b\na");
+        // Each replacement is confirmed, and the whole substitution is undone at once.
+        assert_keys("|a a a", "<space>sa/b/gc<ret>yny", "|b a b");
+        assert_keys("|a a a", "<space>sa/b/gc<ret>nl", "|a b a");
+        assert_keys("|a a a", "<space>sa/b/gc<ret>aU", "|b b b");
+        assert_keys("|a a a", "<space>sa/b/g<ret>u", "|a a a");
+
+        let mut app = test_app("a\nb");
+        app.substitute("%s/b/c/x", 0..=0);
+        assert_eq!(
+            app.status(),
+            Some("invalid flag 'x', expected g, c, i or I")
+        );
+        app.substitute("%s/[ab]/c/", 0..=0);
+        assert_eq!(app.status(), Some("2 substitution(s) on 2 line(s)"));
+    }
+
+    #[test]
+    fn multiple_selections() {
+        assert_keys("|foo bar foo foo", "<C-n><C-n><C-n>cx<esc>", "|x bar |x |x");
+        assert_keys("|a foo\nfoo", "w<C-n><C-n><C-n>cx<esc>", "a |x\n|x");
+        // The selection is matched literally, wrapping around the end of the buffer.
+        assert_keys("a.b axb |a.b", "vll<C-n><C-n>cx<esc>", "|x axb |x");
+        assert_keys("|ab\ncd\nef", "Vjj<A-s><esc>", "a|b\nc|d\ne|f");
+        assert_keys("a |= 1\nbb |= 2", "&", "a  |= 1\nbb |= 2");
+        assert_keys("|a |b |c", "<A-)>", "|c |a |b");
+        assert_keys("|a |b |c", "<A-(><esc>", "|b c a");
+        // Overlapping selections merge, and undo and redo restore all of them.
+        assert_keys("|ab |c", "vlxx", "|");
+        assert_keys("|foo bar foo", "<C-n><C-n>du", "fo|o bar fo|o");
+        assert_keys("|foo bar foo", "<C-n><C-n>du<C-r>", "| bar| ");
+    }
+
+    #[test]
+    fn operators_take_shared_text_once() {
+        assert_keys("|a |b\nc\n", "dd", "|c\n");
+        assert_keys("|a |b\nc\n", "ddu", "|a |b\nc\n");
+        assert_keys("|a |b\nc\n", "yyp", "a b\n|a b\nc\n");
+        assert_keys("|a |b\nc\n", "d2w", "|\n");
+        assert_keys("|a |b\nc\n", "cwx<esc>", "|x\n");
+        assert_keys("|a |b\nc\n", "d$", "|\nc\n");
+        assert_keys("a\n|b\n|c", "dd", "|a");
+    }
+
+    #[test]
+    fn visual_mode() {
+        assert_keys("|foo bar", "vey", "|foo bar");
+        assert_keys("|foo bar", "ved", "| bar");
+        assert_keys("foo |bar", "vbd", "|ar");
+        assert_keys("a\n|b\nc", "Vjd", "|a");
+        assert_keys("ab|c\ndef", "<C-v>jhd", "|a\nd");
+        assert_keys("foo b|ar baz", "viwcx<esc>", "foo |x baz");
+        assert_keys("|abc", "lvlod", "|a");
+    }
+
+    #[test]
+    fn text_objects_can_be_rebound() {
+        let mut app = test_app("a(bc)d");
+        let operator = app.config.keys.get_mut(&KeymapMode::Operator).unwrap();
+        operator.merge(toml::from_str("i = \"no_op\"\nq = \"select_textobject_inner\"").unwrap());
+        type_keys(&mut app, "fbdi(dq(");
+        assert_eq!(app.buffer().text(), "a()d");
+        assert_eq!(app.mode, Mode::Normal);
+    }
+
+    #[test]
+    fn renders_selection_and_cursor() {
+        let mut app = test_app("abc\ndef\n");
+        app.buffer_mut().set_selection(Selection::point(1));
+        for c in "vj".chars() {
+            app.handle_key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE));
+        }
+        let mut terminal = tissue_tui::Terminal::new(TestBackend::new(16, 3)).unwrap();
+
+        terminal.draw(|surface| app.render(surface)).unwrap();
+
+        // The text starts after the gutter.
+        let gutter = app.gutter().width();
+        assert_eq!(terminal.backend().cursor(), (gutter + 1, 1));
+        let surface = terminal.backend().surface();
+        let reversed = |x, y| {
+            surface
+                .get(gutter + x, y)
+                .unwrap()
+                .modifier
+                .contains(Modifier::REVERSED)
+        };
+        assert!(!reversed(0, 0) && reversed(1, 0) && reversed(3, 0));
+        assert!(reversed(0, 1) && reversed(1, 1) && !reversed(2, 1));
+    }
+
+    #[test]
+    fn renders_buffer_and_status_line() {
+        let app = test_app("fn main() {\n\tprintln!();\n}\n");
+        let mut terminal = tissue_tui::Terminal::new(TestBackend::new(40, 4)).unwrap();
+
+        terminal.draw(|surface| app.render(surface)).unwrap();
+
+        terminal.backend().assert_lines(&[
+            "    1 fn main() {                       ",
+            "    2     println!();                   ",
+            "    3 }                                 ",
+            " NOR [scratch]        text 1:1 utf-8 LF ",
+        ]);
+    }
+
+    #[test]
+    fn statusline_segments_and_bufferline() {
+        let mut app = test_app("one\r\ntwo\r\n");
+        app.config.editor.statusline = StatusLineConfig {
+            left: vec![StatusLineElement::Mode, StatusLineElement::Modified],
+            center: vec![StatusLineElement::FilePath],
+            right: vec![StatusLineElement::Position, StatusLineElement::LineEnding],
+        };
+        app.config.editor.bufferline = BufferLine::Multiple;
+        type_keys(&mut app, "jix<esc>");
+        let mut terminal = tissue_tui::Terminal::new(TestBackend::new(30, 3)).unwrap();
+
+        terminal.draw(|surface| app.render(surface)).unwrap();
+        terminal.backend().assert_lines(&[
+            "    1 one                     ",
+            "    2 xtwo                    ",
+            " NOR [+]  [scratch]  2:1 CRLF ",
+        ]);
+
+        app.buffers.push(Buffer::new());
+        terminal.draw(|surface| app.render(surface)).unwrap();
+        terminal.backend().assert_lines(&[
+            " [scratch] [+]  [scratch]     ",
+            "    1 one                     ",
+            " NOR [+]  [scratch]  2:1 CRLF ",
+        ]);
+    }
+
+    #[test]
+    fn mouse_places_the_cursor_and_selects() {
+        let mut app = test_app("foo bar\n\tbaz\nqux\n");
+        app.area = Rect::new(0, 0, 20, 4);
+        let gutter = app.gutter().width();
+        let mouse = |app: &mut App, kind, x, y| {
+            app.handle_mouse(MouseEvent {
+                kind,
+                column: gutter + x,
+                row: y,
+                modifiers: KeyModifiers::NONE,
+            })
+        };
+        let click = |app: &mut App, x, y| mouse(app, MouseEventKind::Down(MouseButton::Left), x, y);
+        let selection = |app: &App| {
+            let primary = app.buffer().selection().primary();
+            (app.mode, primary.anchor, primary.head)
+        };
+
+        // Tabs take four columns, and clicks past the end of a line land on its last character.
+        click(&mut app, 5, 0);
+        assert_eq!(selection(&app), (Mode::Normal, 5, 5));
+        click(&mut app, 4, 1);
+        assert_eq!(selection(&app), (Mode::Normal, 9, 9));
+        click(&mut app, 10, 2);
+        assert_eq!(selection(&app), (Mode::Normal, 15, 15));
+
+        // Double clicks select the word, triple clicks the line.
+        click(&mut app, 5, 0);
+        click(&mut app, 5, 0);
+        assert_eq!(selection(&app), (Mode::Visual, 4, 6));
+        click(&mut app, 5, 0);
+        assert_eq!((app.mode, app.visual), (Mode::Visual, VisualKind::Line));
+
+        // Dragging selects, by lines from the gutter.
+        click(&mut app, 1, 0);
+        assert_eq!(selection(&app), (Mode::Normal, 1, 1));
+        mouse(&mut app, MouseEventKind::Drag(MouseButton::Left), 1, 1);
+        assert_eq!(selection(&app), (Mode::Visual, 1, 8));
+        assert_eq!(app.visual, VisualKind::Char);
+        app.mode = Mode::Normal;
+        app.handle_mouse(MouseEvent {
+            kind: MouseEventKind::Down(MouseButton::Left),
+            column: 0,
+            row: 2,
+            modifiers: KeyModifiers::NONE,
+        });
+        assert_eq!(selection(&app), (Mode::Visual, 13, 13));
+        assert_eq!(app.visual, VisualKind::Line);
+
+        // A middle click pastes the primary selection where it points.
+        app.registers
+            .store(
+                Some('*'),
+                Yank {
+                    text: "X".into(),
+                    linewise: false,
+                },
+                false,
+            )
+            .unwrap();
+        mouse(&mut app, MouseEventKind::Down(MouseButton::Middle), 4, 0);
+        assert_eq!(app.buffer().text(), "foo Xbar\n\tbaz\nqux\n");
+        assert_eq!(selection(&app), (Mode::Normal, 4, 4));
+
+        app.config.editor.mouse = false;
+        click(&mut app, 0, 0);
+        assert_eq!(selection(&app), (Mode::Normal, 4, 4));
+    }
+
+    #[test]
+    fn mouse_wheel_scrolls_the_view() {
+        let text: String = (0..20).map(|line| format!("{line}\n")).collect();
+        let mut app = test_app(&text);
+        app.area = Rect::new(0, 0, 20, 4);
+        let mut scroll = |kind| {
+            let modifiers = KeyModifiers::NONE;
+            app.handle_mouse(MouseEvent {
+                kind,
+                column: 0,
+                row: 0,
+                modifiers,
+            });
+            let buffer = app.buffer();
+            let line = buffer.byte_to_pos(buffer.selection().primary().head).line;
+            (app.view().scroll, line)
+        };
+
+        assert_eq!(scroll(MouseEventKind::ScrollDown), (3, 3));
+        assert_eq!(scroll(MouseEventKind::ScrollDown), (6, 6));
+        assert_eq!(scroll(MouseEventKind::ScrollUp), (3, 5));
+    }
+
+    #[test]
+    fn sessions_restore_buffers_views_and_registers() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let (one, two) = (dir.join("one.txt"), dir.join("two.txt"));
+        std::fs::write(&one, "one\nline\n").unwrap();
+        std::fs::write(&two, "two\nlines\nhere\n").unwrap();
+
+        let mut app = test_app("");
+        app.area = Rect::new(0, 0, 40, 20);
+        app.open(&one).unwrap();
+        type_keys(&mut app, "jwyy<C-w>v");
+        app.open(&two).unwrap();
+        type_keys(&mut app, "G<C-w>s");
+        app.resize_view(Split::Horizontal, 2);
+        let session = app.session();
+        assert_eq!(session.views.len(), 3);
+        assert_eq!(session.focus, 2);
+        let jump = JumpPoint {
+            buffer: 2,
+            selection: Selection::point(0),
+        };
+        assert_eq!(session.views[2].jumps, [jump]);
+
+        let mut restored = test_app("");
+        restored.restore_session(session.clone());
+        assert_eq!(restored.session(), session);
+        assert_eq!(restored.buffer().path(), Some(two.as_path()));
+        assert_eq!(restored.buffer().selection(), &Selection::point(10));
+        let views = restored.layout().views();
+        assert_eq!(restored.view_buffer(views[0]), 1);
+        assert_eq!(
+            restored.registers.get('"', None).unwrap().unwrap().text,
+            "line\n"
+        );
+
+        // Cursors past the end of a file that got shorter move to its end.
+        std::fs::write(&two, "two\n").unwrap();
+        restored.restore_session(session);
+        assert_eq!(restored.buffer().selection(), &Selection::point(4));
+    }
+
+    #[test]
+    fn jumps_go_back_and_forth() {
+        assert_keys("one\n|two\nthree", "G<C-o>", "one\n|two\nthree");
+        assert_keys("one\n|two\nthree", "Ggg<C-o><C-o><tab>", "one\ntwo\n|three");
+        assert_keys("|one\ntwo\none", "/one<ret>n2<C-o>", "|one\ntwo\none");
+
+        // Each view has its own jumplist, across buffers.
+        let mut app = test_app("one\ntwo\n");
+        app.buffers.push(Buffer::from("three\n"));
+        type_keys(&mut app, "j<C-w>vgn");
+        assert_eq!(app.focus, 1);
+        type_keys(&mut app, "<C-o>");
+        assert_eq!(app.focus, 0);
+        assert_eq!(app.buffer().selection(), &Selection::point(4));
+        type_keys(&mut app, "<C-w>h<C-o>");
+        assert_eq!(app.buffer().selection(), &Selection::point(4));
+    }
+
+    #[test]
+    fn views_split_focus_and_close() {
+        let mut app = test_app("one\ntwo\n");
+        app.area = Rect::new(0, 0, 21, 6);
+        app.config.editor.gutter.clear();
+        type_keys(&mut app, "<C-w>vj");
+        let (left, right) = (app.layout().views()[0], app.layout().views()[1]);
+        assert_eq!(app.focused_view, right);
+        assert_eq!(app.buffer().selection().primary().head, 4);
+
+        // Each view keeps its own cursors, mapped through edits made in the other.
+        type_keys(&mut app, "<C-w>h");
+        assert_eq!(app.focused_view, left);
+        assert_eq!(app.buffer().selection().primary().head, 0);
+        type_keys(&mut app, "ix<esc>");
+        assert_eq!(
+            app.buffer().view_selection(right),
+            Some(&Selection::point(5))
+        );
+
+        let mut terminal = tissue_tui::Terminal::new(TestBackend::new(21, 6)).unwrap();
+        terminal.draw(|surface| app.render(surface)).unwrap();
+        terminal.backend().assert_lines(&[
+            "xone      │xone      ",
+            "two       │two       ",
+            "          │          ",
+            "          │          ",
+            "          │          ",
+            " NOR [scr │ [scratch ",
+        ]);
+        assert_eq!(terminal.backend().cursor(), (0, 0));
+
+        // A split for a file that cannot be opened is closed again.
+        let temp = tempfile::tempdir().unwrap();
+        type_keys(&mut app, &format!(":split {}<ret>", temp.path().display()));
+        assert_eq!(app.layout().views().len(), 2);
+        assert_eq!(app.focused_view, left);
+        assert!(app.status().unwrap().starts_with("unable to open"));
+
+        type_keys(&mut app, "<C-w>sifoo<esc>:e b.txt<ret>");
+        assert_eq!(app.layout().views().len(), 3);
+        assert_eq!(app.buffers.len(), 2);
+        type_keys(&mut app, "<C-w>k");
+        assert_eq!(app.buffer().text(), "fooxone\ntwo\n");
+
+        // A click focuses the view under the pointer.
+        let click = MouseEvent {
+            kind: MouseEventKind::Down(MouseButton::Left),
+            column: 3,
+            row: 4,
+            modifiers: KeyModifiers::NONE,
+        };
+        app.handle_mouse(click);
+        assert_eq!(app.buffer().path(), Some(Path::new("b.txt")));
+        // The wheel scrolls the view under the pointer, without focusing it.
+        app.handle_mouse(MouseEvent {
+            kind: MouseEventKind::ScrollDown,
+            column: 15,
+            ..click
+        });
+        assert_eq!(app.views[&right].scroll, 2);
+        assert_eq!(app.buffer().path(), Some(Path::new("b.txt")));
+
+        type_keys(&mut app, ":q<ret>");
+        assert_eq!((app.layout().views().len(), app.exit_code()), (2, None));
+        type_keys(&mut app, "<C-w>o");
+        assert_eq!(app.layout().views().len(), 1);
+        assert_eq!(app.views.len(), 1);
+        type_keys(&mut app, ":close<ret>");
+        assert_eq!(app.status(), Some("cannot close the last view"));
+    }
+
+    #[test]
+    fn diffs_are_updated_after_a_delay() {
+        let mut app = test_app("a\n");
+        app.buffer_mut().set_diff_base(Some(Rope::from("a\n")));
+        type_keys(&mut app, "ox<esc>");
+        app.schedule_diffs();
+        let deadline = app.diff_deadline.unwrap();
+        assert!(app.buffer().diff().is_empty());
+
+        app.diff_deadline = Some(deadline - DIFF_DELAY);
+        app.handle_timeout();
+        assert_eq!(app.buffer().diff(), [(1, DiffMark::Added)]);
+        assert_eq!(app.next_deadline(), None);
+    }
+
+    #[test]
+    fn renders_the_gutter() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("gutter.rs");
+        let mut app = test_app("");
+        app.buffers[0] = Buffer::open(&path).unwrap();
+        app.buffer_mut().apply(&Transaction::insert(0, "a\nB\nc\n"));
+        app.buffer_mut().set_selection(Selection::point(2));
+        app.buffer_mut().toggle_breakpoint(1);
+        app.buffer_mut()
+            .set_diff_base(Some(Rope::from("a\nb\nc\nd\n")));
+        let diagnostic = |line, severity| lsp::Diagnostic {
+            range: lsp::Range::new(lsp::Position::new(line, 0), lsp::Position::new(line, 1)),
+            severity: Some(severity),
+            ..Default::default()
+        };
+        app.diagnostics.insert(
+            language_server::uri(&path).unwrap(),
+            vec![
+                diagnostic(0, lsp::DiagnosticSeverity::WARNING),
+                diagnostic(2, lsp::DiagnosticSeverity::HINT),
+                diagnostic(0, lsp::DiagnosticSeverity::ERROR),
+            ],
+        );
+        app.config.editor.line_number = LineNumber::Relative;
+        app.config.editor.gutter = vec![
+            GutterColumnKind::Breakpoints,
+            GutterColumnKind::Diagnostics,
+            GutterColumnKind::LineNumbers,
+            GutterColumnKind::Spacer,
+            GutterColumnKind::Diff,
+        ];
+        app.set_status("ok");
+        let mut terminal = tissue_tui::Terminal::new(TestBackend::new(10, 5)).unwrap();
+
+        terminal.draw(|surface| app.render(surface)).unwrap();
+
+        let backend = terminal.backend();
+        backend.assert_lines(&[
+            " ●  1  a  ",
+            "●   0 ▍B  ",
+            " ●  1 ▔c  ",
+            "    2     ",
+            "ok        ",
+        ]);
+        assert_eq!(backend.cursor(), (7, 1));
+        let fg = |x, y| backend.surface().get(x, y).unwrap().fg;
+        assert_eq!(fg(1, 0), app.theme.get("error").fg.unwrap());
+        assert_eq!(fg(1, 2), app.theme.get("hint").fg.unwrap());
+        assert_eq!(fg(0, 1), app.theme.get("ui.debug.breakpoint").fg.unwrap());
+        assert_eq!(fg(6, 1), app.theme.get("diff.delta").fg.unwrap());
+
+        app.config.editor.line_number = LineNumber::Off;
+        assert_eq!(app.gutter().width(), 4);
+    }
+
+    #[test]
+    fn renders_syntax_highlights() {
+        let mut app = test_app("fn main() {}\nlet\n");
+        let config = HighlightConfig::new(
+            tree_sitter_rust::language(),
+            "\"fn\" @keyword.function (identifier) @function",
+        )
+        .unwrap();
+        let syntax = Syntax::new(app.buffer().text(), Arc::new(config)).unwrap();
+        app.buffer_mut().set_syntax(Some(syntax));
+        app.buffer_mut().apply(&Transaction::insert(3, "x"));
+        let mut terminal = tissue_tui::Terminal::new(TestBackend::new(16, 3)).unwrap();
+
+        terminal.draw(|surface| app.render(surface)).unwrap();
+
+        let surface = terminal.backend().surface();
+        let fg = |x| surface.get(app.gutter().width() + x, 0).unwrap().fg;
+        assert_eq!(fg(0), app.theme.get("keyword").fg.unwrap());
+        assert_eq!(fg(3), app.theme.get("function").fg.unwrap());
+        assert_eq!(fg(8), Color::Reset);
+    }
+
+    #[test]
+    fn tab_inserts_the_indent_unit_of_the_language() {
+        let mut app = test_app("x\n");
+        app.config.syntax = SyntaxConfig::load_default();
+        for (language, expected) in [(None, "\tx\n"), (Some("python"), "    \tx\n")] {
+            app.buffer_mut().set_language(language.map(String::from));
+            app.handle_key(KeyEvent::new(KeyCode::Char('i'), KeyModifiers::NONE));
+            app.handle_key(KeyEvent::new(KeyCode::Tab, KeyModifiers::NONE));
+            app.handle_key(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE));
+            assert_eq!(app.buffer().text().to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn language_server_results_are_applied() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("app.txt");
+        std::fs::write(&path, "foo bar\nfoo\n").unwrap();
+        let mut app = App::new(
+            Args {
+                files: vec![path.clone()],
+                ..Args::default()
+            },
+            Config::default(),
+            Environment::new([]),
+        )
+        .unwrap();
+        let uri = language_server::uri(&path).unwrap();
+        let range = |line, start, end| {
+            lsp::Range::new(
+                lsp::Position::new(line, start),
+                lsp::Position::new(line, end),
+            )
+        };
+
+        let diagnostic = |line, message: &str| lsp::Diagnostic {
+            range: range(line, 0, 3),
+            message: message.into(),
+            ..Default::default()
+        };
+        app.handle_message(Message::LanguageServer(Notification::PublishDiagnostics(
+            lsp::PublishDiagnosticsParams::new(
+                uri.clone(),
+                vec![diagnostic(1, "second"), diagnostic(0, "first")],
+                None,
+            ),
+        )));
+        app.buffer_mut().set_selection(Selection::point(2));
+        commands::Command::find("goto_next_diagnostic")
+            .unwrap()
+            .execute(&mut Context {
+                app: &mut app,
+                count: None,
+            });
+        assert_eq!(app.buffer().selection().primary().head, 8);
+        assert_eq!(app.status(), Some("second"));
+
+        app.handle_message(Message::Locations(vec![lsp::Location::new(
+            uri.clone(),
+            range(0, 4, 7),
+        )]));
+        assert_eq!(app.buffer().selection().primary().head, 4);
+
+        let edits = vec![
+            lsp::TextEdit::new(range(1, 0, 3), "baz".into()),
+            lsp::TextEdit::new(range(0, 0, 3), "baz".into()),
+        ];
+        app.handle_message(Message::WorkspaceEdit(lsp::WorkspaceEdit::new(
+            [(uri.clone(), edits)].into_iter().collect(),
+        )));
+        assert_eq!(app.buffer().text(), "baz bar\nbaz\n");
+
+        // Overlapping or reversed edits are refused, and focus stays on the buffer edited.
+        app.open(Path::new("other.txt")).unwrap();
+        for (first, second) in [
+            (range(0, 0, 5), range(0, 2, 3)),
+            (range(0, 3, 1), range(1, 0, 0)),
+        ] {
+            let edits = vec![
+                lsp::TextEdit::new(first, "x".into()),
+                lsp::TextEdit::new(second, "y".into()),
+            ];
+            app.handle_message(Message::WorkspaceEdit(lsp::WorkspaceEdit::new(
+                [(uri.clone(), edits)].into_iter().collect(),
+            )));
+            assert_eq!(
+                app.status(),
+                Some(format!("Invalid edit for {uri}").as_str())
+            );
+            assert_eq!(app.buffer().path(), Some(Path::new("other.txt")));
+        }
+        app.focus = 0;
+        assert_eq!(app.buffer().text(), "baz bar\nbaz\n");
+
+        app.mode = Mode::Insert;
+        app.buffer_mut().set_selection(Selection::point(11));
+        let item = |label: &str| lsp::CompletionItem::new_simple(label.into(), String::new());
+        app.handle_message(Message::Completion(vec![
+            item("bazooka"),
+            item("bazaar"),
+            item("qux"),
+        ]));
+        assert_eq!(app.buffer().text(), "baz bar\nbaz\n");
+        assert_eq!(app.status(), Some("bazooka bazaar"));
+        app.handle_message(Message::Completion(vec![item("bazooka")]));
+        assert_eq!(app.buffer().text(), "baz bar\nbazooka\n");
+    }
+
+    #[test]
+    fn debugger_breakpoints_and_frames() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("debug.rs");
+        std::fs::write(&path, "fn main() {\n    let x = 1;\n}\n").unwrap();
+        let mut app = test_app("");
+
+        app.start_debugger(None, &[]);
+        assert_eq!(
+            app.status(),
+            Some("No debugger is configured for this buffer")
+        );
+
+        app.handle_message(Message::StackFrames {
+            thread: 1,
+            frames: vec![StackFrame {
+                id: 1,
+                name: "main".into(),
+                source: Some(dap::types::Source {
+                    name: None,
+                    path: Some(path.clone()),
+                }),
+                line: 2,
+                column: 5,
+            }],
+        });
+        assert_eq!(app.buffer().path(), Some(path.as_path()));
+        assert_eq!(app.buffer().selection().primary().head, 16);
+
+        app.handle_key(KeyEvent::new(KeyCode::F(9), KeyModifiers::NONE));
+        assert_eq!(app.buffer().breakpoints(), [1]);
+        app.handle_key(KeyEvent::new(KeyCode::Char('O'), KeyModifiers::SHIFT));
+        assert_eq!(app.buffer().breakpoints(), [2]);
+
+        app.handle_message(Message::Variables(vec![Variable {
+            name: "x".into(),
+            value: "1".into(),
+            ty: None,
+            variables_reference: 0,
+        }]));
+        assert_eq!(app.status(), Some("x = 1"));
+    }
+
+    #[test]
+    fn quit_reports_unsaved_buffers() {
+        let mut app = test_app("");
+        app.quit(false);
+        assert_eq!(app.exit_code(), Some(EXIT_OK));
+
+        let mut app = test_app("");
+        app.buffer_mut().insert(0, "x");
+        app.quit(false);
+        assert_eq!(app.exit_code(), None);
+        assert!(app.status().unwrap().contains("[scratch]"));
+
+        app.quit(true);
+        assert_eq!(app.exit_code(), Some(EXIT_UNSAVED));
     }
 }
@@ -26,6 +26,7 @@ pub struct Args {
     pub config_file: Option<PathBuf>,
     pub log_file: Option<PathBuf>,
     pub verbosity: Verbosity,
+    pub session: Option<String>,
     pub files: Vec<PathBuf>,
 }
 
@@ -33,7 +34,8 @@ pub struct Args {
 pub enum Cli {
     Help,
     Version,
-    Health(Option<String>),
+    Health(Option<String>, Option<PathBuf>),
+    Detect(PathBuf),
     Options(Args),
 }
 
@@ -47,16 +49,18 @@ impl Cli {
     /// Parses the command line arguments.
     pub fn parse() -> Result<Self> {
         let mut args = Args::default();
+        let mut health = None;
         let mut argv = std::env::args().skip(1).peekable();
 
         while let Some(arg) = argv.next() {
             match arg.as_str() {
                 "-h" | "--help" => return Ok(Self::Help),
                 "-V" | "--version" => return Ok(Self::Version),
-                "--health" => {
-                    let arg = argv.next_if(|v| !v.starts_with('-'));
-                    return Ok(Self::Health(arg));
-                }
+                "--health" => health = Some(argv.next_if(|v| !v.starts_with('-'))),
+                "--detect" => match argv.next() {
+                    Some(path) => return Ok(Self::Detect(path.into())),
+                    None => bail!("missing [FILE] argument for '{}' option", arg),
+                },
                 "-c" | "--config" => match argv.next().as_deref() {
                     Some(path) => args.config_file = Some(path.into()),
                     None => bail!("missing [PATH] argument for '{}' option", arg),
@@ -65,6 +69,10 @@ impl Cli {
                     Some(path) => args.log_file = Some(path.into()),
                     None => bail!("missing [PATH] argument for '{}' option", arg),
                 },
+                "--session" => match argv.next() {
+                    Some(name) => args.session = Some(name),
+                    None => bail!("missing [NAME] argument for '{}' option", arg),
+                },
                 "-v" => match argv.next().as_deref() {
                     Some(level) => {
                         args.verbosity = match level {
@@ -84,6 +92,11 @@ impl Cli {
 
         argv.for_each(|arg| args.files.push(PathBuf::from(arg)));
 
+        // The health check reads the configuration file, which may be given after `--health`.
+        if let Some(category) = health {
+            return Ok(Self::Health(category, args.config_file));
+        }
+
         Ok(Self::Options(args))
     }
 }
@@ -0,0 +1,156 @@
+//! Brackets and quotes closed as they are typed in Insert mode.
+
+use crate::{Buffer, Range, Selection, Transaction};
+use anyhow::{anyhow, Error};
+use serde::Deserialize;
+
+/// The pairs used by languages that do not declare their own.
+pub const DEFAULT_PAIRS: &[(char, char)] = &[
+    ('(', ')'),
+    ('[', ']'),
+    ('{', '}'),
+    ('"', '"'),
+    ('\'', '\''),
+    ('`', '`'),
+];
+
+/// The pairs of a language, declared as `auto-pairs = ["()", "[]", "\"\""]`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "Vec<String>")]
+pub struct AutoPairs(Vec<(char, char)>);
+
+impl Default for AutoPairs {
+    fn default() -> Self {
+        Self(DEFAULT_PAIRS.to_vec())
+    }
+}
+
+impl TryFrom<Vec<String>> for AutoPairs {
+    type Error = Error;
+
+    fn try_from(pairs: Vec<String>) -> Result<Self, Error> {
+        let pairs = pairs.iter().map(|pair| {
+            let mut chars = pair.chars();
+            match (chars.next(), chars.next(), chars.next()) {
+                (Some(open), Some(close), None) => Ok((open, close)),
+                _ => Err(anyhow!("invalid pair '{pair}', expected two characters")),
+            }
+        });
+        Ok(Self(pairs.collect::<Result<_, Error>>()?))
+    }
+}
+
+impl AutoPairs {
+    /// Returns the pairs, opening character first.
+    pub fn pairs(&self) -> &[(char, char)] {
+        &self.0
+    }
+
+    fn closer(&self, open: char) -> Option<char> {
+        let pair = self.0.iter().find(|(o, _)| *o == open);
+        pair.map(|(_, close)| *close)
+    }
+
+    fn is_closer(&self, ch: char) -> bool {
+        self.0.iter().any(|(_, close)| *close == ch)
+    }
+
+    /// Types `ch` at every cursor of `buffer`, in Insert mode.
+    ///
+    /// Typing a closing character right before the same character steps over it. Typing an
+    /// opening character inserts its pair around the cursor, unless the cursor is before a word
+    /// or `in_string_or_comment` says it is in a string or comment. A quote is not paired right
+    /// after a word either, so that apostrophes stay single.
+    pub fn insert(
+        &self,
+        buffer: &Buffer,
+        ch: char,
+        in_string_or_comment: impl Fn(usize) -> bool,
+    ) -> (Transaction, Selection) {
+        let len = ch.len_utf8();
+        let edits = buffer.selection().ranges().iter().map(|range| {
+            let pos = range.head;
+            let next = buffer.char_at(pos);
+            let prev = buffer.chars_before(pos).next().map(|(_, c)| c);
+
+            if next == Some(ch) && self.is_closer(ch) {
+                return (pos, String::new(), len);
+            }
+            let pair = self.closer(ch).filter(|&close| {
+                let before_word = next.map_or(false, |c| !c.is_whitespace() && !self.is_closer(c));
+                let apostrophe = ch == close && prev.map_or(false, char::is_alphanumeric);
+                !before_word && !apostrophe && !in_string_or_comment(pos)
+            });
+            match pair {
+                Some(close) => (pos, format!("{ch}{close}"), len),
+                None => (pos, ch.to_string(), len),
+            }
+        });
+        edit(buffer, edits.collect())
+    }
+
+    /// Deletes the character before every cursor of `buffer`, in Insert mode, along with the
+    /// closing character after the cursor if the two make an empty pair.
+    ///
+    /// Cursors whose deletions overlap, like one in an empty pair and one after it, delete the
+    /// text between them once.
+    pub fn delete_backward(&self, buffer: &Buffer) -> (Transaction, Selection) {
+        let mut changes: Vec<(usize, usize)> = Vec::new();
+        let deletions = buffer.selection().ranges().iter().map(|range| {
+            let pos = range.head;
+            let from = buffer.prev_grapheme_boundary(pos);
+            let prev = buffer.chars_before(pos).next().map(|(_, c)| c);
+            let empty_pair = prev
+                .and_then(|open| self.closer(open))
+                .filter(|&close| buffer.char_at(pos) == Some(close));
+            let to = pos + empty_pair.map_or(0, char::len_utf8);
+            (from, to)
+        });
+        for (from, to) in deletions {
+            match changes.last_mut() {
+                Some(last) if from < last.1 => last.1 = last.1.max(to),
+                _ => changes.push((from, to)),
+            }
+        }
+
+        let transaction = Transaction::delete(changes.iter().map(|&(from, to)| from..to));
+        let selection = buffer.selection().clone().map(&transaction);
+        (transaction, selection)
+    }
+}
+
+// Inserts the text of each `(pos, text, offset)` at `pos` and puts the cursor `offset` bytes after
+// it.
+fn edit(buffer: &Buffer, edits: Vec<(usize, String, usize)>) -> (Transaction, Selection) {
+    let mut delta = 0;
+    let cursors = edits
+        .iter()
+        .map(|(pos, text, offset)| {
+            let cursor = Range::point(pos + delta + offset);
+            delta += text.len();
+            cursor
+        })
+        .collect();
+    let primary = buffer.selection().primary_index();
+    let changes = edits.into_iter().map(|(pos, text, _)| (pos, pos, text));
+    (
+        Transaction::change(changes),
+        Selection::new(cursors, primary),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_pairs() {
+        let pairs = AutoPairs::try_from(vec!["()".to_string(), "\"\"".to_string()]).unwrap();
+        assert_eq!(pairs.pairs(), [('(', ')'), ('"', '"')]);
+        let err = AutoPairs::try_from(vec!["(])".to_string()]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid pair '(])', expected two characters"
+        );
+    }
+}
@@ -0,0 +1,892 @@
+mod grapheme;
+
+use crate::{
+    diff, Assoc, DiffMark, History, Jump, LocalSettings, Selection, Syntax, Transaction, UndoKind,
+};
+use anyhow::{anyhow, Result};
+use ropey::{Rope, RopeSlice};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+use tissue_tui::ViewId;
+
+/// A line/column position in a buffer.
+///
+/// Both fields are zero-based. The column is a byte offset from the start of the line.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Position {
+    /// Line index.
+    pub line: usize,
+    /// Byte offset within the line.
+    pub column: usize,
+}
+
+impl Position {
+    /// Creates a new position.
+    pub const fn new(line: usize, column: usize) -> Self {
+        Self { line, column }
+    }
+}
+
+/// Direction of a cursor movement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Towards the end of the text.
+    Forward,
+    /// Towards the start of the text.
+    Backward,
+}
+
+/// The line ending a buffer is written with.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    /// `\n`, as on Unix.
+    #[default]
+    Lf,
+    /// `\r\n`, as on Windows.
+    Crlf,
+}
+
+impl LineEnding {
+    /// Returns the line ending of the first line of `text`, or [`LineEnding::Lf`] if it has a
+    /// single line.
+    pub fn detect(text: &Rope) -> Self {
+        let line = text.line(0);
+        let len = line.len_bytes();
+        match len >= 2 && line.byte(len - 2) == b'\r' && line.byte(len - 1) == b'\n' {
+            true => Self::Crlf,
+            false => Self::Lf,
+        }
+    }
+
+    /// Returns the name of the line ending, as shown in the status line.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Lf => "LF",
+            Self::Crlf => "CRLF",
+        }
+    }
+}
+
+/// A text buffer backed by a rope.
+///
+/// All offsets are byte offsets into the UTF-8 text. Edits must start and end on character
+/// boundaries, the same as with [`String`].
+#[derive(Debug, Default, Clone)]
+pub struct Buffer {
+    text: Rope,
+    // The line ending of the file when it was opened, which lines ending with `\n` alone are
+    // written with.
+    line_ending: LineEnding,
+    path: Option<PathBuf>,
+    modified: bool,
+    selection: Selection,
+    // The selections of the views showing the buffer that are not focused.
+    view_selections: HashMap<ViewId, Selection>,
+    language: Option<String>,
+    // Settings of the `[editor]` section that differ for the buffer.
+    settings: LocalSettings,
+    version: i32,
+    // Lines with a breakpoint, in ascending order.
+    breakpoints: Vec<usize>,
+    syntax: Option<Syntax>,
+    // The committed version of the file, and the lines that differ from it, which are out of date
+    // when `diff_stale`.
+    diff_base: Option<Rope>,
+    diff: Vec<(usize, DiffMark)>,
+    diff_stale: bool,
+    // Transactions applied since the last call to `take_changes`, with the text before each.
+    changes: Vec<(Rope, Transaction)>,
+    history: History,
+    // The revision of the history that was last opened or saved.
+    saved_revision: usize,
+}
+
+impl Buffer {
+    /// Creates an empty buffer that is not associated with a file.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Opens the file at `path`. A missing file yields an empty buffer that will be created on
+    /// save.
+    pub fn open(path: &Path) -> Result<Self> {
+        let text = match File::open(path) {
+            Ok(file) => Rope::from_reader(BufReader::new(file))?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Rope::new(),
+            Err(err) => return Err(err.into()),
+        };
+
+        Ok(Self {
+            line_ending: LineEnding::detect(&text),
+            text,
+            path: Some(path.to_path_buf()),
+            modified: false,
+            ..Self::default()
+        })
+    }
+
+    /// Writes the buffer to its file.
+    ///
+    /// The text is written to a temporary file next to it, which then replaces the file, so that
+    /// a failed write leaves the file as it was.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the buffer has no path or the file cannot be written.
+    pub fn save(&mut self) -> Result<()> {
+        let path = self
+            .path
+            .as_ref()
+            .ok_or_else(|| anyhow!("cannot save a buffer without a path"))?;
+        // Replace the file a symbolic link points to rather than the link.
+        let path = path.canonicalize().unwrap_or_else(|_| path.clone());
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+
+        let mut file = tempfile::NamedTempFile::new_in(dir)?;
+        if let Ok(metadata) = std::fs::metadata(&path) {
+            file.as_file().set_permissions(metadata.permissions())?;
+        }
+        let mut writer = BufWriter::new(file.as_file_mut());
+        self.write_to(&mut writer)?;
+        writer.flush()?;
+        drop(writer);
+        file.as_file().sync_all()?;
+        file.persist(&path)?;
+        self.commit();
+        self.modified = false;
+        self.saved_revision = self.history.current();
+
+        Ok(())
+    }
+
+    // Writes the text with the line ending of the buffer.
+    fn write_to(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        if self.line_ending == LineEnding::Lf {
+            return self.text.write_to(writer);
+        }
+        for line in self.text.lines() {
+            let len = line.len_bytes();
+            let lf = len >= 1 && line.byte(len - 1) == b'\n';
+            let crlf = len >= 2 && lf && line.byte(len - 2) == b'\r';
+            let text = match lf && !crlf {
+                true => line.byte_slice(..len - 1),
+                false => line,
+            };
+            for chunk in text.chunks() {
+                writer.write_all(chunk.as_bytes())?;
+            }
+            if lf && !crlf {
+                writer.write_all(b"\r\n")?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the path of the file backing this buffer.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Returns the line ending the buffer is written with.
+    pub fn line_ending(&self) -> LineEnding {
+        self.line_ending
+    }
+
+    /// Returns the underlying rope.
+    pub fn text(&self) -> &Rope {
+        &self.text
+    }
+
+    /// Returns `true` if the buffer has been edited since it was opened or saved.
+    pub fn is_modified(&self) -> bool {
+        self.modified
+    }
+
+    /// Returns the selection.
+    pub fn selection(&self) -> &Selection {
+        &self.selection
+    }
+
+    /// Replaces the selection, merging the ranges that overlap.
+    pub fn set_selection(&mut self, selection: Selection) {
+        self.selection = selection.normalize();
+    }
+
+    /// Returns the selection of `view`, a view showing the buffer without focus.
+    pub fn view_selection(&self, view: ViewId) -> Option<&Selection> {
+        self.view_selections.get(&view)
+    }
+
+    /// Keeps `selection` as the selection of `view` while it shows the buffer without focus. It
+    /// is mapped through edits like the selection.
+    pub fn set_view_selection(&mut self, view: ViewId, selection: Selection) {
+        self.view_selections.insert(view, selection.normalize());
+    }
+
+    /// Removes the selection of `view`, as it gets focus or stops showing the buffer.
+    pub fn take_view_selection(&mut self, view: ViewId) -> Option<Selection> {
+        self.view_selections.remove(&view)
+    }
+
+    /// Returns the name of the language of the buffer.
+    pub fn language(&self) -> Option<&str> {
+        self.language.as_deref()
+    }
+
+    /// Sets the name of the language of the buffer.
+    pub fn set_language(&mut self, language: Option<String>) {
+        self.language = language;
+    }
+
+    /// Returns the settings of the `[editor]` section that differ for the buffer.
+    pub fn settings(&self) -> &LocalSettings {
+        &self.settings
+    }
+
+    /// Returns the settings of the buffer for overriding them.
+    pub fn settings_mut(&mut self) -> &mut LocalSettings {
+        &mut self.settings
+    }
+
+    /// Returns the syntax tree, if the language of the buffer has a grammar.
+    pub fn syntax(&self) -> Option<&Syntax> {
+        self.syntax.as_ref()
+    }
+
+    /// Sets the syntax tree, which is kept up to date from then on.
+    pub fn set_syntax(&mut self, syntax: Option<Syntax>) {
+        self.syntax = syntax;
+    }
+
+    /// Sets the text that lines are marked against as added, modified or removed.
+    pub fn set_diff_base(&mut self, diff_base: Option<Rope>) {
+        self.diff_base = diff_base;
+        self.diff_lines();
+    }
+
+    /// Returns the lines that differ from the diff base, in order, as of the last call to
+    /// [`Buffer::update_diff`].
+    pub fn diff(&self) -> &[(usize, DiffMark)] {
+        &self.diff
+    }
+
+    /// Returns whether the text changed since the lines that differ from the diff base were found.
+    pub fn diff_is_stale(&self) -> bool {
+        self.diff_stale
+    }
+
+    /// Finds the lines that differ from the diff base again, if the text changed since they were
+    /// last found.
+    pub fn update_diff(&mut self) {
+        if self.diff_stale {
+            self.diff_lines();
+        }
+    }
+
+    /// Returns the version of the text, which grows with every applied transaction.
+    pub fn version(&self) -> i32 {
+        self.version
+    }
+
+    /// Returns the transactions applied since the last call, each with the text it was applied
+    /// to.
+    pub fn take_changes(&mut self) -> Vec<(Rope, Transaction)> {
+        std::mem::take(&mut self.changes)
+    }
+
+    /// Returns the lines with a breakpoint, in ascending order.
+    pub fn breakpoints(&self) -> &[usize] {
+        &self.breakpoints
+    }
+
+    /// Sets or clears the breakpoint on `line`. Returns `true` if the line now has a breakpoint.
+    pub fn toggle_breakpoint(&mut self, line: usize) -> bool {
+        match self.breakpoints.binary_search(&line) {
+            Ok(index) => {
+                self.breakpoints.remove(index);
+                false
+            }
+            Err(index) => {
+                self.breakpoints.insert(index, line);
+                true
+            }
+        }
+    }
+
+    /// Applies the changes of `transaction` and maps the selection through them.
+    ///
+    /// The transaction becomes part of the undo step that ends with the next call to
+    /// [`Buffer::commit`].
+    pub fn apply(&mut self, transaction: &Transaction) {
+        if transaction.is_empty() {
+            return;
+        }
+
+        let inversion = transaction.invert(self);
+        self.history
+            .record(transaction.clone(), inversion, &self.selection);
+        self.apply_impl(transaction);
+    }
+
+    /// Ends the current undo step.
+    pub fn commit(&mut self) {
+        self.history.commit(&self.selection, SystemTime::now());
+    }
+
+    /// Undoes the last undo step. Returns `false` if there is nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        self.commit();
+        let jump = self.history.undo();
+        self.jump(jump)
+    }
+
+    /// Redoes the last undone step. Returns `false` if there is nothing to redo.
+    pub fn redo(&mut self) -> bool {
+        self.commit();
+        let jump = self.history.redo();
+        self.jump(jump)
+    }
+
+    /// Goes back to the text as it was `kind` ago. Returns `false` if it is already the oldest.
+    pub fn earlier(&mut self, kind: UndoKind) -> bool {
+        self.commit();
+        let jump = self.history.earlier(kind);
+        self.jump(jump)
+    }
+
+    /// Goes forward to the text as it was `kind` later. Returns `false` if it is already the
+    /// newest.
+    pub fn later(&mut self, kind: UndoKind) -> bool {
+        self.commit();
+        let jump = self.history.later(kind);
+        self.jump(jump)
+    }
+
+    fn jump(&mut self, jump: Option<Jump>) -> bool {
+        let Some(jump) = jump else {
+            return false;
+        };
+
+        for transaction in &jump.transactions {
+            self.apply_impl(transaction);
+        }
+        self.selection = jump.selection;
+        self.modified = self.history.current() != self.saved_revision;
+        true
+    }
+
+    /// Returns the undo history.
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    /// Restores the undo history saved in `dir`, unless the file changed since it was saved.
+    /// Returns `true` if there was a history to restore.
+    pub fn load_history(&mut self, dir: &Path) -> Result<bool> {
+        let path = self
+            .path
+            .as_ref()
+            .ok_or_else(|| anyhow!("cannot load the history of a buffer without a path"))?;
+        let file = History::file_for(dir, path)?;
+
+        match History::load(&file, path, &self.text)? {
+            Some(history) => {
+                self.saved_revision = history.current();
+                self.history = history;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Saves the undo history in `dir`. Must be called right after [`Buffer::save`], while the
+    /// text matches the file.
+    pub fn save_history(&self, dir: &Path) -> Result<()> {
+        let path = self
+            .path
+            .as_ref()
+            .ok_or_else(|| anyhow!("cannot save the history of a buffer without a path"))?;
+        let file = History::file_for(dir, path)?;
+        self.history.save(&file, path, &self.text)
+    }
+
+    // Applies `transaction` without recording it in the history.
+    fn apply_impl(&mut self, transaction: &Transaction) {
+        let old_text = self.text.clone();
+        self.changes.push((old_text.clone(), transaction.clone()));
+        self.version += 1;
+
+        // Breakpoints stay with the text of their line.
+        let breakpoints: Vec<_> = self
+            .breakpoints
+            .iter()
+            .map(|&line| transaction.map_pos(self.text.line_to_byte(line), Assoc::After))
+            .collect();
+
+        for change in transaction.changes().iter().rev() {
+            self.delete(change.from..change.to);
+            self.insert(change.from, &change.text);
+        }
+
+        self.selection = std::mem::take(&mut self.selection)
+            .map(transaction)
+            .normalize();
+        for selection in self.view_selections.values_mut() {
+            *selection = std::mem::take(selection).map(transaction).normalize();
+        }
+        self.breakpoints = breakpoints
+            .into_iter()
+            .map(|pos| self.text.byte_to_line(pos))
+            .collect();
+        self.breakpoints.dedup();
+
+        if let Some(syntax) = &mut self.syntax {
+            if let Err(err) = syntax.update(&old_text, &self.text, transaction) {
+                log::error!("failed to reparse buffer: {err:#}");
+                self.syntax = None;
+            }
+        }
+        // Diffing the whole text is too slow for every keystroke, so it waits for `update_diff`.
+        self.diff_stale = self.diff_base.is_some();
+    }
+
+    fn diff_lines(&mut self) {
+        self.diff = match &self.diff_base {
+            Some(base) => diff::line_diff(base, &self.text),
+            None => Vec::new(),
+        };
+        self.diff_stale = false;
+    }
+
+    /// Returns the length of the text in bytes.
+    pub fn len_bytes(&self) -> usize {
+        self.text.len_bytes()
+    }
+
+    /// Returns the number of lines. An empty buffer has one line.
+    pub fn len_lines(&self) -> usize {
+        self.text.len_lines()
+    }
+
+    /// Returns the line at `line_idx`, including its line ending.
+    pub fn line(&self, line_idx: usize) -> RopeSlice<'_> {
+        self.text.line(line_idx)
+    }
+
+    /// Returns the text in `range`.
+    pub fn slice(&self, range: Range<usize>) -> RopeSlice<'_> {
+        self.text.byte_slice(range)
+    }
+
+    /// Inserts `text` at `byte_idx`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `byte_idx` is out of bounds or not on a character boundary.
+    pub fn insert(&mut self, byte_idx: usize, text: &str) {
+        assert!(
+            self.is_char_boundary(byte_idx),
+            "byte index {byte_idx} is not a char boundary"
+        );
+
+        if text.is_empty() {
+            return;
+        }
+
+        self.text.insert(self.text.byte_to_char(byte_idx), text);
+        self.modified = true;
+    }
+
+    /// Deletes the text in `range`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either end of `range` is out of bounds or not on a character boundary.
+    pub fn delete(&mut self, range: Range<usize>) {
+        assert!(
+            self.is_char_boundary(range.start) && self.is_char_boundary(range.end),
+            "byte range {range:?} does not lie on char boundaries"
+        );
+
+        if range.is_empty() {
+            return;
+        }
+
+        let start = self.text.byte_to_char(range.start);
+        let end = self.text.byte_to_char(range.end);
+        self.text.remove(start..end);
+        self.modified = true;
+    }
+
+    /// Returns `true` if `byte_idx` is a character boundary (the end of the text included).
+    pub fn is_char_boundary(&self, byte_idx: usize) -> bool {
+        byte_idx <= self.len_bytes()
+            && self.text.char_to_byte(self.text.byte_to_char(byte_idx)) == byte_idx
+    }
+
+    /// Converts a byte offset to a line/column position.
+    pub fn byte_to_pos(&self, byte_idx: usize) -> Position {
+        let line = self.text.byte_to_line(byte_idx);
+        Position::new(line, byte_idx - self.text.line_to_byte(line))
+    }
+
+    /// Converts a line/column position to a byte offset.
+    ///
+    /// Lines past the end of the buffer are clamped to the last line, and columns are clamped to
+    /// the end of the line (before its line ending).
+    pub fn pos_to_byte(&self, pos: Position) -> usize {
+        let line = pos.line.min(self.len_lines() - 1);
+        let start = self.text.line_to_byte(line);
+        (start + pos.column).min(self.line_end(line))
+    }
+
+    /// Returns the byte offset of the start of `line_idx`.
+    pub fn line_start(&self, line_idx: usize) -> usize {
+        self.text.line_to_byte(line_idx)
+    }
+
+    /// Returns the byte offset of the end of `line_idx`, excluding its line ending.
+    pub fn line_end(&self, line_idx: usize) -> usize {
+        let line = self.text.line(line_idx);
+        let mut len = line.len_bytes();
+
+        if len > 0 && line.byte(len - 1) == b'\n' {
+            len -= 1;
+        }
+        if len > 0 && line.byte(len - 1) == b'\r' {
+            len -= 1;
+        }
+
+        self.text.line_to_byte(line_idx) + len
+    }
+
+    /// Returns the character starting at `byte_idx`.
+    pub fn char_at(&self, byte_idx: usize) -> Option<char> {
+        self.text.get_char(self.text.byte_to_char(byte_idx))
+    }
+
+    /// Returns the characters from `byte_idx` onwards along with their byte offsets.
+    pub fn chars_at(&self, byte_idx: usize) -> impl Iterator<Item = (usize, char)> + '_ {
+        let mut pos = byte_idx;
+        self.text
+            .chars_at(self.text.byte_to_char(byte_idx))
+            .map(move |c| {
+                pos += c.len_utf8();
+                (pos - c.len_utf8(), c)
+            })
+    }
+
+    /// Returns the characters before `byte_idx` in reverse order along with their byte offsets.
+    pub fn chars_before(&self, byte_idx: usize) -> impl Iterator<Item = (usize, char)> + '_ {
+        let mut pos = byte_idx;
+        self.text
+            .chars_at(self.text.byte_to_char(byte_idx))
+            .reversed()
+            .map(move |c| {
+                pos -= c.len_utf8();
+                (pos, c)
+            })
+    }
+
+    /// Returns the byte offset of the grapheme boundary after `byte_idx`.
+    pub fn next_grapheme_boundary(&self, byte_idx: usize) -> usize {
+        grapheme::next_boundary(self.text.slice(..), byte_idx)
+    }
+
+    /// Returns the byte offset of the grapheme boundary before `byte_idx`.
+    pub fn prev_grapheme_boundary(&self, byte_idx: usize) -> usize {
+        grapheme::prev_boundary(self.text.slice(..), byte_idx)
+    }
+
+    /// Returns `true` if `byte_idx` lies on a grapheme boundary.
+    pub fn is_grapheme_boundary(&self, byte_idx: usize) -> bool {
+        self.is_char_boundary(byte_idx) && grapheme::is_boundary(self.text.slice(..), byte_idx)
+    }
+
+    /// Returns the number of graphemes between the start of the line and `byte_idx`.
+    pub fn grapheme_column(&self, byte_idx: usize) -> usize {
+        let mut pos = self.text.line_to_byte(self.text.byte_to_line(byte_idx));
+        let mut column = 0;
+
+        while pos < byte_idx {
+            pos = self.next_grapheme_boundary(pos);
+            column += 1;
+        }
+
+        column
+    }
+
+    /// Moves `byte_idx` by `count` graphemes in `direction`, stopping at either end of the text.
+    pub fn move_horizontally(&self, byte_idx: usize, direction: Direction, count: usize) -> usize {
+        (0..count).fold(byte_idx, |pos, _| match direction {
+            Direction::Forward => self.next_grapheme_boundary(pos),
+            Direction::Backward => self.prev_grapheme_boundary(pos),
+        })
+    }
+
+    /// Moves `byte_idx` by `count` lines in `direction`.
+    ///
+    /// The cursor lands on the grapheme at `column` (or at the end of a shorter line). When no
+    /// column is given the current grapheme column is used. Returns the new byte offset and the
+    /// column to keep for subsequent vertical moves.
+    pub fn move_vertically(
+        &self,
+        byte_idx: usize,
+        direction: Direction,
+        count: usize,
+        column: Option<usize>,
+    ) -> (usize, usize) {
+        let column = column.unwrap_or_else(|| self.grapheme_column(byte_idx));
+        let line = self.text.byte_to_line(byte_idx);
+        let line = match direction {
+            Direction::Forward => line.saturating_add(count).min(self.len_lines() - 1),
+            Direction::Backward => line.saturating_sub(count),
+        };
+
+        let end = self.line_end(line);
+        let mut pos = self.text.line_to_byte(line);
+        for _ in 0..column {
+            if pos >= end {
+                break;
+            }
+            pos = self.next_grapheme_boundary(pos);
+        }
+
+        (pos.min(end), column)
+    }
+}
+
+impl From<&str> for Buffer {
+    fn from(text: &str) -> Self {
+        let text = Rope::from_str(text);
+        Self {
+            line_ending: LineEnding::detect(&text),
+            text,
+            ..Self::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use unicode_segmentation::UnicodeSegmentation;
+
+    #[derive(Debug, Clone)]
+    enum Edit {
+        Insert(usize, String),
+        Delete(usize, usize),
+    }
+
+    fn text() -> impl Strategy<Value = String> {
+        // Mix of ASCII, multi-byte characters, combining marks, emoji and line breaks.
+        proptest::collection::vec(
+            prop_oneof![
+                Just("a"),
+                Just("Z"),
+                Just(" "),
+                Just("\n"),
+                Just("\r\n"),
+                Just("é"),
+                Just("e\u{301}"),
+                Just("한"),
+                Just("🦀"),
+                Just("👨‍👩‍👧"),
+            ],
+            0..16,
+        )
+        .prop_map(|parts| parts.concat())
+    }
+
+    fn edit() -> impl Strategy<Value = Edit> {
+        prop_oneof![
+            (any::<usize>(), text()).prop_map(|(at, text)| Edit::Insert(at, text)),
+            (any::<usize>(), any::<usize>()).prop_map(|(a, b)| Edit::Delete(a, b)),
+        ]
+    }
+
+    // Picks a char boundary of `model` from an arbitrary seed.
+    fn boundary(model: &str, seed: usize) -> usize {
+        let boundaries: Vec<_> = model
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain([model.len()])
+            .collect();
+        boundaries[seed % boundaries.len()]
+    }
+
+    proptest! {
+        #[test]
+        fn edits_match_string_model(initial in text(), edits in proptest::collection::vec(edit(), 0..32)) {
+            let mut buffer = Buffer::from(initial.as_str());
+            let mut model = initial;
+
+            for edit in edits {
+                match edit {
+                    Edit::Insert(at, text) => {
+                        let at = boundary(&model, at);
+                        buffer.insert(at, &text);
+                        model.insert_str(at, &text);
+                    }
+                    Edit::Delete(a, b) => {
+                        let (a, b) = (boundary(&model, a), boundary(&model, b));
+                        let range = a.min(b)..a.max(b);
+                        buffer.delete(range.clone());
+                        model.replace_range(range, "");
+                    }
+                }
+
+                prop_assert_eq!(buffer.text().to_string(), model.as_str());
+                prop_assert_eq!(buffer.len_bytes(), model.len());
+            }
+        }
+
+        #[test]
+        fn positions_round_trip(model in text()) {
+            let buffer = Buffer::from(model.as_str());
+
+            for (byte, _) in model.char_indices().chain([(model.len(), ' ')]) {
+                let pos = buffer.byte_to_pos(byte);
+                let line_start: usize = model[..byte].rfind('\n').map_or(0, |i| i + 1);
+                prop_assert_eq!(pos.line, model[..byte].matches('\n').count());
+                prop_assert_eq!(pos.column, byte - line_start);
+
+                // The '\n' of a CRLF pair is clamped onto the '\r'.
+                let expected = if model[..byte].ends_with('\r') && model[byte..].starts_with('\n') {
+                    byte - 1
+                } else {
+                    byte
+                };
+                prop_assert_eq!(buffer.pos_to_byte(pos), expected);
+            }
+        }
+
+        #[test]
+        fn grapheme_boundaries_match_model(model in text()) {
+            let buffer = Buffer::from(model.as_str());
+            let boundaries: Vec<_> = model
+                .grapheme_indices(true)
+                .map(|(i, _)| i)
+                .chain([model.len()])
+                .collect();
+
+            for window in boundaries.windows(2) {
+                prop_assert_eq!(buffer.next_grapheme_boundary(window[0]), window[1]);
+                prop_assert_eq!(buffer.prev_grapheme_boundary(window[1]), window[0]);
+            }
+
+            for (byte, _) in model.char_indices() {
+                prop_assert_eq!(buffer.is_grapheme_boundary(byte), boundaries.contains(&byte));
+            }
+        }
+    }
+
+    #[test]
+    fn move_vertically_keeps_column() {
+        let buffer = Buffer::from("héllo\nab\nwörld🦀!\n");
+
+        let (pos, column) = buffer.move_vertically(4, Direction::Forward, 1, None);
+        assert_eq!((buffer.byte_to_pos(pos), column), (Position::new(1, 2), 3));
+
+        let (pos, _) = buffer.move_vertically(pos, Direction::Forward, 1, Some(column));
+        assert_eq!(buffer.byte_to_pos(pos), Position::new(2, 4));
+
+        let (pos, _) = buffer.move_vertically(pos, Direction::Forward, 5, Some(column));
+        assert_eq!(buffer.byte_to_pos(pos), Position::new(3, 0));
+    }
+
+    #[test]
+    fn move_horizontally_steps_over_graphemes() {
+        let buffer = Buffer::from("a👨‍👩‍👧e\u{301}");
+
+        assert_eq!(buffer.move_horizontally(0, Direction::Forward, 1), 1);
+        assert_eq!(buffer.move_horizontally(0, Direction::Forward, 2), 19);
+        assert_eq!(buffer.move_horizontally(0, Direction::Forward, 9), 22);
+        assert_eq!(buffer.move_horizontally(22, Direction::Backward, 2), 1);
+    }
+
+    #[test]
+    fn breakpoints_follow_their_lines() {
+        let mut buffer = Buffer::from("a\nb\nc\nd\n");
+        assert!(buffer.toggle_breakpoint(1));
+        assert!(buffer.toggle_breakpoint(3));
+
+        buffer.apply(&Transaction::insert(0, "x\n"));
+        assert_eq!(buffer.breakpoints(), [2, 4]);
+
+        // Deleting a line with a breakpoint moves it to the line after.
+        buffer.apply(&Transaction::delete(std::iter::once(4..6)));
+        assert_eq!(buffer.breakpoints(), [2, 3]);
+        buffer.apply(&Transaction::delete(std::iter::once(4..6)));
+        assert_eq!(buffer.breakpoints(), [2]);
+
+        assert!(!buffer.toggle_breakpoint(2));
+        assert!(buffer.breakpoints().is_empty());
+    }
+
+    #[test]
+    fn save_replaces_the_file() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("file.txt");
+        std::fs::write(&path, "old text\n").unwrap();
+        let mut permissions = std::fs::metadata(&path).unwrap().permissions();
+        permissions.set_readonly(true);
+        std::fs::set_permissions(&path, permissions.clone()).unwrap();
+
+        let mut buffer = Buffer::open(&path).unwrap();
+        buffer.apply(&Transaction::insert(0, "new\n"));
+        buffer.save().unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "new\nold text\n");
+        assert!(!buffer.is_modified());
+        assert_eq!(std::fs::metadata(&path).unwrap().permissions(), permissions);
+        assert_eq!(std::fs::read_dir(temp.path()).unwrap().count(), 1);
+
+        // A file that cannot be written is an error rather than a silent loss.
+        let mut buffer = Buffer::open(&temp.path().join("missing/file.txt")).unwrap();
+        buffer.apply(&Transaction::insert(0, "text"));
+        assert!(buffer.save().is_err());
+    }
+
+    #[test]
+    fn save_keeps_the_line_ending_of_the_file() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("file.txt");
+        std::fs::write(&path, "one\r\ntwo\r\n").unwrap();
+
+        let mut buffer = Buffer::open(&path).unwrap();
+        assert_eq!(buffer.line_ending(), LineEnding::Crlf);
+        buffer.apply(&Transaction::insert(5, "new\n"));
+        buffer.save().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "one\r\nnew\r\ntwo\r\n"
+        );
+
+        assert_eq!(Buffer::from("one\ntwo\r\n").line_ending(), LineEnding::Lf);
+        assert_eq!(Buffer::from("one").line_ending(), LineEnding::Lf);
+    }
+
+    #[test]
+    fn diff_waits_for_update() {
+        let mut buffer = Buffer::from("a\nb\n");
+        buffer.set_diff_base(Some(Rope::from("a\nb\n")));
+        assert!(buffer.diff().is_empty());
+
+        buffer.apply(&Transaction::insert(0, "x\n"));
+        assert!(buffer.diff_is_stale());
+        assert!(buffer.diff().is_empty());
+        buffer.update_diff();
+        assert!(!buffer.diff_is_stale());
+        assert_eq!(buffer.diff(), [(0, DiffMark::Added)]);
+    }
+}
@@ -0,0 +1,89 @@
+//! Grapheme cluster boundaries over a rope.
+//!
+//! Graphemes can span rope chunks, so we drive a [`GraphemeCursor`] chunk by chunk instead of
+//! materializing the text.
+
+use ropey::RopeSlice;
+use unicode_segmentation::{GraphemeCursor, GraphemeIncomplete};
+
+/// Returns the byte index of the next grapheme boundary after `byte_idx`.
+pub fn next_boundary(slice: RopeSlice, byte_idx: usize) -> usize {
+    let len = slice.len_bytes();
+    if byte_idx >= len {
+        return len;
+    }
+
+    let (mut chunk, mut chunk_start, _, _) = slice.chunk_at_byte(byte_idx);
+    let mut cursor = GraphemeCursor::new(byte_idx, len, true);
+
+    loop {
+        match cursor.next_boundary(chunk, chunk_start) {
+            Ok(None) => return len,
+            Ok(Some(n)) => return n,
+            Err(GraphemeIncomplete::NextChunk) => {
+                chunk_start += chunk.len();
+                chunk = slice.chunk_at_byte(chunk_start).0;
+            }
+            Err(GraphemeIncomplete::PreContext(n)) => {
+                let (ctx, ctx_start, _, _) = slice.chunk_at_byte(n - 1);
+                cursor.provide_context(ctx, ctx_start);
+            }
+            Err(err) => unreachable!("unexpected grapheme cursor state: {err:?}"),
+        }
+    }
+}
+
+/// Returns the byte index of the previous grapheme boundary before `byte_idx`.
+pub fn prev_boundary(slice: RopeSlice, byte_idx: usize) -> usize {
+    if byte_idx == 0 {
+        return 0;
+    }
+
+    let len = slice.len_bytes();
+    let byte_idx = byte_idx.min(len);
+    let (mut chunk, mut chunk_start, _, _) = slice.chunk_at_byte(byte_idx);
+    let mut cursor = GraphemeCursor::new(byte_idx, len, true);
+
+    loop {
+        match cursor.prev_boundary(chunk, chunk_start) {
+            Ok(None) => return 0,
+            Ok(Some(n)) => return n,
+            Err(GraphemeIncomplete::PrevChunk) => {
+                let (prev, prev_start, _, _) = slice.chunk_at_byte(chunk_start - 1);
+                chunk = prev;
+                chunk_start = prev_start;
+            }
+            Err(GraphemeIncomplete::PreContext(n)) => {
+                let (ctx, ctx_start, _, _) = slice.chunk_at_byte(n - 1);
+                cursor.provide_context(ctx, ctx_start);
+            }
+            Err(err) => unreachable!("unexpected grapheme cursor state: {err:?}"),
+        }
+    }
+}
+
+/// Returns `true` if `byte_idx` lies on a grapheme boundary.
+pub fn is_boundary(slice: RopeSlice, byte_idx: usize) -> bool {
+    let len = slice.len_bytes();
+    if byte_idx > len {
+        return false;
+    }
+
+    let (mut chunk, mut chunk_start, _, _) = slice.chunk_at_byte(byte_idx);
+    let mut cursor = GraphemeCursor::new(byte_idx, len, true);
+
+    loop {
+        match cursor.is_boundary(chunk, chunk_start) {
+            Ok(n) => return n,
+            Err(GraphemeIncomplete::PreContext(n)) => {
+                let (ctx, ctx_start, _, _) = slice.chunk_at_byte(n - 1);
+                cursor.provide_context(ctx, ctx_start);
+            }
+            Err(GraphemeIncomplete::NextChunk) => {
+                chunk_start += chunk.len();
+                chunk = slice.chunk_at_byte(chunk_start).0;
+            }
+            Err(err) => unreachable!("unexpected grapheme cursor state: {err:?}"),
+        }
+    }
+}
@@ -0,0 +1,575 @@
+//! System clipboards, reached through the first provider available in the environment.
+
+use crate::{ClipboardConfig, ClipboardProvider};
+use anyhow::{anyhow, bail, Context, Result};
+use std::{
+    collections::HashMap,
+    ffi::OsString,
+    io::{Read, Write},
+    path::PathBuf,
+    process::{Child, Command, ExitStatus, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+// How long a clipboard program may run before it is killed, so that a provider that hangs, such
+// as a tool waiting for a display that went away, cannot freeze the editor.
+const PROGRAM_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Which of the system clipboards to use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ClipboardType {
+    /// The clipboard of copy and paste.
+    Clipboard,
+    /// The primary selection of X11 and Wayland, pasted with the middle mouse button.
+    Primary,
+}
+
+/// The environment providers are detected in.
+#[derive(Debug, Clone)]
+pub struct Environment {
+    vars: HashMap<String, String>,
+}
+
+impl Environment {
+    /// Returns the environment of the editor.
+    pub fn current() -> Self {
+        Self::new(std::env::vars())
+    }
+
+    /// Creates an environment with the given variables, whose `PATH` is searched for programs.
+    pub fn new(vars: impl IntoIterator<Item = (String, String)>) -> Self {
+        Self {
+            vars: vars.into_iter().collect(),
+        }
+    }
+
+    fn var(&self, name: &str) -> Option<&str> {
+        self.vars
+            .get(name)
+            .map(String::as_str)
+            .filter(|value| !value.is_empty())
+    }
+
+    fn require_var(&self, name: &str) -> Result<(), String> {
+        self.var(name)
+            .map(drop)
+            .ok_or_else(|| format!("{name} is not set"))
+    }
+
+    fn which(&self, program: &str) -> Result<PathBuf, String> {
+        let path = self.var("PATH").map(OsString::from);
+        let cwd = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("/"));
+        which::which_in(program, path, cwd).map_err(|_| format!("{program} is not installed"))
+    }
+}
+
+/// A provider considered during detection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Probe {
+    /// The name of the provider.
+    pub name: &'static str,
+    /// Why the provider cannot be used, or `None` if it can.
+    pub skipped: Option<String>,
+}
+
+// A program and its arguments.
+#[derive(Debug, Clone)]
+struct Program {
+    path: PathBuf,
+    args: Vec<String>,
+}
+
+impl Program {
+    fn find(env: &Environment, program: &str, args: &[&str]) -> Result<Self, String> {
+        Ok(Self {
+            path: env.which(program)?,
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+        })
+    }
+
+    fn name(&self) -> String {
+        self.path.file_name().map_or_else(
+            || self.path.display().to_string(),
+            |name| name.to_string_lossy().into(),
+        )
+    }
+
+    // Writes `contents` to the standard input of the program and waits for it to exit. Its output
+    // is discarded, so that programs like xclip, which stay around to serve the clipboard, cannot
+    // keep the wait hanging on a pipe.
+    fn copy(&self, contents: &str) -> Result<()> {
+        let mut child = Command::new(&self.path)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .with_context(|| format!("failed to run {}", self.name()))?;
+        // Unwrap OK since stdin is piped.
+        let mut stdin = child.stdin.take().unwrap();
+        let contents = contents.to_string();
+        let writer = thread::spawn(move || stdin.write_all(contents.as_bytes()));
+
+        let status = self.wait(&mut child)?;
+        // Unwrap OK since writing cannot panic.
+        writer.join().unwrap()?;
+        if !status.success() {
+            bail!("{} failed with {status}", self.name());
+        }
+        Ok(())
+    }
+
+    fn paste(&self) -> Result<String> {
+        let mut child = Command::new(&self.path)
+            .args(&self.args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("failed to run {}", self.name()))?;
+        // Unwrap OK since both streams are piped.
+        let mut stdout = child.stdout.take().unwrap();
+        let mut stderr = child.stderr.take().unwrap();
+        let reader = thread::spawn(move || {
+            let mut output = Vec::new();
+            stdout.read_to_end(&mut output).map(|_| output)
+        });
+
+        let status = self.wait(&mut child)?;
+        // Unwrap OK since reading cannot panic.
+        let output = reader.join().unwrap()?;
+        if !status.success() {
+            let mut message = String::new();
+            stderr.read_to_string(&mut message).ok();
+            bail!("{} failed with {status}: {}", self.name(), message.trim());
+        }
+        String::from_utf8(output).map_err(|_| anyhow!("the clipboard is not valid UTF-8"))
+    }
+
+    // Waits for `child` to exit, and kills it once `PROGRAM_TIMEOUT` has passed.
+    fn wait(&self, child: &mut Child) -> Result<ExitStatus> {
+        let deadline = Instant::now() + PROGRAM_TIMEOUT;
+        loop {
+            if let Some(status) = child.try_wait()? {
+                return Ok(status);
+            }
+            if Instant::now() >= deadline {
+                child.kill().ok();
+                child.wait().ok();
+                bail!("{} did not finish in time", self.name());
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Provider {
+    // Programs copying from their input and pasting to their output, for the clipboard and
+    // optionally for the primary selection.
+    Programs {
+        copy: Program,
+        paste: Program,
+        primary: Option<(Program, Program)>,
+    },
+    // Asks the terminal to set its clipboard with an OSC 52 escape sequence.
+    Osc52,
+    // Keeps the copied text inside the editor.
+    Internal,
+}
+
+/// A system clipboard.
+#[derive(Debug, Clone)]
+pub struct Clipboard {
+    name: &'static str,
+    provider: Provider,
+    // Text copied last, read back by providers that cannot read the clipboard.
+    contents: HashMap<ClipboardType, String>,
+}
+
+impl Default for Clipboard {
+    fn default() -> Self {
+        Self::with_provider("internal", Provider::Internal)
+    }
+}
+
+impl Clipboard {
+    /// Returns the first provider available in `env`, or the one set by `config`, along with
+    /// every provider considered and why the others were skipped.
+    pub fn detect(config: &ClipboardConfig, env: &Environment) -> (Self, Vec<Probe>) {
+        let forced = Some(config.provider).filter(|&provider| provider != ClipboardProvider::Auto);
+        let mut clipboard = None;
+        let mut probes = Vec::new();
+
+        for provider in ClipboardProvider::ALL {
+            let name = provider.name();
+            let skipped = match (forced, &clipboard) {
+                (Some(forced), _) if forced != provider => {
+                    Some(format!("provider is set to {}", forced.name()))
+                }
+                (_, Some(Clipboard { name: selected, .. })) => match probe(provider, config, env) {
+                    Ok(_) => Some(format!("available, but {selected} comes first")),
+                    Err(reason) => Some(reason),
+                },
+                (_, None) => match probe(provider, config, env) {
+                    Ok(provider) => {
+                        clipboard = Some(Self::with_provider(name, provider));
+                        None
+                    }
+                    Err(reason) => Some(reason),
+                },
+            };
+            probes.push(Probe { name, skipped });
+        }
+
+        (clipboard.unwrap_or_default(), probes)
+    }
+
+    fn with_provider(name: &'static str, provider: Provider) -> Self {
+        Self {
+            name,
+            provider,
+            contents: HashMap::new(),
+        }
+    }
+
+    /// Returns the name of the provider.
+    pub fn name(&self) -> &str {
+        self.name
+    }
+
+    /// Returns the contents of the clipboard.
+    pub fn get(&self, ty: ClipboardType) -> Result<String> {
+        match (&self.provider, ty) {
+            (Provider::Programs { paste, .. }, ClipboardType::Clipboard) => paste.paste(),
+            (
+                Provider::Programs {
+                    primary: Some((_, paste)),
+                    ..
+                },
+                ClipboardType::Primary,
+            ) => paste.paste(),
+            _ => Ok(self.contents.get(&ty).cloned().unwrap_or_default()),
+        }
+    }
+
+    /// Replaces the contents of the clipboard.
+    pub fn set(&mut self, ty: ClipboardType, contents: &str) -> Result<()> {
+        match (&self.provider, ty) {
+            (Provider::Programs { copy, .. }, ClipboardType::Clipboard) => copy.copy(contents)?,
+            (
+                Provider::Programs {
+                    primary: Some((copy, _)),
+                    ..
+                },
+                ClipboardType::Primary,
+            ) => copy.copy(contents)?,
+            (Provider::Osc52, _) => {
+                let selection = match ty {
+                    ClipboardType::Clipboard => 'c',
+                    ClipboardType::Primary => 'p',
+                };
+                let mut stdout = std::io::stdout().lock();
+                write!(
+                    stdout,
+                    "\x1b]52;{selection};{}\x07",
+                    base64(contents.as_bytes())
+                )?;
+                stdout.flush()?;
+            }
+            _ => {}
+        }
+        self.contents.insert(ty, contents.to_string());
+        Ok(())
+    }
+}
+
+fn probe(
+    provider: ClipboardProvider,
+    config: &ClipboardConfig,
+    env: &Environment,
+) -> Result<Provider, String> {
+    let programs = |copy: Result<Program, String>, paste: Result<Program, String>| {
+        Ok(Provider::Programs {
+            copy: copy?,
+            paste: paste?,
+            primary: None,
+        })
+    };
+    let with_primary = |provider: Result<Provider, String>, copy, paste| match provider? {
+        Provider::Programs {
+            copy: clipboard_copy,
+            paste: clipboard_paste,
+            ..
+        } => Ok(Provider::Programs {
+            copy: clipboard_copy,
+            paste: clipboard_paste,
+            primary: Some((copy?, paste?)),
+        }),
+        provider => Ok(provider),
+    };
+
+    match provider {
+        ClipboardProvider::Custom => {
+            let command = |args: &[String], key| match args.split_first() {
+                Some((program, args)) => {
+                    let args: Vec<_> = args.iter().map(String::as_str).collect();
+                    Program::find(env, program, &args)
+                }
+                None => Err(format!("'{key}' is not configured")),
+            };
+            let provider = programs(
+                command(&config.copy, "copy"),
+                command(&config.paste, "paste"),
+            )?;
+            match config.primary_copy.is_empty() && config.primary_paste.is_empty() {
+                true => Ok(provider),
+                false => with_primary(
+                    Ok(provider),
+                    command(&config.primary_copy, "primary-copy"),
+                    command(&config.primary_paste, "primary-paste"),
+                ),
+            }
+        }
+        ClipboardProvider::Wayland => {
+            env.require_var("WAYLAND_DISPLAY")?;
+            let copy = |args| Program::find(env, "wl-copy", args);
+            let paste = |args| Program::find(env, "wl-paste", args);
+            with_primary(
+                programs(copy(&["--type", "text/plain"]), paste(&["--no-newline"])),
+                copy(&["--primary", "--type", "text/plain"]),
+                paste(&["--primary", "--no-newline"]),
+            )
+        }
+        ClipboardProvider::Xclip => {
+            env.require_var("DISPLAY")?;
+            let xclip = |args| Program::find(env, "xclip", args);
+            with_primary(
+                programs(
+                    xclip(&["-i", "-selection", "clipboard"]),
+                    xclip(&["-o", "-selection", "clipboard"]),
+                ),
+                xclip(&["-i", "-selection", "primary"]),
+                xclip(&["-o", "-selection", "primary"]),
+            )
+        }
+        ClipboardProvider::Xsel => {
+            env.require_var("DISPLAY")?;
+            let xsel = |args| Program::find(env, "xsel", args);
+            with_primary(
+                programs(xsel(&["-i", "-b"]), xsel(&["-o", "-b"])),
+                xsel(&["-i", "-p"]),
+                xsel(&["-o", "-p"]),
+            )
+        }
+        ClipboardProvider::Tmux => {
+            env.require_var("TMUX")?;
+            let tmux = |args| Program::find(env, "tmux", args);
+            programs(tmux(&["load-buffer", "-"]), tmux(&["save-buffer", "-"]))
+        }
+        ClipboardProvider::Osc52 => match env.var("TERM") {
+            None => Err("TERM is not set".into()),
+            Some("dumb") => Err("the terminal is dumb".into()),
+            Some(_) => Ok(Provider::Osc52),
+        },
+        ClipboardProvider::Auto | ClipboardProvider::Internal => Ok(Provider::Internal),
+    }
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::with_capacity((bytes.len() + 2) / 3 * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &byte)| n | (byte as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            match i <= chunk.len() {
+                true => encoded.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char),
+                false => encoded.push('='),
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{os::unix::fs::PermissionsExt, path::Path};
+    use tempfile::TempDir;
+
+    // Creates a directory of fake programs that keep the clipboard in `<dir>/<name>.<selection>`,
+    // where the selection is the last argument.
+    fn fake_programs(programs: &[&str]) -> TempDir {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        for program in programs {
+            let path = dir.join(program);
+            let script = format!(
+                "#!/bin/sh\n\
+                 for arg; do selection=\"$arg\"; done\n\
+                 file=\"{dir}/{program}.${{selection#-}}\"\n\
+                 case \"$1\" in\n\
+                 -o|save-buffer) cat \"$file\" 2>/dev/null || {{ echo empty >&2; exit 1; }} ;;\n\
+                 *) cat > \"$file\" ;;\n\
+                 esac\n",
+                dir = dir.display()
+            );
+            std::fs::write(&path, script).unwrap();
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        temp
+    }
+
+    fn env(path: &Path, vars: &[(&str, &str)]) -> Environment {
+        let path = ("PATH".to_string(), path.display().to_string());
+        Environment::new(
+            vars.iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .chain([path]),
+        )
+    }
+
+    fn skipped(probes: &[Probe]) -> Vec<(&str, &str)> {
+        probes
+            .iter()
+            .map(|probe| (probe.name, probe.skipped.as_deref().unwrap_or("selected")))
+            .collect()
+    }
+
+    #[test]
+    fn providers_are_detected_in_order() {
+        let temp = fake_programs(&["xsel", "tmux"]);
+        let dir = temp.path();
+        let config = ClipboardConfig::default();
+
+        let env = env(
+            dir,
+            &[("DISPLAY", ":0"), ("TMUX", "/tmp/tmux"), ("TERM", "xterm")],
+        );
+        let (clipboard, probes) = Clipboard::detect(&config, &env);
+        assert_eq!(clipboard.name(), "xsel");
+        assert_eq!(
+            skipped(&probes),
+            [
+                ("custom", "'copy' is not configured"),
+                ("wayland", "WAYLAND_DISPLAY is not set"),
+                ("xclip", "xclip is not installed"),
+                ("xsel", "selected"),
+                ("tmux", "available, but xsel comes first"),
+                ("osc52", "available, but xsel comes first"),
+                ("internal", "available, but xsel comes first"),
+            ]
+        );
+
+        let (clipboard, probes) = Clipboard::detect(&config, &self::env(dir, &[]));
+        assert_eq!(clipboard.name(), "internal");
+        assert_eq!(skipped(&probes)[5], ("osc52", "TERM is not set"));
+
+        let config = ClipboardConfig {
+            provider: ClipboardProvider::Tmux,
+            ..ClipboardConfig::default()
+        };
+        let (clipboard, probes) = Clipboard::detect(&config, &env);
+        assert_eq!(clipboard.name(), "tmux");
+        assert_eq!(skipped(&probes)[3], ("xsel", "provider is set to tmux"));
+    }
+
+    #[test]
+    fn programs_copy_and_paste() {
+        let temp = fake_programs(&["xclip", "tmux"]);
+        let dir = temp.path();
+
+        let env = env(dir, &[("DISPLAY", ":0")]);
+        let (mut clipboard, _) = Clipboard::detect(&ClipboardConfig::default(), &env);
+        assert_eq!(clipboard.name(), "xclip");
+        let err = clipboard.get(ClipboardType::Clipboard).unwrap_err();
+        assert_eq!(err.to_string(), "xclip failed with exit status: 1: empty");
+
+        clipboard.set(ClipboardType::Clipboard, "copied\n").unwrap();
+        clipboard.set(ClipboardType::Primary, "selected").unwrap();
+        assert_eq!(clipboard.get(ClipboardType::Clipboard).unwrap(), "copied\n");
+        assert_eq!(clipboard.get(ClipboardType::Primary).unwrap(), "selected");
+
+        // tmux has no primary selection, which is kept inside the editor instead.
+        let env = self::env(dir, &[("TMUX", "/tmp/tmux")]);
+        let (mut clipboard, _) = Clipboard::detect(&ClipboardConfig::default(), &env);
+        clipboard.set(ClipboardType::Clipboard, "buffer").unwrap();
+        clipboard.set(ClipboardType::Primary, "internal").unwrap();
+        assert_eq!(clipboard.get(ClipboardType::Clipboard).unwrap(), "buffer");
+        assert_eq!(
+            std::fs::read_to_string(dir.join("tmux.")).unwrap(),
+            "buffer"
+        );
+        assert_eq!(clipboard.get(ClipboardType::Primary).unwrap(), "internal");
+    }
+
+    #[test]
+    fn custom_commands_come_first() {
+        let temp = fake_programs(&["copy", "xclip"]);
+        let dir = temp.path();
+        let config: ClipboardConfig = toml::from_str(
+            r#"
+            copy = ["copy", "-i", "board"]
+            paste = ["copy", "-o", "board"]
+            "#,
+        )
+        .unwrap();
+
+        let env = env(dir, &[("DISPLAY", ":0")]);
+        let (mut clipboard, probes) = Clipboard::detect(&config, &env);
+        assert_eq!(clipboard.name(), "custom");
+        assert_eq!(
+            skipped(&probes)[2],
+            ("xclip", "available, but custom comes first")
+        );
+        clipboard.set(ClipboardType::Clipboard, "text").unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.join("copy.board")).unwrap(),
+            "text"
+        );
+        assert_eq!(clipboard.get(ClipboardType::Clipboard).unwrap(), "text");
+
+        let err = toml::from_str::<ClipboardConfig>("provider = \"clippy\"").unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("unknown variant `clippy`, expected one of"));
+    }
+
+    #[test]
+    fn hanging_programs_are_killed() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let path = dir.join("hang");
+        std::fs::write(&path, "#!/bin/sh\nexec sleep 60\n").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let config: ClipboardConfig = toml::from_str(
+            r#"
+            copy = ["hang"]
+            paste = ["hang"]
+            "#,
+        )
+        .unwrap();
+
+        let (mut clipboard, _) = Clipboard::detect(&config, &env(dir, &[]));
+        let start = Instant::now();
+        let err = clipboard.get(ClipboardType::Clipboard).unwrap_err();
+        assert_eq!(err.to_string(), "hang did not finish in time");
+        let err = clipboard.set(ClipboardType::Clipboard, "text").unwrap_err();
+        assert_eq!(err.to_string(), "hang did not finish in time");
+        assert!(start.elapsed() < 3 * PROGRAM_TIMEOUT);
+    }
+
+    #[test]
+    fn base64_pads_the_last_group() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+    }
+}
@@ -0,0 +1,1894 @@
+pub(crate) mod typed;
+
+use crate::{
+    language_server,
+    motion::{self, Motion, MotionKind},
+    selection::{Range, Selection},
+    textobject::TextObject,
+    transaction::Transaction,
+    App, AutoPairs, Buffer, Direction, Message, Mode, PromptKind, Search, UndoKind, VisualKind,
+    Yank,
+};
+use std::fmt;
+use tissue_lsp::lsp;
+use tissue_tui::{Side, Split};
+
+/// State passed to a command when it runs.
+#[derive(Debug)]
+pub struct Context<'a> {
+    /// The application the command acts on.
+    pub app: &'a mut App,
+    /// The count typed before the command, if any.
+    pub count: Option<usize>,
+}
+
+impl Context<'_> {
+    /// Returns the count typed before the command, defaulting to 1.
+    pub fn count(&self) -> usize {
+        self.count.unwrap_or(1)
+    }
+}
+
+/// A named editor command that keys can be bound to.
+#[derive(Clone, Copy)]
+pub struct Command {
+    /// The name used to refer to the command in the configuration.
+    pub name: &'static str,
+    /// A one-line description of the command.
+    pub doc: &'static str,
+    fun: fn(&mut Context),
+}
+
+impl Command {
+    /// Looks up a command by name.
+    pub fn find(name: &str) -> Option<&'static Command> {
+        COMMANDS.iter().find(|command| command.name == name)
+    }
+
+    /// Returns all commands.
+    pub fn all() -> &'static [Command] {
+        COMMANDS
+    }
+
+    /// Runs the command.
+    pub fn execute(&self, cx: &mut Context) {
+        (self.fun)(cx)
+    }
+}
+
+impl fmt::Debug for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Command").field(&self.name).finish()
+    }
+}
+
+impl PartialEq for Command {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+/// An operator waiting for a motion or text object.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    /// Delete the text.
+    Delete,
+    /// Delete the text and enter Insert mode.
+    Change,
+    /// Copy the text.
+    Yank,
+}
+
+/// A command waiting for the next typed character.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CharArgument {
+    /// `f`, `t`, `F` and `T`.
+    Find {
+        /// Search direction.
+        direction: Direction,
+        /// Stop next to the character.
+        till: bool,
+    },
+    /// `r`.
+    Replace,
+    /// `i` or `a` after an operator or in Visual mode.
+    TextObject {
+        /// Exclude surrounding whitespace or delimiters.
+        inner: bool,
+    },
+    /// `"`, which picks the register of the next command.
+    Register,
+}
+
+macro_rules! commands {
+    ($($name:ident => $doc:literal,)*) => {
+        const COMMANDS: &[Command] = &[
+            $(Command { name: stringify!($name), doc: $doc, fun: $name },)*
+        ];
+    };
+}
+
+commands! {
+    no_op => "Do nothing",
+    quit => "Close the view, or the editor from the last view",
+    force_quit => "Close the view, or the editor from the last view, discarding unsaved changes",
+    write => "Save the current buffer",
+    write_quit => "Save the current buffer and close the view, or the editor from the last view",
+    normal_mode => "Enter normal mode",
+    insert_mode => "Insert before the cursor",
+    append_mode => "Insert after the cursor",
+    insert_at_line_start => "Insert before the first non-blank of the line",
+    append_at_line_end => "Insert at the end of the line",
+    open_below => "Open a line below the cursor and insert",
+    open_above => "Open a line above the cursor and insert",
+    visual_mode => "Select characterwise",
+    visual_line_mode => "Select linewise",
+    visual_block_mode => "Select a block",
+    flip_selection => "Move the cursor to the other end of the selection",
+    select_next_match => "Select the word under the cursor, or add a selection on the next match",
+    split_selection_into_lines => "Split every selection into one selection per line",
+    align_selections => "Align the selections in a column by padding them with spaces",
+    rotate_selection_contents_forward => "Move the text of every selection into the next one",
+    rotate_selection_contents_backward => "Move the text of every selection into the previous one",
+    move_char_left => "Move left",
+    move_char_right => "Move right",
+    move_line_down => "Move down",
+    move_line_up => "Move up",
+    move_next_word_start => "Move to the start of the next word",
+    move_prev_word_start => "Move to the start of the previous word",
+    move_next_word_end => "Move to the end of the next word",
+    move_next_long_word_start => "Move to the start of the next WORD",
+    move_prev_long_word_start => "Move to the start of the previous WORD",
+    move_next_long_word_end => "Move to the end of the next WORD",
+    goto_line_start => "Go to the start of the line",
+    goto_first_nonblank => "Go to the first non-blank of the line",
+    goto_line_end => "Go to the end of the line",
+    goto_file_start => "Go to the first line, or to line [count]",
+    goto_last_line => "Go to the last line, or to line [count]",
+    goto_next_paragraph => "Go to the end of the paragraph",
+    goto_prev_paragraph => "Go to the start of the paragraph",
+    find_next_char => "Go to the next occurrence of a character",
+    find_prev_char => "Go to the previous occurrence of a character",
+    till_next_char => "Go till the next occurrence of a character",
+    till_prev_char => "Go till the previous occurrence of a character",
+    repeat_find => "Repeat the last character search",
+    repeat_find_reverse => "Repeat the last character search in the opposite direction",
+    select_textobject_inner => "Select inside the text object named by the next character",
+    select_textobject_around => "Select around the text object named by the next character",
+    delete => "Delete the text covered by a motion or the selection",
+    change => "Change the text covered by a motion or the selection",
+    yank => "Copy the text covered by a motion or the selection",
+    delete_char => "Delete the character under the cursor",
+    delete_to_line_end => "Delete to the end of the line",
+    change_to_line_end => "Change to the end of the line",
+    yank_line => "Copy the line",
+    select_register => "Use the register named by the next key for the next command",
+    paste_after => "Paste after the cursor",
+    paste_before => "Paste before the cursor",
+    replace_char => "Replace the character under the cursor",
+    undo => "Undo the last change",
+    redo => "Redo the last undone change",
+    earlier => "Go back to an older text state, across undo branches",
+    later => "Go forward to a newer text state, across undo branches",
+    search_forward => "Search forward for a pattern",
+    search_backward => "Search backward for a pattern",
+    search_next => "Go to the next match of the last search",
+    search_prev => "Go to the previous match of the last search",
+    substitute => "Replace the matches of a pattern in the buffer or the selected lines",
+    global_search => "Search the files of the workspace for a pattern",
+    command_mode => "Type a command, such as :w or :set",
+    file_picker => "Open a file of the workspace",
+    buffer_picker => "Go to an open buffer",
+    symbol_picker => "Go to a symbol of the buffer",
+    workspace_symbol_picker => "Go to a symbol of the workspace",
+    diagnostics_picker => "Go to a diagnostic of any document",
+    theme_picker => "Switch to another theme",
+    command_palette => "Run a command by name",
+    insert_newline => "Insert a line break",
+    insert_tab => "Insert a tab",
+    delete_char_backward => "Delete the character before the cursor",
+    delete_char_forward => "Delete the character under the cursor",
+    hover => "Show documentation for the symbol under the cursor",
+    goto_definition => "Go to the definition of the symbol under the cursor",
+    goto_references => "Go to the references to the symbol under the cursor",
+    completion => "Complete the word before the cursor",
+    goto_next_diagnostic => "Go to the next diagnostic",
+    goto_prev_diagnostic => "Go to the previous diagnostic",
+    jump_backward => "Go back to the [count]th place jumped from",
+    jump_forward => "Go forward to the [count]th place jumped back from",
+    buffer_next => "Go to the next buffer",
+    buffer_previous => "Go to the previous buffer",
+    split_horizontal => "Split the view, opening the new view below",
+    split_vertical => "Split the view, opening the new view on the right",
+    view_left => "Focus the view on the left",
+    view_right => "Focus the view on the right",
+    view_up => "Focus the view above",
+    view_down => "Focus the view below",
+    swap_view_left => "Swap the view with the one on the left",
+    swap_view_right => "Swap the view with the one on the right",
+    swap_view_up => "Swap the view with the one above",
+    swap_view_down => "Swap the view with the one below",
+    grow_view_height => "Make the view [count] rows taller",
+    shrink_view_height => "Make the view [count] rows shorter",
+    grow_view_width => "Make the view [count] columns wider",
+    shrink_view_width => "Make the view [count] columns narrower",
+    equalize_views => "Give every view the same size",
+    close_view => "Close the view, unless it is the last one",
+    only_view => "Close every other view",
+    debug_start => "Start a debug session with the first configuration of the language",
+    debug_stop => "End the debug session",
+    debug_continue => "Continue the debuggee, or start a debug session",
+    debug_pause => "Pause the debuggee",
+    debug_next => "Step over the current line",
+    debug_step_in => "Step into the function called on the current line",
+    debug_step_out => "Step out of the current function",
+    debug_toggle_breakpoint => "Set or clear a breakpoint on the current line",
+    debug_variables => "Show the variables of the current stack frame",
+}
+
+fn no_op(_cx: &mut Context) {}
+
+fn quit(cx: &mut Context) {
+    cx.app.quit(false);
+}
+
+fn force_quit(cx: &mut Context) {
+    cx.app.quit(true);
+}
+
+fn write(cx: &mut Context) {
+    cx.app.write();
+}
+
+fn write_quit(cx: &mut Context) {
+    if cx.app.write() {
+        cx.app.quit(false);
+    }
+}
+
+fn jump_backward(cx: &mut Context) {
+    cx.app.jump(Direction::Backward, cx.count());
+}
+
+fn jump_forward(cx: &mut Context) {
+    cx.app.jump(Direction::Forward, cx.count());
+}
+
+fn buffer_next(cx: &mut Context) {
+    cx.app.save_jump();
+    let len = cx.app.buffers.len();
+    cx.app.focus = (cx.app.focus + 1) % len;
+}
+
+fn buffer_previous(cx: &mut Context) {
+    cx.app.save_jump();
+    let len = cx.app.buffers.len();
+    cx.app.focus = (cx.app.focus + len - 1) % len;
+}
+
+// Views
+
+fn split_horizontal(cx: &mut Context) {
+    cx.app.split(Split::Horizontal);
+}
+
+fn split_vertical(cx: &mut Context) {
+    cx.app.split(Split::Vertical);
+}
+
+fn view_left(cx: &mut Context) {
+    cx.app.focus_neighbor(Side::Left);
+}
+
+fn view_right(cx: &mut Context) {
+    cx.app.focus_neighbor(Side::Right);
+}
+
+fn view_up(cx: &mut Context) {
+    cx.app.focus_neighbor(Side::Up);
+}
+
+fn view_down(cx: &mut Context) {
+    cx.app.focus_neighbor(Side::Down);
+}
+
+fn swap_view_left(cx: &mut Context) {
+    cx.app.swap_view(Side::Left);
+}
+
+fn swap_view_right(cx: &mut Context) {
+    cx.app.swap_view(Side::Right);
+}
+
+fn swap_view_up(cx: &mut Context) {
+    cx.app.swap_view(Side::Up);
+}
+
+fn swap_view_down(cx: &mut Context) {
+    cx.app.swap_view(Side::Down);
+}
+
+fn grow_view_height(cx: &mut Context) {
+    let count = cx.count() as i32;
+    cx.app.resize_view(Split::Horizontal, count);
+}
+
+fn shrink_view_height(cx: &mut Context) {
+    let count = cx.count() as i32;
+    cx.app.resize_view(Split::Horizontal, -count);
+}
+
+fn grow_view_width(cx: &mut Context) {
+    let count = cx.count() as i32;
+    cx.app.resize_view(Split::Vertical, count);
+}
+
+fn shrink_view_width(cx: &mut Context) {
+    let count = cx.count() as i32;
+    cx.app.resize_view(Split::Vertical, -count);
+}
+
+fn equalize_views(cx: &mut Context) {
+    cx.app.equalize_views();
+}
+
+fn close_view(cx: &mut Context) {
+    if !cx.app.close_view() {
+        cx.app.set_status("Cannot close the last view");
+    }
+}
+
+fn only_view(cx: &mut Context) {
+    cx.app.close_other_views();
+}
+
+// History
+
+fn undo(cx: &mut Context) {
+    let count = cx.count();
+    if !(0..count).fold(false, |undone, _| cx.app.buffer_mut().undo() || undone) {
+        cx.app.set_status("Already at oldest change");
+    }
+}
+
+fn redo(cx: &mut Context) {
+    let count = cx.count();
+    if !(0..count).fold(false, |redone, _| cx.app.buffer_mut().redo() || redone) {
+        cx.app.set_status("Already at newest change");
+    }
+}
+
+fn earlier(cx: &mut Context) {
+    let steps = UndoKind::Steps(cx.count());
+    if !cx.app.buffer_mut().earlier(steps) {
+        cx.app.set_status("Already at oldest change");
+    }
+}
+
+fn later(cx: &mut Context) {
+    let steps = UndoKind::Steps(cx.count());
+    if !cx.app.buffer_mut().later(steps) {
+        cx.app.set_status("Already at newest change");
+    }
+}
+
+// Search
+
+fn search_forward(cx: &mut Context) {
+    cx.app
+        .open_prompt(PromptKind::Search(Direction::Forward), "");
+}
+
+fn search_backward(cx: &mut Context) {
+    cx.app
+        .open_prompt(PromptKind::Search(Direction::Backward), "");
+}
+
+fn search_next(cx: &mut Context) {
+    let direction = cx.app.search_direction;
+    cx.app.search_next(direction, cx.count());
+}
+
+fn search_prev(cx: &mut Context) {
+    let direction = match cx.app.search_direction {
+        Direction::Forward => Direction::Backward,
+        Direction::Backward => Direction::Forward,
+    };
+    cx.app.search_next(direction, cx.count());
+}
+
+fn substitute(cx: &mut Context) {
+    let line = match cx.app.mode {
+        Mode::Visual => "s/",
+        _ => "%s/",
+    };
+    command_line(cx, line);
+}
+
+fn global_search(cx: &mut Context) {
+    cx.app.open_prompt(PromptKind::Grep, "");
+}
+
+// Command mode
+
+fn command_mode(cx: &mut Context) {
+    command_line(cx, "");
+}
+
+// Enters Command mode with `line` typed. Commands apply to the selected lines in Visual mode, or
+// to the line of the cursor.
+fn command_line(cx: &mut Context, line: &str) {
+    let buffer = cx.app.buffer();
+    let primary = buffer.selection().primary();
+    let first = buffer.byte_to_pos(primary.from()).line;
+    let lines = match cx.app.mode {
+        Mode::Visual => first..=buffer.byte_to_pos(primary.to()).line,
+        _ => first..=first,
+    };
+    cx.app.mode = Mode::Command;
+    cx.app.open_prompt(PromptKind::Command { lines }, line);
+}
+
+// Pickers
+
+fn file_picker(cx: &mut Context) {
+    cx.app.file_picker();
+}
+
+fn buffer_picker(cx: &mut Context) {
+    cx.app.buffer_picker();
+}
+
+fn symbol_picker(cx: &mut Context) {
+    cx.app.symbol_picker();
+}
+
+fn workspace_symbol_picker(cx: &mut Context) {
+    cx.app.workspace_symbol_picker();
+}
+
+fn diagnostics_picker(cx: &mut Context) {
+    cx.app.diagnostics_picker();
+}
+
+fn theme_picker(cx: &mut Context) {
+    cx.app.theme_picker();
+}
+
+fn command_palette(cx: &mut Context) {
+    cx.app.command_palette();
+}
+
+// Language server
+
+fn hover(cx: &mut Context) {
+    cx.app
+        .request_language_server(|client, uri, pos| async move {
+            let contents = match client.hover(uri, pos).await? {
+                Some(hover) => match hover.contents {
+                    lsp::HoverContents::Scalar(content) => marked_string(content),
+                    lsp::HoverContents::Array(contents) => contents
+                        .into_iter()
+                        .map(marked_string)
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                    lsp::HoverContents::Markup(content) => content.value,
+                },
+                None => String::new(),
+            };
+
+            // The status line has room for a single line.
+            let contents = contents.split_whitespace().collect::<Vec<_>>().join(" ");
+            Ok(Message::Status(match contents.is_empty() {
+                true => "No documentation".into(),
+                false => contents,
+            }))
+        });
+}
+
+fn marked_string(content: lsp::MarkedString) -> String {
+    match content {
+        lsp::MarkedString::String(content) => content,
+        lsp::MarkedString::LanguageString(content) => content.value,
+    }
+}
+
+fn goto_definition(cx: &mut Context) {
+    cx.app
+        .request_language_server(|client, uri, pos| async move {
+            Ok(Message::Locations(client.goto_definition(uri, pos).await?))
+        });
+}
+
+fn goto_references(cx: &mut Context) {
+    cx.app
+        .request_language_server(|client, uri, pos| async move {
+            Ok(Message::Locations(client.references(uri, pos).await?))
+        });
+}
+
+fn completion(cx: &mut Context) {
+    cx.app
+        .request_language_server(|client, uri, pos| async move {
+            Ok(Message::Completion(client.completion(uri, pos).await?))
+        });
+}
+
+fn goto_diagnostic(cx: &mut Context, direction: Direction) {
+    let app = &mut *cx.app;
+    let buffer = app.buffer();
+    let head = buffer.selection().primary().head;
+    let Some(diagnostics) = buffer
+        .path()
+        .and_then(language_server::uri)
+        .and_then(|uri| app.diagnostics.get(&uri))
+    else {
+        return;
+    };
+
+    let mut targets: Vec<_> = diagnostics
+        .iter()
+        .filter_map(|diagnostic| {
+            let pos = tissue_lsp::lsp_pos_to_pos(
+                buffer.text(),
+                diagnostic.range.start,
+                app.language_server()
+                    .map(|client| client.offset_encoding())
+                    .unwrap_or_default(),
+            )?;
+            Some((pos, diagnostic.message.clone()))
+        })
+        .collect();
+    targets.sort_by_key(|&(pos, _)| pos);
+
+    let target = match direction {
+        Direction::Forward => targets.into_iter().find(|&(pos, _)| pos > head),
+        Direction::Backward => targets.into_iter().rev().find(|&(pos, _)| pos < head),
+    };
+
+    if let Some((pos, message)) = target {
+        app.save_jump();
+        set_selection(app, Selection::point(pos));
+        app.set_status(message);
+    }
+}
+
+fn goto_next_diagnostic(cx: &mut Context) {
+    goto_diagnostic(cx, Direction::Forward);
+}
+
+fn goto_prev_diagnostic(cx: &mut Context) {
+    goto_diagnostic(cx, Direction::Backward);
+}
+
+// Debugger
+
+fn debug_start(cx: &mut Context) {
+    cx.app.start_debugger(None, &[]);
+}
+
+fn debug_stop(cx: &mut Context) {
+    cx.app.stop_debugger();
+}
+
+fn debug_continue(cx: &mut Context) {
+    if cx.app.debugger.is_none() {
+        return cx.app.start_debugger(None, &[]);
+    }
+    cx.app
+        .resume_debuggee(|client, thread| async move { client.continue_thread(thread).await });
+}
+
+fn debug_pause(cx: &mut Context) {
+    cx.app.pause_debuggee();
+}
+
+fn debug_next(cx: &mut Context) {
+    cx.app
+        .resume_debuggee(|client, thread| async move { client.next(thread).await });
+}
+
+fn debug_step_in(cx: &mut Context) {
+    cx.app
+        .resume_debuggee(|client, thread| async move { client.step_in(thread).await });
+}
+
+fn debug_step_out(cx: &mut Context) {
+    cx.app
+        .resume_debuggee(|client, thread| async move { client.step_out(thread).await });
+}
+
+fn debug_toggle_breakpoint(cx: &mut Context) {
+    cx.app.toggle_breakpoint();
+}
+
+fn debug_variables(cx: &mut Context) {
+    cx.app.request_variables();
+}
+
+// Modes
+
+fn set_selection(app: &mut App, selection: Selection) {
+    app.buffer_mut().set_selection(selection);
+}
+
+fn enter_insert_mode<F>(cx: &mut Context, f: F)
+where
+    F: Fn(&Buffer, Range) -> usize,
+{
+    let buffer = cx.app.buffer();
+    let selection = buffer
+        .selection()
+        .clone()
+        .transform(|range| Range::point(f(buffer, range)));
+
+    set_selection(cx.app, selection);
+    cx.app.mode = Mode::Insert;
+}
+
+fn normal_mode(cx: &mut Context) {
+    let app = &mut *cx.app;
+    let buffer = app.buffer();
+
+    let selection = match app.mode {
+        // Leaving Insert mode puts the cursor back onto the last inserted character.
+        Mode::Insert => buffer
+            .selection()
+            .clone()
+            .transform(|range| Range::point(motion::char_left(buffer, range.head, 1))),
+        // A second escape drops all cursors but the primary.
+        Mode::Normal => Selection::point(buffer.selection().primary().head),
+        Mode::Visual | Mode::Command => buffer
+            .selection()
+            .clone()
+            .transform(|range| Range::point(range.head)),
+    };
+
+    set_selection(app, selection);
+    app.mode = Mode::Normal;
+    app.operator = None;
+    app.register = None;
+}
+
+fn insert_mode(cx: &mut Context) {
+    enter_insert_mode(cx, |_, range| range.from());
+}
+
+fn append_mode(cx: &mut Context) {
+    enter_insert_mode(cx, |buffer, range| {
+        motion::char_right(buffer, range.to(), 1)
+    });
+}
+
+fn insert_at_line_start(cx: &mut Context) {
+    enter_insert_mode(cx, |buffer, range| {
+        motion::first_non_blank(buffer, buffer.byte_to_pos(range.head).line)
+    });
+}
+
+fn append_at_line_end(cx: &mut Context) {
+    enter_insert_mode(cx, |buffer, range| {
+        buffer.line_end(buffer.byte_to_pos(range.head).line)
+    });
+}
+
+fn open_line(cx: &mut Context, below: bool) {
+    let buffer = cx.app.buffer();
+    let changes: Vec<_> = buffer
+        .selection()
+        .ranges()
+        .iter()
+        .map(|range| {
+            let line = buffer.byte_to_pos(range.head).line;
+            let indent = indentation(buffer, line);
+            if below {
+                let end = buffer.line_end(line);
+                (end, end, format!("\n{indent}"))
+            } else {
+                let start = buffer.line_start(line);
+                (start, start, format!("{indent}\n"))
+            }
+        })
+        .collect();
+
+    let cursors: Vec<_> = changes
+        .iter()
+        .scan(0, |delta, (at, _, text)| {
+            let cursor = at + *delta + if below { text.len() } else { text.len() - 1 };
+            *delta += text.len();
+            Some(Range::point(cursor))
+        })
+        .collect();
+
+    cx.app.buffer_mut().apply(&Transaction::change(changes));
+    set_selection(cx.app, Selection::new(cursors, 0));
+    cx.app.mode = Mode::Insert;
+}
+
+fn open_below(cx: &mut Context) {
+    open_line(cx, true);
+}
+
+fn open_above(cx: &mut Context) {
+    open_line(cx, false);
+}
+
+fn enter_visual_mode(cx: &mut Context, kind: VisualKind) {
+    let app = &mut *cx.app;
+
+    if app.mode == Mode::Visual && app.visual == kind {
+        normal_mode(cx);
+        return;
+    }
+
+    if app.mode != Mode::Visual {
+        let selection = app
+            .buffer()
+            .selection()
+            .clone()
+            .transform(|range| Range::point(range.head));
+        set_selection(app, selection);
+    }
+
+    app.mode = Mode::Visual;
+    app.visual = kind;
+}
+
+fn visual_mode(cx: &mut Context) {
+    enter_visual_mode(cx, VisualKind::Char);
+}
+
+fn visual_line_mode(cx: &mut Context) {
+    enter_visual_mode(cx, VisualKind::Line);
+}
+
+fn visual_block_mode(cx: &mut Context) {
+    enter_visual_mode(cx, VisualKind::Block);
+}
+
+fn flip_selection(cx: &mut Context) {
+    let selection = cx
+        .app
+        .buffer()
+        .selection()
+        .clone()
+        .transform(|range| Range::new(range.head, range.anchor));
+    set_selection(cx.app, selection);
+}
+
+// Selections
+
+// Selects the word under the primary cursor, or adds a selection on the next match of the primary
+// selection in Visual mode, wrapping around the end of the buffer.
+fn select_next_match(cx: &mut Context) {
+    for _ in 0..cx.count() {
+        let app = &mut *cx.app;
+        let buffer = app.buffer();
+        let selection = buffer.selection();
+        let primary = selection.primary();
+
+        if app.mode != Mode::Visual {
+            let word = TextObject::Word { big: false };
+            let Some(range) = word
+                .range(buffer, primary.head, true)
+                .filter(|r| !r.is_empty())
+            else {
+                return;
+            };
+            let range = Range::new(range.start, buffer.prev_grapheme_boundary(range.end));
+            set_selection(app, Selection::single(range));
+            app.mode = Mode::Visual;
+            app.visual = VisualKind::Char;
+            continue;
+        }
+
+        let text = buffer.text();
+        let current = primary.inclusive(buffer);
+        let pattern = text.byte_slice(current.clone()).to_string();
+        if pattern.is_empty() {
+            return;
+        }
+        let search = match Search::with_case(&regex::escape(&pattern), true) {
+            Ok(search) => search,
+            Err(err) => return app.set_status(format!("{err:#}")),
+        };
+        let found = search
+            .find_iter(text, current.end..text.len_bytes())
+            .chain(search.find_iter(text, 0..current.end))
+            .find(|found| {
+                found.as_ref().map_or(true, |found| {
+                    !selection
+                        .ranges()
+                        .iter()
+                        .any(|range| range.from() < found.end && found.start <= range.to())
+                })
+            });
+
+        let found = match found {
+            Some(Ok(found)) => found,
+            Some(Err(err)) => return app.set_status(format!("{err:#}")),
+            None => return app.set_status("No more matches"),
+        };
+        let range = Range::new(found.start, buffer.prev_grapheme_boundary(found.end));
+        let selection = selection.clone().push(range);
+        set_selection(app, selection);
+        app.visual = VisualKind::Char;
+    }
+}
+
+fn split_selection_into_lines(cx: &mut Context) {
+    let app = &mut *cx.app;
+    let buffer = app.buffer();
+    let selection = buffer.selection();
+
+    let mut ranges = Vec::new();
+    let mut primary = 0;
+    for (index, &range) in selection.ranges().iter().enumerate() {
+        if index == selection.primary_index() {
+            primary = ranges.len();
+        }
+        if app.visual == VisualKind::Block {
+            ranges.extend(
+                block_ranges(buffer, range)
+                    .into_iter()
+                    .filter(|range| !range.is_empty())
+                    .map(|range| Range::new(range.start, buffer.prev_grapheme_boundary(range.end))),
+            );
+            continue;
+        }
+
+        let first = buffer.byte_to_pos(range.from()).line;
+        let last = buffer.byte_to_pos(range.to()).line;
+        ranges.extend((first..=last).map(|line| {
+            let (from, to) = match app.visual {
+                VisualKind::Line => (buffer.line_start(line), motion::last_grapheme(buffer, line)),
+                _ => (
+                    range.from().max(buffer.line_start(line)),
+                    range.to().min(motion::last_grapheme(buffer, line)),
+                ),
+            };
+            Range::new(from, to.max(from))
+        }));
+    }
+
+    if !ranges.is_empty() {
+        let primary = primary.min(ranges.len() - 1);
+        set_selection(app, Selection::new(ranges, primary));
+        app.visual = VisualKind::Char;
+    }
+}
+
+// Pads the selections with spaces so that they all start in the same column.
+fn align_selections(cx: &mut Context) {
+    let buffer = cx.app.buffer();
+    let starts: Vec<_> = buffer
+        .selection()
+        .ranges()
+        .iter()
+        .map(|range| {
+            let from = range.from();
+            (
+                from,
+                buffer.byte_to_pos(from).line,
+                buffer.grapheme_column(from),
+            )
+        })
+        .collect();
+
+    if starts.windows(2).any(|w| w[0].1 == w[1].1) {
+        return cx
+            .app
+            .set_status("Cannot align selections on the same line");
+    }
+    let column = starts
+        .iter()
+        .map(|&(_, _, column)| column)
+        .max()
+        .unwrap_or(0);
+    let changes = starts
+        .into_iter()
+        .map(|(from, _, col)| (from, from, " ".repeat(column - col)));
+
+    cx.app.buffer_mut().apply(&Transaction::change(changes));
+}
+
+// Replaces the text of every selection with the text of its neighbour, keeping each selection
+// over its new text.
+fn rotate_selection_contents(cx: &mut Context, direction: Direction) {
+    let buffer = cx.app.buffer();
+    let selection = buffer.selection().clone();
+    let ranges: Vec<_> = selection
+        .ranges()
+        .iter()
+        .map(|range| range.inclusive(buffer))
+        .collect();
+    if ranges.len() < 2 {
+        return;
+    }
+
+    let mut texts: Vec<_> = ranges
+        .iter()
+        .map(|range| buffer.slice(range.clone()).to_string())
+        .collect();
+    match direction {
+        Direction::Forward => texts.rotate_right(1),
+        Direction::Backward => texts.rotate_left(1),
+    }
+
+    let mut delta = 0isize;
+    let spans: Vec<_> = ranges
+        .iter()
+        .zip(&texts)
+        .map(|(range, text)| {
+            let start = (range.start as isize + delta) as usize;
+            delta += text.len() as isize - range.len() as isize;
+            start..start + text.len()
+        })
+        .collect();
+
+    cx.app.buffer_mut().apply(&Transaction::change(
+        ranges
+            .into_iter()
+            .zip(texts)
+            .map(|(range, text)| (range.start, range.end, text)),
+    ));
+
+    let buffer = cx.app.buffer();
+    let rotated: Vec<_> = selection
+        .ranges()
+        .iter()
+        .zip(spans)
+        .map(|(range, span)| {
+            let end = buffer.prev_grapheme_boundary(span.end).max(span.start);
+            match range.head < range.anchor {
+                true => Range::new(end, span.start),
+                false => Range::new(span.start, end),
+            }
+        })
+        .collect();
+    set_selection(cx.app, Selection::new(rotated, selection.primary_index()));
+}
+
+fn rotate_selection_contents_forward(cx: &mut Context) {
+    rotate_selection_contents(cx, Direction::Forward);
+}
+
+fn rotate_selection_contents_backward(cx: &mut Context) {
+    rotate_selection_contents(cx, Direction::Backward);
+}
+
+// Motions
+
+// Moves every cursor with `f`, or applies the pending operator to the text covered by the move.
+fn apply_motion<F>(cx: &mut Context, f: F)
+where
+    F: Fn(&Buffer, usize, usize, Option<Operator>) -> Option<Motion>,
+{
+    let app = &mut *cx.app;
+    let (operator, operator_count) = match app.operator.take() {
+        Some((operator, count)) => (Some(operator), count),
+        None => (None, None),
+    };
+    let count = operator_count.unwrap_or(1) * cx.count.unwrap_or(1);
+    let buffer = app.buffer();
+    let selection = buffer.selection().clone();
+
+    let Some(operator) = operator else {
+        let visual = app.mode == Mode::Visual;
+        let selection = selection.transform(|range| match f(buffer, range.head, count, None) {
+            Some(motion) if visual => range.put_head(motion.pos),
+            Some(motion) => Range::point(motion.pos),
+            None => range,
+        });
+        set_selection(app, selection);
+        return;
+    };
+
+    let mut linewise = false;
+    let ranges: Vec<_> = selection
+        .ranges()
+        .iter()
+        .filter_map(|range| {
+            let motion = f(buffer, range.head, count, Some(operator))?;
+            let (range, is_linewise) = operator_range(buffer, range.head, motion);
+            linewise |= is_linewise;
+            Some(range)
+        })
+        .collect();
+
+    apply_operator(app, operator, ranges, linewise);
+}
+
+// Returns the text covered by moving from `start` with `motion`, and whether it is linewise.
+fn operator_range(buffer: &Buffer, start: usize, motion: Motion) -> (std::ops::Range<usize>, bool) {
+    let (from, to) = (start.min(motion.pos), start.max(motion.pos));
+    let from_line = buffer.byte_to_pos(from).line;
+    let to_line = buffer.byte_to_pos(to).line;
+
+    match motion.kind {
+        MotionKind::Linewise => (lines_range(buffer, from_line, to_line), true),
+        MotionKind::Inclusive if to < buffer.line_end(to_line) => {
+            (from..buffer.next_grapheme_boundary(to), false)
+        }
+        MotionKind::Inclusive => (from..to, false),
+        // An exclusive motion ending at the start of a later line stops at the end of the line
+        // before, and covers whole lines if it also started before the first non-blank.
+        MotionKind::Exclusive if to_line > from_line && to == buffer.line_start(to_line) => {
+            if from <= motion::first_non_blank(buffer, from_line) {
+                (lines_range(buffer, from_line, to_line - 1), true)
+            } else {
+                (from..buffer.line_end(to_line - 1), false)
+            }
+        }
+        MotionKind::Exclusive => (from..to, false),
+    }
+}
+
+// Returns the bytes of lines `first..=last`, including their line endings.
+fn lines_range(buffer: &Buffer, first: usize, last: usize) -> std::ops::Range<usize> {
+    let end = if last + 1 < buffer.len_lines() {
+        buffer.line_start(last + 1)
+    } else {
+        buffer.len_bytes()
+    };
+
+    buffer.line_start(first)..end
+}
+
+fn move_char_left(cx: &mut Context) {
+    apply_motion(cx, |buffer, pos, count, _| {
+        Some(Motion::new(
+            motion::char_left(buffer, pos, count),
+            MotionKind::Exclusive,
+        ))
+    });
+}
+
+fn move_char_right(cx: &mut Context) {
+    apply_motion(cx, |buffer, pos, count, _| {
+        Some(Motion::new(
+            motion::char_right(buffer, pos, count),
+            MotionKind::Exclusive,
+        ))
+    });
+}
+
+fn move_line(cx: &mut Context, direction: Direction) {
+    apply_motion(cx, |buffer, pos, count, _| {
+        let target = motion::line_vertical(buffer, pos, direction, count);
+        let moved = buffer.byte_to_pos(target).line != buffer.byte_to_pos(pos).line;
+        moved.then(|| Motion::new(target, MotionKind::Linewise))
+    });
+}
+
+fn move_line_down(cx: &mut Context) {
+    move_line(cx, Direction::Forward);
+}
+
+fn move_line_up(cx: &mut Context) {
+    move_line(cx, Direction::Backward);
+}
+
+fn word_forward(cx: &mut Context, big: bool) {
+    apply_motion(cx, |buffer, pos, count, operator| {
+        let on_blank = buffer.char_at(pos).map_or(true, char::is_whitespace);
+
+        match operator {
+            // `cw` changes to the end of the word, like `ce`.
+            Some(Operator::Change) if !on_blank => Some(Motion::new(
+                motion::next_word_end(buffer, pos, count, big),
+                MotionKind::Inclusive,
+            )),
+            // The last word moved over by an operator ends at the end of its line.
+            Some(_) => {
+                let target = motion::next_word_start(buffer, pos, count, big);
+                let line = buffer.byte_to_pos(target).line;
+                let target = if line > buffer.byte_to_pos(pos).line {
+                    buffer.line_end(line - 1).max(pos)
+                } else {
+                    target
+                };
+                Some(Motion::new(target, MotionKind::Exclusive))
+            }
+            None => Some(Motion::new(
+                motion::next_word_start(buffer, pos, count, big),
+                MotionKind::Exclusive,
+            )),
+        }
+    });
+}
+
+fn move_next_word_start(cx: &mut Context) {
+    word_forward(cx, false);
+}
+
+fn move_next_long_word_start(cx: &mut Context) {
+    word_forward(cx, true);
+}
+
+fn move_prev_word_start(cx: &mut Context) {
+    apply_motion(cx, |buffer, pos, count, _| {
+        Some(Motion::new(
+            motion::prev_word_start(buffer, pos, count, false),
+            MotionKind::Exclusive,
+        ))
+    });
+}
+
+fn move_prev_long_word_start(cx: &mut Context) {
+    apply_motion(cx, |buffer, pos, count, _| {
+        Some(Motion::new(
+            motion::prev_word_start(buffer, pos, count, true),
+            MotionKind::Exclusive,
+        ))
+    });
+}
+
+fn move_next_word_end(cx: &mut Context) {
+    apply_motion(cx, |buffer, pos, count, _| {
+        Some(Motion::new(
+            motion::next_word_end(buffer, pos, count, false),
+            MotionKind::Inclusive,
+        ))
+    });
+}
+
+fn move_next_long_word_end(cx: &mut Context) {
+    apply_motion(cx, |buffer, pos, count, _| {
+        Some(Motion::new(
+            motion::next_word_end(buffer, pos, count, true),
+            MotionKind::Inclusive,
+        ))
+    });
+}
+
+fn goto_line_start(cx: &mut Context) {
+    apply_motion(cx, |buffer, pos, _, _| {
+        let line = buffer.byte_to_pos(pos).line;
+        Some(Motion::new(buffer.line_start(line), MotionKind::Exclusive))
+    });
+}
+
+fn goto_first_nonblank(cx: &mut Context) {
+    apply_motion(cx, |buffer, pos, _, _| {
+        let line = buffer.byte_to_pos(pos).line;
+        Some(Motion::new(
+            motion::first_non_blank(buffer, line),
+            MotionKind::Exclusive,
+        ))
+    });
+}
+
+fn goto_line_end(cx: &mut Context) {
+    apply_motion(cx, |buffer, pos, count, _| {
+        let line = (buffer.byte_to_pos(pos).line + count - 1).min(last_line(buffer));
+        Some(Motion::new(
+            motion::last_grapheme(buffer, line),
+            MotionKind::Inclusive,
+        ))
+    });
+}
+
+// Returns the last line that can hold the cursor, which excludes the empty line after a trailing
+// line break.
+fn last_line(buffer: &Buffer) -> usize {
+    let last = buffer.len_lines() - 1;
+    if last > 0 && buffer.line_start(last) == buffer.len_bytes() {
+        last - 1
+    } else {
+        last
+    }
+}
+
+fn goto_line(cx: &mut Context, default: fn(&Buffer) -> usize) {
+    let line = cx.count.map(|count| count - 1);
+    cx.count = None;
+    cx.app.save_jump();
+
+    apply_motion(cx, |buffer, _, _, _| {
+        let line = line
+            .unwrap_or_else(|| default(buffer))
+            .min(last_line(buffer));
+        Some(Motion::new(
+            motion::first_non_blank(buffer, line),
+            MotionKind::Linewise,
+        ))
+    });
+}
+
+fn goto_file_start(cx: &mut Context) {
+    goto_line(cx, |_| 0);
+}
+
+fn goto_last_line(cx: &mut Context) {
+    goto_line(cx, last_line);
+}
+
+fn goto_next_paragraph(cx: &mut Context) {
+    apply_motion(cx, |buffer, pos, count, _| {
+        Some(Motion::new(
+            motion::next_paragraph(buffer, pos, count),
+            MotionKind::Exclusive,
+        ))
+    });
+}
+
+fn goto_prev_paragraph(cx: &mut Context) {
+    apply_motion(cx, |buffer, pos, count, _| {
+        Some(Motion::new(
+            motion::prev_paragraph(buffer, pos, count),
+            MotionKind::Exclusive,
+        ))
+    });
+}
+
+fn await_char(cx: &mut Context, argument: CharArgument) {
+    cx.app.pending_char = Some((argument, cx.count));
+}
+
+fn find_next_char(cx: &mut Context) {
+    await_char(
+        cx,
+        CharArgument::Find {
+            direction: Direction::Forward,
+            till: false,
+        },
+    );
+}
+
+fn find_prev_char(cx: &mut Context) {
+    await_char(
+        cx,
+        CharArgument::Find {
+            direction: Direction::Backward,
+            till: false,
+        },
+    );
+}
+
+fn till_next_char(cx: &mut Context) {
+    await_char(
+        cx,
+        CharArgument::Find {
+            direction: Direction::Forward,
+            till: true,
+        },
+    );
+}
+
+fn till_prev_char(cx: &mut Context) {
+    await_char(
+        cx,
+        CharArgument::Find {
+            direction: Direction::Backward,
+            till: true,
+        },
+    );
+}
+
+fn find_char(cx: &mut Context, ch: char, direction: Direction, till: bool, repeat: bool) {
+    apply_motion(cx, |buffer, pos, count, _| {
+        let kind = match direction {
+            Direction::Forward => MotionKind::Inclusive,
+            Direction::Backward => MotionKind::Exclusive,
+        };
+        // Repeating `t` would find the character right next to the cursor again, so skip it.
+        let from = match (repeat && till, direction) {
+            (true, Direction::Forward) => motion::char_right(buffer, pos, 1),
+            (true, Direction::Backward) => motion::char_left(buffer, pos, 1),
+            (false, _) => pos,
+        };
+        motion::find_char(buffer, from, ch, direction, till, count)
+            .map(|pos| Motion::new(pos, kind))
+    });
+}
+
+fn repeat_find(cx: &mut Context) {
+    if let Some((ch, direction, till)) = cx.app.last_find {
+        find_char(cx, ch, direction, till, true);
+    }
+}
+
+fn repeat_find_reverse(cx: &mut Context) {
+    if let Some((ch, direction, till)) = cx.app.last_find {
+        let reverse = match direction {
+            Direction::Forward => Direction::Backward,
+            Direction::Backward => Direction::Forward,
+        };
+        find_char(cx, ch, reverse, till, true);
+    }
+}
+
+/// Runs the command that was waiting for a character.
+pub fn char_argument(cx: &mut Context, argument: CharArgument, ch: char) {
+    match argument {
+        CharArgument::Find { direction, till } => {
+            cx.app.last_find = Some((ch, direction, till));
+            find_char(cx, ch, direction, till, false);
+        }
+        CharArgument::Replace => replace(cx, ch),
+        CharArgument::TextObject { inner } => match TextObject::from_char(ch) {
+            Some(object) => select_textobject(cx, object, inner),
+            None => cx.app.operator = None,
+        },
+        CharArgument::Register => {
+            cx.app.register = Some(ch);
+            // Keep the count for the command, as in `3"ayy`.
+            cx.app.count = cx.count;
+        }
+    }
+}
+
+fn select_textobject_inner(cx: &mut Context) {
+    await_char(cx, CharArgument::TextObject { inner: true });
+}
+
+fn select_textobject_around(cx: &mut Context) {
+    await_char(cx, CharArgument::TextObject { inner: false });
+}
+
+fn select_textobject(cx: &mut Context, object: TextObject, inner: bool) {
+    let app = &mut *cx.app;
+    let buffer = app.buffer();
+    let ranges: Vec<_> = buffer
+        .selection()
+        .ranges()
+        .iter()
+        .filter_map(|range| object.range(buffer, range.head, inner))
+        .filter(|range| !range.is_empty())
+        .collect();
+    let selected: Vec<_> = ranges
+        .iter()
+        .map(|range| Range::new(range.start, buffer.prev_grapheme_boundary(range.end)))
+        .collect();
+
+    match app.operator.take() {
+        Some((operator, _)) => apply_operator(app, operator, ranges, object.is_linewise()),
+        None if !selected.is_empty() => {
+            set_selection(app, Selection::new(selected, 0));
+            if object.is_linewise() {
+                app.visual = VisualKind::Line;
+            }
+        }
+        None => {}
+    }
+}
+
+// Operators
+
+// Sets `operator` as pending, or applies it to the selection in Visual mode. Typing an operator
+// twice (`dd`) applies it to whole lines.
+fn operator(cx: &mut Context, operator: Operator) {
+    let app = &mut *cx.app;
+
+    if app.mode == Mode::Visual {
+        let (ranges, linewise) = visual_ranges(app);
+        apply_operator(app, operator, ranges, linewise);
+        return;
+    }
+
+    match app.operator.take() {
+        Some((pending, count)) if pending == operator => {
+            let count = count.unwrap_or(1) * cx.count.unwrap_or(1);
+            linewise_operator(app, operator, count);
+        }
+        _ => app.operator = Some((operator, cx.count)),
+    }
+}
+
+fn linewise_operator(app: &mut App, operator: Operator, count: usize) {
+    let buffer = app.buffer();
+    let ranges: Vec<_> = buffer
+        .selection()
+        .ranges()
+        .iter()
+        .map(|range| {
+            let line = buffer.byte_to_pos(range.head).line;
+            lines_range(buffer, line, (line + count - 1).min(last_line(buffer)))
+        })
+        .collect();
+
+    apply_operator(app, operator, ranges, true);
+}
+
+// Returns the text selected in Visual mode.
+fn visual_ranges(app: &App) -> (Vec<std::ops::Range<usize>>, bool) {
+    let buffer = app.buffer();
+    let ranges = buffer.selection().ranges();
+
+    match app.visual {
+        VisualKind::Char => (
+            ranges.iter().map(|range| range.inclusive(buffer)).collect(),
+            false,
+        ),
+        VisualKind::Line => (
+            ranges
+                .iter()
+                .map(|range| {
+                    let from = buffer.byte_to_pos(range.from()).line;
+                    let to = buffer.byte_to_pos(range.to()).line;
+                    lines_range(buffer, from, to)
+                })
+                .collect(),
+            true,
+        ),
+        VisualKind::Block => (
+            ranges
+                .iter()
+                .flat_map(|range| block_ranges(buffer, *range))
+                .collect(),
+            false,
+        ),
+    }
+}
+
+/// Returns the part of each line covered by a block selection between `range`'s ends.
+pub fn block_ranges(buffer: &Buffer, range: Range) -> Vec<std::ops::Range<usize>> {
+    let anchor = (
+        buffer.byte_to_pos(range.anchor).line,
+        buffer.grapheme_column(range.anchor),
+    );
+    let head = (
+        buffer.byte_to_pos(range.head).line,
+        buffer.grapheme_column(range.head),
+    );
+    let (first_col, last_col) = (anchor.1.min(head.1), anchor.1.max(head.1));
+
+    (anchor.0.min(head.0)..=anchor.0.max(head.0))
+        .map(|line| {
+            let end = buffer.line_end(line);
+            let column = |n: usize| {
+                (0..n).fold(buffer.line_start(line), |pos, _| {
+                    buffer.next_grapheme_boundary(pos).min(end)
+                })
+            };
+            column(first_col)..column(last_col + 1)
+        })
+        .collect()
+}
+
+fn apply_operator(
+    app: &mut App,
+    operator: Operator,
+    ranges: Vec<std::ops::Range<usize>>,
+    linewise: bool,
+) {
+    let ranges = merge_ranges(ranges, false);
+    let Some(first) = ranges.first().cloned() else {
+        return;
+    };
+    let text: Vec<_> = ranges
+        .iter()
+        .map(|range| app.buffer().slice(range.clone()).to_string())
+        .collect();
+    // Yanked lines always end with a line break, even the last line of the buffer.
+    let text = match linewise {
+        true => text
+            .iter()
+            .map(|text| match text.ends_with('\n') {
+                true => text.clone(),
+                false => format!("{text}\n"),
+            })
+            .collect(),
+        false => text.join("\n"),
+    };
+    let register = app.register.take();
+    let deleted = operator != Operator::Yank;
+    if let Err(err) = app
+        .registers
+        .store(register, Yank { text, linewise }, deleted)
+    {
+        // Nothing is deleted when the text it would take cannot be kept.
+        app.set_status(format!("{err:#}"));
+        app.mode = Mode::Normal;
+        return;
+    }
+    let buffer = app.buffer();
+
+    match operator {
+        // Yanking with several selections leaves a cursor at the start of each of them.
+        Operator::Yank if buffer.selection().ranges().len() > 1 => {
+            let selection = buffer
+                .selection()
+                .clone()
+                .transform(|range| Range::point(range.from()));
+            set_selection(app, selection);
+            app.mode = Mode::Normal;
+        }
+        Operator::Yank => {
+            let head = buffer.selection().primary().head;
+            let cursor = match linewise {
+                true if first.contains(&head) => head,
+                _ => first.start,
+            };
+            set_selection(app, Selection::point(cursor));
+            app.mode = Mode::Normal;
+        }
+        Operator::Delete => {
+            let ranges = merge_ranges(ranges, linewise)
+                .into_iter()
+                .map(|range| match linewise {
+                    // Deleting the last lines takes the line break before them instead.
+                    true if range.end == buffer.len_bytes()
+                        && range.start > 0
+                        && !buffer.slice(range.clone()).to_string().ends_with('\n') =>
+                    {
+                        buffer.prev_grapheme_boundary(range.start)..range.end
+                    }
+                    _ => range,
+                })
+                .collect::<Vec<_>>();
+            let transaction = Transaction::delete(ranges.iter().cloned());
+            let cursors: Vec<_> = ranges
+                .iter()
+                .map(|range| Range::point(transaction.map_pos(range.start, crate::Assoc::Before)))
+                .collect();
+
+            // A block leaves a single cursor at its top left corner.
+            let block = app.mode == Mode::Visual && app.visual == VisualKind::Block;
+            let primary = primary_index(app, cursors.len());
+            let buffer = app.buffer_mut();
+            buffer.apply(&transaction);
+            let cursors = cursors
+                .into_iter()
+                .take(if block { 1 } else { usize::MAX })
+                .map(|range| match linewise {
+                    true => {
+                        let line = buffer.byte_to_pos(range.head).line.min(last_line(buffer));
+                        Range::point(motion::first_non_blank(buffer, line))
+                    }
+                    false => range,
+                })
+                .collect::<Vec<_>>();
+            let primary = primary.min(cursors.len() - 1);
+            buffer.set_selection(Selection::new(cursors, primary));
+            app.mode = Mode::Normal;
+        }
+        Operator::Change => {
+            // Changing whole lines keeps one empty line to type into.
+            let ranges: Vec<_> = ranges
+                .into_iter()
+                .map(|range| match linewise {
+                    true => {
+                        let last = buffer
+                            .byte_to_pos(range.end.saturating_sub(1).max(range.start))
+                            .line;
+                        range.start..buffer.line_end(last).max(range.start)
+                    }
+                    false => range,
+                })
+                .collect();
+            let transaction = Transaction::delete(ranges.iter().cloned());
+            let cursors = ranges
+                .iter()
+                .map(|range| Range::point(transaction.map_pos(range.start, crate::Assoc::Before)))
+                .collect::<Vec<_>>();
+            let primary = primary_index(app, cursors.len());
+
+            let buffer = app.buffer_mut();
+            buffer.apply(&transaction);
+            buffer.set_selection(Selection::new(cursors, primary));
+            app.mode = Mode::Insert;
+        }
+    }
+}
+
+// Sorts `ranges` and merges those that overlap, or also those that touch if `adjacent`, so that
+// cursors sharing text take it once.
+fn merge_ranges(
+    mut ranges: Vec<std::ops::Range<usize>>,
+    adjacent: bool,
+) -> Vec<std::ops::Range<usize>> {
+    ranges.sort_by_key(|range| (range.start, range.end));
+    let mut merged: Vec<std::ops::Range<usize>> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last)
+                if range.start < last.end
+                    || range == *last
+                    || adjacent && range.start == last.end =>
+            {
+                last.end = last.end.max(range.end);
+            }
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+// Returns the index of the primary selection among `len` cursors made one for each selection, or
+// the first if they were made some other way.
+fn primary_index(app: &App, len: usize) -> usize {
+    let selection = app.buffer().selection();
+    match selection.ranges().len() == len {
+        true => selection.primary_index(),
+        false => 0,
+    }
+}
+
+fn delete(cx: &mut Context) {
+    operator(cx, Operator::Delete);
+}
+
+fn change(cx: &mut Context) {
+    operator(cx, Operator::Change);
+}
+
+fn yank(cx: &mut Context) {
+    operator(cx, Operator::Yank);
+}
+
+fn delete_char(cx: &mut Context) {
+    if cx.app.mode == Mode::Visual {
+        return delete(cx);
+    }
+    cx.app.operator = Some((Operator::Delete, None));
+    move_char_right(cx);
+}
+
+fn delete_to_line_end(cx: &mut Context) {
+    cx.app.operator = Some((Operator::Delete, None));
+    goto_line_end(cx);
+}
+
+fn change_to_line_end(cx: &mut Context) {
+    cx.app.operator = Some((Operator::Change, None));
+    goto_line_end(cx);
+}
+
+fn yank_line(cx: &mut Context) {
+    let count = cx.count();
+    linewise_operator(cx.app, Operator::Yank, count);
+}
+
+fn select_register(cx: &mut Context) {
+    await_char(cx, CharArgument::Register);
+}
+
+fn paste(cx: &mut Context, after: bool) {
+    let name = cx.app.register.take().unwrap_or('"');
+    let file = cx.app.buffer().path();
+    let Some(Yank { text, linewise }) = (match cx.app.registers.get(name, file) {
+        Ok(yank) => yank,
+        Err(err) => return cx.app.set_status(format!("{err:#}")),
+    }) else {
+        return;
+    };
+    let text = text.repeat(cx.count());
+    let buffer = cx.app.buffer();
+
+    let mut changes: Vec<_> = buffer
+        .selection()
+        .ranges()
+        .iter()
+        .map(|range| {
+            let line = buffer.byte_to_pos(range.head).line;
+            match (linewise, after) {
+                (true, true) if line + 1 < buffer.len_lines() => {
+                    let at = buffer.line_start(line + 1);
+                    let text = if text.ends_with('\n') || at < buffer.len_bytes() {
+                        text.clone()
+                    } else {
+                        format!("{text}\n")
+                    };
+                    (at, text)
+                }
+                (true, true) => {
+                    let text = text.strip_suffix('\n').unwrap_or(&text);
+                    (buffer.len_bytes(), format!("\n{text}"))
+                }
+                (true, false) => (buffer.line_start(line), text.clone()),
+                (false, true) => (motion::char_right(buffer, range.head, 1), text.clone()),
+                (false, false) => (range.head, text.clone()),
+            }
+        })
+        .collect();
+    // Cursors on the same line paste whole lines once.
+    if linewise {
+        changes.dedup_by_key(|(at, _)| *at);
+    }
+
+    let cursors: Vec<_> = changes
+        .iter()
+        .scan(0, |delta, (at, text)| {
+            let start = at + *delta;
+            *delta += text.len();
+            Some((start, text.len()))
+        })
+        .collect();
+
+    cx.app.buffer_mut().apply(&Transaction::change(
+        changes.into_iter().map(|(at, text)| (at, at, text)),
+    ));
+
+    let buffer = cx.app.buffer();
+    let cursors = cursors
+        .into_iter()
+        .map(|(start, len)| match linewise {
+            true => {
+                let line = buffer
+                    .byte_to_pos(start + usize::from(start > 0 && !after))
+                    .line;
+                let line = if after && buffer.char_at(start) == Some('\n') {
+                    line + 1
+                } else {
+                    line
+                };
+                Range::point(motion::first_non_blank(buffer, line))
+            }
+            false => Range::point(buffer.prev_grapheme_boundary(start + len)),
+        })
+        .collect();
+    set_selection(cx.app, Selection::new(cursors, 0));
+}
+
+fn paste_after(cx: &mut Context) {
+    paste(cx, true);
+}
+
+fn paste_before(cx: &mut Context) {
+    paste(cx, false);
+}
+
+fn replace_char(cx: &mut Context) {
+    await_char(cx, CharArgument::Replace);
+}
+
+fn replace(cx: &mut Context, ch: char) {
+    let count = cx.count();
+    let buffer = cx.app.buffer();
+
+    let changes: Vec<_> = buffer
+        .selection()
+        .ranges()
+        .iter()
+        .filter_map(|range| {
+            let end = buffer.move_horizontally(range.head, Direction::Forward, count);
+            let line_end = buffer.line_end(buffer.byte_to_pos(range.head).line);
+            (end <= line_end && end > range.head)
+                .then(|| (range.head, end, ch.to_string().repeat(count)))
+        })
+        .collect();
+
+    // The cursor ends up on the last replaced character.
+    let mut delta = 0isize;
+    let cursors: Vec<_> = changes
+        .iter()
+        .map(|(from, to, text)| {
+            let start = (*from as isize + delta) as usize;
+            delta += text.len() as isize - (to - from) as isize;
+            Range::point(start + text.len() - ch.len_utf8())
+        })
+        .collect();
+
+    if cursors.is_empty() {
+        return;
+    }
+    cx.app.buffer_mut().apply(&Transaction::change(changes));
+    set_selection(cx.app, Selection::new(cursors, 0));
+}
+
+// Insert mode
+
+/// Inserts `text` at every cursor.
+pub fn insert_text(app: &mut App, text: &str) {
+    let buffer = app.buffer();
+    let changes: Vec<_> = buffer
+        .selection()
+        .ranges()
+        .iter()
+        .map(|range| (range.head, range.head, text))
+        .collect();
+
+    app.buffer_mut().apply(&Transaction::change(changes));
+}
+
+/// Inserts `ch` at every cursor, closing brackets and quotes if auto-pairs are enabled.
+pub fn insert_char(app: &mut App, ch: char) {
+    let Some(pairs) = auto_pairs(app) else {
+        insert_text(app, ch.encode_utf8(&mut [0; 4]));
+        return;
+    };
+    let buffer = app.buffer();
+    let syntax = buffer.syntax();
+    let (transaction, selection) = pairs.insert(buffer, ch, |pos| {
+        syntax.map_or(false, |syntax| {
+            syntax.in_string_or_comment(buffer.text(), pos)
+        })
+    });
+    app.buffer_mut().apply(&transaction);
+    set_selection(app, selection);
+}
+
+// Returns the pairs of the language of the current buffer, unless auto-pairs are disabled.
+fn auto_pairs(app: &App) -> Option<AutoPairs> {
+    let language = app
+        .language_config()
+        .and_then(|language| language.auto_pairs.clone());
+    app.auto_pairs().then(|| language.unwrap_or_default())
+}
+
+fn indentation(buffer: &Buffer, line: usize) -> String {
+    let start = buffer.line_start(line);
+    buffer
+        .chars_at(start)
+        .take_while(|&(i, c)| i < buffer.line_end(line) && (c == ' ' || c == '\t'))
+        .map(|(_, c)| c)
+        .collect()
+}
+
+fn insert_newline(cx: &mut Context) {
+    let buffer = cx.app.buffer();
+    let changes: Vec<_> = buffer
+        .selection()
+        .ranges()
+        .iter()
+        .map(|range| {
+            let line = buffer.byte_to_pos(range.head).line;
+            let indent = indentation(buffer, line);
+            (range.head, range.head, format!("\n{indent}"))
+        })
+        .collect();
+
+    cx.app.buffer_mut().apply(&Transaction::change(changes));
+}
+
+fn insert_tab(cx: &mut Context) {
+    let unit = match cx
+        .app
+        .language_config()
+        .and_then(|language| language.indent.as_ref())
+    {
+        Some(indent) => indent.unit.clone(),
+        None => "\t".to_string(),
+    };
+    insert_text(cx.app, &unit);
+}
+
+fn delete_char_backward(cx: &mut Context) {
+    if let Some(pairs) = auto_pairs(cx.app) {
+        let (transaction, selection) = pairs.delete_backward(cx.app.buffer());
+        cx.app.buffer_mut().apply(&transaction);
+        set_selection(cx.app, selection);
+        return;
+    }
+    let buffer = cx.app.buffer();
+    let ranges: Vec<_> = buffer
+        .selection()
+        .ranges()
+        .iter()
+        .map(|range| buffer.prev_grapheme_boundary(range.head)..range.head)
+        .collect();
+
+    cx.app.buffer_mut().apply(&Transaction::delete(ranges));
+}
+
+fn delete_char_forward(cx: &mut Context) {
+    let buffer = cx.app.buffer();
+    let ranges: Vec<_> = buffer
+        .selection()
+        .ranges()
+        .iter()
+        .map(|range| range.head..buffer.next_grapheme_boundary(range.head))
+        .collect();
+
+    cx.app.buffer_mut().apply(&Transaction::delete(ranges));
+}
+
+/// Keeps every cursor on a grapheme of its line, as Normal and Visual mode require.
+pub fn clamp_cursors(app: &mut App) {
+    let normal = app.mode == Mode::Normal;
+    let buffer = app.buffer();
+    let selection = buffer.selection().clone().transform(|range| {
+        let clamp = |pos: usize| {
+            let line = buffer.byte_to_pos(pos).line.min(last_line(buffer));
+            pos.min(motion::last_grapheme(buffer, line))
+        };
+        match normal {
+            true => Range::point(clamp(range.head)),
+            false => Range::new(clamp(range.anchor), clamp(range.head)),
+        }
+    });
+
+    set_selection(app, selection);
+}
@@ -0,0 +1,532 @@
+//! Commands typed in Command mode after `:`, such as `:w` or `:set mouse false`.
+//!
+//! A name that is not a typed command runs the command of the same name from the registry that
+//! key bindings use, so `:insert_mode` works like the key it is bound to.
+
+use crate::{App, Command, Context, Session, SettingScope, ThemeLoader, UndoKind, SETTINGS};
+use anyhow::{anyhow, bail, Result};
+use std::{fmt, ops::RangeInclusive, path::Path};
+use tissue_tui::Split;
+
+/// What the arguments of a typed command are completed with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Completer {
+    /// Nothing.
+    None,
+    /// Paths of files and directories.
+    Path,
+    /// Names of themes.
+    Theme,
+    /// Names of languages.
+    Language,
+    /// Keys of the editor settings, then their values.
+    Setting,
+    /// Keys of the editor settings that languages and buffers can override, then their values.
+    LocalSetting,
+}
+
+/// A command typed in Command mode.
+#[derive(Clone, Copy)]
+pub struct TypedCommand {
+    /// The name typed to run the command.
+    pub name: &'static str,
+    /// Shorter names for the command.
+    pub aliases: &'static [&'static str],
+    /// How the command is typed, followed by a one-line description.
+    pub doc: &'static str,
+    /// What the arguments are completed with.
+    pub completer: Completer,
+    fun: fn(&mut Context, &CommandLine) -> Result<()>,
+}
+
+impl TypedCommand {
+    /// Looks up a typed command by name or alias.
+    pub fn find(name: &str) -> Option<&'static TypedCommand> {
+        TYPED_COMMANDS
+            .iter()
+            .find(|command| command.name == name || command.aliases.contains(&name))
+    }
+
+    /// Returns all typed commands.
+    pub fn all() -> &'static [TypedCommand] {
+        TYPED_COMMANDS
+    }
+}
+
+impl fmt::Debug for TypedCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("TypedCommand").field(&self.name).finish()
+    }
+}
+
+/// A line typed in Command mode, split into its parts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandLine<'a> {
+    /// Whether the line starts with `%`, which applies the command to every line.
+    pub every_line: bool,
+    /// The name of the command.
+    pub name: &'a str,
+    /// Whether the name is followed by `!`.
+    pub force: bool,
+    /// The rest of the line, without the whitespace after the name.
+    pub args: &'a str,
+    /// The lines the command applies to without `%`: the selected ones when Command mode was
+    /// entered from Visual mode, or the line of the cursor.
+    pub lines: RangeInclusive<usize>,
+}
+
+impl<'a> CommandLine<'a> {
+    /// Splits `line` into its parts.
+    pub fn parse(line: &'a str, lines: RangeInclusive<usize>) -> Self {
+        let line = line.trim_start();
+        let (every_line, line) = match line.strip_prefix('%') {
+            Some(line) => (true, line),
+            None => (false, line),
+        };
+        let end = line
+            .find(|c: char| !c.is_alphanumeric() && c != '_')
+            .unwrap_or(line.len());
+        let (name, rest) = line.split_at(end);
+        let (force, rest) = match rest.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, rest),
+        };
+        Self {
+            every_line,
+            name,
+            force,
+            args: rest.trim_start(),
+            lines,
+        }
+    }
+
+    // Fails if the command was given arguments.
+    fn no_args(&self) -> Result<()> {
+        match self.args.trim().is_empty() {
+            true => Ok(()),
+            false => bail!(":{} takes no arguments", self.name),
+        }
+    }
+}
+
+const TYPED_COMMANDS: &[TypedCommand] = &[
+    TypedCommand {
+        name: "write",
+        aliases: &["w"],
+        doc: ":write - Save the current buffer",
+        completer: Completer::None,
+        fun: write,
+    },
+    TypedCommand {
+        name: "quit",
+        aliases: &["q"],
+        doc:
+            ":quit[!] - Close the view or, from the last one, the editor, discarding changes with !",
+        completer: Completer::None,
+        fun: quit,
+    },
+    TypedCommand {
+        name: "write_quit",
+        aliases: &["wq", "x"],
+        doc: ":wq - Save the current buffer and close the view or, from the last one, the editor",
+        completer: Completer::None,
+        fun: write_quit,
+    },
+    TypedCommand {
+        name: "edit",
+        aliases: &["e", "open", "o"],
+        doc: ":edit <path> - Open a file",
+        completer: Completer::Path,
+        fun: edit,
+    },
+    TypedCommand {
+        name: "split",
+        aliases: &["sp"],
+        doc: ":split [path] - Split the view, opening the new view below, with a file",
+        completer: Completer::Path,
+        fun: split,
+    },
+    TypedCommand {
+        name: "vsplit",
+        aliases: &["vs"],
+        doc: ":vsplit [path] - Split the view, opening the new view on the right, with a file",
+        completer: Completer::Path,
+        fun: vsplit,
+    },
+    TypedCommand {
+        name: "close",
+        aliases: &["clo"],
+        doc: ":close - Close the view, unless it is the last one",
+        completer: Completer::None,
+        fun: close,
+    },
+    TypedCommand {
+        name: "only",
+        aliases: &["on"],
+        doc: ":only - Close every other view",
+        completer: Completer::None,
+        fun: only,
+    },
+    TypedCommand {
+        name: "session",
+        aliases: &[],
+        doc: ":session save <name> - Save the buffers, views and registers, to open with --session",
+        completer: Completer::None,
+        fun: session,
+    },
+    TypedCommand {
+        name: "set",
+        aliases: &[],
+        doc: ":set <key> [value] - Change a setting of the [editor] section, or show it",
+        completer: Completer::Setting,
+        fun: set,
+    },
+    TypedCommand {
+        name: "setlocal",
+        aliases: &["setl"],
+        doc: ":setlocal <key> [value] - Change a setting for this buffer only, or show it",
+        completer: Completer::LocalSetting,
+        fun: set_local,
+    },
+    TypedCommand {
+        name: "setlang",
+        aliases: &[],
+        doc:
+            ":setlang <key> [value] - Change a setting for the language of this buffer, or show it",
+        completer: Completer::LocalSetting,
+        fun: set_lang,
+    },
+    TypedCommand {
+        name: "theme",
+        aliases: &[],
+        doc: ":theme [name] - Switch to a theme, or show the current one",
+        completer: Completer::Theme,
+        fun: theme,
+    },
+    TypedCommand {
+        name: "lang",
+        aliases: &[],
+        doc: ":lang [name] - Set the language of the buffer, or show the current one",
+        completer: Completer::Language,
+        fun: lang,
+    },
+    TypedCommand {
+        name: "substitute",
+        aliases: &["s"],
+        doc: ":[%]s/pattern/replacement/[flags] - Replace the matches of a pattern",
+        completer: Completer::None,
+        fun: substitute,
+    },
+    TypedCommand {
+        name: "earlier",
+        aliases: &["ear"],
+        doc: ":earlier [steps|time] - Go back to an older text state, such as :earlier 5m",
+        completer: Completer::None,
+        fun: earlier,
+    },
+    TypedCommand {
+        name: "later",
+        aliases: &["lat"],
+        doc: ":later [steps|time] - Go forward to a newer text state, such as :later 30s",
+        completer: Completer::None,
+        fun: later,
+    },
+    TypedCommand {
+        name: "help",
+        aliases: &["h"],
+        doc: ":help <command> - Show how to use a command",
+        completer: Completer::None,
+        fun: help,
+    },
+];
+
+/// Runs the command typed as `line` in Command mode.
+pub fn execute(app: &mut App, line: &str, lines: RangeInclusive<usize>) -> Result<()> {
+    let line = CommandLine::parse(line, lines);
+    if line.name.is_empty() {
+        return match line.args.is_empty() {
+            true => Ok(()),
+            false => Err(anyhow!("missing command name")),
+        };
+    }
+
+    let mut cx = Context { app, count: None };
+    if let Some(command) = TypedCommand::find(line.name) {
+        return (command.fun)(&mut cx, &line);
+    }
+    match Command::find(line.name) {
+        Some(command) => {
+            line.no_args()?;
+            command.execute(&mut cx);
+            Ok(())
+        }
+        None => bail!("unknown command '{}'", line.name),
+    }
+}
+
+/// Completes the word before the end of `line`: the command name, or the argument being typed.
+///
+/// Returns where the word starts in `line` and what it may be replaced with, sorted.
+pub fn complete(app: &App, line: &str) -> (usize, Vec<String>) {
+    let start = line.len() - line.trim_start().len();
+    let start = start + usize::from(line[start..].starts_with('%'));
+    let Some(end) = line[start..].find(char::is_whitespace) else {
+        let name = &line[start..];
+        let typed = TypedCommand::all().iter().map(|command| command.name);
+        let names = typed.chain(Command::all().iter().map(|command| command.name));
+        let mut names: Vec<_> = names
+            .filter(|candidate| candidate.starts_with(name))
+            .map(str::to_string)
+            .collect();
+        names.sort();
+        names.dedup();
+        return (start, names);
+    };
+
+    let name = line[start..start + end].trim_end_matches('!');
+    let Some(command) = TypedCommand::find(name) else {
+        return (line.len(), Vec::new());
+    };
+    let word_start = line.trim_end_matches(|c: char| !c.is_whitespace()).len();
+    let word = &line[word_start..];
+    // The arguments typed before the word.
+    let previous = line[start + end..word_start].split_whitespace().count();
+
+    let mut candidates = match command.completer {
+        Completer::None => Vec::new(),
+        Completer::Path => complete_path(word),
+        Completer::Theme => ThemeLoader::default().names(),
+        Completer::Language => {
+            let languages = app.config.syntax.languages.iter();
+            languages.map(|language| language.name.clone()).collect()
+        }
+        Completer::Setting | Completer::LocalSetting if previous == 0 => {
+            let local = command.completer == Completer::LocalSetting;
+            let settings = SETTINGS
+                .iter()
+                .filter(|setting| setting.is_local() || !local);
+            settings.map(|setting| setting.key.to_string()).collect()
+        }
+        Completer::Setting | Completer::LocalSetting => {
+            let key = line[start + end..].split_whitespace().next();
+            let setting = SETTINGS.iter().find(|setting| Some(setting.key) == key);
+            let values = setting.map_or(&[][..], |setting| setting.values);
+            values.iter().map(|value| value.to_string()).collect()
+        }
+    };
+    candidates.retain(|candidate| candidate.starts_with(word));
+    candidates.sort();
+    candidates.dedup();
+    (word_start, candidates)
+}
+
+// Lists the entries of the directory that `word` is in whose names start like its last
+// component. Directories end with a separator, and hidden entries are only listed once the name
+// starts with a dot.
+fn complete_path(word: &str) -> Vec<String> {
+    let (dir, prefix) = match word.rfind('/') {
+        Some(i) => (&word[..=i], &word[i + 1..]),
+        None => ("", word),
+    };
+    let Ok(entries) = std::fs::read_dir(if dir.is_empty() {
+        Path::new(".")
+    } else {
+        Path::new(dir)
+    }) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let name = entry.file_name().into_string().ok()?;
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+            let separator = match entry.file_type().ok()?.is_dir() {
+                true => "/",
+                false => "",
+            };
+            Some(format!("{dir}{name}{separator}"))
+        })
+        .collect()
+}
+
+fn write(cx: &mut Context, line: &CommandLine) -> Result<()> {
+    line.no_args()?;
+    cx.app.write();
+    Ok(())
+}
+
+fn quit(cx: &mut Context, line: &CommandLine) -> Result<()> {
+    line.no_args()?;
+    cx.app.quit(line.force);
+    Ok(())
+}
+
+fn write_quit(cx: &mut Context, line: &CommandLine) -> Result<()> {
+    line.no_args()?;
+    if cx.app.write() {
+        cx.app.quit(line.force);
+    }
+    Ok(())
+}
+
+fn edit(cx: &mut Context, line: &CommandLine) -> Result<()> {
+    let path = line.args.trim();
+    if path.is_empty() {
+        bail!("missing path, expected :edit <path>");
+    }
+    cx.app.open(Path::new(path))?;
+    Ok(())
+}
+
+fn split(cx: &mut Context, line: &CommandLine) -> Result<()> {
+    split_with(cx, line, Split::Horizontal)
+}
+
+fn vsplit(cx: &mut Context, line: &CommandLine) -> Result<()> {
+    split_with(cx, line, Split::Vertical)
+}
+
+// Splits the view the `split` way, and opens the path given as argument in the new view.
+fn split_with(cx: &mut Context, line: &CommandLine, split: Split) -> Result<()> {
+    let path = line.args.trim();
+    cx.app.split(split);
+    if !path.is_empty() {
+        // A file that cannot be opened leaves no view behind for it.
+        if let Err(err) = cx.app.open(Path::new(path)) {
+            cx.app.close_view();
+            return Err(err);
+        }
+    }
+    Ok(())
+}
+
+fn close(cx: &mut Context, line: &CommandLine) -> Result<()> {
+    line.no_args()?;
+    match cx.app.close_view() {
+        true => Ok(()),
+        false => bail!("cannot close the last view"),
+    }
+}
+
+fn only(cx: &mut Context, line: &CommandLine) -> Result<()> {
+    line.no_args()?;
+    cx.app.close_other_views();
+    Ok(())
+}
+
+fn session(cx: &mut Context, line: &CommandLine) -> Result<()> {
+    let args: Vec<_> = line.args.split_whitespace().collect();
+    let ["save", name] = args[..] else {
+        bail!("expected :session save <name>");
+    };
+    let file = Session::file(name)?;
+    cx.app.session().save(&file)?;
+    cx.app.set_status(format!("Saved session '{name}'"));
+    Ok(())
+}
+
+fn set(cx: &mut Context, line: &CommandLine) -> Result<()> {
+    set_in(cx, line, SettingScope::Global)
+}
+
+fn set_local(cx: &mut Context, line: &CommandLine) -> Result<()> {
+    set_in(cx, line, SettingScope::Buffer)
+}
+
+fn set_lang(cx: &mut Context, line: &CommandLine) -> Result<()> {
+    set_in(cx, line, SettingScope::Language)
+}
+
+// Sets the setting named by the first argument to the second one in `scope`, or shows the value
+// that applies there without a second argument.
+fn set_in(cx: &mut Context, line: &CommandLine, scope: SettingScope) -> Result<()> {
+    let mut args = line.args.split_whitespace();
+    let (Some(key), value, None) = (args.next(), args.next(), args.next()) else {
+        bail!("expected :{} <key> [value]", line.name);
+    };
+    match value {
+        Some(value) => cx.app.set_setting(scope, key, value),
+        None => {
+            let value = cx.app.get_setting(scope, key)?;
+            cx.app.set_status(format!("{key} = {value}"));
+            Ok(())
+        }
+    }
+}
+
+fn theme(cx: &mut Context, line: &CommandLine) -> Result<()> {
+    match line.args.trim() {
+        "" => {
+            let name = cx.app.theme.name().to_string();
+            cx.app.set_status(name);
+        }
+        name => cx.app.set_theme(name),
+    }
+    Ok(())
+}
+
+fn lang(cx: &mut Context, line: &CommandLine) -> Result<()> {
+    match line.args.trim() {
+        "" => {
+            let language = cx.app.buffer().language().unwrap_or("text").to_string();
+            cx.app.set_status(language);
+            Ok(())
+        }
+        name => cx.app.set_language(name),
+    }
+}
+
+fn substitute(cx: &mut Context, line: &CommandLine) -> Result<()> {
+    let every_line = if line.every_line { "%" } else { "" };
+    let command = format!("{every_line}s{}", line.args);
+    cx.app.substitute(&command, line.lines.clone());
+    Ok(())
+}
+
+fn earlier(cx: &mut Context, line: &CommandLine) -> Result<()> {
+    let kind: UndoKind = line.args.parse()?;
+    if !cx.app.buffer_mut().earlier(kind) {
+        cx.app.set_status("Already at oldest change");
+    }
+    Ok(())
+}
+
+fn later(cx: &mut Context, line: &CommandLine) -> Result<()> {
+    let kind: UndoKind = line.args.parse()?;
+    if !cx.app.buffer_mut().later(kind) {
+        cx.app.set_status("Already at newest change");
+    }
+    Ok(())
+}
+
+fn help(cx: &mut Context, line: &CommandLine) -> Result<()> {
+    let name = line.args.trim().trim_start_matches(':');
+    let doc = match (TypedCommand::find(name), Command::find(name)) {
+        (Some(command), _) => command.doc.to_string(),
+        (None, Some(command)) => format!(":{} - {}", command.name, command.doc),
+        (None, None) if name.is_empty() => bail!("missing command, expected :help <command>"),
+        (None, None) => bail!("unknown command '{name}'"),
+    };
+    cx.app.set_status(doc);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_command_lines() {
+        let line = CommandLine::parse(" %s/a/b/g", 2..=3);
+        assert!(line.every_line);
+        assert_eq!((line.name, line.force, line.args), ("s", false, "/a/b/g"));
+
+        let line = CommandLine::parse("q!", 0..=0);
+        assert_eq!((line.name, line.force, line.args), ("q", true, ""));
+        let line = CommandLine::parse("set  mouse false", 0..=0);
+        assert_eq!((line.name, line.args), ("set", "mouse false"));
+        assert_eq!(TypedCommand::find("wq").unwrap().name, "write_quit");
+    }
+}
@@ -1,23 +1,31 @@
+mod detect;
 mod editor;
 mod language;
 mod syntax;
 
-pub use self::{editor::EditorConfig, language::LanguageConfig, syntax::SyntaxConfig};
+pub use self::{
+    detect::{DetectedBy, Detection},
+    editor::{
+        BufferLine, ClipboardConfig, ClipboardProvider, EditorConfig, GutterColumnKind, LineNumber,
+        LocalSettings, SearchConfig, Setting, SettingScope, StatusLineConfig, StatusLineElement,
+        SETTINGS,
+    },
+    language::{DebugConfig, DebugRequest, IndentConfig, LanguageConfig},
+    syntax::SyntaxConfig,
+};
 
-use crate::{KeyBinding, Mode};
+use crate::{keymap, KeyBindings};
 use anyhow::{anyhow, Result};
 use serde::Deserialize;
-use std::{collections::HashMap, path::PathBuf};
-
-type KeyBindings = HashMap<Mode, KeyBinding>;
+use std::path::PathBuf;
 
 /// Global configuration.
-#[derive(Debug, Default, Deserialize)]
+#[derive(Debug, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct Config {
     pub theme: Option<String>,
-    // TODO: Load default keybindings.
-    #[serde(skip)]
+    /// Built-in key bindings merged with the `[keys.<mode>]` sections.
+    #[serde(default = "keymap::default", deserialize_with = "keymap::deserialize")]
     pub keys: KeyBindings,
     #[serde(default)]
     pub editor: EditorConfig,
@@ -25,6 +33,17 @@ pub struct Config {
     pub syntax: SyntaxConfig,
 }
 
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            theme: None,
+            keys: keymap::default(),
+            editor: EditorConfig::default(),
+            syntax: SyntaxConfig::default(),
+        }
+    }
+}
+
 impl Config {
     pub fn load(config_file_path: &PathBuf) -> Result<Self> {
         let mut config = match std::fs::read_to_string(config_file_path) {
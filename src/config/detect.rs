@@ -0,0 +1,392 @@
+use super::{LanguageConfig, SyntaxConfig};
+use ropey::Rope;
+use std::{fmt, path::Path};
+
+// How many lines at the start and at the end of a file may hold a modeline.
+const MODELINE_LINES: usize = 5;
+
+/// What a language was detected from, strongest first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DetectedBy {
+    /// A vim (`vim: ft=rust`) or emacs (`-*- mode: rust -*-`) modeline.
+    Modeline,
+    /// The full file name, such as `Makefile`.
+    FileName,
+    /// A glob pattern, such as `*.spec.ts` or `.github/workflows/*.yml`.
+    Glob,
+    /// The file extension.
+    Extension,
+    /// The interpreter of a `#!` line.
+    Shebang,
+}
+
+impl fmt::Display for DetectedBy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::Modeline => "modeline",
+            Self::FileName => "file name",
+            Self::Glob => "glob",
+            Self::Extension => "extension",
+            Self::Shebang => "shebang",
+        })
+    }
+}
+
+/// A language matching a file.
+#[derive(Debug, Clone, Copy)]
+pub struct Detection<'a> {
+    /// The matching language.
+    pub language: &'a LanguageConfig,
+    /// What the language was detected from.
+    pub by: DetectedBy,
+    /// What matched: the file type, the interpreter or the modeline value.
+    pub matched: &'a str,
+}
+
+impl SyntaxConfig {
+    /// Returns the language of a file, detected from its `path` and `text`.
+    ///
+    /// See [`SyntaxConfig::detect_all`] for the order in which languages are tried.
+    pub fn detect(&self, path: Option<&Path>, text: Option<&Rope>) -> Option<Detection<'_>> {
+        self.detect_all(path, text).into_iter().next()
+    }
+
+    /// Returns every language matching a file, best match first.
+    ///
+    /// Modelines win over the file name, then over globs and extensions listed in
+    /// [`LanguageConfig::file_types`], and shebang lines are tried last. Ties go to the language
+    /// declared first.
+    pub fn detect_all(&self, path: Option<&Path>, text: Option<&Rope>) -> Vec<Detection<'_>> {
+        let mut detections = Vec::new();
+
+        if let Some(mode) = text.and_then(modeline) {
+            detections.extend(
+                self.languages
+                    .iter()
+                    .filter(|language| {
+                        language.name.eq_ignore_ascii_case(&mode)
+                            || language.file_types.contains(&mode)
+                    })
+                    .map(|language| Detection {
+                        language,
+                        by: DetectedBy::Modeline,
+                        matched: &language.name,
+                    }),
+            );
+        }
+
+        if let Some(path) = path {
+            for language in &self.languages {
+                for file_type in &language.file_types {
+                    if let Some(by) = match_file_type(file_type, path) {
+                        detections.push(Detection {
+                            language,
+                            by,
+                            matched: file_type,
+                        });
+                    }
+                }
+            }
+        }
+
+        if let Some(interpreter) = text.and_then(shebang) {
+            for language in &self.languages {
+                if let Some(shebang) = language
+                    .shebangs
+                    .iter()
+                    .find(|shebang| matches_interpreter(shebang, &interpreter))
+                {
+                    detections.push(Detection {
+                        language,
+                        by: DetectedBy::Shebang,
+                        matched: shebang,
+                    });
+                }
+            }
+        }
+
+        // Stable, so ties keep the order of declaration.
+        detections.sort_by_key(|detection| detection.by);
+        detections
+    }
+}
+
+fn match_file_type(file_type: &str, path: &Path) -> Option<DetectedBy> {
+    let file_name = path.file_name()?.to_str()?;
+
+    if file_type.contains(['*', '?', '[']) {
+        let path = path.to_str()?.replace(std::path::MAIN_SEPARATOR, "/");
+        let matched = match file_type.contains('/') {
+            // Anchor the pattern at a directory boundary anywhere in the path.
+            true => (0..path.len())
+                .filter(|&i| i == 0 || path.as_bytes()[i - 1] == b'/')
+                .any(|i| glob_match(file_type, &path[i..])),
+            false => glob_match(file_type, file_name),
+        };
+        matched.then_some(DetectedBy::Glob)
+    } else if file_type == file_name {
+        Some(DetectedBy::FileName)
+    } else if path
+        .extension()
+        .map_or(false, |extension| extension == file_type)
+    {
+        Some(DetectedBy::Extension)
+    } else {
+        None
+    }
+}
+
+// Matches `text` against a glob where `*` matches within a path component, `**` matches across
+// components, `?` matches one character and `[...]` one of a set of characters.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    fn matches(pattern: &[char], text: &[char]) -> bool {
+        match pattern {
+            [] => text.is_empty(),
+            ['*', '*', rest @ ..] => {
+                let rest = rest.strip_prefix(&['/']).unwrap_or(rest);
+                (0..=text.len()).any(|i| matches(rest, &text[i..]))
+            }
+            ['*', rest @ ..] => (0..=text.len())
+                .take_while(|&i| i == 0 || text[i - 1] != '/')
+                .any(|i| matches(rest, &text[i..])),
+            ['?', rest @ ..] => matches!(text, [c, ..] if *c != '/') && matches(rest, &text[1..]),
+            ['[', rest @ ..] => {
+                let Some(end) = rest.iter().skip(1).position(|&c| c == ']').map(|i| i + 1) else {
+                    return text.first() == Some(&'[') && matches(rest, &text[1..]);
+                };
+                let (set, negated) = match &rest[..end] {
+                    ['!' | '^', set @ ..] => (set, true),
+                    set => (set, false),
+                };
+                let Some(&c) = text.first() else {
+                    return false;
+                };
+                let mut found = false;
+                let mut i = 0;
+                while i < set.len() {
+                    if i + 2 < set.len() && set[i + 1] == '-' {
+                        found |= (set[i]..=set[i + 2]).contains(&c);
+                        i += 3;
+                    } else {
+                        found |= set[i] == c;
+                        i += 1;
+                    }
+                }
+                found != negated && matches(&rest[end + 1..], &text[1..])
+            }
+            [p, rest @ ..] => text.first() == Some(p) && matches(rest, &text[1..]),
+        }
+    }
+
+    let pattern: Vec<_> = pattern.chars().collect();
+    let text: Vec<_> = text.chars().collect();
+    matches(&pattern, &text)
+}
+
+// Returns the interpreter named by the `#!` line, looking through `env`.
+fn shebang(text: &Rope) -> Option<String> {
+    let line = text.lines().next()?.to_string();
+    let mut words = line.strip_prefix("#!")?.split_whitespace();
+    let program = |word: &str| word.rsplit('/').next().unwrap_or(word).to_string();
+
+    let interpreter = program(words.next()?);
+    if interpreter != "env" {
+        return Some(interpreter);
+    }
+    // Skip options such as `-S` and variable assignments.
+    words
+        .find(|word| !word.starts_with('-') && !word.contains('='))
+        .map(program)
+}
+
+// `python` matches `python3` and `python3.11`.
+fn matches_interpreter(shebang: &str, interpreter: &str) -> bool {
+    interpreter == shebang
+        || interpreter.strip_prefix(shebang).map_or(false, |version| {
+            version.chars().all(|c| c.is_ascii_digit() || c == '.')
+        })
+}
+
+// Returns the file type set by a modeline in the first or last lines of `text`.
+fn modeline(text: &Rope) -> Option<String> {
+    let len = text.len_lines();
+    let head = 0..len.min(MODELINE_LINES);
+    let tail = len.saturating_sub(MODELINE_LINES).max(head.end)..len;
+
+    head.chain(tail).find_map(|line| {
+        let line = text.line(line).to_string();
+        emacs_modeline(&line).or_else(|| vim_modeline(&line))
+    })
+}
+
+// `-*- mode: rust -*-`, `-*- rust -*-` or `-*- mode: python; coding: utf-8 -*-`.
+fn emacs_modeline(line: &str) -> Option<String> {
+    let (_, rest) = line.split_once("-*-")?;
+    let (variables, _) = rest.split_once("-*-")?;
+
+    let mode = match variables.contains(':') {
+        true => variables.split(';').find_map(|variable| {
+            let (name, value) = variable.split_once(':')?;
+            name.trim().eq_ignore_ascii_case("mode").then_some(value)
+        })?,
+        false => variables,
+    };
+    let mode = mode.trim();
+    (!mode.is_empty()).then(|| mode.to_lowercase())
+}
+
+// `vim: set ft=rust :`, `vim:ft=rust` or `vi: filetype=python ts=4`.
+fn vim_modeline(line: &str) -> Option<String> {
+    let start = ["vim:", "vi:", "ex:"].iter().find_map(|marker| {
+        line.match_indices(marker)
+            .find(|&(i, _)| i == 0 || line[..i].ends_with(char::is_whitespace))
+            .map(|(i, marker)| i + marker.len())
+    })?;
+    let options = line[start..].trim_start();
+    let options = options
+        .strip_prefix("set ")
+        .or_else(|| options.strip_prefix("se "))
+        .unwrap_or(options);
+
+    options
+        .split(|c: char| c.is_whitespace() || c == ':')
+        .find_map(|option| {
+            let (name, value) = option.split_once('=')?;
+            matches!(name, "ft" | "filetype").then(|| value.to_string())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> SyntaxConfig {
+        toml::from_str(
+            r#"
+            [[language]]
+            name = "rust"
+            file-types = ["rs"]
+
+            [[language]]
+            name = "toml"
+            file-types = ["toml", "Cargo.lock"]
+
+            [[language]]
+            name = "make"
+            file-types = ["Makefile", "mk"]
+
+            [[language]]
+            name = "yaml"
+            file-types = ["yml", ".github/workflows/*.yml"]
+
+            [[language]]
+            name = "github-action"
+            file-types = [".github/workflows/*.yml"]
+
+            [[language]]
+            name = "python"
+            file-types = ["py"]
+            shebangs = ["python"]
+            "#,
+        )
+        .unwrap()
+    }
+
+    fn detect(
+        config: &SyntaxConfig,
+        path: Option<&str>,
+        text: &str,
+    ) -> Option<(String, DetectedBy)> {
+        let text = Rope::from_str(text);
+        config
+            .detect(path.map(Path::new), Some(&text))
+            .map(|detection| (detection.language.name.clone(), detection.by))
+    }
+
+    #[test]
+    fn paths_match_extensions_file_names_and_globs() {
+        let config = config();
+        let name = |path| detect(&config, Some(path), "").map(|(name, by)| (name, by.to_string()));
+
+        assert_eq!(
+            name("src/main.rs"),
+            Some(("rust".into(), "extension".into()))
+        );
+        assert_eq!(
+            name("Cargo.lock"),
+            Some(("toml".into(), "file name".into()))
+        );
+        assert_eq!(
+            name("sub/Makefile"),
+            Some(("make".into(), "file name".into()))
+        );
+        assert_eq!(
+            name("/repo/.github/workflows/ci.yml"),
+            Some(("yaml".into(), "glob".into()))
+        );
+        assert_eq!(
+            name("/repo/ci.yml"),
+            Some(("yaml".into(), "extension".into()))
+        );
+        assert_eq!(name("README"), None);
+
+        let path = Path::new("/repo/.github/workflows/ci.yml");
+        let all: Vec<_> = config
+            .detect_all(Some(path), None)
+            .iter()
+            .map(|detection| (detection.language.name.as_str(), detection.by))
+            .collect();
+        assert_eq!(
+            all,
+            [
+                ("yaml", DetectedBy::Glob),
+                ("github-action", DetectedBy::Glob),
+                ("yaml", DetectedBy::Extension)
+            ]
+        );
+    }
+
+    #[test]
+    fn contents_match_shebangs_and_modelines() {
+        let config = config();
+
+        assert_eq!(
+            detect(&config, Some("script"), "#!/usr/bin/env -S python3 -u\n"),
+            Some(("python".into(), DetectedBy::Shebang))
+        );
+        assert_eq!(
+            detect(&config, None, "#!/usr/local/bin/python3.11\n"),
+            Some(("python".into(), DetectedBy::Shebang))
+        );
+        assert_eq!(detect(&config, None, "#!/bin/pythonic\n"), None);
+        assert_eq!(
+            detect(&config, Some("build.py"), "# vim: set ft=rust :\n"),
+            Some(("rust".into(), DetectedBy::Modeline))
+        );
+        assert_eq!(detect(&config, None, &"x\n".repeat(20)), None);
+        let text = format!(
+            "{}# -*- mode: Python; coding: utf-8 -*-\n",
+            "x\n".repeat(20)
+        );
+        assert_eq!(
+            detect(&config, Some("a.rs"), &text),
+            Some(("python".into(), DetectedBy::Modeline))
+        );
+        assert_eq!(
+            detect(&config, None, "// vi:ts=4:ft=mk\n"),
+            Some(("make".into(), DetectedBy::Modeline))
+        );
+        assert_eq!(detect(&config, None, "let vim:ft = 1\n"), None);
+    }
+
+    #[test]
+    fn globs_match_components() {
+        assert!(glob_match("*.spec.ts", "app.spec.ts"));
+        assert!(!glob_match("*.ts", "src/app.ts"));
+        assert!(glob_match("src/**/*.ts", "src/a/b/app.ts"));
+        assert!(glob_match("src/**/*.ts", "src/app.ts"));
+        assert!(glob_match("file?.[ch]", "file1.h"));
+        assert!(!glob_match("file?.[!ch]", "file1.h"));
+        assert!(glob_match("[a-c]x", "bx"));
+    }
+}
@@ -1,19 +1,757 @@
+use anyhow::{anyhow, bail, Result};
 use serde::Deserialize;
+use std::{fmt, str::FromStr};
 
+/// The `[editor]` section of the configuration.
+///
+/// Each setting can also be read and changed by its key at runtime with [`EditorConfig::get`]
+/// and [`EditorConfig::set`]. Those listed as local in [`SETTINGS`] can be overridden per
+/// language or per buffer by [`LocalSettings`].
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "kebab-case", deny_unknown_fields, default)]
 pub struct EditorConfig {
-    auto_pairs: bool,
-    line_number: String,
-    mouse: bool,
+    /// Inserts the closing bracket or quote along with the opening one.
+    pub auto_pairs: bool,
+    /// Restores the session of the workspace when started without files or `--session`, and
+    /// saves it on exit.
+    pub auto_session: bool,
+    /// How line numbers are shown.
+    pub line_number: LineNumber,
+    /// The columns of the gutter left of the text, in order.
+    pub gutter: Vec<GutterColumnKind>,
+    /// When the open buffers are listed in a line above the views.
+    pub bufferline: BufferLine,
+    /// Handles mouse clicks and scrolling instead of leaving them to the terminal.
+    pub mouse: bool,
+    /// The `[editor.statusline]` section.
+    pub statusline: StatusLineConfig,
+    /// The `[editor.clipboard]` section.
+    pub clipboard: ClipboardConfig,
+    /// The `[editor.search]` section.
+    pub search: SearchConfig,
 }
 
 impl Default for EditorConfig {
     fn default() -> Self {
         Self {
             auto_pairs: true,
-            line_number: "absolute".into(),
+            auto_session: false,
+            line_number: LineNumber::Absolute,
+            gutter: GutterColumnKind::DEFAULT.to_vec(),
+            bufferline: BufferLine::Never,
             mouse: true,
+            statusline: StatusLineConfig::default(),
+            clipboard: ClipboardConfig::default(),
+            search: SearchConfig::default(),
         }
     }
 }
+
+impl EditorConfig {
+    /// Returns the value of the setting called `key`.
+    pub fn get(&self, key: &str) -> Result<String> {
+        let setting = Setting::find(key)?;
+        Ok((setting.get)(self))
+    }
+
+    /// Sets the setting called `key` to `value`.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        let setting = Setting::find(key)?;
+        setting.check(value)?;
+        (setting.set)(self, value)
+    }
+}
+
+/// How line numbers are shown.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LineNumber {
+    /// The number of every line.
+    #[default]
+    Absolute,
+    /// The distance of every line from the cursor.
+    Relative,
+    /// The number of the cursor line and the distance of the other lines from it.
+    Hybrid,
+    /// No line numbers.
+    Off,
+}
+
+impl LineNumber {
+    /// The names of the variants, as written in the configuration.
+    pub const NAMES: &'static [&'static str] = &["absolute", "relative", "hybrid", "off"];
+}
+
+impl fmt::Display for LineNumber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(Self::NAMES[*self as usize])
+    }
+}
+
+impl FromStr for LineNumber {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "absolute" => Self::Absolute,
+            "relative" => Self::Relative,
+            "hybrid" => Self::Hybrid,
+            "off" => Self::Off,
+            _ => bail!("invalid line number style '{s}'"),
+        })
+    }
+}
+
+/// A column of the gutter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum GutterColumnKind {
+    /// A sign on lines with a diagnostic, for the most severe one.
+    Diagnostics,
+    /// A sign on lines with a breakpoint.
+    Breakpoints,
+    /// Line numbers, unless `line-number` is `off`.
+    LineNumbers,
+    /// A mark on lines that changed since the last git commit.
+    Diff,
+    /// An empty column.
+    Spacer,
+}
+
+impl GutterColumnKind {
+    /// The columns of the gutter unless configured otherwise.
+    pub const DEFAULT: &'static [Self] = &[
+        Self::Diagnostics,
+        Self::Breakpoints,
+        Self::LineNumbers,
+        Self::Diff,
+    ];
+
+    /// The names of the variants, as written in the configuration.
+    pub const NAMES: &'static [&'static str] = &[
+        "diagnostics",
+        "breakpoints",
+        "line-numbers",
+        "diff",
+        "spacer",
+    ];
+}
+
+impl fmt::Display for GutterColumnKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(Self::NAMES[*self as usize])
+    }
+}
+
+impl FromStr for GutterColumnKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "diagnostics" => Self::Diagnostics,
+            "breakpoints" => Self::Breakpoints,
+            "line-numbers" => Self::LineNumbers,
+            "diff" => Self::Diff,
+            "spacer" => Self::Spacer,
+            _ => bail!("invalid gutter column '{s}'"),
+        })
+    }
+}
+
+/// When the line listing the open buffers is shown.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BufferLine {
+    /// Never.
+    #[default]
+    Never,
+    /// Always.
+    Always,
+    /// While more than one buffer is open.
+    Multiple,
+}
+
+impl BufferLine {
+    /// The names of the variants, as written in the configuration.
+    pub const NAMES: &'static [&'static str] = &["never", "always", "multiple"];
+}
+
+impl fmt::Display for BufferLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(Self::NAMES[*self as usize])
+    }
+}
+
+impl FromStr for BufferLine {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "never" => Self::Never,
+            "always" => Self::Always,
+            "multiple" => Self::Multiple,
+            _ => bail!("invalid bufferline '{s}'"),
+        })
+    }
+}
+
+/// The elements of the status line of each view, in order, in three groups: aligned left, centered
+/// and aligned right.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields, default)]
+pub struct StatusLineConfig {
+    /// The elements aligned left.
+    pub left: Vec<StatusLineElement>,
+    /// The elements in the middle.
+    pub center: Vec<StatusLineElement>,
+    /// The elements aligned right.
+    pub right: Vec<StatusLineElement>,
+}
+
+impl Default for StatusLineConfig {
+    fn default() -> Self {
+        Self {
+            left: vec![
+                StatusLineElement::Mode,
+                StatusLineElement::FilePath,
+                StatusLineElement::Modified,
+                StatusLineElement::PendingKeys,
+            ],
+            center: Vec::new(),
+            right: vec![
+                StatusLineElement::LspProgress,
+                StatusLineElement::Diagnostics,
+                StatusLineElement::Language,
+                StatusLineElement::Position,
+                StatusLineElement::Encoding,
+                StatusLineElement::LineEnding,
+            ],
+        }
+    }
+}
+
+/// An element of the status line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum StatusLineElement {
+    /// The editing mode, only in the view with focus.
+    Mode,
+    /// The path of the file, or `[scratch]`.
+    FilePath,
+    /// `[+]` if the buffer has unsaved changes.
+    Modified,
+    /// The name of the language of the buffer.
+    Language,
+    /// The progress reported by language servers.
+    LspProgress,
+    /// The number of errors and warnings in the buffer.
+    Diagnostics,
+    /// The line and column of the primary cursor.
+    Position,
+    /// The encoding of the file.
+    Encoding,
+    /// The line ending of the file, `LF` or `CRLF`.
+    LineEnding,
+    /// The count and keys typed so far of a command, only in the view with focus.
+    PendingKeys,
+}
+
+impl StatusLineElement {
+    /// The names of the variants, as written in the configuration.
+    pub const NAMES: &'static [&'static str] = &[
+        "mode",
+        "file-path",
+        "modified",
+        "language",
+        "lsp-progress",
+        "diagnostics",
+        "position",
+        "encoding",
+        "line-ending",
+        "pending-keys",
+    ];
+}
+
+impl fmt::Display for StatusLineElement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(Self::NAMES[*self as usize])
+    }
+}
+
+impl FromStr for StatusLineElement {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "mode" => Self::Mode,
+            "file-path" => Self::FilePath,
+            "modified" => Self::Modified,
+            "language" => Self::Language,
+            "lsp-progress" => Self::LspProgress,
+            "diagnostics" => Self::Diagnostics,
+            "position" => Self::Position,
+            "encoding" => Self::Encoding,
+            "line-ending" => Self::LineEnding,
+            "pending-keys" => Self::PendingKeys,
+            _ => bail!("invalid status line element '{s}'"),
+        })
+    }
+}
+
+/// Where a setting changed at runtime applies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SettingScope {
+    /// Every buffer that does not override it.
+    Global,
+    /// The buffers of a language that do not override it.
+    Language,
+    /// A single buffer.
+    Buffer,
+}
+
+/// A setting of the `[editor]` section, known by the key it is written with in the
+/// configuration: its name, preceded by the names of the nested sections and a dot.
+pub struct Setting {
+    /// The key of the setting, such as `search.smart-case`.
+    pub key: &'static str,
+    /// A one-line description of the setting.
+    pub doc: &'static str,
+    /// The values the setting accepts, or the items of the list it holds.
+    pub values: &'static [&'static str],
+    // Whether the setting holds a list, written with commas between the items or as `none`.
+    list: bool,
+    get: fn(&EditorConfig) -> String,
+    // Sets the setting to a value checked to be one of `values`.
+    set: fn(&mut EditorConfig, &str) -> Result<()>,
+    // How languages and buffers override the setting, if they can.
+    local: Option<LocalSetting>,
+}
+
+// Reads and overrides a setting in `LocalSettings`.
+struct LocalSetting {
+    get: fn(&LocalSettings) -> Option<String>,
+    set: fn(&mut LocalSettings, &str) -> Result<()>,
+}
+
+impl fmt::Debug for Setting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Setting")
+            .field("key", &self.key)
+            .field("values", &self.values)
+            .field("local", &self.is_local())
+            .finish()
+    }
+}
+
+const BOOL: &[&str] = &["true", "false"];
+
+/// Every setting of the `[editor]` section that can be changed at runtime.
+pub const SETTINGS: &[Setting] = &[
+    Setting {
+        key: "auto-pairs",
+        doc: "Insert the closing bracket or quote along with the opening one",
+        values: BOOL,
+        list: false,
+        get: |config| config.auto_pairs.to_string(),
+        set: |config, value| {
+            config.auto_pairs = value.parse()?;
+            Ok(())
+        },
+        local: Some(LocalSetting {
+            get: |settings| settings.auto_pairs.map(|value| value.to_string()),
+            set: |settings, value| {
+                settings.auto_pairs = Some(value.parse()?);
+                Ok(())
+            },
+        }),
+    },
+    Setting {
+        key: "auto-session",
+        doc: "Save the session of the workspace on exit, to restore it when started without files",
+        values: BOOL,
+        list: false,
+        get: |config| config.auto_session.to_string(),
+        set: |config, value| {
+            config.auto_session = value.parse()?;
+            Ok(())
+        },
+        local: None,
+    },
+    Setting {
+        key: "bufferline",
+        doc: "When the open buffers are listed in a line above the views",
+        values: BufferLine::NAMES,
+        list: false,
+        get: |config| config.bufferline.to_string(),
+        set: |config, value| {
+            config.bufferline = value.parse()?;
+            Ok(())
+        },
+        local: None,
+    },
+    Setting {
+        key: "clipboard.provider",
+        doc: "The clipboard provider to use instead of the first one detected",
+        values: ClipboardProvider::NAMES,
+        list: false,
+        get: |config| config.clipboard.provider.to_string(),
+        set: |config, value| {
+            config.clipboard.provider = value.parse()?;
+            Ok(())
+        },
+        local: None,
+    },
+    Setting {
+        key: "gutter",
+        doc: "The columns of the gutter left of the text, in order",
+        values: GutterColumnKind::NAMES,
+        list: true,
+        get: |config| join(&config.gutter),
+        set: |config, value| {
+            config.gutter = split(value)?;
+            Ok(())
+        },
+        local: None,
+    },
+    Setting {
+        key: "line-number",
+        doc: "How line numbers are shown",
+        values: LineNumber::NAMES,
+        list: false,
+        get: |config| config.line_number.to_string(),
+        set: |config, value| {
+            config.line_number = value.parse()?;
+            Ok(())
+        },
+        local: Some(LocalSetting {
+            get: |settings| settings.line_number.map(|value| value.to_string()),
+            set: |settings, value| {
+                settings.line_number = Some(value.parse()?);
+                Ok(())
+            },
+        }),
+    },
+    Setting {
+        key: "mouse",
+        doc: "Handle mouse clicks and scrolling instead of leaving them to the terminal",
+        values: BOOL,
+        list: false,
+        get: |config| config.mouse.to_string(),
+        set: |config, value| {
+            config.mouse = value.parse()?;
+            Ok(())
+        },
+        local: None,
+    },
+    Setting {
+        key: "search.smart-case",
+        doc: "Ignore case unless the pattern has an uppercase letter",
+        values: BOOL,
+        list: false,
+        get: |config| config.search.smart_case.to_string(),
+        set: |config, value| {
+            config.search.smart_case = value.parse()?;
+            Ok(())
+        },
+        local: None,
+    },
+    Setting {
+        key: "search.wrap-around",
+        doc: "Continue searching from the other end of the buffer",
+        values: BOOL,
+        list: false,
+        get: |config| config.search.wrap_around.to_string(),
+        set: |config, value| {
+            config.search.wrap_around = value.parse()?;
+            Ok(())
+        },
+        local: None,
+    },
+    Setting {
+        key: "statusline.left",
+        doc: "The elements of the status line aligned left, in order",
+        values: StatusLineElement::NAMES,
+        list: true,
+        get: |config| join(&config.statusline.left),
+        set: |config, value| {
+            config.statusline.left = split(value)?;
+            Ok(())
+        },
+        local: None,
+    },
+    Setting {
+        key: "statusline.center",
+        doc: "The elements in the middle of the status line, in order",
+        values: StatusLineElement::NAMES,
+        list: true,
+        get: |config| join(&config.statusline.center),
+        set: |config, value| {
+            config.statusline.center = split(value)?;
+            Ok(())
+        },
+        local: None,
+    },
+    Setting {
+        key: "statusline.right",
+        doc: "The elements of the status line aligned right, in order",
+        values: StatusLineElement::NAMES,
+        list: true,
+        get: |config| join(&config.statusline.right),
+        set: |config, value| {
+            config.statusline.right = split(value)?;
+            Ok(())
+        },
+        local: None,
+    },
+];
+
+impl Setting {
+    /// Looks up the setting called `key`.
+    pub fn find(key: &str) -> Result<&'static Setting> {
+        SETTINGS
+            .iter()
+            .find(|setting| setting.key == key)
+            .ok_or_else(|| {
+                let keys: Vec<_> = SETTINGS.iter().map(|setting| setting.key).collect();
+                anyhow!(
+                    "unknown setting '{key}', expected one of {}",
+                    keys.join(", ")
+                )
+            })
+    }
+
+    /// Returns whether languages and buffers can override the setting.
+    pub fn is_local(&self) -> bool {
+        self.local.is_some()
+    }
+
+    // Checks that `value` is one of the values of the setting, or a list of them.
+    fn check(&self, value: &str) -> Result<()> {
+        let valid = match self.list {
+            true => value == "none" || value.split(',').all(|item| self.values.contains(&item)),
+            false => self.values.contains(&value),
+        };
+        if !valid {
+            let expected = match self.list {
+                true => "none or a comma-separated list of",
+                false => "one of",
+            };
+            bail!(
+                "invalid value '{value}' for {}, expected {expected} {}",
+                self.key,
+                self.values.join(", ")
+            );
+        }
+        Ok(())
+    }
+}
+
+// Writes the items of a list setting.
+fn join<T: fmt::Display>(items: &[T]) -> String {
+    match items.is_empty() {
+        true => "none".to_string(),
+        false => items.iter().map(T::to_string).collect::<Vec<_>>().join(","),
+    }
+}
+
+// Parses the items of a list setting, written by `join`.
+fn split<T: FromStr<Err = anyhow::Error>>(value: &str) -> Result<Vec<T>> {
+    match value {
+        "none" => Ok(Vec::new()),
+        _ => value.split(',').map(str::parse).collect(),
+    }
+}
+
+/// The settings of the `[editor]` section that a language or a buffer overrides, written as
+/// `editor = { line-number = "off" }` in a language. Settings that are not set fall back to the
+/// wider scope.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields, default)]
+pub struct LocalSettings {
+    /// Overrides [`EditorConfig::auto_pairs`].
+    pub auto_pairs: Option<bool>,
+    /// Overrides [`EditorConfig::line_number`].
+    pub line_number: Option<LineNumber>,
+}
+
+impl LocalSettings {
+    /// Returns the value of the setting called `key`, if it is overridden.
+    pub fn get(&self, key: &str) -> Result<Option<String>> {
+        let (_, local) = local(key)?;
+        Ok((local.get)(self))
+    }
+
+    /// Overrides the setting called `key` with `value`.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        let (setting, local) = local(key)?;
+        setting.check(value)?;
+        (local.set)(self, value)
+    }
+
+    /// Returns these settings, with the ones not set taken from `other`.
+    pub fn or(self, other: LocalSettings) -> LocalSettings {
+        LocalSettings {
+            auto_pairs: self.auto_pairs.or(other.auto_pairs),
+            line_number: self.line_number.or(other.line_number),
+        }
+    }
+}
+
+// Looks up the setting called `key`, which must be one that can be overridden.
+fn local(key: &str) -> Result<(&'static Setting, &'static LocalSetting)> {
+    let setting = Setting::find(key)?;
+    match &setting.local {
+        Some(local) => Ok((setting, local)),
+        None => bail!("{key} is a global setting, it cannot be set per language or buffer"),
+    }
+}
+
+/// How searches and substitutions match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields, default)]
+pub struct SearchConfig {
+    /// Ignores case unless the pattern has an uppercase letter.
+    pub smart_case: bool,
+    /// Continues searching from the other end of the buffer.
+    pub wrap_around: bool,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        Self {
+            smart_case: true,
+            wrap_around: true,
+        }
+    }
+}
+
+/// How to reach the system clipboard.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields, default)]
+pub struct ClipboardConfig {
+    /// The provider to use instead of the first one detected.
+    pub provider: ClipboardProvider,
+    /// Command copying its standard input, used by the `custom` provider.
+    pub copy: Vec<String>,
+    /// Command printing the clipboard, used by the `custom` provider.
+    pub paste: Vec<String>,
+    /// Like `copy`, for the primary selection.
+    pub primary_copy: Vec<String>,
+    /// Like `paste`, for the primary selection.
+    pub primary_paste: Vec<String>,
+}
+
+/// A clipboard provider, in the order they are detected.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ClipboardProvider {
+    /// The first provider available.
+    #[default]
+    Auto,
+    /// The commands set by `copy` and `paste`.
+    Custom,
+    /// `wl-copy` and `wl-paste`.
+    Wayland,
+    /// `xclip`.
+    Xclip,
+    /// `xsel`.
+    Xsel,
+    /// The buffers of `tmux`.
+    Tmux,
+    /// The OSC 52 escape sequence, which sets the clipboard of the terminal.
+    Osc52,
+    /// A clipboard kept inside the editor.
+    Internal,
+}
+
+impl ClipboardProvider {
+    /// Every provider but [`ClipboardProvider::Auto`], in the order they are detected.
+    pub const ALL: [Self; 7] = [
+        Self::Custom,
+        Self::Wayland,
+        Self::Xclip,
+        Self::Xsel,
+        Self::Tmux,
+        Self::Osc52,
+        Self::Internal,
+    ];
+
+    /// The names of the variants, as written in the configuration.
+    pub const NAMES: &'static [&'static str] = &[
+        "auto", "custom", "wayland", "xclip", "xsel", "tmux", "osc52", "internal",
+    ];
+
+    /// Returns the name of the provider in configuration files.
+    pub fn name(self) -> &'static str {
+        Self::NAMES[self as usize]
+    }
+}
+
+impl fmt::Display for ClipboardProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for ClipboardProvider {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "auto" => Self::Auto,
+            "custom" => Self::Custom,
+            "wayland" => Self::Wayland,
+            "xclip" => Self::Xclip,
+            "xsel" => Self::Xsel,
+            "tmux" => Self::Tmux,
+            "osc52" => Self::Osc52,
+            "internal" => Self::Internal,
+            _ => bail!("invalid clipboard provider '{s}'"),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_setting_can_be_read_and_set() {
+        let mut config = EditorConfig::default();
+        for setting in SETTINGS {
+            let mut values: Vec<String> = setting.values.iter().map(|v| v.to_string()).collect();
+            if setting.list {
+                values.push("none".to_string());
+                values.push(setting.values.join(","));
+            }
+            for value in values {
+                config.set(setting.key, &value).unwrap();
+                assert_eq!(config.get(setting.key).unwrap(), value, "{}", setting.key);
+            }
+            assert!(config.set(setting.key, "bogus").is_err(), "{}", setting.key);
+
+            let mut local = LocalSettings::default();
+            let value = setting.values[0];
+            match setting.is_local() {
+                true => {
+                    assert_eq!(local.get(setting.key).unwrap(), None);
+                    local.set(setting.key, value).unwrap();
+                    assert_eq!(local.get(setting.key).unwrap().as_deref(), Some(value));
+                }
+                false => {
+                    let err = local.set(setting.key, value).unwrap_err();
+                    assert_eq!(
+                        err.to_string(),
+                        format!(
+                            "{} is a global setting, it cannot be set per language or buffer",
+                            setting.key
+                        )
+                    );
+                }
+            }
+        }
+
+        assert!(config.get("tab-width").is_err());
+        assert!(config.set("tab-width", "4").is_err());
+    }
+}
@@ -1,11 +1,161 @@
+use super::LocalSettings;
+use crate::AutoPairs;
+use anyhow::{anyhow, Result};
 use serde::Deserialize;
+use std::path::Path;
 
-#[derive(Debug, Default, Deserialize)]
+#[derive(Debug, Default, Clone, Deserialize)]
 #[serde(rename_all = "kebab-case", deny_unknown_fields, default)]
 pub struct LanguageConfig {
     pub name: String,                    // rust
-    pub file_types: Vec<String>,         // [rs]
+    pub file_types: Vec<String>,         // [rs, Cargo.lock, .cargo/*.toml]
+    pub shebangs: Vec<String>,           // [rust-script]
     pub roots: Vec<String>,              // [Cargo.toml, Cargo.lock]
     pub language_server: Option<String>, // rust-analyzer
     pub debugger: Option<String>,        // lldb-vscode
+    pub grammar: Option<String>,         // rust
+    /// How lines are indented; with tabs if unset.
+    pub indent: Option<IndentConfig>,
+    /// Ways of starting a session with `debugger`; the first one is the default.
+    pub debug_configs: Vec<DebugConfig>,
+    /// Settings of the `[editor]` section that differ for the language.
+    pub editor: LocalSettings,
+    /// Brackets and quotes closed as they are typed; [`crate::DEFAULT_PAIRS`] if unset.
+    pub auto_pairs: Option<AutoPairs>,
+}
+
+/// Indentation of a language, declared as `indent = { tab-width = 4, unit = "    " }`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct IndentConfig {
+    /// How many columns a tab takes.
+    pub tab_width: usize,
+    /// What one level of indentation is made of.
+    pub unit: String,
+}
+
+/// How a debug session gets hold of the debuggee.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DebugRequest {
+    /// The adapter starts the debuggee.
+    #[default]
+    Launch,
+    /// The adapter attaches to a running debuggee.
+    Attach,
+}
+
+/// A way of starting a debug session, declared as `[[language.debug-configs]]`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct DebugConfig {
+    /// Picks the configuration when starting a session.
+    pub name: String,
+    /// Whether to launch the debuggee or attach to it.
+    #[serde(default)]
+    pub request: DebugRequest,
+    /// Arguments of the `launch` or `attach` request, passed to the adapter as is after
+    /// substituting placeholders.
+    #[serde(default)]
+    pub args: toml::value::Table,
+}
+
+impl DebugConfig {
+    /// Returns the arguments of the request.
+    ///
+    /// In strings, `{0}`, `{1}`, … are replaced with the matching entry of `params` and `{file}`
+    /// with the path of the file being edited.
+    pub fn arguments(&self, params: &[String], file: Option<&Path>) -> Result<serde_json::Value> {
+        fn substitute(
+            value: toml::Value,
+            params: &[String],
+            file: Option<&Path>,
+        ) -> Result<toml::Value> {
+            Ok(match value {
+                toml::Value::String(s) => toml::Value::String(placeholders(&s, params, file)?),
+                toml::Value::Array(items) => toml::Value::Array(
+                    items
+                        .into_iter()
+                        .map(|item| substitute(item, params, file))
+                        .collect::<Result<_>>()?,
+                ),
+                toml::Value::Table(table) => toml::Value::Table(
+                    table
+                        .into_iter()
+                        .map(|(key, value)| Ok((key, substitute(value, params, file)?)))
+                        .collect::<Result<_>>()?,
+                ),
+                value => value,
+            })
+        }
+
+        let args = substitute(toml::Value::Table(self.args.clone()), params, file)?;
+        serde_json::to_value(args).map_err(|err| anyhow!(err))
+    }
+}
+
+fn placeholders(s: &str, params: &[String], file: Option<&Path>) -> Result<String> {
+    let mut result = String::new();
+    let mut rest = s;
+
+    while let Some(start) = rest.find('{') {
+        result.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .map(|end| start + end)
+            .ok_or_else(|| anyhow!("unclosed placeholder in '{s}'"))?;
+        let name = &rest[start + 1..end];
+        match name {
+            "file" => {
+                let file = file.ok_or_else(|| anyhow!("the buffer has no file"))?;
+                result.push_str(&file.to_string_lossy());
+            }
+            _ => {
+                let index: usize = name
+                    .parse()
+                    .map_err(|_| anyhow!("unknown placeholder '{{{name}}}'"))?;
+                let param = params
+                    .get(index)
+                    .ok_or_else(|| anyhow!("missing argument {index} for '{s}'"))?;
+                result.push_str(param);
+            }
+        }
+        rest = &rest[end + 1..];
+    }
+    result.push_str(rest);
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn arguments_substitute_placeholders() {
+        let config: DebugConfig = toml::from_str(
+            r#"
+            name = "binary"
+            args = { program = "{0}", args = ["{1}", "{file}"], stop-on-entry = true }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.request, DebugRequest::Launch);
+
+        let params = ["target/debug/tis".to_string(), "-v".to_string()];
+        assert_eq!(
+            config
+                .arguments(&params, Some(Path::new("src/main.rs")))
+                .unwrap(),
+            json!({
+                "program": "target/debug/tis",
+                "args": ["-v", "src/main.rs"],
+                "stop-on-entry": true,
+            })
+        );
+
+        let err = config.arguments(&params[..1], None).unwrap_err();
+        assert_eq!(err.to_string(), "missing argument 1 for '{1}'");
+    }
 }
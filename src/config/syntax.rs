@@ -2,6 +2,7 @@ use super::LanguageConfig;
 use crate::metadata;
 use anyhow::{anyhow, Result};
 use serde::Deserialize;
+use std::path::Path;
 
 /// Syntax configuration for all languages.
 #[derive(Debug, Default, Deserialize)]
@@ -29,6 +30,17 @@ impl SyntaxConfig {
             .map_err(|e| anyhow!(e))
     }
 
+    /// Returns the language of the file at `path`, chosen by its name alone.
+    pub fn language_for(&self, path: &Path) -> Option<&LanguageConfig> {
+        self.detect(Some(path), None)
+            .map(|detection| detection.language)
+    }
+
+    /// Returns the language called `name`.
+    pub fn language(&self, name: &str) -> Option<&LanguageConfig> {
+        self.languages.iter().find(|language| language.name == name)
+    }
+
     pub fn load_default() -> Self {
         load_default_syntax_config()
             .try_into()
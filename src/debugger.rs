@@ -0,0 +1,164 @@
+//! Debug sessions with the adapters named by [`LanguageConfig::debugger`].
+
+use crate::{language_server, Buffer, DebugRequest, LanguageConfig, Message};
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tissue_dap::{
+    types::{SourceBreakpoint, StackFrame},
+    Client,
+};
+use tokio::sync::mpsc::UnboundedSender;
+
+/// A running debug session.
+#[derive(Debug)]
+pub struct DebugSession {
+    /// The connection to the adapter.
+    pub client: Arc<Client>,
+    /// The thread that stopped last.
+    pub thread: Option<i64>,
+    /// Whether the debuggee is stopped.
+    pub stopped: bool,
+    /// The stack of the stopped thread, innermost frame first.
+    pub frames: Vec<StackFrame>,
+    /// Whether the adapter accepts breakpoints.
+    pub initialized: bool,
+    /// The exit code of the debuggee, once it exited.
+    pub exit_code: Option<i64>,
+}
+
+impl DebugSession {
+    /// Creates a session for a freshly started adapter.
+    pub fn new(client: Arc<Client>) -> Self {
+        Self {
+            client,
+            thread: None,
+            stopped: false,
+            frames: Vec::new(),
+            initialized: false,
+            exit_code: None,
+        }
+    }
+}
+
+/// Starts the debug adapter of `language` for the file at `path` and sends it the `request`.
+///
+/// The outcome is reported as [`Message::DebuggerStarted`], after which events from the adapter
+/// are forwarded to `sender`. The request itself is not awaited before that, since some adapters
+/// only answer it once they are configured.
+pub async fn start(
+    language: LanguageConfig,
+    path: PathBuf,
+    request: DebugRequest,
+    arguments: Value,
+    sender: UnboundedSender<Message>,
+) {
+    let command = match language.debugger.as_deref() {
+        Some(command) => command,
+        None => {
+            let err = anyhow!("no debugger configured for {}", language.name);
+            sender.send(Message::DebuggerStarted(Err(err))).ok();
+            return;
+        }
+    };
+    let root = language_server::workspace_root(&language, &path);
+
+    let result = Client::start(command, &[], &root);
+    let (client, mut events) = match result {
+        Ok((client, events)) => (Arc::new(client), events),
+        Err(err) => {
+            sender.send(Message::DebuggerStarted(Err(err))).ok();
+            return;
+        }
+    };
+    if let Err(err) = client.initialize(adapter_id(command)).await {
+        sender.send(Message::DebuggerStarted(Err(err))).ok();
+        return;
+    }
+    sender
+        .send(Message::DebuggerStarted(Ok(client.clone())))
+        .ok();
+
+    let events_sender = sender.clone();
+    tokio::spawn(async move {
+        while let Some(event) = events.recv().await {
+            if events_sender.send(Message::Debugger(event)).is_err() {
+                break;
+            }
+        }
+    });
+
+    let result = match request {
+        DebugRequest::Launch => client.launch(arguments).await,
+        DebugRequest::Attach => client.attach(arguments).await,
+    };
+    if let Err(err) = result {
+        sender.send(Message::DebuggerStarted(Err(err))).ok();
+    }
+}
+
+// Names the kind of adapter after its binary, e.g. `lldb-vscode`.
+fn adapter_id(command: &str) -> &str {
+    Path::new(command)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or(command)
+}
+
+/// Returns the breakpoints of `buffer` as sent to adapters.
+pub fn breakpoints(buffer: &Buffer) -> Vec<SourceBreakpoint> {
+    buffer
+        .breakpoints()
+        .iter()
+        .map(|&line| SourceBreakpoint {
+            line: line + 1,
+            condition: None,
+        })
+        .collect()
+}
+
+/// Sends breakpoints, grouped by file, to the adapter.
+pub async fn set_breakpoints(
+    client: &Client,
+    breakpoints: Vec<(PathBuf, Vec<SourceBreakpoint>)>,
+) -> Result<()> {
+    for (path, breakpoints) in breakpoints {
+        let path = language_server::absolute(&path);
+        for breakpoint in client.set_breakpoints(&path, breakpoints).await? {
+            if !breakpoint.verified {
+                log::warn!(
+                    "breakpoint in {} not set: {}",
+                    path.display(),
+                    breakpoint.message.as_deref().unwrap_or("unknown reason")
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns the byte offset of the 1-based `line` and `column` of a stack frame in `buffer`.
+pub fn frame_pos(buffer: &Buffer, line: usize, column: usize) -> usize {
+    let text = buffer.text();
+    let line = line.saturating_sub(1).min(text.len_lines() - 1);
+    let slice = text.line(line);
+    text.line_to_byte(line) + slice.char_to_byte(column.saturating_sub(1).min(slice.len_chars()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_map_to_offsets() {
+        let buffer = Buffer::from("fn main() {\n    é = 1;\n}\n");
+
+        assert_eq!(frame_pos(&buffer, 1, 1), 0);
+        assert_eq!(frame_pos(&buffer, 2, 5), 16);
+        assert_eq!(frame_pos(&buffer, 2, 6), 18);
+        assert_eq!(frame_pos(&buffer, 9, 1), buffer.len_bytes());
+    }
+}
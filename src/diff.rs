@@ -0,0 +1,95 @@
+//! Lines changed since the version of a file committed to git, marked in the gutter.
+
+use anyhow::{bail, Result};
+use ropey::Rope;
+use similar::{Algorithm, DiffOp, TextDiff};
+use std::{path::Path, process::Command, time::Duration};
+
+// Diffing gives up on finding the smallest diff past this, so that huge files stay responsive.
+const DIFF_TIMEOUT: Duration = Duration::from_millis(10);
+
+/// How a line differs from the diff base.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffMark {
+    /// The line was added.
+    Added,
+    /// The line replaced one or more lines.
+    Modified,
+    /// Lines were removed before the line.
+    Removed,
+}
+
+/// Returns the contents of the file at `path` in the `HEAD` commit of its git repository.
+pub fn diff_base(path: &Path) -> Result<Rope> {
+    let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+        bail!("{} is not a file", path.display());
+    };
+    let dir = match dir.as_os_str().is_empty() {
+        true => Path::new("."),
+        false => dir,
+    };
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .arg("show")
+        .arg(format!("HEAD:./{}", name.to_string_lossy()))
+        .output()?;
+    if !output.status.success() {
+        bail!("{}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(Rope::from(String::from_utf8(output.stdout)?))
+}
+
+/// Returns the lines of `text` that differ from `base`, in order.
+pub fn line_diff(base: &Rope, text: &Rope) -> Vec<(usize, DiffMark)> {
+    let (base, text) = (base.to_string(), text.to_string());
+    let diff = TextDiff::configure()
+        .algorithm(Algorithm::Myers)
+        .timeout(DIFF_TIMEOUT)
+        .diff_lines(&base, &text);
+    let last_line = diff.new_slices().len().saturating_sub(1);
+
+    let mut marks = Vec::new();
+    for op in diff.ops() {
+        match *op {
+            DiffOp::Equal { .. } => {}
+            DiffOp::Delete { new_index, .. } => {
+                marks.push((new_index.min(last_line), DiffMark::Removed));
+            }
+            DiffOp::Insert {
+                new_index, new_len, ..
+            } => {
+                marks.extend((new_index..new_index + new_len).map(|line| (line, DiffMark::Added)));
+            }
+            DiffOp::Replace {
+                new_index, new_len, ..
+            } => {
+                let lines = new_index..new_index + new_len;
+                marks.extend(lines.map(|line| (line, DiffMark::Modified)));
+            }
+        }
+    }
+    marks.dedup_by_key(|(line, _)| *line);
+    marks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn marks_added_modified_and_removed_lines() {
+        let base = Rope::from("a\nb\nc\nd\ne\n");
+        let text = Rope::from("a\nB\nc\nnew\nd\n");
+
+        assert_eq!(
+            line_diff(&base, &text),
+            [
+                (1, DiffMark::Modified),
+                (3, DiffMark::Added),
+                (4, DiffMark::Removed),
+            ]
+        );
+        assert_eq!(line_diff(&base, &base), []);
+    }
+}
@@ -0,0 +1,274 @@
+//! Searches the files of a workspace, streaming the matches to the application as each file is
+//! searched, and lists the files of a workspace for the file picker.
+
+use crate::Message;
+use ignore::WalkBuilder;
+use regex::bytes::Regex;
+use std::{
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+};
+use tokio::sync::{
+    mpsc::{self, UnboundedSender},
+    Semaphore,
+};
+
+/// How many matches a search stops at.
+pub const MAX_GREP_MATCHES: usize = 10_000;
+
+// How many paths are sent at once while listing files.
+const FILES_BATCH: usize = 256;
+
+/// A line matching a workspace search.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GrepMatch {
+    /// The file the line is in.
+    pub path: PathBuf,
+    /// The line, counting from 0.
+    pub line: usize,
+    /// The text of the line, without leading and trailing whitespace.
+    pub text: String,
+}
+
+/// A running search or file listing, which is cancelled when the handle is dropped.
+#[derive(Debug)]
+pub struct GrepHandle {
+    id: usize,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl GrepHandle {
+    /// Returns the id that the messages of the search or listing carry.
+    pub fn id(&self) -> usize {
+        self.id
+    }
+}
+
+impl Drop for GrepHandle {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Searches the files under `root` for lines matching `regex`, skipping the files ignored by
+/// `.gitignore`, `.ignore` and hidden files.
+///
+/// Files are searched in parallel. The matches of each file are sent as
+/// [`Message::GrepMatches`], and [`Message::GrepFinished`] follows once every file is searched.
+pub fn grep(root: &Path, regex: Regex, id: usize, sender: UnboundedSender<Message>) -> GrepHandle {
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let (paths, mut receiver) = mpsc::channel(256);
+
+    let walker = WalkBuilder::new(root).build();
+    let walk_cancelled = cancelled.clone();
+    tokio::task::spawn_blocking(move || {
+        for entry in walker {
+            if walk_cancelled.load(Ordering::Relaxed) {
+                break;
+            }
+            match entry {
+                Ok(entry) if entry.file_type().map_or(false, |kind| kind.is_file()) => {
+                    if paths.blocking_send(entry.into_path()).is_err() {
+                        break;
+                    }
+                }
+                Ok(_) => {}
+                Err(err) => log::debug!("grep: {err}"),
+            }
+        }
+    });
+
+    let search_cancelled = cancelled.clone();
+    tokio::spawn(async move {
+        let cancelled = search_cancelled;
+        let tasks = std::thread::available_parallelism().map_or(4, NonZeroUsize::get);
+        let semaphore = Arc::new(Semaphore::new(tasks));
+        let regex = Arc::new(regex);
+        let count = Arc::new(AtomicUsize::new(0));
+
+        while let Some(path) = receiver.recv().await {
+            if cancelled.load(Ordering::Relaxed) {
+                break;
+            }
+            let permit = semaphore.clone().acquire_owned().await;
+            let permit = permit.expect("the semaphore is never closed");
+            let (regex, count, cancelled) = (regex.clone(), count.clone(), cancelled.clone());
+            let sender = sender.clone();
+            // Reading and searching a file blocks, like the walk.
+            tokio::task::spawn_blocking(move || {
+                let _permit = permit;
+                let matches = match std::fs::read(&path) {
+                    Ok(bytes) => search(&path, &bytes, &regex),
+                    Err(err) => {
+                        log::debug!("grep: cannot read {}: {err}", path.display());
+                        return;
+                    }
+                };
+                if matches.is_empty() || cancelled.load(Ordering::Relaxed) {
+                    return;
+                }
+                if count.fetch_add(matches.len(), Ordering::Relaxed) + matches.len()
+                    >= MAX_GREP_MATCHES
+                {
+                    cancelled.store(true, Ordering::Relaxed);
+                }
+                sender.send(Message::GrepMatches { id, matches }).ok();
+            });
+        }
+
+        // Wait for the files being searched.
+        let _ = semaphore.acquire_many(tasks as u32).await;
+        let truncated = count.load(Ordering::Relaxed) >= MAX_GREP_MATCHES;
+        if truncated || !cancelled.load(Ordering::Relaxed) {
+            sender.send(Message::GrepFinished { id, truncated }).ok();
+        }
+    });
+
+    GrepHandle { id, cancelled }
+}
+
+/// Lists the files under `root`, skipping the same files as [`grep`].
+///
+/// Paths are sent in batches as [`Message::Files`] while the directories are walked.
+pub fn list_files(root: &Path, id: usize, sender: UnboundedSender<Message>) -> GrepHandle {
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let walker = WalkBuilder::new(root).build();
+    let walk_cancelled = cancelled.clone();
+
+    tokio::task::spawn_blocking(move || {
+        let mut paths = Vec::with_capacity(FILES_BATCH);
+        for entry in walker {
+            if walk_cancelled.load(Ordering::Relaxed) {
+                return;
+            }
+            match entry {
+                Ok(entry) if entry.file_type().map_or(false, |kind| kind.is_file()) => {
+                    paths.push(entry.into_path());
+                }
+                Ok(_) => {}
+                Err(err) => log::debug!("list files: {err}"),
+            }
+            if paths.len() == FILES_BATCH {
+                let batch = std::mem::replace(&mut paths, Vec::with_capacity(FILES_BATCH));
+                if sender.send(Message::Files { id, paths: batch }).is_err() {
+                    return;
+                }
+            }
+        }
+        if !paths.is_empty() {
+            sender.send(Message::Files { id, paths }).ok();
+        }
+    });
+
+    GrepHandle { id, cancelled }
+}
+
+// Returns the lines of the file at `path` holding `bytes` that match `regex`. Binary files have
+// no matches, and bytes that are not UTF-8 show as replacement characters in the matched lines.
+fn search(path: &Path, bytes: &[u8], regex: &Regex) -> Vec<GrepMatch> {
+    if bytes.iter().take(8192).any(|&byte| byte == 0) {
+        return Vec::new();
+    }
+
+    bytes
+        .split_inclusive(|&byte| byte == b'\n')
+        .map(|line| {
+            let line = line.strip_suffix(b"\n").unwrap_or(line);
+            line.strip_suffix(b"\r").unwrap_or(line)
+        })
+        .enumerate()
+        .filter(|(_, line)| regex.is_match(line))
+        .map(|(line, text)| GrepMatch {
+            path: path.to_path_buf(),
+            line,
+            text: String::from_utf8_lossy(text).trim().to_string(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn searches_files_that_are_not_ignored() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        std::fs::create_dir_all(dir.join(".git")).unwrap();
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        std::fs::write(dir.join(".gitignore"), "target/\n").unwrap();
+        std::fs::write(dir.join("src/main.rs"), "fn main() {\n    needle();\n}\n").unwrap();
+        std::fs::write(dir.join("src/lib.rs"), "// needle\n").unwrap();
+        std::fs::create_dir_all(dir.join("target")).unwrap();
+        std::fs::write(dir.join("target/out.rs"), "needle\n").unwrap();
+        std::fs::write(dir.join("binary"), b"needle\0").unwrap();
+        std::fs::write(dir.join("latin1.txt"), b"caf\xe9\r\n\tneedle \xe0\r\n").unwrap();
+
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let regex = Regex::new("needle").unwrap();
+        let handle = grep(dir, regex, 7, sender);
+
+        let mut found = Vec::new();
+        while let Some(message) = receiver.recv().await {
+            match message {
+                Message::GrepMatches { id, matches } => {
+                    assert_eq!(id, handle.id());
+                    found.extend(matches);
+                }
+                Message::GrepFinished { truncated, .. } => {
+                    assert!(!truncated);
+                    break;
+                }
+                message => panic!("unexpected message {message:?}"),
+            }
+        }
+        found.sort_by(|a, b| a.path.cmp(&b.path));
+
+        let expected = [
+            (dir.join("latin1.txt"), 1, "needle \u{fffd}"),
+            (dir.join("src/lib.rs"), 0, "// needle"),
+            (dir.join("src/main.rs"), 1, "needle();"),
+        ];
+        let found: Vec<_> = found
+            .iter()
+            .map(|m| (m.path.clone(), m.line, m.text.as_str()))
+            .collect();
+        assert_eq!(found, expected);
+    }
+
+    #[tokio::test]
+    async fn lists_files_that_are_not_ignored() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        std::fs::create_dir_all(dir.join(".git")).unwrap();
+        std::fs::create_dir_all(dir.join("target")).unwrap();
+        std::fs::write(dir.join(".gitignore"), "target/\n").unwrap();
+        std::fs::write(dir.join("target/out.rs"), "").unwrap();
+        for i in 0..FILES_BATCH + 1 {
+            std::fs::write(dir.join(format!("{i}.rs")), "").unwrap();
+        }
+
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let handle = list_files(dir, 3, sender);
+
+        let mut batches = Vec::new();
+        while let Some(message) = receiver.recv().await {
+            match message {
+                Message::Files { id, paths } => {
+                    assert_eq!(id, handle.id());
+                    batches.push(paths);
+                }
+                message => panic!("unexpected message {message:?}"),
+            }
+        }
+        assert_eq!(batches.len(), 2);
+        let paths: Vec<_> = batches.into_iter().flatten().collect();
+        assert_eq!(paths.len(), FILES_BATCH + 1);
+        assert!(paths.iter().all(|path| path.extension().unwrap() == "rs"));
+        assert!(!paths.contains(&dir.join("target/out.rs")));
+    }
+}
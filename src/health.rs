@@ -1,4 +1,6 @@
-use crate::{LanguageConfig, SyntaxConfig};
+use crate::{
+    metadata, syntax, Clipboard, Config, Environment, LanguageConfig, QueryKind, SyntaxConfig,
+};
 use anyhow::Result;
 use crossterm::style::Stylize;
 use std::io::Write;
@@ -26,10 +28,10 @@ impl From<Option<String>> for HealthCategory {
 pub struct Health {}
 
 impl Health {
-    pub fn check(category: HealthCategory) -> Result<()> {
+    pub fn check(category: HealthCategory, config: &Config) -> Result<()> {
         match category {
-            HealthCategory::All => check_all()?,
-            HealthCategory::Clipboard => check_clipboard()?,
+            HealthCategory::All => check_all(config)?,
+            HealthCategory::Clipboard => check_clipboard(config)?,
             HealthCategory::Languages => check_languages()?,
             HealthCategory::Language(lang) => check_language(lang)?,
         }
@@ -38,21 +40,54 @@ impl Health {
     }
 }
 
-// TODO: Add additional information.
-fn check_all() -> Result<()> {
+fn check_all(config: &Config) -> Result<()> {
+    check_clipboard(config)?;
+    println!();
     check_languages()
 }
 
-fn check_clipboard() -> Result<()> {
-    todo!()
+fn check_clipboard(config: &Config) -> Result<()> {
+    let stdout = std::io::stdout();
+    let mut stdout = stdout.lock();
+
+    let (clipboard, probes) = Clipboard::detect(&config.editor.clipboard, &Environment::current());
+
+    writeln!(
+        stdout,
+        "{} {}",
+        "Clipboard provider:".blue().bold(),
+        clipboard.name()
+    )?;
+    for probe in probes {
+        match probe.skipped {
+            None => writeln!(
+                stdout,
+                "{}",
+                format!("  ✓ {:<10} selected", probe.name).green()
+            )?,
+            Some(reason) => writeln!(
+                stdout,
+                "{}",
+                format!("  ✘ {:<10} {reason}", probe.name).yellow()
+            )?,
+        }
+    }
+
+    Ok(())
 }
 
 fn check_languages() -> Result<()> {
     let stdout = std::io::stdout();
     let mut stdout = stdout.lock();
 
-    // TODO: Add tree-sitter features.
-    let headings = vec!["Language", "LSP", "DAP"];
+    let headings = vec![
+        "Language",
+        "LSP",
+        "DAP",
+        "Highlight",
+        "Textobjects",
+        "Indent",
+    ];
 
     let columns = crossterm::terminal::size().map(|(c, _)| c).unwrap_or(80);
     let column_width = columns as usize / headings.len();
@@ -75,6 +110,17 @@ fn check_languages() -> Result<()> {
         None => format("None").yellow(),
     };
 
+    let runtime_dir = metadata::runtime_dir();
+    let check_query = |language: &LanguageConfig, kind| {
+        let grammar = language.grammar.as_ref().map_or(false, |grammar| {
+            syntax::grammar_path(&runtime_dir, grammar).exists()
+        });
+        match grammar && syntax::query_path(&runtime_dir, language, kind).exists() {
+            true => format("✓").green(),
+            false => format("✘").red(),
+        }
+    };
+
     for heading in headings {
         write!(stdout, "{}", format(heading).blue().bold())?;
     }
@@ -92,16 +138,21 @@ fn check_languages() -> Result<()> {
 
     syntax_config.languages.sort_by(|a, b| a.name.cmp(&b.name));
 
-    for LanguageConfig {
-        name,
-        language_server,
-        debugger,
-        ..
-    } in syntax_config.languages
-    {
-        write!(stdout, "{}", format(&name).reset())?;
-        write!(stdout, "{}", check_program(language_server))?;
-        write!(stdout, "{}", check_program(debugger))?;
+    for language in syntax_config.languages {
+        write!(stdout, "{}", format(&language.name).reset())?;
+        write!(
+            stdout,
+            "{}",
+            check_program(language.language_server.clone())
+        )?;
+        write!(stdout, "{}", check_program(language.debugger.clone()))?;
+        for kind in [
+            QueryKind::Highlights,
+            QueryKind::Textobjects,
+            QueryKind::Indents,
+        ] {
+            write!(stdout, "{}", check_query(&language, kind))?;
+        }
         writeln!(stdout)?;
     }
 
@@ -164,8 +215,35 @@ fn check_language(language: String) -> Result<()> {
         None => "None".to_string().yellow(),
     };
 
+    let runtime_dir = metadata::runtime_dir();
+    let grammar = match language.grammar {
+        Some(ref name) => {
+            let path = syntax::grammar_path(&runtime_dir, name);
+            match path.exists() {
+                true => path.display().to_string().green(),
+                false => format!("{} (Not Found)", path.display()).red(),
+            }
+        }
+        None => "None".to_string().yellow(),
+    };
+    let query = |kind| {
+        let path = syntax::query_path(&runtime_dir, language, kind);
+        match path.exists() {
+            true => path.display().to_string().green(),
+            false => format!("{} (Not Found)", path.display()).red(),
+        }
+    };
+
     writeln!(stdout, "Language-server : {language_server}")?;
     writeln!(stdout, "Debug-adapter   : {debug_adapter}")?;
+    writeln!(stdout, "Grammar         : {grammar}")?;
+    writeln!(stdout, "Highlights      : {}", query(QueryKind::Highlights))?;
+    writeln!(
+        stdout,
+        "Textobjects     : {}",
+        query(QueryKind::Textobjects)
+    )?;
+    writeln!(stdout, "Indents         : {}", query(QueryKind::Indents))?;
 
     Ok(())
 }
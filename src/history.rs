@@ -0,0 +1,599 @@
+//! Undo history kept as a tree of revisions.
+//!
+//! Every revision but the root holds the transactions that lead to it from its parent, so undoing
+//! a change and then making another one starts a new branch instead of losing the undone change.
+//! Undo and redo move along the branch that was visited last, while [`History::earlier`] and
+//! [`History::later`] move through the revisions in the order they were made, hopping between
+//! branches like Vim's `:earlier` and `:later`.
+
+use crate::{Selection, Transaction};
+use anyhow::{anyhow, bail, Result};
+use ropey::Rope;
+use serde::{Deserialize, Serialize};
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{Duration, SystemTime},
+};
+
+/// The undo history of a buffer.
+#[derive(Debug, Clone)]
+pub struct History {
+    revisions: Vec<Revision>,
+    current: usize,
+    // Changes applied since the last commit, which become the next revision.
+    pending: Option<Revision>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct Revision {
+    parent: usize,
+    // The child that redo goes to: the one made or visited last.
+    last_child: Option<usize>,
+    transactions: Vec<Transaction>,
+    // The inverse of each transaction, in the same order.
+    inversions: Vec<Transaction>,
+    // The selections before and after the transactions, restored when they are undone or redone.
+    selection_before: Selection,
+    selection_after: Selection,
+    timestamp: SystemTime,
+}
+
+/// The transactions to apply to a buffer to move to another revision.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Jump {
+    /// The transactions, in the order they must be applied.
+    pub transactions: Vec<Transaction>,
+    /// The selection to restore afterwards.
+    pub selection: Selection,
+}
+
+/// How far to move through the history with [`History::earlier`] and [`History::later`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UndoKind {
+    /// A number of revisions.
+    Steps(usize),
+    /// A span of time.
+    TimePeriod(Duration),
+}
+
+impl FromStr for UndoKind {
+    type Err = anyhow::Error;
+
+    /// Parses a number of steps (`3`) or a time with a unit of `s`, `m`, `h` or `d` (`5m`). An
+    /// empty string is a single step.
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Ok(Self::Steps(1));
+        }
+
+        let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        let (number, unit) = s.split_at(split);
+        let error = || {
+            anyhow!(
+                "invalid undo amount '{s}', expected a number of steps or a time such as 30s, 5m, \
+                 2h or 1d"
+            )
+        };
+        let number: u64 = number.parse().map_err(|_| error())?;
+        let seconds = match unit {
+            "" => return Ok(Self::Steps(number as usize)),
+            "s" => 1,
+            "m" => 60,
+            "h" => 60 * 60,
+            "d" => 24 * 60 * 60,
+            _ => return Err(error()),
+        };
+        Ok(Self::TimePeriod(Duration::from_secs(
+            number.saturating_mul(seconds),
+        )))
+    }
+}
+
+// The format undo history is saved in.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct SavedHistory {
+    path: PathBuf,
+    // Hash of the text of the file at the current revision.
+    hash: String,
+    current: usize,
+    revisions: Vec<Revision>,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self {
+            revisions: vec![Revision {
+                parent: 0,
+                last_child: None,
+                transactions: Vec::new(),
+                inversions: Vec::new(),
+                selection_before: Selection::default(),
+                selection_after: Selection::default(),
+                timestamp: SystemTime::now(),
+            }],
+            current: 0,
+            pending: None,
+        }
+    }
+}
+
+impl History {
+    /// Returns the index of the current revision. The original text is revision 0, and later
+    /// revisions have higher indices.
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    /// Returns `true` if changes were recorded since the last commit.
+    pub fn has_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// Records `transaction`, along with its `inversion` and the `selection` before it, as part of
+    /// the next revision.
+    pub fn record(
+        &mut self,
+        transaction: Transaction,
+        inversion: Transaction,
+        selection: &Selection,
+    ) {
+        let pending = self.pending.get_or_insert_with(|| Revision {
+            parent: self.current,
+            last_child: None,
+            transactions: Vec::new(),
+            inversions: Vec::new(),
+            selection_before: selection.clone(),
+            selection_after: selection.clone(),
+            timestamp: SystemTime::now(),
+        });
+        pending.transactions.push(transaction);
+        pending.inversions.push(inversion);
+    }
+
+    /// Turns the changes recorded since the last commit into a revision made at `timestamp` and
+    /// left with `selection`, so that they are undone in a single step.
+    pub fn commit(&mut self, selection: &Selection, timestamp: SystemTime) {
+        let Some(mut revision) = self.pending.take() else {
+            return;
+        };
+
+        revision.selection_after = selection.clone();
+        revision.timestamp = timestamp;
+        let index = self.revisions.len();
+        self.revisions[self.current].last_child = Some(index);
+        self.revisions.push(revision);
+        self.current = index;
+    }
+
+    /// Moves to the parent of the current revision.
+    ///
+    /// This and the other methods that move between revisions expect the changes recorded since
+    /// the last commit to be committed first.
+    pub fn undo(&mut self) -> Option<Jump> {
+        debug_assert!(self.pending.is_none(), "uncommitted changes");
+        match self.current {
+            0 => None,
+            current => Some(self.jump_to(self.revisions[current].parent)),
+        }
+    }
+
+    /// Moves to the child of the current revision that was made or visited last.
+    pub fn redo(&mut self) -> Option<Jump> {
+        debug_assert!(self.pending.is_none(), "uncommitted changes");
+        let child = self.revisions[self.current].last_child?;
+        Some(self.jump_to(child))
+    }
+
+    /// Moves back in time by `kind`, whatever branch that leads to.
+    pub fn earlier(&mut self, kind: UndoKind) -> Option<Jump> {
+        debug_assert!(self.pending.is_none(), "uncommitted changes");
+        let target = match kind {
+            UndoKind::Steps(steps) => self.current.saturating_sub(steps),
+            UndoKind::TimePeriod(period) => {
+                let timestamp = self.revisions[self.current].timestamp;
+                let time = timestamp
+                    .checked_sub(period)
+                    .unwrap_or(SystemTime::UNIX_EPOCH);
+                (0..self.current)
+                    .rev()
+                    .find(|&index| self.revisions[index].timestamp <= time)
+                    .unwrap_or(0)
+            }
+        };
+        (target != self.current).then(|| self.jump_to(target))
+    }
+
+    /// Moves forward in time by `kind`, whatever branch that leads to.
+    pub fn later(&mut self, kind: UndoKind) -> Option<Jump> {
+        debug_assert!(self.pending.is_none(), "uncommitted changes");
+        let last = self.revisions.len() - 1;
+        let target = match kind {
+            UndoKind::Steps(steps) => self.current.saturating_add(steps).min(last),
+            UndoKind::TimePeriod(period) => {
+                let timestamp = self.revisions[self.current].timestamp;
+                let time = timestamp.checked_add(period);
+                (self.current + 1..=last)
+                    .find(|&index| {
+                        time.map_or(false, |time| self.revisions[index].timestamp >= time)
+                    })
+                    .unwrap_or(last)
+            }
+        };
+        (target != self.current).then(|| self.jump_to(target))
+    }
+
+    // Moves to the revision at `target` by undoing changes up to the common ancestor, then
+    // redoing changes down to `target`.
+    fn jump_to(&mut self, target: usize) -> Jump {
+        let ancestor = self.common_ancestor(self.current, target);
+        let up = self.path_up(self.current, ancestor);
+        let mut down = self.path_up(target, ancestor);
+        down.reverse();
+
+        let mut transactions = Vec::new();
+        for &index in &up {
+            transactions.extend(self.revisions[index].inversions.iter().rev().cloned());
+        }
+        for &index in &down {
+            transactions.extend(self.revisions[index].transactions.iter().cloned());
+            let parent = self.revisions[index].parent;
+            self.revisions[parent].last_child = Some(index);
+        }
+
+        let selection = match (up.last(), down.last()) {
+            (_, Some(&index)) => self.revisions[index].selection_after.clone(),
+            (Some(&index), None) => self.revisions[index].selection_before.clone(),
+            (None, None) => self.revisions[target].selection_after.clone(),
+        };
+        self.current = target;
+
+        Jump {
+            transactions,
+            selection,
+        }
+    }
+
+    // Parents always come before their children, so the larger index is never the ancestor.
+    fn common_ancestor(&self, mut a: usize, mut b: usize) -> usize {
+        while a != b {
+            if a > b {
+                a = self.revisions[a].parent;
+            } else {
+                b = self.revisions[b].parent;
+            }
+        }
+        a
+    }
+
+    // Returns the revisions from `from` up to `ancestor`, excluding `ancestor`.
+    fn path_up(&self, mut from: usize, ancestor: usize) -> Vec<usize> {
+        let mut path = Vec::new();
+        while from != ancestor {
+            path.push(from);
+            from = self.revisions[from].parent;
+        }
+        path
+    }
+
+    /// Returns the file in `dir` that the history of the file at `path` is saved to.
+    pub fn file_for(dir: &Path, path: &Path) -> Result<PathBuf> {
+        let path = path.canonicalize()?;
+        let hash = fnv1a([path.as_os_str().to_string_lossy().as_bytes()]);
+        Ok(dir.join(format!("{hash:016x}.toml")))
+    }
+
+    /// Saves the history to `file`, along with the canonical `path` of the file being edited and
+    /// a hash of its `text`, which must be the text at the current revision.
+    ///
+    /// Changes that were not committed yet are not saved.
+    pub fn save(&self, file: &Path, path: &Path, text: &Rope) -> Result<()> {
+        let saved = SavedHistory {
+            path: path.canonicalize()?,
+            hash: format!("{:016x}", fnv1a(text.chunks().map(str::as_bytes))),
+            current: self.current,
+            revisions: self.revisions.clone(),
+        };
+
+        let s = toml::to_string(&saved)?;
+        if let Some(dir) = file.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(file, s)?;
+        Ok(())
+    }
+
+    /// Restores the history saved by [`History::save`] for the file at `path` holding `text`.
+    ///
+    /// Returns `None` if there is no saved history, or if it is stale because the file changed
+    /// since it was saved.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or does not hold a valid history.
+    pub fn load(file: &Path, path: &Path, text: &Rope) -> Result<Option<Self>> {
+        let s = match std::fs::read_to_string(file) {
+            Ok(s) => s,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        let saved: SavedHistory = toml::from_str(&s).map_err(|err| anyhow!(err))?;
+
+        let hash = format!("{:016x}", fnv1a(text.chunks().map(str::as_bytes)));
+        if saved.path != path.canonicalize()? || saved.hash != hash {
+            return Ok(None);
+        }
+
+        let history = Self {
+            revisions: saved.revisions,
+            current: saved.current,
+            pending: None,
+        };
+        history.validate(text)?;
+        Ok(Some(history))
+    }
+
+    // Checks that every revision can be reached from `text` at the current revision without
+    // going out of bounds, so that a corrupted history fails here instead of in the buffer.
+    fn validate(&self, text: &Rope) -> Result<()> {
+        let revisions = &self.revisions;
+        if self.current >= revisions.len() {
+            bail!("current revision {} does not exist", self.current);
+        }
+        for (index, revision) in revisions.iter().enumerate() {
+            let parent_ok = match index {
+                0 => revision.transactions.is_empty() && revision.inversions.is_empty(),
+                _ => {
+                    revision.parent < index
+                        && revision.transactions.len() == revision.inversions.len()
+                }
+            };
+            let child_ok = revision.last_child.map_or(true, |child| {
+                child < revisions.len() && child > index && revisions[child].parent == index
+            });
+            if !parent_ok || !child_ok {
+                bail!("revision {index} is not linked correctly");
+            }
+        }
+
+        // Walk up to the original text, then rebuild every revision from it.
+        let mut root = text.clone();
+        for index in self.path_up(self.current, 0) {
+            for inversion in revisions[index].inversions.iter().rev() {
+                apply_checked(&mut root, inversion)
+                    .map_err(|err| anyhow!("revision {index}: {err}"))?;
+            }
+        }
+
+        let mut texts = vec![root];
+        for (index, revision) in revisions.iter().enumerate().skip(1) {
+            let parent = &texts[revision.parent];
+            let mut text = parent.clone();
+            for transaction in &revision.transactions {
+                apply_checked(&mut text, transaction)
+                    .map_err(|err| anyhow!("revision {index}: {err}"))?;
+            }
+            let in_bounds = |selection: &Selection, text: &Rope| {
+                selection
+                    .ranges()
+                    .iter()
+                    .all(|range| range.to() <= text.len_bytes())
+            };
+            if !in_bounds(&revision.selection_before, parent)
+                || !in_bounds(&revision.selection_after, &text)
+            {
+                bail!("revision {index}: selection out of bounds");
+            }
+
+            let mut undone = text.clone();
+            for inversion in revision.inversions.iter().rev() {
+                apply_checked(&mut undone, inversion)
+                    .map_err(|err| anyhow!("revision {index}: {err}"))?;
+            }
+            if undone != *parent {
+                bail!("revision {index}: inversions do not restore its parent");
+            }
+            texts.push(text);
+        }
+
+        Ok(())
+    }
+}
+
+// Applies `transaction` to `text`, or fails if its changes are out of order or out of bounds.
+fn apply_checked(text: &mut Rope, transaction: &Transaction) -> Result<()> {
+    let changes = transaction.changes();
+    let ordered = changes.iter().all(|change| change.from <= change.to)
+        && changes.windows(2).all(|w| w[0].to <= w[1].from);
+    let is_boundary = |byte_idx: usize| {
+        byte_idx <= text.len_bytes() && text.char_to_byte(text.byte_to_char(byte_idx)) == byte_idx
+    };
+    if !ordered
+        || !changes
+            .iter()
+            .all(|change| is_boundary(change.from) && is_boundary(change.to))
+    {
+        bail!("changes out of bounds");
+    }
+
+    for change in changes.iter().rev() {
+        let from = text.byte_to_char(change.from);
+        text.remove(from..text.byte_to_char(change.to));
+        text.insert(from, &change.text);
+    }
+    Ok(())
+}
+
+// A 64-bit FNV-1a hash, which unlike the hasher of the standard library is stable across
+// releases.
+pub(crate) fn fnv1a<'a>(chunks: impl IntoIterator<Item = &'a [u8]>) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325_u64;
+    for chunk in chunks {
+        for &byte in chunk {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Buffer;
+
+    fn commit(history: &mut History, buffer: &mut Buffer, transaction: Transaction, secs: u64) {
+        history.record(
+            transaction.clone(),
+            transaction.invert(buffer),
+            buffer.selection(),
+        );
+        buffer.apply(&transaction);
+        history.commit(
+            buffer.selection(),
+            SystemTime::UNIX_EPOCH + Duration::from_secs(secs),
+        );
+    }
+
+    fn jump(buffer: &mut Buffer, jump: Option<Jump>) -> String {
+        for transaction in jump.expect("nowhere to go").transactions {
+            buffer.apply(&transaction);
+        }
+        buffer.text().to_string()
+    }
+
+    #[test]
+    fn undo_branches() {
+        let mut history = History::default();
+        let mut buffer = Buffer::from("a");
+        commit(&mut history, &mut buffer, Transaction::insert(1, "b"), 10);
+        commit(&mut history, &mut buffer, Transaction::insert(2, "c"), 20);
+
+        assert_eq!(jump(&mut buffer, history.undo()), "ab");
+        commit(&mut history, &mut buffer, Transaction::insert(2, "d"), 30);
+        assert_eq!(buffer.text(), "abd");
+
+        // Undo and redo follow the newest branch, while earlier and later visit every revision
+        // in the order it was made.
+        assert_eq!(jump(&mut buffer, history.undo()), "ab");
+        assert_eq!(jump(&mut buffer, history.redo()), "abd");
+        assert_eq!(
+            jump(&mut buffer, history.earlier(UndoKind::Steps(1))),
+            "abc"
+        );
+        assert_eq!(jump(&mut buffer, history.undo()), "ab");
+        assert_eq!(jump(&mut buffer, history.redo()), "abc");
+        assert_eq!(jump(&mut buffer, history.later(UndoKind::Steps(5))), "abd");
+        assert!(history.redo().is_none());
+
+        let minute = Duration::from_secs(60);
+        assert_eq!(
+            jump(&mut buffer, history.earlier(UndoKind::TimePeriod(minute))),
+            "a"
+        );
+        assert!(history.undo().is_none());
+        assert_eq!(jump(&mut buffer, history.later(UndoKind::Steps(1))), "ab");
+        assert_eq!(
+            jump(
+                &mut buffer,
+                history.later(UndoKind::TimePeriod(Duration::from_secs(5)))
+            ),
+            "abc"
+        );
+    }
+
+    #[test]
+    fn committed_changes_are_undone_together() {
+        let mut history = History::default();
+        let mut buffer = Buffer::from("");
+        for (pos, text) in ["a", "b", "c"].into_iter().enumerate() {
+            let transaction = Transaction::insert(pos, text);
+            history.record(
+                transaction.clone(),
+                transaction.invert(&buffer),
+                buffer.selection(),
+            );
+            buffer.apply(&transaction);
+        }
+        assert!(history.has_pending());
+        history.commit(&Selection::point(3), SystemTime::now());
+
+        let undo = history.undo().unwrap();
+        assert_eq!(undo.selection, Selection::point(0));
+        assert_eq!(jump(&mut buffer, Some(undo)), "");
+        let redo = history.redo().unwrap();
+        assert_eq!(redo.selection, Selection::point(3));
+        assert_eq!(jump(&mut buffer, Some(redo)), "abc");
+    }
+
+    #[test]
+    fn parses_undo_kinds() {
+        assert_eq!("".parse::<UndoKind>().unwrap(), UndoKind::Steps(1));
+        assert_eq!("4".parse::<UndoKind>().unwrap(), UndoKind::Steps(4));
+        assert_eq!(
+            "5m".parse::<UndoKind>().unwrap(),
+            UndoKind::TimePeriod(Duration::from_secs(300))
+        );
+        assert_eq!(
+            "2x".parse::<UndoKind>().unwrap_err().to_string(),
+            "invalid undo amount '2x', expected a number of steps or a time such as 30s, 5m, 2h \
+             or 1d"
+        );
+    }
+
+    #[test]
+    fn history_persists_until_the_file_changes() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let path = dir.join("file.txt");
+        std::fs::write(&path, "one\n").unwrap();
+        let file = History::file_for(&dir.join("undo"), &path).unwrap();
+
+        let mut history = History::default();
+        let mut buffer = Buffer::from("one\n");
+        commit(
+            &mut history,
+            &mut buffer,
+            Transaction::insert(4, "two\n"),
+            10,
+        );
+        commit(
+            &mut history,
+            &mut buffer,
+            Transaction::delete(std::iter::once(0..4)),
+            20,
+        );
+        jump(&mut buffer, history.undo());
+        history.save(&file, &path, buffer.text()).unwrap();
+
+        let mut restored = History::load(&file, &path, buffer.text()).unwrap().unwrap();
+        assert_eq!(restored.current(), 1);
+        assert_eq!(jump(&mut buffer, restored.undo()), "one\n");
+        assert_eq!(
+            jump(&mut buffer, restored.later(UndoKind::Steps(2))),
+            "two\n"
+        );
+
+        // A history saved for other text is stale, and one that does not fit is rejected.
+        assert!(History::load(&file, &path, &Rope::from("one\n"))
+            .unwrap()
+            .is_none());
+        let s = std::fs::read_to_string(&file).unwrap();
+        std::fs::write(&file, s.replace("to = 8", "to = 80")).unwrap();
+        let err = History::load(&file, &path, &Rope::from("one\ntwo\n")).unwrap_err();
+        assert_eq!(err.to_string(), "revision 1: changes out of bounds");
+
+        // So is one whose inversions do not undo its changes.
+        let tampered = s.replace("\none\n", "\nONE\n");
+        assert_ne!(tampered, s);
+        std::fs::write(&file, tampered).unwrap();
+        let err = History::load(&file, &path, &Rope::from("one\ntwo\n")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "revision 2: inversions do not restore its parent"
+        );
+    }
+}
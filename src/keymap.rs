@@ -0,0 +1,339 @@
+mod key;
+
+pub use self::key::Key;
+
+use crate::{Command, Mode};
+use serde::{
+    de::{self, MapAccess, Visitor},
+    Deserialize, Deserializer,
+};
+use std::{collections::HashMap, fmt, time::Duration};
+use tokio::time::Instant;
+
+/// Key bindings for every mode.
+pub type KeyBindings = HashMap<KeymapMode, KeyBinding>;
+
+/// A set of key bindings: those of an editing [`Mode`], or those of Normal mode while an
+/// operator waits for its motion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeymapMode {
+    /// Bindings of Normal mode.
+    Normal,
+    /// Bindings of Insert mode.
+    Insert,
+    /// Bindings of Visual mode.
+    Visual,
+    /// Bindings of Command mode.
+    Command,
+    /// Bindings after an operator, like `i` in `di(`. Keys not bound here are looked up in
+    /// Normal mode.
+    Operator,
+}
+
+impl From<Mode> for KeymapMode {
+    fn from(mode: Mode) -> Self {
+        match mode {
+            Mode::Normal => Self::Normal,
+            Mode::Insert => Self::Insert,
+            Mode::Visual => Self::Visual,
+            Mode::Command => Self::Command,
+        }
+    }
+}
+
+/// How long to wait for the next key of a pending sequence.
+pub const TIMEOUT: Duration = Duration::from_millis(1000);
+
+/// A node of a keymap trie.
+///
+/// Each mode has a [`KeyBinding::Sequence`] at its root. Keys lead either to a command or to
+/// another sequence that waits for more keys.
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeyBinding {
+    /// Runs a command.
+    Command(&'static Command),
+    /// Waits for one of the following keys.
+    Sequence(HashMap<Key, KeyBinding>),
+}
+
+impl Default for KeyBinding {
+    fn default() -> Self {
+        Self::Sequence(HashMap::new())
+    }
+}
+
+impl KeyBinding {
+    /// Follows `keys` down the trie.
+    pub fn search(&self, keys: &[Key]) -> Option<&KeyBinding> {
+        keys.iter().try_fold(self, |node, key| match node {
+            Self::Sequence(map) => map.get(key),
+            Self::Command(_) => None,
+        })
+    }
+
+    /// Merges `other` on top of this binding. Keys bound in `other` win.
+    pub fn merge(&mut self, other: KeyBinding) {
+        match (self, other) {
+            (Self::Sequence(map), Self::Sequence(other)) => {
+                for (key, binding) in other {
+                    match map.get_mut(&key) {
+                        Some(existing) => existing.merge(binding),
+                        None => {
+                            map.insert(key, binding);
+                        }
+                    }
+                }
+            }
+            (this, other) => *this = other,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for KeyBinding {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(KeyBindingVisitor)
+    }
+}
+
+struct KeyBindingVisitor;
+
+impl<'de> Visitor<'de> for KeyBindingVisitor {
+    type Value = KeyBinding;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a command name or a table of key bindings")
+    }
+
+    fn visit_str<E: de::Error>(self, name: &str) -> Result<Self::Value, E> {
+        Command::find(name)
+            .map(KeyBinding::Command)
+            .ok_or_else(|| E::custom(format!("unknown command '{name}'")))
+    }
+
+    fn visit_map<M: MapAccess<'de>>(self, mut access: M) -> Result<Self::Value, M::Error> {
+        let mut map = HashMap::new();
+
+        while let Some(key) = access.next_key::<String>()? {
+            let key = key.parse::<Key>().map_err(de::Error::custom)?;
+            map.insert(key, access.next_value()?);
+        }
+
+        Ok(KeyBinding::Sequence(map))
+    }
+}
+
+/// Returns the built-in key bindings.
+pub fn default() -> KeyBindings {
+    toml::from_str(include_str!("./keymap/default.toml")).expect("unable to parse built-in keymap")
+}
+
+/// Deserializes user key bindings and merges them on top of the built-in ones.
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<KeyBindings, D::Error> {
+    let mut keys = default();
+
+    for (mode, binding) in KeyBindings::deserialize(deserializer)? {
+        keys.entry(mode).or_default().merge(binding);
+    }
+
+    Ok(keys)
+}
+
+/// Outcome of feeding a key to [`Keymaps::get`].
+#[derive(Debug, PartialEq)]
+pub enum KeymapResult {
+    /// The keys pressed so far are bound to a command.
+    Matched(&'static Command),
+    /// The keys pressed so far are a prefix of a longer sequence.
+    Pending,
+    /// The key is not bound to anything.
+    NotFound,
+    /// The key broke off a pending sequence; all its keys are discarded.
+    Cancelled(Vec<Key>),
+}
+
+/// Tracks key sequences that are in progress.
+#[derive(Debug, Default)]
+pub struct Keymaps {
+    pending: Vec<Key>,
+    deadline: Option<Instant>,
+}
+
+impl Keymaps {
+    /// Feeds `key` to the bindings of `mode`.
+    pub fn get(&mut self, keys: &KeyBindings, mode: KeymapMode, key: Key) -> KeymapResult {
+        self.pending.push(key);
+
+        let search = |mode| keys.get(&mode).and_then(|root| root.search(&self.pending));
+        let binding = match mode {
+            KeymapMode::Operator => search(mode).or_else(|| search(KeymapMode::Normal)),
+            _ => search(mode),
+        };
+        match binding {
+            Some(KeyBinding::Command(command)) => {
+                self.reset();
+                KeymapResult::Matched(command)
+            }
+            Some(KeyBinding::Sequence(_)) => {
+                self.deadline = Some(Instant::now() + TIMEOUT);
+                KeymapResult::Pending
+            }
+            None if self.pending.len() == 1 => {
+                self.reset();
+                KeymapResult::NotFound
+            }
+            None => {
+                let keys = std::mem::take(&mut self.pending);
+                self.reset();
+                KeymapResult::Cancelled(keys)
+            }
+        }
+    }
+
+    /// Returns the keys of the sequence in progress.
+    pub fn pending(&self) -> &[Key] {
+        &self.pending
+    }
+
+    /// Returns when the sequence in progress times out.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// Discards the sequence in progress if it timed out.
+    pub fn expire(&mut self, now: Instant) {
+        if matches!(self.deadline, Some(deadline) if deadline <= now) {
+            self.reset();
+        }
+    }
+
+    /// Discards the sequence in progress.
+    pub fn reset(&mut self) {
+        self.pending.clear();
+        self.deadline = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    fn key(s: &str) -> Key {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parse_keys() {
+        assert_eq!(key("g"), Key::new(KeyCode::Char('g')));
+        assert_eq!(key("-"), Key::new(KeyCode::Char('-')));
+        assert_eq!(key("ret"), Key::new(KeyCode::Enter));
+        assert_eq!(key("F5"), Key::new(KeyCode::F(5)));
+        assert_eq!(
+            key("C-A-x"),
+            Key {
+                code: KeyCode::Char('x'),
+                modifiers: KeyModifiers::CONTROL | KeyModifiers::ALT,
+            }
+        );
+        assert_eq!(key("C-A-x").to_string(), "C-A-x");
+        // Shift is part of the character, but not of other keys.
+        let event = |code| Key::from(KeyEvent::new(code, KeyModifiers::SHIFT));
+        assert_eq!(key("S-a"), key("A"));
+        assert_eq!(event(KeyCode::Char('A')), key("S-a"));
+        assert_eq!(event(KeyCode::Char('a')), key("A"));
+        assert_eq!(event(KeyCode::Char('!')), key("!"));
+        assert_eq!(event(KeyCode::Left), key("S-left"));
+        assert_ne!(key("S-left"), key("left"));
+        assert_eq!(key("S-left").to_string(), "S-left");
+        assert!("C-".parse::<Key>().is_err());
+        assert!("X-a".parse::<Key>().is_err());
+        assert!("foo".parse::<Key>().is_err());
+    }
+
+    #[test]
+    fn user_bindings_override_defaults() {
+        #[derive(Deserialize)]
+        struct Config {
+            #[serde(deserialize_with = "deserialize")]
+            keys: KeyBindings,
+        }
+
+        let config: Config = toml::from_str(
+            r#"
+            [keys.normal]
+            "C-q" = "force_quit"
+            g = { x = "write" }
+            "#,
+        )
+        .unwrap();
+        let normal = &config.keys[&KeymapMode::Normal];
+
+        let lookup =
+            |keys: &[&str]| match normal.search(&keys.iter().map(|k| key(k)).collect::<Vec<_>>()) {
+                Some(KeyBinding::Command(command)) => Some(command.name),
+                _ => None,
+            };
+
+        assert_eq!(lookup(&["C-q"]), Some("force_quit"));
+        assert_eq!(lookup(&["g", "x"]), Some("write"));
+        assert_eq!(lookup(&["g", "n"]), Some("buffer_next"));
+        assert_eq!(lookup(&["i"]), Some("insert_mode"));
+        assert!(config.keys.contains_key(&KeymapMode::Insert));
+    }
+
+    #[test]
+    fn unknown_commands_are_rejected() {
+        let err = toml::from_str::<KeyBindings>("[normal]\nx = \"explode\"").unwrap_err();
+        assert!(
+            err.to_string().contains("unknown command 'explode'"),
+            "{err}"
+        );
+
+        let err = toml::from_str::<KeyBindings>("[normal]\n\"C-\" = \"quit\"").unwrap_err();
+        assert!(err.to_string().contains("invalid key"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn pending_sequences_resolve_cancel_and_time_out() {
+        let keys = default();
+        let mut keymaps = Keymaps::default();
+
+        assert_eq!(
+            keymaps.get(&keys, KeymapMode::Normal, key("g")),
+            KeymapResult::Pending
+        );
+        assert_eq!(
+            keymaps.get(&keys, KeymapMode::Normal, key("n")),
+            KeymapResult::Matched(Command::find("buffer_next").unwrap())
+        );
+
+        keymaps.get(&keys, KeymapMode::Normal, key("Z"));
+        assert_eq!(
+            keymaps.get(&keys, KeymapMode::Normal, key("y")),
+            KeymapResult::Cancelled(vec![key("Z"), key("y")])
+        );
+        assert_eq!(
+            keymaps.get(&keys, KeymapMode::Insert, key("y")),
+            KeymapResult::NotFound
+        );
+
+        // A pending operator looks up its own bindings first, then those of Normal mode.
+        assert_eq!(
+            keymaps.get(&keys, KeymapMode::Operator, key("i")),
+            KeymapResult::Matched(Command::find("select_textobject_inner").unwrap())
+        );
+        assert_eq!(
+            keymaps.get(&keys, KeymapMode::Operator, key("w")),
+            KeymapResult::Matched(Command::find("move_next_word_start").unwrap())
+        );
+
+        keymaps.get(&keys, KeymapMode::Normal, key("g"));
+        let deadline = keymaps.deadline().unwrap();
+        keymaps.expire(deadline - Duration::from_millis(1));
+        assert_eq!(keymaps.pending(), &[key("g")]);
+        keymaps.expire(deadline);
+        assert!(keymaps.pending().is_empty());
+        assert_eq!(keymaps.deadline(), None);
+    }
+}
@@ -0,0 +1,137 @@
+use anyhow::{anyhow, bail, Error, Result};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::{fmt, str::FromStr};
+
+/// A key press, as written in the configuration file (e.g. `C-s`, `A-ret`, `g`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Key {
+    /// The key itself.
+    pub code: KeyCode,
+    /// Modifiers held down with the key.
+    pub modifiers: KeyModifiers,
+}
+
+impl Key {
+    /// Creates a key without modifiers.
+    pub const fn new(code: KeyCode) -> Self {
+        Self {
+            code,
+            modifiers: KeyModifiers::NONE,
+        }
+    }
+
+    // Drops shift from a character key, which the character itself encodes: `S-a` is `A`, and a
+    // terminal reports `!` typed with shift as `!`. Other keys keep it, as in `S-left`.
+    fn normalize(mut self) -> Self {
+        if let KeyCode::Char(c) = self.code {
+            if self.modifiers.contains(KeyModifiers::SHIFT) {
+                let mut upper = c.to_uppercase();
+                if let (Some(upper), None) = (upper.next(), upper.next()) {
+                    self.code = KeyCode::Char(upper);
+                }
+                self.modifiers.remove(KeyModifiers::SHIFT);
+            }
+        }
+        self
+    }
+
+    /// Returns the character typed by this key, if it is a plain character.
+    pub fn char(&self) -> Option<char> {
+        match (self.code, self.modifiers) {
+            (KeyCode::Char(c), KeyModifiers::NONE) => Some(c),
+            _ => None,
+        }
+    }
+}
+
+impl From<KeyEvent> for Key {
+    fn from(event: KeyEvent) -> Self {
+        Self {
+            code: event.code,
+            modifiers: event.modifiers,
+        }
+        .normalize()
+    }
+}
+
+const NAMED_KEYS: &[(&str, KeyCode)] = &[
+    ("backspace", KeyCode::Backspace),
+    ("ret", KeyCode::Enter),
+    ("space", KeyCode::Char(' ')),
+    ("minus", KeyCode::Char('-')),
+    ("left", KeyCode::Left),
+    ("right", KeyCode::Right),
+    ("up", KeyCode::Up),
+    ("down", KeyCode::Down),
+    ("home", KeyCode::Home),
+    ("end", KeyCode::End),
+    ("pageup", KeyCode::PageUp),
+    ("pagedown", KeyCode::PageDown),
+    ("tab", KeyCode::Tab),
+    ("del", KeyCode::Delete),
+    ("ins", KeyCode::Insert),
+    ("esc", KeyCode::Esc),
+];
+
+impl FromStr for Key {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = s;
+
+        // A lone `-` is the minus key, not a separator.
+        while rest.len() > 2 && rest.as_bytes()[1] == b'-' {
+            let modifier = match rest.as_bytes()[0] {
+                b'C' => KeyModifiers::CONTROL,
+                b'A' => KeyModifiers::ALT,
+                b'S' => KeyModifiers::SHIFT,
+                _ => bail!("invalid key modifier in '{s}'"),
+            };
+            if modifiers.contains(modifier) {
+                bail!("repeated key modifier in '{s}'");
+            }
+            modifiers.insert(modifier);
+            rest = &rest[2..];
+        }
+
+        let code = match NAMED_KEYS.iter().find(|(name, _)| *name == rest) {
+            Some(&(_, code)) => code,
+            None => match rest.strip_prefix('F').and_then(|n| n.parse::<u8>().ok()) {
+                Some(n @ 1..=12) => KeyCode::F(n),
+                _ => {
+                    let mut chars = rest.chars();
+                    match (chars.next(), chars.next()) {
+                        (Some(c), None) => KeyCode::Char(c),
+                        _ => return Err(anyhow!("invalid key '{s}'")),
+                    }
+                }
+            },
+        };
+
+        Ok(Self { code, modifiers }.normalize())
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (modifier, prefix) in [
+            (KeyModifiers::CONTROL, "C-"),
+            (KeyModifiers::ALT, "A-"),
+            (KeyModifiers::SHIFT, "S-"),
+        ] {
+            if self.modifiers.contains(modifier) {
+                f.write_str(prefix)?;
+            }
+        }
+
+        match NAMED_KEYS.iter().find(|(_, code)| *code == self.code) {
+            Some((name, _)) => f.write_str(name),
+            None => match self.code {
+                KeyCode::F(n) => write!(f, "F{n}"),
+                KeyCode::Char(c) => write!(f, "{c}"),
+                code => write!(f, "{code:?}"),
+            },
+        }
+    }
+}
@@ -0,0 +1,248 @@
+//! Connects buffers to the language servers named by [`LanguageConfig::language_server`].
+
+use crate::{metadata, LanguageConfig, Message, Transaction};
+use anyhow::{anyhow, Result};
+use ropey::Rope;
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tissue_lsp::{lsp, Client, OffsetEncoding};
+use tokio::sync::mpsc::UnboundedSender;
+
+/// Returns the workspace root for the file at `path`, found by [`metadata::workspace_root`] with
+/// the language's root markers, or the current directory.
+pub fn workspace_root(language: &LanguageConfig, path: &Path) -> PathBuf {
+    let path = absolute(path);
+    let dir = path.parent().unwrap_or(&path);
+    metadata::workspace_root(dir, &language.roots)
+        .or_else(|| std::env::current_dir().ok())
+        .unwrap_or_else(|| dir.to_path_buf())
+}
+
+/// Returns the URI of the file at `path`.
+pub fn uri(path: &Path) -> Option<lsp::Url> {
+    lsp::Url::from_file_path(absolute(path)).ok()
+}
+
+/// Returns `path` relative to the current directory, if it is not absolute already.
+pub fn absolute(path: &Path) -> PathBuf {
+    match std::env::current_dir() {
+        Ok(dir) if path.is_relative() => dir.join(path),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Starts and initializes the language server of `language` for the file at `path`.
+///
+/// Notifications from the server are forwarded to `sender`.
+pub async fn start(
+    language: &LanguageConfig,
+    path: &Path,
+    sender: UnboundedSender<Message>,
+) -> Result<Arc<Client>> {
+    let command = language
+        .language_server
+        .as_deref()
+        .ok_or_else(|| anyhow!("no language server configured for {}", language.name))?;
+    let root = workspace_root(language, path);
+
+    let (client, mut notifications) = Client::start(command, &[], &root)?;
+    client.initialize().await?;
+
+    tokio::spawn(async move {
+        while let Some(notification) = notifications.recv().await {
+            if sender.send(Message::LanguageServer(notification)).is_err() {
+                break;
+            }
+        }
+    });
+
+    Ok(Arc::new(client))
+}
+
+/// Describes `transaction`, applied to `old_text`, as incremental content changes.
+///
+/// The protocol applies changes one after the other, so they are listed from the end of the text
+/// to the start. That way each range is still valid in the text `old_text` was.
+pub fn content_changes(
+    old_text: &Rope,
+    transaction: &Transaction,
+    encoding: OffsetEncoding,
+) -> Vec<lsp::TextDocumentContentChangeEvent> {
+    transaction
+        .changes()
+        .iter()
+        .rev()
+        .map(|change| lsp::TextDocumentContentChangeEvent {
+            range: Some(lsp::Range::new(
+                tissue_lsp::pos_to_lsp_pos(old_text, change.from, encoding),
+                tissue_lsp::pos_to_lsp_pos(old_text, change.to, encoding),
+            )),
+            range_length: None,
+            text: change.text.clone(),
+        })
+        .collect()
+}
+
+/// A symbol defined in a document, such as a function or a type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    /// The name of the symbol.
+    pub name: String,
+    /// What the symbol is.
+    pub kind: lsp::SymbolKind,
+    /// The symbol it is defined in, if any.
+    pub container: Option<String>,
+    /// The document the symbol is in.
+    pub uri: lsp::Url,
+    /// The line the symbol starts on, counting from 0.
+    pub line: usize,
+}
+
+impl Symbol {
+    /// Returns the name of the kind of symbol, such as `function`.
+    pub fn kind_name(&self) -> String {
+        format!("{:?}", self.kind).to_lowercase().replace('_', " ")
+    }
+}
+
+/// Lists the symbols of a `textDocument/documentSymbol` response for the document at `uri`,
+/// nested symbols after the symbol containing them.
+pub fn document_symbols(uri: &lsp::Url, response: lsp::DocumentSymbolResponse) -> Vec<Symbol> {
+    fn flatten(
+        uri: &lsp::Url,
+        symbols: Vec<lsp::DocumentSymbol>,
+        container: Option<&str>,
+        flat: &mut Vec<Symbol>,
+    ) {
+        for symbol in symbols {
+            flat.push(Symbol {
+                name: symbol.name.clone(),
+                kind: symbol.kind,
+                container: container.map(str::to_string),
+                uri: uri.clone(),
+                line: symbol.selection_range.start.line as usize,
+            });
+            let children = symbol.children.unwrap_or_default();
+            flatten(uri, children, Some(&symbol.name), flat);
+        }
+    }
+
+    match response {
+        lsp::DocumentSymbolResponse::Flat(symbols) => {
+            symbols.into_iter().map(Symbol::from).collect()
+        }
+        lsp::DocumentSymbolResponse::Nested(symbols) => {
+            let mut flat = Vec::new();
+            flatten(uri, symbols, None, &mut flat);
+            flat
+        }
+    }
+}
+
+/// Lists the symbols of a `workspace/symbol` response.
+pub fn workspace_symbols(response: lsp::WorkspaceSymbolResponse) -> Vec<Symbol> {
+    match response {
+        lsp::WorkspaceSymbolResponse::Flat(symbols) => {
+            symbols.into_iter().map(Symbol::from).collect()
+        }
+        lsp::WorkspaceSymbolResponse::Nested(symbols) => symbols
+            .into_iter()
+            .map(|symbol| {
+                let (uri, line) = match symbol.location {
+                    lsp::OneOf::Left(location) => (location.uri, location.range.start.line),
+                    lsp::OneOf::Right(location) => (location.uri, 0),
+                };
+                Symbol {
+                    name: symbol.name,
+                    kind: symbol.kind,
+                    container: symbol.container_name,
+                    uri,
+                    line: line as usize,
+                }
+            })
+            .collect(),
+    }
+}
+
+impl From<lsp::SymbolInformation> for Symbol {
+    fn from(symbol: lsp::SymbolInformation) -> Self {
+        Self {
+            name: symbol.name,
+            kind: symbol.kind,
+            container: symbol.container_name,
+            uri: symbol.location.uri,
+            line: symbol.location.range.start.line as usize,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changes_apply_back_to_front() {
+        let text = Rope::from("ab\ncd\n");
+        let transaction = Transaction::change([(1, 1, "x"), (3, 5, "é\n")]);
+
+        let changes = content_changes(&text, &transaction, OffsetEncoding::Utf16);
+
+        let ranges: Vec<_> = changes.iter().map(|change| change.range.unwrap()).collect();
+        assert_eq!(
+            ranges,
+            [
+                lsp::Range::new(lsp::Position::new(1, 0), lsp::Position::new(1, 2)),
+                lsp::Range::new(lsp::Position::new(0, 1), lsp::Position::new(0, 1)),
+            ]
+        );
+        assert_eq!(changes[0].text, "é\n");
+        assert_eq!(changes[1].text, "x");
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn flattens_nested_document_symbols() {
+        let uri = lsp::Url::parse("file:///main.rs").unwrap();
+        let range =
+            |line| lsp::Range::new(lsp::Position::new(line, 0), lsp::Position::new(line, 1));
+        let symbol = |name: &str, kind, line, children| lsp::DocumentSymbol {
+            name: name.into(),
+            detail: None,
+            kind,
+            tags: None,
+            deprecated: None,
+            range: range(line),
+            selection_range: range(line),
+            children,
+        };
+        let method = symbol("len", lsp::SymbolKind::METHOD, 3, None);
+        let response = lsp::DocumentSymbolResponse::Nested(vec![
+            symbol("Buffer", lsp::SymbolKind::STRUCT, 1, Some(vec![method])),
+            symbol("main", lsp::SymbolKind::FUNCTION, 7, None),
+        ]);
+
+        let symbols = document_symbols(&uri, response);
+
+        let symbols: Vec<_> = symbols
+            .iter()
+            .map(|s| {
+                (
+                    s.name.as_str(),
+                    s.container.as_deref(),
+                    s.line,
+                    s.kind_name(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            symbols,
+            [
+                ("Buffer", None, 1, "struct".to_string()),
+                ("len", Some("Buffer"), 3, "method".to_string()),
+                ("main", None, 7, "function".to_string()),
+            ]
+        );
+    }
+}
@@ -8,25 +8,105 @@
 
 mod app;
 mod args;
+mod auto_pairs;
+mod buffer;
+mod clipboard;
+mod commands;
 mod config;
+mod debugger;
+mod diff;
+mod grep;
 mod health;
+mod history;
+mod keymap;
+mod language_server;
 pub mod metadata;
+mod motion;
+mod prompt;
+mod register;
+mod search;
+mod selection;
+mod session;
+mod syntax;
+mod textobject;
+mod theme;
+mod transaction;
+mod view;
 
 pub use self::{
-    app::App,
+    app::{App, Message},
     args::{Args, Cli, Verbosity},
-    config::{Config, EditorConfig, LanguageConfig, SyntaxConfig},
+    auto_pairs::{AutoPairs, DEFAULT_PAIRS},
+    buffer::{Buffer, Direction, LineEnding, Position},
+    clipboard::{Clipboard, ClipboardType, Environment, Probe},
+    commands::{
+        typed::{CommandLine, Completer, TypedCommand},
+        CharArgument, Command, Context, Operator,
+    },
+    config::{
+        BufferLine, ClipboardConfig, ClipboardProvider, Config, DebugConfig, DebugRequest,
+        DetectedBy, Detection, EditorConfig, GutterColumnKind, IndentConfig, LanguageConfig,
+        LineNumber, LocalSettings, SearchConfig, Setting, SettingScope, StatusLineConfig,
+        StatusLineElement, SyntaxConfig, SETTINGS,
+    },
+    debugger::DebugSession,
+    diff::DiffMark,
+    grep::{GrepHandle, GrepMatch, MAX_GREP_MATCHES},
     health::{Health, HealthCategory},
+    history::{History, Jump, UndoKind},
+    keymap::{Key, KeyBinding, KeyBindings, KeymapMode, KeymapResult, Keymaps},
+    language_server::Symbol,
     metadata::Metadata,
+    motion::{Motion, MotionKind},
+    prompt::{Prompt, PromptEvent, PromptKind},
+    register::{Registers, SavedRegisters, Yank, HISTORY_SIZE, RING_SIZE},
+    search::{Matches, Search, Substitution},
+    selection::{Range, Selection},
+    session::{Session, SessionBuffer, SessionView},
+    syntax::{HighlightConfig, Loader, QueryKind, Syntax},
+    textobject::TextObject,
+    theme::{ColorDepth, Theme, ThemeLoader},
+    transaction::{Assoc, Change, Transaction},
+    view::{JumpList, JumpPoint, View},
 };
 
-#[derive(Debug, Default)]
+use serde::Deserialize;
+use std::fmt;
+
+/// Editing mode.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Mode {
+    /// Keys run commands.
     #[default]
     Normal = 0,
+    /// Keys insert text.
     Insert = 1,
+    /// Commands act on a selection.
     Visual = 2,
+    /// A command is typed after `:`.
+    Command = 3,
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Normal => "NOR",
+            Self::Insert => "INS",
+            Self::Visual => "VIS",
+            Self::Command => "CMD",
+        })
+    }
 }
 
-#[derive(Debug, Default)]
-pub struct KeyBinding {}
+/// The shape of a Visual mode selection.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum VisualKind {
+    /// Selects characters between the ends (`v`).
+    #[default]
+    Char,
+    /// Selects whole lines (`V`).
+    Line,
+    /// Selects a rectangle of columns (`C-v`).
+    Block,
+}
@@ -1,6 +1,16 @@
 use anyhow::{anyhow, Context, Result};
-use std::{path::PathBuf, process};
-use tissue::{metadata, App, Args, Cli, Config, Health, HealthCategory, Metadata, Verbosity};
+use crossterm::style::Stylize;
+use ropey::Rope;
+use std::{
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+    process,
+};
+use tissue::{
+    metadata, App, Args, Cli, Config, Environment, Health, HealthCategory, Metadata, SyntaxConfig,
+    Verbosity,
+};
 
 fn print_help_and_exit() -> ! {
     println!(
@@ -21,12 +31,14 @@ OPTIONS:
     -V, --version                Prints version information
     --health [CATEGORY]          Performs health check and prints the result (default: 'all')
                                  ('all', 'clipboard', 'languages', or a language)
+    --detect [FILE]              Prints the languages matching a file, best match first
     -c, --config [PATH]          Specifies the configuration file
                                  (default: '{config_file}')
     -l, --log [PATH]             Specifies the log file
                                  (default: '{log_file}')
     -v [LEVEL]                   Specifies the logging verbosity (default: 0)
-                                 (0: WARN, 1: INFO, 2: DEBUG, 3: TRACE)",
+                                 (0: WARN, 1: INFO, 2: DEBUG, 3: TRACE)
+    --session [NAME]             Restores the session saved with ':session save [NAME]'",
             name = env!("CARGO_PKG_NAME"),
             description = env!("CARGO_PKG_DESCRIPTION"),
             bin = env!("CARGO_BIN_NAME"),
@@ -42,10 +54,19 @@ fn print_version_and_exit() -> ! {
     process::exit(0);
 }
 
-fn print_health_and_exit(category: Option<String>) -> ! {
+fn print_health_and_exit(category: Option<String>, config_file: Option<PathBuf>) -> ! {
     let category = HealthCategory::from(category);
+    let config_file = config_file.unwrap_or_else(metadata::default_config_file);
+    let config = match Config::load(&config_file) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{err}");
+            eprintln!("{}", "Using default configuration".yellow());
+            Config::default()
+        }
+    };
 
-    if let Err(err) = Health::check(category) {
+    if let Err(err) = Health::check(category, &config) {
         eprintln!("{err}");
         process::exit(1);
     }
@@ -53,6 +74,38 @@ fn print_health_and_exit(category: Option<String>) -> ! {
     process::exit(0);
 }
 
+fn print_detection_and_exit(path: &Path) -> ! {
+    let result = SyntaxConfig::load().and_then(|config| {
+        let text = match File::open(path) {
+            Ok(file) => Some(Rope::from_reader(BufReader::new(file))?),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => None,
+            Err(err) => return Err(err.into()),
+        };
+        let detections = config.detect_all(Some(path), text.as_ref());
+
+        match detections.first() {
+            Some(detection) => println!("{}: {}", path.display(), detection.language.name),
+            None => println!("{}: no language detected", path.display()),
+        }
+        for detection in &detections {
+            println!(
+                "    {:<10} {:<12} '{}'",
+                detection.language.name, detection.by, detection.matched
+            );
+        }
+        Ok(detections.is_empty())
+    });
+
+    match result {
+        Ok(false) => process::exit(0),
+        Ok(true) => process::exit(1),
+        Err(err) => {
+            eprintln!("{err}");
+            process::exit(1);
+        }
+    }
+}
+
 fn args() -> Result<Args> {
     let cli = Cli::parse().context(format!(
         "could not parse arguments (run '{} --help' for more information)",
@@ -62,7 +115,8 @@ fn args() -> Result<Args> {
     let args = match cli {
         Cli::Help => print_help_and_exit(),
         Cli::Version => print_version_and_exit(),
-        Cli::Health(arg) => print_health_and_exit(arg),
+        Cli::Health(arg, config_file) => print_health_and_exit(arg, config_file),
+        Cli::Detect(path) => print_detection_and_exit(&path),
         Cli::Options(args) => args,
     };
 
@@ -116,7 +170,8 @@ pub async fn tissue_main() -> Result<ExitCode> {
         }
     };
 
-    let app = App::new(args, config).context("unable to create new application")?;
+    let app = App::new(args, config, Environment::current())
+        .context("unable to create new application")?;
 
     app.run().await
 }
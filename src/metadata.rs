@@ -88,6 +88,11 @@ pub fn config_dir() -> PathBuf {
     path
 }
 
+/// Returns the runtime directory, which holds tree-sitter grammars and queries.
+pub fn runtime_dir() -> PathBuf {
+    config_dir().join("runtime")
+}
+
 // Returns the cache directory.
 pub fn cache_dir() -> PathBuf {
     let base = base_dir();
@@ -130,3 +135,37 @@ pub fn local_config_dirs() -> Vec<PathBuf> {
 
     dirs
 }
+
+/// Returns the root of the workspace holding `dir`: the closest directory holding one of the
+/// `markers`, or the root of its git repository if there is none up to there.
+pub fn workspace_root(dir: &Path, markers: &[String]) -> Option<PathBuf> {
+    dir.ancestors()
+        .find(|ancestor| {
+            markers.iter().any(|marker| ancestor.join(marker).exists())
+                || ancestor.join(".git").exists()
+        })
+        .map(Path::to_path_buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn root_is_the_closest_marker_within_the_repository() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let src = dir.join("repo/crate/src");
+        std::fs::create_dir_all(&src).unwrap();
+        std::fs::create_dir_all(dir.join("repo/.git")).unwrap();
+        std::fs::write(dir.join("Cargo.lock"), "").unwrap();
+        std::fs::write(dir.join("repo/crate/Cargo.toml"), "").unwrap();
+
+        let markers = ["Cargo.toml".to_string(), "Cargo.lock".to_string()];
+        assert_eq!(workspace_root(&src, &markers), Some(dir.join("repo/crate")));
+        // Markers above the repository are out of reach.
+        assert_eq!(workspace_root(&src, &markers[1..]), Some(dir.join("repo")));
+        assert_eq!(workspace_root(dir, &markers[1..]), Some(dir.to_path_buf()));
+        assert_eq!(workspace_root(dir, &["missing".into()]), None);
+    }
+}
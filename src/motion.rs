@@ -0,0 +1,314 @@
+//! Cursor motions.
+//!
+//! Each motion takes the cursor position and a count, and returns where the cursor lands along
+//! with how an operator should treat the text in between.
+
+use crate::{Buffer, Direction};
+
+/// How an operator treats the text covered by a motion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MotionKind {
+    /// The grapheme at the target is not included.
+    Exclusive,
+    /// The grapheme at the target is included.
+    Inclusive,
+    /// Whole lines are included.
+    Linewise,
+}
+
+/// The target of a motion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Motion {
+    /// Byte offset the cursor moves to.
+    pub pos: usize,
+    /// How an operator treats the covered text.
+    pub kind: MotionKind,
+}
+
+impl Motion {
+    /// Creates a motion.
+    pub const fn new(pos: usize, kind: MotionKind) -> Self {
+        Self { pos, kind }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CharCategory {
+    Whitespace,
+    Eol,
+    Word,
+    Punctuation,
+}
+
+// `big` treats every non-blank character as part of a word, like Vim's WORD.
+fn category(c: char, big: bool) -> CharCategory {
+    match c {
+        '\n' | '\r' => CharCategory::Eol,
+        c if c.is_whitespace() => CharCategory::Whitespace,
+        c if big || c.is_alphanumeric() || c == '_' => CharCategory::Word,
+        _ => CharCategory::Punctuation,
+    }
+}
+
+/// Returns `true` if `a` and `b` belong to the same word.
+pub fn same_word(a: char, b: char, big: bool) -> bool {
+    category(a, big) == category(b, big)
+}
+
+/// Returns `true` if the line is empty or only holds whitespace.
+pub fn is_blank_line(buffer: &Buffer, line: usize) -> bool {
+    buffer.line(line).chars().all(char::is_whitespace)
+}
+
+/// Moves `count` graphemes left without leaving the line.
+pub fn char_left(buffer: &Buffer, pos: usize, count: usize) -> usize {
+    let start = buffer.line_start(buffer.byte_to_pos(pos).line);
+    buffer
+        .move_horizontally(pos, Direction::Backward, count)
+        .max(start)
+}
+
+/// Moves `count` graphemes right without leaving the line.
+///
+/// The cursor may land just past the last grapheme, which operators need to cover it.
+pub fn char_right(buffer: &Buffer, pos: usize, count: usize) -> usize {
+    let end = buffer.line_end(buffer.byte_to_pos(pos).line);
+    buffer
+        .move_horizontally(pos, Direction::Forward, count)
+        .min(end)
+}
+
+/// Moves `count` lines up or down, keeping the grapheme column.
+pub fn line_vertical(buffer: &Buffer, pos: usize, direction: Direction, count: usize) -> usize {
+    buffer.move_vertically(pos, direction, count, None).0
+}
+
+/// Moves to the start of the `count`th next word (`w`).
+pub fn next_word_start(buffer: &Buffer, pos: usize, count: usize, big: bool) -> usize {
+    (0..count).fold(pos, |pos, _| {
+        let mut chars = buffer.chars_at(pos).peekable();
+        let Some(&(_, first)) = chars.peek() else {
+            return pos;
+        };
+
+        let first = category(first, big);
+        if matches!(first, CharCategory::Word | CharCategory::Punctuation) {
+            while chars.next_if(|&(_, c)| category(c, big) == first).is_some() {}
+        }
+
+        // Skip blanks, stopping at an empty line since it counts as a word.
+        let mut line_start = false;
+        while let Some(&(i, c)) = chars.peek() {
+            match category(c, big) {
+                CharCategory::Whitespace => line_start = false,
+                CharCategory::Eol if line_start => return i,
+                CharCategory::Eol => line_start = c == '\n',
+                _ => return i,
+            }
+            chars.next();
+        }
+
+        buffer.len_bytes()
+    })
+}
+
+/// Moves to the end of the `count`th next word (`e`).
+pub fn next_word_end(buffer: &Buffer, pos: usize, count: usize, big: bool) -> usize {
+    (0..count).fold(pos, |pos, _| {
+        let mut chars = buffer.chars_at(pos).skip(1).peekable();
+
+        while chars
+            .next_if(|&(_, c)| {
+                matches!(
+                    category(c, big),
+                    CharCategory::Whitespace | CharCategory::Eol
+                )
+            })
+            .is_some()
+        {}
+
+        let Some((mut end, first)) = chars.next() else {
+            return pos;
+        };
+        let first = category(first, big);
+
+        while let Some((i, _)) = chars.next_if(|&(_, c)| category(c, big) == first) {
+            end = i;
+        }
+
+        end
+    })
+}
+
+/// Moves to the start of the `count`th previous word (`b`).
+pub fn prev_word_start(buffer: &Buffer, pos: usize, count: usize, big: bool) -> usize {
+    (0..count).fold(pos, |pos, _| {
+        let mut chars = buffer.chars_before(pos).peekable();
+
+        while chars
+            .next_if(|&(_, c)| {
+                matches!(
+                    category(c, big),
+                    CharCategory::Whitespace | CharCategory::Eol
+                )
+            })
+            .is_some()
+        {}
+
+        let Some((mut start, first)) = chars.next() else {
+            return 0;
+        };
+        let first = category(first, big);
+
+        while let Some((i, _)) = chars.next_if(|&(_, c)| category(c, big) == first) {
+            start = i;
+        }
+
+        start
+    })
+}
+
+/// Moves to the blank line after the `count`th next paragraph (`}`).
+pub fn next_paragraph(buffer: &Buffer, pos: usize, count: usize) -> usize {
+    let last = buffer.len_lines() - 1;
+    let mut line = buffer.byte_to_pos(pos).line;
+
+    for _ in 0..count {
+        while line < last && is_blank_line(buffer, line) {
+            line += 1;
+        }
+        while line < last && !is_blank_line(buffer, line) {
+            line += 1;
+        }
+    }
+
+    if is_blank_line(buffer, line) {
+        buffer.line_start(line)
+    } else {
+        buffer.line_end(line)
+    }
+}
+
+/// Moves to the blank line before the `count`th previous paragraph (`{`).
+pub fn prev_paragraph(buffer: &Buffer, pos: usize, count: usize) -> usize {
+    let mut line = buffer.byte_to_pos(pos).line;
+
+    for _ in 0..count {
+        while line > 0 && is_blank_line(buffer, line) {
+            line -= 1;
+        }
+        while line > 0 && !is_blank_line(buffer, line) {
+            line -= 1;
+        }
+    }
+
+    buffer.line_start(line)
+}
+
+/// Finds the `count`th occurrence of `ch` on the current line (`f`, `t`, `F` and `T`).
+///
+/// With `till` the cursor stops next to the character instead of on it.
+pub fn find_char(
+    buffer: &Buffer,
+    pos: usize,
+    ch: char,
+    direction: Direction,
+    till: bool,
+    count: usize,
+) -> Option<usize> {
+    let line = buffer.byte_to_pos(pos).line;
+    let (start, end) = (buffer.line_start(line), buffer.line_end(line));
+
+    match direction {
+        Direction::Forward => {
+            let from = buffer.next_grapheme_boundary(pos).min(end);
+            let (found, _) = buffer
+                .chars_at(from)
+                .take_while(|&(i, _)| i < end)
+                .filter(|&(_, c)| c == ch)
+                .nth(count - 1)?;
+            Some(if till {
+                buffer.prev_grapheme_boundary(found).max(pos)
+            } else {
+                found
+            })
+        }
+        Direction::Backward => {
+            let (found, _) = buffer
+                .chars_before(pos)
+                .take_while(|&(i, _)| i >= start)
+                .filter(|&(_, c)| c == ch)
+                .nth(count - 1)?;
+            Some(if till {
+                buffer.next_grapheme_boundary(found).min(pos)
+            } else {
+                found
+            })
+        }
+    }
+}
+
+/// Returns the first non-blank character of the line.
+pub fn first_non_blank(buffer: &Buffer, line: usize) -> usize {
+    let start = buffer.line_start(line);
+    let end = buffer.line_end(line);
+
+    buffer
+        .chars_at(start)
+        .take_while(|&(i, _)| i < end)
+        .find(|&(_, c)| !c.is_whitespace())
+        .map_or(end, |(i, _)| i)
+}
+
+/// Returns the last grapheme of the line, or its start if it is empty.
+pub fn last_grapheme(buffer: &Buffer, line: usize) -> usize {
+    let start = buffer.line_start(line);
+    let end = buffer.line_end(line);
+
+    if end > start {
+        buffer.prev_grapheme_boundary(end)
+    } else {
+        start
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn word_motions() {
+        let buffer = Buffer::from("foo.bar  baz\n\nqux");
+
+        assert_eq!(next_word_start(&buffer, 0, 1, false), 3);
+        assert_eq!(next_word_start(&buffer, 0, 1, true), 9);
+        assert_eq!(next_word_start(&buffer, 9, 1, false), 13);
+        assert_eq!(next_word_start(&buffer, 13, 1, false), 14);
+        assert_eq!(next_word_end(&buffer, 0, 1, false), 2);
+        assert_eq!(next_word_end(&buffer, 2, 2, false), 6);
+        assert_eq!(prev_word_start(&buffer, 9, 1, false), 4);
+        assert_eq!(prev_word_start(&buffer, 14, 2, true), 0);
+    }
+
+    #[test]
+    fn find_char_stays_on_line() {
+        let buffer = Buffer::from("a,b,c\nd,e");
+
+        assert_eq!(
+            find_char(&buffer, 0, ',', Direction::Forward, false, 2),
+            Some(3)
+        );
+        assert_eq!(
+            find_char(&buffer, 0, ',', Direction::Forward, true, 1),
+            Some(0)
+        );
+        assert_eq!(
+            find_char(&buffer, 0, ',', Direction::Forward, false, 3),
+            None
+        );
+        assert_eq!(
+            find_char(&buffer, 4, 'a', Direction::Backward, true, 1),
+            Some(1)
+        );
+    }
+}
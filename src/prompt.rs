@@ -0,0 +1,237 @@
+//! A line of input typed in the status line, such as a search pattern.
+
+use crate::Direction;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::ops::RangeInclusive;
+
+/// What a prompt asks for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PromptKind {
+    /// A pattern to search for (`/` and `?`).
+    Search(Direction),
+    /// A pattern to search the workspace for.
+    Grep,
+    /// A command typed in Command mode.
+    Command {
+        /// The lines the command applies to, unless it starts with `%`.
+        lines: RangeInclusive<usize>,
+    },
+}
+
+/// What a key did to a prompt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PromptEvent {
+    /// The line or the cursor changed.
+    Update,
+    /// The line was submitted with enter.
+    Submit,
+    /// The prompt was closed without submitting.
+    Abort,
+    /// The key did nothing.
+    Ignored,
+}
+
+/// A line being typed in the status line.
+#[derive(Debug, Clone)]
+pub struct Prompt {
+    kind: PromptKind,
+    line: String,
+    // Byte offset of the cursor in `line`.
+    cursor: usize,
+    // The history entry shown, counted from the newest, and the line typed before browsing.
+    history: Option<(usize, String)>,
+}
+
+impl Prompt {
+    /// Creates a prompt with `line` already typed.
+    pub fn new(kind: PromptKind, line: impl Into<String>) -> Self {
+        let line = line.into();
+        Self {
+            kind,
+            cursor: line.len(),
+            line,
+            history: None,
+        }
+    }
+
+    /// Returns what the prompt asks for.
+    pub fn kind(&self) -> &PromptKind {
+        &self.kind
+    }
+
+    /// Returns the typed line.
+    pub fn line(&self) -> &str {
+        &self.line
+    }
+
+    /// Returns the byte offset of the cursor in the line.
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    /// Returns the character shown before the line.
+    pub fn prefix(&self) -> char {
+        match self.kind {
+            PromptKind::Search(Direction::Forward) => '/',
+            PromptKind::Search(Direction::Backward) => '?',
+            PromptKind::Grep => '/',
+            PromptKind::Command { .. } => ':',
+        }
+    }
+
+    /// Edits the line with `event`. Up and down browse `history`, which is ordered oldest first.
+    pub fn handle_key(&mut self, event: KeyEvent, history: &[String]) -> PromptEvent {
+        let ctrl = event.modifiers.contains(KeyModifiers::CONTROL);
+        match event.code {
+            KeyCode::Esc => return PromptEvent::Abort,
+            KeyCode::Char('c') if ctrl => return PromptEvent::Abort,
+            KeyCode::Enter => return PromptEvent::Submit,
+            KeyCode::Backspace if self.line.is_empty() => return PromptEvent::Abort,
+            KeyCode::Backspace => {
+                let start = self.prev_boundary();
+                self.line.replace_range(start..self.cursor, "");
+                self.cursor = start;
+            }
+            KeyCode::Delete => {
+                let end = self.next_boundary();
+                self.line.replace_range(self.cursor..end, "");
+            }
+            KeyCode::Left => self.cursor = self.prev_boundary(),
+            KeyCode::Right => self.cursor = self.next_boundary(),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = self.line.len(),
+            KeyCode::Char('a') if ctrl => self.cursor = 0,
+            KeyCode::Char('e') if ctrl => self.cursor = self.line.len(),
+            KeyCode::Char('u') if ctrl => {
+                self.line.replace_range(..self.cursor, "");
+                self.cursor = 0;
+            }
+            KeyCode::Char('w') if ctrl => {
+                let start = word_start(&self.line[..self.cursor]);
+                self.line.replace_range(start..self.cursor, "");
+                self.cursor = start;
+            }
+            KeyCode::Up => return self.browse(history, true),
+            KeyCode::Down => return self.browse(history, false),
+            KeyCode::Char(c) if !ctrl && !event.modifiers.contains(KeyModifiers::ALT) => {
+                self.line.insert(self.cursor, c);
+                self.cursor += c.len_utf8();
+            }
+            _ => return PromptEvent::Ignored,
+        }
+        PromptEvent::Update
+    }
+
+    // Shows the previous (`older`) or next entry of `history`, or the line typed before browsing
+    // when going past the newest one.
+    fn browse(&mut self, history: &[String], older: bool) -> PromptEvent {
+        let index = match (&self.history, older) {
+            (None, true) => 0,
+            (None, false) => return PromptEvent::Ignored,
+            (Some((index, _)), true) => index + 1,
+            (Some((0, _)), false) => {
+                let (_, typed) = self.history.take().expect("browsing history");
+                self.set_line(typed);
+                return PromptEvent::Update;
+            }
+            (Some((index, _)), false) => index - 1,
+        };
+        let Some(entry) = history.iter().rev().nth(index) else {
+            return PromptEvent::Ignored;
+        };
+
+        let entry = entry.clone();
+        let typed = match self.history.take() {
+            Some((_, typed)) => typed,
+            None => self.line.clone(),
+        };
+        self.history = Some((index, typed));
+        self.set_line(entry);
+        PromptEvent::Update
+    }
+
+    /// Replaces the line, moving the cursor to its end.
+    pub fn set_line(&mut self, line: String) {
+        self.cursor = line.len();
+        self.line = line;
+    }
+
+    fn prev_boundary(&self) -> usize {
+        self.line[..self.cursor]
+            .char_indices()
+            .last()
+            .map_or(0, |(i, _)| i)
+    }
+
+    fn next_boundary(&self) -> usize {
+        self.line[self.cursor..]
+            .chars()
+            .next()
+            .map_or(self.cursor, |c| self.cursor + c.len_utf8())
+    }
+}
+
+// Returns where the word before the end of `s` starts, along with the spaces after it.
+fn word_start(s: &str) -> usize {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    let trimmed = s.trim_end();
+    let word = trimmed.chars().next_back().map_or(false, is_word);
+    trimmed
+        .char_indices()
+        .rev()
+        .find(|&(_, c)| c.is_whitespace() || is_word(c) != word)
+        .map_or(0, |(i, c)| i + c.len_utf8())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn type_keys(prompt: &mut Prompt, keys: &[KeyEvent], history: &[String]) -> PromptEvent {
+        keys.iter()
+            .map(|&key| prompt.handle_key(key, history))
+            .last()
+            .unwrap()
+    }
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    fn ctrl(c: char) -> KeyEvent {
+        KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL)
+    }
+
+    #[test]
+    fn edits_the_line() {
+        let mut prompt = Prompt::new(PromptKind::Search(Direction::Forward), "foo bar");
+        let keys = [ctrl('w'), key(KeyCode::Char('é')), key(KeyCode::Left)];
+        assert_eq!(type_keys(&mut prompt, &keys, &[]), PromptEvent::Update);
+        assert_eq!((prompt.line(), prompt.cursor()), ("foo é", 4));
+
+        let keys = [key(KeyCode::Backspace), ctrl('a'), key(KeyCode::Delete)];
+        type_keys(&mut prompt, &keys, &[]);
+        assert_eq!((prompt.line(), prompt.cursor()), ("ooé", 0));
+
+        let keys = [ctrl('e'), ctrl('u'), key(KeyCode::Backspace)];
+        assert_eq!(type_keys(&mut prompt, &keys, &[]), PromptEvent::Abort);
+    }
+
+    #[test]
+    fn browses_history() {
+        let history = ["old".to_string(), "new".to_string()];
+        let mut prompt = Prompt::new(PromptKind::Search(Direction::Backward), "typed");
+
+        type_keys(&mut prompt, &[key(KeyCode::Up)], &history);
+        assert_eq!(prompt.line(), "new");
+        type_keys(&mut prompt, &[key(KeyCode::Up), key(KeyCode::Up)], &history);
+        assert_eq!(prompt.line(), "old");
+        type_keys(
+            &mut prompt,
+            &[key(KeyCode::Down), key(KeyCode::Down)],
+            &history,
+        );
+        assert_eq!(prompt.line(), "typed");
+        assert_eq!(prompt.prefix(), '?');
+    }
+}
@@ -0,0 +1,355 @@
+//! Registers that yanked and deleted text is stored in and pasted from.
+//!
+//! | Register        | Contents                                                  |
+//! |-----------------|-----------------------------------------------------------|
+//! | `"`             | The latest yank or delete                                 |
+//! | `0`             | The latest yank                                           |
+//! | `1` to `9`      | The yank ring: the latest yanks and deletes, newest first |
+//! | `a` to `z`      | Named registers; `A` to `Z` append to them                |
+//! | `+` and `*`     | The system clipboard and primary selection                |
+//! | `_`             | Discards what is written to it                            |
+//! | `/` (read-only) | The last search pattern                                   |
+//! | `:` (read-only) | The last command line                                     |
+//! | `%` (read-only) | The path of the current buffer                            |
+
+use crate::{Clipboard, ClipboardType};
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    path::Path,
+};
+
+/// How many yanks and deletes the yank ring remembers.
+pub const RING_SIZE: usize = 9;
+
+/// How many entries the search and command histories remember.
+pub const HISTORY_SIZE: usize = 100;
+
+/// Text copied by an operator.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Yank {
+    /// The copied text.
+    pub text: String,
+    /// Whether whole lines were copied.
+    pub linewise: bool,
+}
+
+/// The registers of the editor.
+#[derive(Debug, Default)]
+pub struct Registers {
+    history: SavedRegisters,
+    clipboard: Clipboard,
+}
+
+/// The registers that persist across runs of the editor: all but `+`, `*` and `%`.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct SavedRegisters {
+    named: BTreeMap<String, Yank>,
+    last_yank: Option<Yank>,
+    ring: VecDeque<Yank>,
+    search: Option<String>,
+    search_history: Vec<String>,
+    command: Option<String>,
+    command_history: Vec<String>,
+}
+
+impl Registers {
+    /// Creates empty registers, bridging `+` and `*` to `clipboard`.
+    pub fn new(clipboard: Clipboard) -> Self {
+        Self {
+            history: SavedRegisters::default(),
+            clipboard,
+        }
+    }
+
+    /// Returns the contents of the register called `name`, or `None` if it is empty.
+    ///
+    /// `file` is the path of the current buffer, which `%` holds.
+    pub fn get(&self, name: char, file: Option<&Path>) -> Result<Option<Yank>> {
+        let history = &self.history;
+        let yank = match name {
+            '"' => history.ring.front().cloned(),
+            '0' => history.last_yank.clone(),
+            '1'..='9' => history.ring.get(name as usize - '1' as usize).cloned(),
+            'a'..='z' | 'A'..='Z' => history
+                .named
+                .get(&name.to_ascii_lowercase().to_string())
+                .cloned(),
+            '+' | '*' => {
+                let text = self.clipboard.get(clipboard_type(name))?;
+                let linewise = text.ends_with('\n');
+                Some(Yank { text, linewise })
+            }
+            '_' => None,
+            '/' => history.search.clone().map(charwise),
+            ':' => history.command.clone().map(charwise),
+            '%' => file.map(|file| charwise(file.display().to_string())),
+            _ => bail!("unknown register '{name}'"),
+        };
+        Ok(yank.filter(|yank| !yank.text.is_empty()))
+    }
+
+    /// Stores `yank` in the register called `name`, or in the unnamed register if `None`.
+    ///
+    /// Everything but `_`, `+` and `*` also goes to the unnamed register and the yank ring, and
+    /// yanks that are not `deleted` go to `0`. Text copied to the system clipboard is left out of
+    /// them, as they are saved to disk.
+    pub fn store(&mut self, name: Option<char>, yank: Yank, deleted: bool) -> Result<()> {
+        let name = name.unwrap_or('"');
+        let yank = match name {
+            '"' => yank,
+            'a'..='z' => {
+                self.history.named.insert(name.to_string(), yank.clone());
+                yank
+            }
+            'A'..='Z' => {
+                let named = self
+                    .history
+                    .named
+                    .entry(name.to_ascii_lowercase().to_string())
+                    .or_insert_with(|| charwise(String::new()));
+                if yank.linewise && !named.text.is_empty() && !named.text.ends_with('\n') {
+                    named.text.push('\n');
+                }
+                named.text.push_str(&yank.text);
+                named.linewise |= yank.linewise;
+                named.clone()
+            }
+            '+' | '*' => {
+                self.clipboard
+                    .set(clipboard_type(name), &yank.text)
+                    .with_context(|| format!("failed to copy to the {}", self.clipboard.name()))?;
+                return Ok(());
+            }
+            '_' => return Ok(()),
+            '0'..='9' | '/' | ':' | '%' => bail!("register '{name}' is read-only"),
+            _ => bail!("unknown register '{name}'"),
+        };
+
+        if !deleted {
+            self.history.last_yank = Some(yank.clone());
+        }
+        self.history.ring.push_front(yank);
+        self.history.ring.truncate(RING_SIZE);
+        Ok(())
+    }
+
+    /// Returns the yank ring, newest first.
+    pub fn ring(&self) -> impl Iterator<Item = &Yank> {
+        self.history.ring.iter()
+    }
+
+    /// Sets the last search pattern, held by `/`, and adds it to the search history.
+    pub fn set_search(&mut self, pattern: impl Into<String>) {
+        let pattern = pattern.into();
+        remember(&mut self.history.search_history, &pattern);
+        self.history.search = Some(pattern);
+    }
+
+    /// Returns the search history, oldest first.
+    pub fn search_history(&self) -> &[String] {
+        &self.history.search_history
+    }
+
+    /// Sets the last command line, held by `:`, and adds it to the command history.
+    pub fn set_command(&mut self, line: impl Into<String>) {
+        let line = line.into();
+        remember(&mut self.history.command_history, &line);
+        self.history.command = Some(line);
+    }
+
+    /// Returns the command history, oldest first.
+    pub fn command_history(&self) -> &[String] {
+        &self.history.command_history
+    }
+
+    /// Returns the clipboard bridged by `+` and `*`.
+    pub fn clipboard(&self) -> &Clipboard {
+        &self.clipboard
+    }
+
+    /// Bridges `+` and `*` to `clipboard` from now on.
+    pub fn set_clipboard(&mut self, clipboard: Clipboard) {
+        self.clipboard = clipboard;
+    }
+
+    /// Restores the registers saved by [`Registers::save`], unless there is no such file.
+    pub fn load(&mut self, path: &Path) -> Result<()> {
+        let s = match std::fs::read_to_string(path) {
+            Ok(s) => s,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err.into()),
+        };
+        self.restore(toml::from_str(&s).map_err(|err| anyhow!(err))?);
+        Ok(())
+    }
+
+    /// Returns the registers that [`Registers::save`] saves.
+    pub fn saved(&self) -> &SavedRegisters {
+        &self.history
+    }
+
+    /// Replaces every register but `+`, `*` and `%` with `saved`.
+    pub fn restore(&mut self, saved: SavedRegisters) {
+        self.history = saved;
+        self.history.ring.truncate(RING_SIZE);
+    }
+
+    /// Saves every register but `+`, `*` and `%` to `path`.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let s = toml::to_string(&self.history)?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, s)?;
+        Ok(())
+    }
+}
+
+fn clipboard_type(name: char) -> ClipboardType {
+    match name {
+        '*' => ClipboardType::Primary,
+        _ => ClipboardType::Clipboard,
+    }
+}
+
+// Adds `entry` to the end of `history`, removing an earlier copy of it and the oldest entries past
+// `HISTORY_SIZE`.
+fn remember(history: &mut Vec<String>, entry: &str) {
+    history.retain(|other| other != entry);
+    history.push(entry.to_string());
+    if history.len() > HISTORY_SIZE {
+        history.drain(..history.len() - HISTORY_SIZE);
+    }
+}
+
+fn charwise(text: String) -> Yank {
+    Yank {
+        text,
+        linewise: false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn yank(text: &str, linewise: bool) -> Yank {
+        Yank {
+            text: text.into(),
+            linewise,
+        }
+    }
+
+    fn text(registers: &Registers, name: char) -> Option<String> {
+        registers.get(name, None).unwrap().map(|yank| yank.text)
+    }
+
+    #[test]
+    fn yanks_and_deletes_fill_the_ring() {
+        let mut registers = Registers::default();
+        registers.store(None, yank("one", false), false).unwrap();
+        registers.store(None, yank("two\n", true), true).unwrap();
+        registers
+            .store(Some('_'), yank("gone", false), true)
+            .unwrap();
+
+        assert_eq!(registers.get('"', None).unwrap(), Some(yank("two\n", true)));
+        assert_eq!(text(&registers, '0').as_deref(), Some("one"));
+        assert_eq!(text(&registers, '2').as_deref(), Some("one"));
+        assert_eq!(text(&registers, '_'), None);
+
+        for i in 0..20 {
+            registers
+                .store(None, yank(&i.to_string(), false), false)
+                .unwrap();
+        }
+        assert_eq!(registers.ring().count(), RING_SIZE);
+        assert_eq!(text(&registers, '9').as_deref(), Some("11"));
+    }
+
+    #[test]
+    fn named_registers_append() {
+        let mut registers = Registers::default();
+        registers.store(Some('A'), yank("a", false), false).unwrap();
+        registers.store(Some('A'), yank("b", false), false).unwrap();
+        registers
+            .store(Some('A'), yank("line\n", true), false)
+            .unwrap();
+        registers.store(Some('q'), yank("q", false), false).unwrap();
+
+        assert_eq!(
+            registers.get('a', None).unwrap(),
+            Some(yank("ab\nline\n", true))
+        );
+        assert_eq!(text(&registers, '"').as_deref(), Some("q"));
+        assert_eq!(text(&registers, 'Q').as_deref(), Some("q"));
+    }
+
+    #[test]
+    fn special_registers() {
+        let mut registers = Registers::default();
+        registers.set_search("fo+");
+
+        assert_eq!(text(&registers, '/').as_deref(), Some("fo+"));
+        assert_eq!(
+            registers.get('%', Some(Path::new("src/main.rs"))).unwrap(),
+            Some(yank("src/main.rs", false))
+        );
+        let err = registers.store(Some('%'), yank("x", false), false);
+        assert_eq!(err.unwrap_err().to_string(), "register '%' is read-only");
+        let err = registers.get('?', None);
+        assert_eq!(err.unwrap_err().to_string(), "unknown register '?'");
+
+        // The internal clipboard keeps what was copied.
+        registers
+            .store(Some('+'), yank("clip\n", true), false)
+            .unwrap();
+        registers
+            .store(Some('*'), yank("primary", false), false)
+            .unwrap();
+        assert_eq!(
+            registers.get('+', None).unwrap(),
+            Some(yank("clip\n", true))
+        );
+        assert_eq!(text(&registers, '*').as_deref(), Some("primary"));
+        assert_eq!(text(&registers, '"'), None);
+    }
+
+    #[test]
+    fn registers_persist() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("registers.toml");
+        let mut registers = Registers::default();
+        registers
+            .store(Some('a'), yank("a\n", true), false)
+            .unwrap();
+        registers.store(None, yank("deleted", false), true).unwrap();
+        registers.store(None, yank("yanked", false), false).unwrap();
+        registers
+            .store(Some('+'), yank("clipboard", false), false)
+            .unwrap();
+        registers.set_search("needle");
+        registers.set_search("hay");
+        registers.set_search("needle");
+        registers.set_command("w");
+        registers.save(&path).unwrap();
+
+        let mut restored = Registers::default();
+        restored.load(&path).unwrap();
+        assert_eq!(restored.get('a', None).unwrap(), Some(yank("a\n", true)));
+        assert_eq!(text(&restored, '2').as_deref(), Some("deleted"));
+        assert_eq!(text(&restored, '0').as_deref(), Some("yanked"));
+        assert_eq!(restored.ring().count(), 3);
+        assert_eq!(text(&restored, '/').as_deref(), Some("needle"));
+        assert_eq!(restored.search_history(), ["hay", "needle"]);
+        assert_eq!(restored.command_history(), ["w"]);
+        assert_eq!(text(&restored, ':').as_deref(), Some("w"));
+        assert_eq!(text(&restored, '+'), None);
+
+        std::fs::remove_file(&path).unwrap();
+        restored.load(&path).unwrap();
+    }
+}
@@ -0,0 +1,506 @@
+//! Regex search over the rope of a buffer.
+//!
+//! Matches are found by walking the lazy DFAs of `regex-automata` over the bytes of the rope, one
+//! chunk after the other, so that searching never copies the text into a single string. Capture
+//! groups, which the DFAs cannot report, are resolved afterwards by running the `regex` crate on
+//! the lines of a match.
+//!
+//! The DFAs give up on patterns they cannot run over some text, such as a Unicode `\b` next to a
+//! non-ASCII character. The rest of such a search copies the searched lines and runs the slower
+//! meta regex over them instead.
+
+mod substitute;
+
+pub use self::substitute::Substitution;
+
+use anyhow::{anyhow, bail, Result};
+use regex_automata::{
+    hybrid::{
+        dfa,
+        regex::{Cache, Regex},
+    },
+    meta,
+    util::{start, syntax},
+    Anchored, Input,
+};
+use ropey::Rope;
+use std::{fmt, ops::Range};
+
+/// A compiled search pattern.
+pub struct Search {
+    pattern: String,
+    regex: Regex,
+    // Runs where `regex` gives up.
+    fallback: meta::Regex,
+    captures: regex::Regex,
+}
+
+impl fmt::Debug for Search {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Search").field(&self.pattern).finish()
+    }
+}
+
+impl Search {
+    /// Compiles `pattern`. With `smart_case`, the search ignores case unless the pattern has an
+    /// uppercase letter.
+    ///
+    /// `^` and `$` match at line boundaries.
+    pub fn new(pattern: &str, smart_case: bool) -> Result<Self> {
+        let ignore_case = smart_case && !has_uppercase(pattern);
+        Self::with_case(pattern, !ignore_case)
+    }
+
+    /// Compiles `pattern`, ignoring case unless `case_sensitive` is set.
+    pub fn with_case(pattern: &str, case_sensitive: bool) -> Result<Self> {
+        let captures = regex::RegexBuilder::new(pattern)
+            .multi_line(true)
+            .case_insensitive(!case_sensitive)
+            .build()
+            .map_err(|err| match err {
+                regex::Error::Syntax(message) => anyhow!("invalid pattern: {message}"),
+                err => anyhow!("invalid pattern: {err}"),
+            })?;
+        let syntax = syntax::Config::new()
+            .multi_line(true)
+            .case_insensitive(!case_sensitive);
+        let regex = Regex::builder()
+            .syntax(syntax)
+            .dfa(dfa::Config::new().unicode_word_boundary(true))
+            .build(pattern)
+            .map_err(|err| anyhow!("invalid pattern: {err}"))?;
+        let fallback = meta::Regex::builder()
+            .syntax(syntax)
+            .build(pattern)
+            .map_err(|err| anyhow!("invalid pattern: {err}"))?;
+
+        Ok(Self {
+            pattern: pattern.to_string(),
+            regex,
+            fallback,
+            captures,
+        })
+    }
+
+    /// Returns the pattern the search was compiled from.
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Returns the matches that lie within `range` of `text`, in order.
+    pub fn find_iter<'a>(&'a self, text: &'a Rope, range: Range<usize>) -> Matches<'a> {
+        Matches {
+            search: self,
+            text,
+            cache: self.regex.create_cache(),
+            pos: range.start,
+            end: range.end,
+            last_end: None,
+            lines: None,
+        }
+    }
+
+    /// Returns the first match that starts after `pos`, and whether the search wrapped around the
+    /// end of the text to find it.
+    pub fn find_next(
+        &self,
+        text: &Rope,
+        pos: usize,
+        wrap: bool,
+    ) -> Result<Option<(Range<usize>, bool)>> {
+        let len = text.len_bytes();
+        let from = next_char_boundary(text, pos).min(len);
+        if let Some(found) = self.find_iter(text, from..len).next().transpose()? {
+            return Ok(Some((found, false)));
+        }
+        if !wrap {
+            return Ok(None);
+        }
+        let found = self.find_iter(text, 0..len).next().transpose()?;
+        Ok(found.map(|found| (found, true)))
+    }
+
+    /// Returns the last match that starts before `pos`, and whether the search wrapped around the
+    /// start of the text to find it.
+    pub fn find_prev(
+        &self,
+        text: &Rope,
+        pos: usize,
+        wrap: bool,
+    ) -> Result<Option<(Range<usize>, bool)>> {
+        let len = text.len_bytes();
+        let line = text.byte_to_line(pos.min(len));
+
+        // Search ever larger windows of lines before `pos`, so that a nearby match is found
+        // without going through the whole text.
+        let mut lines = 1;
+        loop {
+            let from = text.line_to_byte(line.saturating_sub(lines - 1));
+            let mut last = None;
+            for found in self.find_iter(text, from..len) {
+                let found = found?;
+                if found.start >= pos {
+                    break;
+                }
+                last = Some(found);
+            }
+            if last.is_some() {
+                return Ok(last.map(|found| (found, false)));
+            }
+            if from == 0 {
+                break;
+            }
+            lines *= 2;
+        }
+
+        if !wrap {
+            return Ok(None);
+        }
+        let mut last = None;
+        for found in self.find_iter(text, pos.min(len)..len) {
+            last = Some(found?);
+        }
+        Ok(last.map(|found| (found, true)))
+    }
+
+    /// Expands `replacement` for the match at `found`, with `$0` standing for the whole match and
+    /// `$1` or `${name}` for its capture groups.
+    pub fn expand(&self, text: &Rope, found: Range<usize>, replacement: &str) -> String {
+        let first = text.byte_to_line(found.start);
+        let last = text.byte_to_line(found.end);
+        let window = text.line_to_byte(first)..text.line_to_byte((last + 1).min(text.len_lines()));
+        let haystack = text.byte_slice(window.clone()).to_string();
+        let offset = found.start - window.start;
+
+        let mut expanded = String::new();
+        match self.captures.captures_at(&haystack, offset) {
+            Some(captures)
+                if captures.get(0).map(|m| m.range()) == Some(offset..found.end - window.start) =>
+            {
+                captures.expand(replacement, &mut expanded);
+            }
+            // The match depends on text outside of its lines, such as `\A`, so it is expanded on
+            // its own.
+            _ => {
+                let matched = text.byte_slice(found).to_string();
+                match self.captures.captures(&matched) {
+                    Some(captures) => captures.expand(replacement, &mut expanded),
+                    None => expanded.push_str(replacement),
+                }
+            }
+        }
+        expanded
+    }
+
+    // Returns the leftmost-first match that lies within `range`.
+    fn find_in(
+        &self,
+        cache: &mut Cache,
+        text: &Rope,
+        range: Range<usize>,
+    ) -> Result<Option<Range<usize>>> {
+        let Some(end) = self.find_end(cache, text, range.clone())? else {
+            return Ok(None);
+        };
+        let start = self.find_start(cache, text, range.start..end)?;
+        Ok(Some(start..end))
+    }
+
+    // Runs the forward DFA over `range` and returns where the leftmost-first match ends.
+    fn find_end(
+        &self,
+        cache: &mut Cache,
+        text: &Rope,
+        range: Range<usize>,
+    ) -> Result<Option<usize>> {
+        let dfa = self.regex.forward();
+        let cache = cache.forward_mut();
+        let look_behind = range.start.checked_sub(1).map(|pos| text.byte(pos));
+        let config = start::Config::new()
+            .anchored(Anchored::No)
+            .look_behind(look_behind);
+        let mut sid = dfa.start_state(cache, &config).map_err(gave_up)?;
+        let mut end = None;
+
+        let mut bytes = text.bytes_at(range.start);
+        for pos in range.clone() {
+            let byte = bytes.next().expect("range within the text");
+            sid = dfa.next_state(cache, sid, byte).map_err(gave_up)?;
+            // Matches are reported one byte late.
+            if sid.is_match() {
+                end = Some(pos);
+            } else if sid.is_dead() {
+                return Ok(end);
+            } else if sid.is_quit() {
+                bail!("{}", quit(byte));
+            }
+        }
+
+        sid = match text.get_byte(range.end) {
+            Some(byte) => dfa.next_state(cache, sid, byte),
+            None => dfa.next_eoi_state(cache, sid),
+        }
+        .map_err(gave_up)?;
+        if sid.is_match() {
+            end = Some(range.end);
+        }
+        Ok(end)
+    }
+
+    // Runs the reverse DFA from the end of a match back to the start of `range`, and returns where
+    // the match starts.
+    fn find_start(&self, cache: &mut Cache, text: &Rope, range: Range<usize>) -> Result<usize> {
+        let dfa = self.regex.reverse();
+        let cache = cache.reverse_mut();
+        let config = start::Config::new()
+            .anchored(Anchored::Yes)
+            .look_behind(text.get_byte(range.end));
+        let mut sid = dfa.start_state(cache, &config).map_err(gave_up)?;
+        let mut start = range.end;
+
+        let mut bytes = text.bytes_at(range.end);
+        for pos in range.clone().rev() {
+            let byte = bytes.prev().expect("range within the text");
+            sid = dfa.next_state(cache, sid, byte).map_err(gave_up)?;
+            if sid.is_match() {
+                start = pos + 1;
+            } else if sid.is_dead() {
+                return Ok(start);
+            } else if sid.is_quit() {
+                bail!("{}", quit(byte));
+            }
+        }
+
+        sid = match range.start.checked_sub(1) {
+            Some(pos) => dfa.next_state(cache, sid, text.byte(pos)),
+            None => dfa.next_eoi_state(cache, sid),
+        }
+        .map_err(gave_up)?;
+        if sid.is_match() {
+            start = range.start;
+        }
+        Ok(start)
+    }
+}
+
+/// An iterator over the matches of a [`Search`].
+pub struct Matches<'a> {
+    search: &'a Search,
+    text: &'a Rope,
+    cache: Cache,
+    pos: usize,
+    end: usize,
+    // The end of the last match, after which an empty match is skipped.
+    last_end: Option<usize>,
+    // Where the searched lines start and their text, once the DFAs gave up on them.
+    lines: Option<(usize, String)>,
+}
+
+impl fmt::Debug for Matches<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Matches")
+            .field("search", self.search)
+            .field("pos", &self.pos)
+            .field("end", &self.end)
+            .finish()
+    }
+}
+
+impl Iterator for Matches<'_> {
+    type Item = Result<Range<usize>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.pos <= self.end {
+            let found = match &self.lines {
+                Some((offset, lines)) => {
+                    let input = Input::new(lines).range(self.pos - offset..self.end - offset);
+                    match self.search.fallback.find(input) {
+                        Some(found) => found.start() + offset..found.end() + offset,
+                        None => break,
+                    }
+                }
+                None => match self
+                    .search
+                    .find_in(&mut self.cache, self.text, self.pos..self.end)
+                {
+                    Ok(Some(found)) => found,
+                    Ok(None) => break,
+                    Err(err) => {
+                        log::debug!("{err:#}, searching a copy of the text instead");
+                        self.lines = Some(lines_around(self.text, self.pos..self.end));
+                        continue;
+                    }
+                },
+            };
+
+            // Empty matches move on by a character, and are skipped when they touch the previous
+            // match or fall inside a character.
+            let empty = found.is_empty();
+            self.pos = match empty {
+                true => next_char_boundary(self.text, found.end),
+                false => found.end,
+            };
+            if empty
+                && (self.last_end == Some(found.end) || !is_char_boundary(self.text, found.end))
+            {
+                continue;
+            }
+            self.last_end = Some(found.end);
+            return Some(Ok(found));
+        }
+
+        self.pos = self.end + 1;
+        None
+    }
+}
+
+// Returns `true` if `pattern` has an uppercase letter outside of an escape such as `\W`.
+pub(crate) fn has_uppercase(pattern: &str) -> bool {
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            c if c.is_uppercase() => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+fn is_char_boundary(text: &Rope, pos: usize) -> bool {
+    pos <= text.len_bytes() && text.char_to_byte(text.byte_to_char(pos)) == pos
+}
+
+// Returns the start of the character after the one at `pos`, or one past the end of the text.
+fn next_char_boundary(text: &Rope, pos: usize) -> usize {
+    if pos >= text.len_bytes() {
+        return pos + 1;
+    }
+    text.char_to_byte(text.byte_to_char(pos) + 1)
+}
+
+// Returns where the lines of `range` start and their text, from the line before to the line after
+// so that look-around assertions see the same text as in the rope.
+fn lines_around(text: &Rope, range: Range<usize>) -> (usize, String) {
+    let first = text.byte_to_line(range.start).saturating_sub(1);
+    let last = (text.byte_to_line(range.end) + 2).min(text.len_lines());
+    let lines = text.line_to_byte(first)..text.line_to_byte(last);
+    (lines.start, text.byte_slice(lines).to_string())
+}
+
+fn gave_up(err: impl fmt::Display) -> anyhow::Error {
+    anyhow!("search gave up: {err}")
+}
+
+fn quit(byte: u8) -> String {
+    format!("search gave up on byte {byte:#04x}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, text: &str) -> Vec<String> {
+        let rope = Rope::from(text);
+        Search::new(pattern, true)
+            .unwrap()
+            .find_iter(&rope, 0..rope.len_bytes())
+            .map(|found| rope.byte_slice(found.unwrap()).to_string())
+            .collect()
+    }
+
+    // A rope made of many small chunks, so that matches cross chunk boundaries.
+    fn chunked(line: &str, count: usize) -> Rope {
+        let mut rope = Rope::new();
+        for _ in 0..count {
+            rope.insert(rope.len_chars(), line);
+        }
+        rope
+    }
+
+    #[test]
+    fn finds_matches_across_chunks() {
+        assert_eq!(matches("a+", "baaa a"), ["aaa", "a"]);
+        assert_eq!(matches(r"\bfoo\b", "foo food foo"), ["foo", "foo"]);
+        assert_eq!(matches("^b", "ab\nba\nb"), ["b", "b"]);
+        assert_eq!(matches("x*", "ab"), ["", "", ""]);
+        assert_eq!(matches("é|ü", "aéü"), ["é", "ü"]);
+        assert_eq!(matches(r"o\nt", "foo\ntwo"), ["o\nt"]);
+        // The DFAs give up on `\b` next to non-ASCII text, where the meta regex takes over.
+        assert_eq!(
+            matches(r"\bfoo\b", "foo é foo foos\nfoo"),
+            ["foo", "foo", "foo"]
+        );
+        assert_eq!(matches(r"\bétat\b", "l'état états\nétat"), ["état", "état"]);
+        assert_eq!(matches(r"^\w+\b", "àb c\nd"), ["àb", "d"]);
+
+        let rope = chunked("lorem ipsum dolor sit amet, ", 500);
+        let search = Search::new(r"amet, lorem", true).unwrap();
+        let found: Vec<_> = search
+            .find_iter(&rope, 0..rope.len_bytes())
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(found.len(), 499);
+        assert!(found
+            .iter()
+            .all(|found| rope.byte_slice(found.clone()) == "amet, lorem"));
+    }
+
+    #[test]
+    fn smart_case() {
+        assert_eq!(matches("foo", "Foo foo"), ["Foo", "foo"]);
+        assert_eq!(matches("Foo", "Foo foo"), ["Foo"]);
+        assert_eq!(matches(r"\Sfoo", "xFoo"), ["xFoo"]);
+    }
+
+    #[test]
+    fn finds_next_and_previous_matches() {
+        let rope = Rope::from("one two\none two\none");
+        let search = Search::new("one", true).unwrap();
+
+        assert_eq!(
+            search.find_next(&rope, 0, true).unwrap(),
+            Some((8..11, false))
+        );
+        assert_eq!(
+            search.find_next(&rope, 8, true).unwrap(),
+            Some((16..19, false))
+        );
+        assert_eq!(
+            search.find_next(&rope, 17, true).unwrap(),
+            Some((0..3, true))
+        );
+        assert_eq!(search.find_next(&rope, 17, false).unwrap(), None);
+        assert_eq!(
+            search.find_prev(&rope, 16, true).unwrap(),
+            Some((8..11, false))
+        );
+        assert_eq!(
+            search.find_prev(&rope, 3, true).unwrap(),
+            Some((0..3, false))
+        );
+        assert_eq!(
+            search.find_prev(&rope, 0, true).unwrap(),
+            Some((16..19, true))
+        );
+    }
+
+    #[test]
+    fn expands_capture_groups() {
+        let rope = Rope::from("let x = 1;\nlet y = 2;");
+        let search = Search::new(r"let (?P<name>\w+) = (\d)", true).unwrap();
+        let found = search.find_next(&rope, 0, false).unwrap().unwrap().0;
+
+        assert_eq!(found, 11..20);
+        assert_eq!(
+            search.expand(&rope, found, "$2 -> ${name} ($0)"),
+            "2 -> y (let y = 2)"
+        );
+        assert!(Search::new("(", true)
+            .unwrap_err()
+            .to_string()
+            .starts_with("invalid pattern: "));
+    }
+}
@@ -0,0 +1,150 @@
+use anyhow::{bail, Result};
+use std::str::FromStr;
+
+/// A parsed `:s/pattern/replacement/flags` command.
+///
+/// The delimiter is the character after `s`, and may be any punctuation. A `%` before `s` applies
+/// the command to every line instead of the current ones.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Substitution {
+    /// Whether the command starts with `%`.
+    pub every_line: bool,
+    /// The pattern, empty for the last search.
+    pub pattern: String,
+    /// The replacement, in the syntax of [`Search::expand`](crate::Search::expand).
+    pub replacement: String,
+    /// Replaces every match on a line rather than the first one (`g`).
+    pub global: bool,
+    /// Asks before each replacement (`c`).
+    pub confirm: bool,
+    /// Forces case sensitivity on (`I`) or off (`i`) instead of smart case.
+    pub case_sensitive: Option<bool>,
+}
+
+impl FromStr for Substitution {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (every_line, s) = match s.strip_prefix('%') {
+            Some(s) => (true, s),
+            None => (false, s),
+        };
+        let Some(s) = s.strip_prefix('s') else {
+            bail!("invalid substitution '{s}', expected s/pattern/replacement/flags");
+        };
+        let mut chars = s.chars();
+        let delimiter = match chars.next() {
+            Some(c) if c.is_ascii_punctuation() && c != '\\' => c,
+            Some(c) => bail!("invalid delimiter '{c}', expected a punctuation character such as /"),
+            None => bail!("missing pattern, expected s/pattern/replacement/flags"),
+        };
+        let rest = chars.as_str();
+
+        let (pattern, rest) = split(rest, delimiter);
+        let (replacement, flags) = split(rest.unwrap_or_default(), delimiter);
+
+        let mut substitution = Self {
+            every_line,
+            pattern,
+            replacement: replacement_template(&replacement),
+            global: false,
+            confirm: false,
+            case_sensitive: None,
+        };
+        for flag in flags.unwrap_or_default().chars() {
+            match flag {
+                'g' => substitution.global = true,
+                'c' => substitution.confirm = true,
+                'i' => substitution.case_sensitive = Some(false),
+                'I' => substitution.case_sensitive = Some(true),
+                flag => bail!("invalid flag '{flag}', expected g, c, i or I"),
+            }
+        }
+        Ok(substitution)
+    }
+}
+
+// Splits `s` at the first `delimiter` that is not escaped, removing the escape of the delimiter.
+fn split(s: &str, delimiter: char) -> (String, Option<&str>) {
+    let mut part = String::new();
+    let mut chars = s.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some((_, next)) if next == delimiter => part.push(next),
+                Some((_, next)) => {
+                    part.push('\\');
+                    part.push(next);
+                }
+                None => part.push('\\'),
+            },
+            c if c == delimiter => return (part, Some(&s[i + c.len_utf8()..])),
+            c => part.push(c),
+        }
+    }
+    (part, None)
+}
+
+// Converts the Vim syntax of a replacement, with `&` and `\1`, to the one of the `regex` crate.
+fn replacement_template(s: &str) -> String {
+    let mut template = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '&' => template.push_str("${0}"),
+            '\\' => match chars.next() {
+                Some(digit @ '0'..='9') => {
+                    template.push_str("${");
+                    template.push(digit);
+                    template.push('}');
+                }
+                Some('n') => template.push('\n'),
+                Some('t') => template.push('\t'),
+                Some('$') => template.push_str("$$"),
+                Some(c) => template.push(c),
+                None => template.push('\\'),
+            },
+            c => template.push(c),
+        }
+    }
+    template
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_substitutions() {
+        let substitution: Substitution = r"%s/(\w+)\/x/<\1 & \&>/gI".parse().unwrap();
+        assert_eq!(
+            substitution,
+            Substitution {
+                every_line: true,
+                pattern: r"(\w+)/x".into(),
+                replacement: "<${1} ${0} &>".into(),
+                global: true,
+                confirm: false,
+                case_sensitive: Some(true),
+            }
+        );
+
+        let substitution: Substitution = "s#a#b".parse().unwrap();
+        assert!(!substitution.every_line && !substitution.global);
+        assert_eq!(
+            (
+                substitution.pattern.as_str(),
+                substitution.replacement.as_str()
+            ),
+            ("a", "b")
+        );
+        assert_eq!(
+            "s//$1\\n/c".parse::<Substitution>().unwrap().replacement,
+            "$1\n"
+        );
+
+        let err = "s/a/b/x".parse::<Substitution>().unwrap_err();
+        assert_eq!(err.to_string(), "invalid flag 'x', expected g, c, i or I");
+        assert!("sa".parse::<Substitution>().is_err());
+    }
+}
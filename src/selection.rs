@@ -0,0 +1,215 @@
+use crate::{
+    transaction::{Assoc, Transaction},
+    Buffer,
+};
+use anyhow::{bail, Error};
+use serde::{Deserialize, Serialize};
+
+/// A selection range between `anchor` and `head`, both byte offsets.
+///
+/// In Normal and Visual mode the cursor covers the grapheme starting at `head`, so a range
+/// selects the graphemes at both of its ends. In Insert mode the cursor sits before `head`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Range {
+    /// The end that stays put while the selection is extended.
+    pub anchor: usize,
+    /// The end that moves with the cursor.
+    pub head: usize,
+}
+
+impl Range {
+    /// Creates a new range.
+    pub const fn new(anchor: usize, head: usize) -> Self {
+        Self { anchor, head }
+    }
+
+    /// Creates a range with both ends at `pos`.
+    pub const fn point(pos: usize) -> Self {
+        Self::new(pos, pos)
+    }
+
+    /// Returns the smaller end.
+    pub fn from(&self) -> usize {
+        self.anchor.min(self.head)
+    }
+
+    /// Returns the larger end.
+    pub fn to(&self) -> usize {
+        self.anchor.max(self.head)
+    }
+
+    /// Returns the byte range covered in Visual mode, which includes the grapheme at the end.
+    pub fn inclusive(&self, buffer: &Buffer) -> std::ops::Range<usize> {
+        self.from()..buffer.next_grapheme_boundary(self.to())
+    }
+
+    /// Returns the range with its head moved to `head`.
+    pub fn put_head(self, head: usize) -> Self {
+        Self::new(self.anchor, head)
+    }
+
+    /// Maps the range through the changes of `transaction`.
+    pub fn map(self, transaction: &Transaction) -> Self {
+        Self::new(
+            transaction.map_pos(self.anchor, Assoc::After),
+            transaction.map_pos(self.head, Assoc::After),
+        )
+    }
+}
+
+/// A set of ranges, one of which is the primary.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "RawSelection")]
+pub struct Selection {
+    ranges: Vec<Range>,
+    primary: usize,
+}
+
+// A selection as deserialized, before it is checked to have a primary range.
+#[derive(Deserialize)]
+struct RawSelection {
+    ranges: Vec<Range>,
+    primary: usize,
+}
+
+impl TryFrom<RawSelection> for Selection {
+    type Error = Error;
+
+    fn try_from(raw: RawSelection) -> Result<Self, Error> {
+        if raw.primary >= raw.ranges.len() {
+            bail!("selection must have a primary range");
+        }
+        Ok(Self::new(raw.ranges, raw.primary))
+    }
+}
+
+impl Default for Selection {
+    fn default() -> Self {
+        Self::point(0)
+    }
+}
+
+impl Selection {
+    /// Creates a selection from `ranges`, with the range at `primary` as the primary.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `ranges` is empty or `primary` is out of bounds.
+    pub fn new(ranges: Vec<Range>, primary: usize) -> Self {
+        assert!(
+            primary < ranges.len(),
+            "selection must have a primary range"
+        );
+        Self { ranges, primary }
+    }
+
+    /// Creates a selection of a single cursor at `pos`.
+    pub fn point(pos: usize) -> Self {
+        Self::single(Range::point(pos))
+    }
+
+    /// Creates a selection of a single range.
+    pub fn single(range: Range) -> Self {
+        Self {
+            ranges: vec![range],
+            primary: 0,
+        }
+    }
+
+    /// Returns all ranges.
+    pub fn ranges(&self) -> &[Range] {
+        &self.ranges
+    }
+
+    /// Returns the primary range.
+    pub fn primary(&self) -> Range {
+        self.ranges[self.primary]
+    }
+
+    /// Returns the index of the primary range.
+    pub fn primary_index(&self) -> usize {
+        self.primary
+    }
+
+    /// Applies `f` to every range.
+    pub fn transform<F: FnMut(Range) -> Range>(mut self, f: F) -> Self {
+        self.ranges = self.ranges.into_iter().map(f).collect();
+        self
+    }
+
+    /// Maps every range through the changes of `transaction`.
+    pub fn map(self, transaction: &Transaction) -> Self {
+        self.transform(|range| range.map(transaction))
+    }
+
+    /// Adds `range` and makes it the primary.
+    pub fn push(mut self, range: Range) -> Self {
+        self.ranges.push(range);
+        self.primary = self.ranges.len() - 1;
+        self
+    }
+
+    /// Sorts the ranges and merges the ones that overlap, counting the graphemes at both ends of
+    /// a range as selected.
+    ///
+    /// A merged range spans all the ranges merged into it, points the way the first of them
+    /// does, and is the primary if any of them was.
+    pub fn normalize(self) -> Self {
+        if self.ranges.len() == 1 {
+            return self;
+        }
+
+        let mut ranges: Vec<_> = self.ranges.into_iter().enumerate().collect();
+        ranges.sort_by_key(|(_, range)| range.from());
+
+        let mut merged: Vec<Range> = Vec::with_capacity(ranges.len());
+        let mut primary = 0;
+        for (index, range) in ranges {
+            match merged.last_mut() {
+                Some(last) if range.from() <= last.to() => {
+                    let (from, to) = (last.from(), last.to().max(range.to()));
+                    *last = match last.head < last.anchor {
+                        true => Range::new(to, from),
+                        false => Range::new(from, to),
+                    };
+                }
+                _ => merged.push(range),
+            }
+            if index == self.primary {
+                primary = merged.len() - 1;
+            }
+        }
+
+        Self {
+            ranges: merged,
+            primary,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_merges_overlapping_ranges() {
+        let selection = Selection::new(
+            vec![
+                Range::new(8, 6),
+                Range::point(0),
+                Range::new(2, 4),
+                Range::point(4),
+                Range::point(7),
+                Range::point(0),
+            ],
+            3,
+        )
+        .normalize();
+
+        assert_eq!(
+            selection.ranges(),
+            [Range::point(0), Range::new(2, 4), Range::new(8, 6)]
+        );
+        assert_eq!(selection.primary(), Range::new(2, 4));
+    }
+}
@@ -0,0 +1,255 @@
+//! Sessions: the open buffers, the views with their layout, cursors, scroll positions and
+//! jumplists, and the registers, saved under a name with `:session save` and restored with
+//! `--session`.
+
+use crate::{history::fnv1a, metadata, JumpPoint, SavedRegisters, Selection};
+use anyhow::{anyhow, bail, Result};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::path::{Path, PathBuf};
+use tissue_tui::{LayoutTree, Split};
+
+/// A saved set of buffers and views.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Session {
+    /// The index of the view with focus.
+    pub focus: usize,
+    /// The open buffers.
+    pub buffers: Vec<SessionBuffer>,
+    /// The arrangement of the views.
+    #[serde(with = "layout")]
+    pub layout: LayoutTree,
+    /// The views, from top left to bottom right, like the views of the layout.
+    pub views: Vec<SessionView>,
+    /// The registers.
+    #[serde(default)]
+    pub registers: SavedRegisters,
+}
+
+/// A buffer of a [`Session`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct SessionBuffer {
+    /// The absolute path of the file, or `None` for a scratch buffer, whose text is not saved.
+    pub path: Option<PathBuf>,
+    /// The selection of the buffer.
+    pub selection: Selection,
+}
+
+/// A view of a [`Session`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct SessionView {
+    /// The index of the buffer shown.
+    pub buffer: usize,
+    /// The first line shown.
+    pub scroll: usize,
+    /// The cursors of the view.
+    pub selection: Selection,
+    /// The jumplist of the view, the most recent place last.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub jumps: Vec<JumpPoint>,
+}
+
+impl Session {
+    /// Returns the file that the session called `name` is saved to.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `name` cannot be a file name.
+    pub fn file(name: &str) -> Result<PathBuf> {
+        if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+            bail!("invalid session name '{name}'");
+        }
+        Ok(sessions_dir().join(format!("{name}.toml")))
+    }
+
+    /// Returns the file that the session of the workspace holding `dir` is saved to. The
+    /// workspace is the git repository holding `dir`, or `dir` itself outside of one.
+    pub fn workspace_file(dir: &Path) -> Result<PathBuf> {
+        let root = metadata::workspace_root(dir, &[]).unwrap_or_else(|| dir.to_path_buf());
+        let root = root.canonicalize()?;
+        let hash = fnv1a([root.as_os_str().to_string_lossy().as_bytes()]);
+        Ok(sessions_dir()
+            .join("workspaces")
+            .join(format!("{hash:016x}.toml")))
+    }
+
+    /// Reads the session saved to `file`.
+    pub fn load(file: &Path) -> Result<Self> {
+        let s = std::fs::read_to_string(file)?;
+        let session: Self = toml::from_str(&s).map_err(|err| anyhow!(err))?;
+        session.validate()?;
+        Ok(session)
+    }
+
+    /// Saves the session to `file`.
+    pub fn save(&self, file: &Path) -> Result<()> {
+        let s = toml::to_string(self)?;
+        if let Some(dir) = file.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(file, s)?;
+        Ok(())
+    }
+
+    // Checks that the views match the layout and show buffers of the session.
+    fn validate(&self) -> Result<()> {
+        fn count(tree: &LayoutTree) -> usize {
+            match tree {
+                LayoutTree::Split(_, children) if !children.is_empty() => {
+                    children.iter().map(|(child, _)| count(child)).sum()
+                }
+                _ => 1,
+            }
+        }
+        if count(&self.layout) != self.views.len() {
+            bail!("the layout has {} views", count(&self.layout));
+        }
+        if self.focus >= self.views.len() {
+            bail!("view {} does not exist", self.focus);
+        }
+        let mut buffers = self.views.iter().flat_map(|view| {
+            let jumps = view.jumps.iter().map(|point| point.buffer);
+            std::iter::once(view.buffer).chain(jumps)
+        });
+        if let Some(buffer) = buffers.find(|&buffer| buffer >= self.buffers.len()) {
+            bail!("buffer {buffer} does not exist");
+        }
+        Ok(())
+    }
+}
+
+fn sessions_dir() -> PathBuf {
+    metadata::cache_dir().join("sessions")
+}
+
+// Saves a `LayoutTree` as nested tables, which unlike enum variants with data can be written as
+// TOML.
+mod layout {
+    use super::*;
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename_all = "kebab-case")]
+    enum SplitKind {
+        Horizontal,
+        Vertical,
+    }
+
+    // A view, without a split, or a split of children.
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename_all = "kebab-case", deny_unknown_fields)]
+    struct Node {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        split: Option<SplitKind>,
+        #[serde(default = "default_weight")]
+        weight: f32,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        children: Vec<Node>,
+    }
+
+    fn default_weight() -> f32 {
+        1.0
+    }
+
+    fn to_node(tree: &LayoutTree, weight: f32) -> Node {
+        let (split, children) = match tree {
+            LayoutTree::View => (None, Vec::new()),
+            LayoutTree::Split(split, children) => {
+                let split = match split {
+                    Split::Horizontal => SplitKind::Horizontal,
+                    Split::Vertical => SplitKind::Vertical,
+                };
+                let children = children.iter().map(|(child, w)| to_node(child, *w));
+                (Some(split), children.collect())
+            }
+        };
+        Node {
+            split,
+            weight,
+            children,
+        }
+    }
+
+    fn to_tree(node: Node) -> (LayoutTree, f32) {
+        let tree = match node.split {
+            None => LayoutTree::View,
+            Some(split) => {
+                let split = match split {
+                    SplitKind::Horizontal => Split::Horizontal,
+                    SplitKind::Vertical => Split::Vertical,
+                };
+                LayoutTree::Split(split, node.children.into_iter().map(to_tree).collect())
+            }
+        };
+        (tree, node.weight)
+    }
+
+    pub fn serialize<S: Serializer>(tree: &LayoutTree, serializer: S) -> Result<S::Ok, S::Error> {
+        to_node(tree, 1.0).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<LayoutTree, D::Error> {
+        Node::deserialize(deserializer).map(|node| to_tree(node).0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Range;
+
+    #[test]
+    fn sessions_round_trip_through_files() {
+        let view = |buffer, scroll| SessionView {
+            buffer,
+            scroll,
+            selection: Selection::single(Range::new(1, 3)),
+            jumps: vec![JumpPoint {
+                buffer: 1 - buffer,
+                selection: Selection::point(5),
+            }],
+        };
+        let session = Session {
+            focus: 1,
+            buffers: vec![
+                SessionBuffer {
+                    path: Some("/tmp/one.rs".into()),
+                    selection: Selection::point(2),
+                },
+                SessionBuffer {
+                    path: None,
+                    selection: Selection::default(),
+                },
+            ],
+            layout: LayoutTree::Split(
+                Split::Vertical,
+                vec![
+                    (LayoutTree::View, 1.0),
+                    (
+                        LayoutTree::Split(
+                            Split::Horizontal,
+                            vec![(LayoutTree::View, 0.5), (LayoutTree::View, 1.5)],
+                        ),
+                        2.0,
+                    ),
+                ],
+            ),
+            views: vec![view(0, 4), view(1, 0), view(0, 0)],
+            registers: SavedRegisters::default(),
+        };
+
+        let temp = tempfile::tempdir().unwrap();
+        let file = temp.path().join("session.toml");
+        session.save(&file).unwrap();
+        assert_eq!(Session::load(&file).unwrap(), session);
+
+        let mut broken = session.clone();
+        broken.views.pop();
+        assert!(broken.validate().is_err());
+        let mut broken = session;
+        broken.views[0].jumps[0].buffer = 2;
+        assert!(broken.validate().is_err());
+        assert!(Session::file("../work").is_err());
+    }
+}
@@ -0,0 +1,420 @@
+//! Tree-sitter parsing and highlighting.
+//!
+//! Grammars are shared libraries in the `grammars` directory of [`metadata::runtime_dir`], named
+//! after [`LanguageConfig::grammar`]. Queries are in `queries/<language>`: `highlights.scm`,
+//! `textobjects.scm` and `indents.scm`.
+
+use crate::{metadata, LanguageConfig, Transaction};
+use anyhow::{anyhow, Context, Result};
+use ropey::Rope;
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    fmt,
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tree_sitter::{InputEdit, Language, Node, Parser, Point, Query, QueryCursor, Tree};
+
+/// A kind of query file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueryKind {
+    /// Captures name the scopes to highlight nodes with.
+    Highlights,
+    /// Captures name text objects such as `function.inside`.
+    Textobjects,
+    /// Captures name nodes that change the indentation.
+    Indents,
+}
+
+impl QueryKind {
+    fn file_name(self) -> &'static str {
+        match self {
+            Self::Highlights => "highlights.scm",
+            Self::Textobjects => "textobjects.scm",
+            Self::Indents => "indents.scm",
+        }
+    }
+}
+
+/// Returns the path of the shared library of `grammar` in `runtime_dir`.
+pub fn grammar_path(runtime_dir: &Path, grammar: &str) -> PathBuf {
+    runtime_dir
+        .join("grammars")
+        .join(grammar)
+        .with_extension(std::env::consts::DLL_EXTENSION)
+}
+
+/// Returns the path of the query file of `kind` for `language` in `runtime_dir`.
+pub fn query_path(runtime_dir: &Path, language: &LanguageConfig, kind: QueryKind) -> PathBuf {
+    runtime_dir
+        .join("queries")
+        .join(&language.name)
+        .join(kind.file_name())
+}
+
+/// Loads `grammar` from the shared library at `path`.
+pub fn load_grammar(path: &Path, grammar: &str) -> Result<Language> {
+    let symbol = format!("tree_sitter_{}", grammar.replace('-', "_"));
+
+    // SAFETY: grammars are built by tree-sitter and export a single function returning the
+    // language.
+    unsafe {
+        let library = libloading::Library::new(path)
+            .with_context(|| format!("unable to load grammar {}", path.display()))?;
+        let language = {
+            let constructor: libloading::Symbol<unsafe extern "C" fn() -> Language> = library
+                .get(symbol.as_bytes())
+                .with_context(|| format!("{} has no '{symbol}'", path.display()))?;
+            constructor()
+        };
+        // The language points into the library, so it must stay loaded.
+        std::mem::forget(library);
+        Ok(language)
+    }
+}
+
+/// A grammar with its highlight query.
+pub struct HighlightConfig {
+    language: Language,
+    query: Query,
+}
+
+impl HighlightConfig {
+    /// Compiles the highlight query `highlights` for `language`.
+    pub fn new(language: Language, highlights: &str) -> Result<Self> {
+        let query = Query::new(language, highlights).map_err(|err| anyhow!("{err:?}"))?;
+        Ok(Self { language, query })
+    }
+
+    /// Returns the scopes the captures of the query map to, such as `keyword.function`.
+    pub fn scopes(&self) -> &[String] {
+        self.query.capture_names()
+    }
+}
+
+impl fmt::Debug for HighlightConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("HighlightConfig")
+            .field("scopes", &self.scopes())
+            .finish()
+    }
+}
+
+/// Loads grammars and highlight queries from the runtime directory, once per language.
+#[derive(Debug)]
+pub struct Loader {
+    runtime_dir: PathBuf,
+    // `None` if loading failed, so that it is not retried.
+    configs: HashMap<String, Option<Arc<HighlightConfig>>>,
+}
+
+impl Default for Loader {
+    fn default() -> Self {
+        Self::new(metadata::runtime_dir())
+    }
+}
+
+impl Loader {
+    /// Creates a loader for the runtime directory `runtime_dir`.
+    pub fn new(runtime_dir: PathBuf) -> Self {
+        Self {
+            runtime_dir,
+            configs: HashMap::new(),
+        }
+    }
+
+    /// Returns the highlight configuration of `language`, or `None` if it has no grammar or the
+    /// grammar or its query cannot be loaded.
+    pub fn highlight_config(&mut self, language: &LanguageConfig) -> Option<Arc<HighlightConfig>> {
+        if let Some(config) = self.configs.get(&language.name) {
+            return config.clone();
+        }
+
+        let config = match self.load(language) {
+            Ok(config) => config.map(Arc::new),
+            Err(err) => {
+                log::error!("failed to load syntax for {}: {err:#}", language.name);
+                None
+            }
+        };
+        self.configs.insert(language.name.clone(), config.clone());
+        config
+    }
+
+    fn load(&self, language: &LanguageConfig) -> Result<Option<HighlightConfig>> {
+        let Some(grammar) = &language.grammar else {
+            return Ok(None);
+        };
+        let ts_language = load_grammar(&grammar_path(&self.runtime_dir, grammar), grammar)?;
+        let path = query_path(&self.runtime_dir, language, QueryKind::Highlights);
+        let highlights = std::fs::read_to_string(&path)
+            .with_context(|| format!("unable to read {}", path.display()))?;
+        HighlightConfig::new(ts_language, &highlights).map(Some)
+    }
+}
+
+/// The syntax tree of a buffer, kept up to date as the buffer is edited.
+#[derive(Clone)]
+pub struct Syntax {
+    config: Arc<HighlightConfig>,
+    tree: Tree,
+}
+
+impl Syntax {
+    /// Parses `text`.
+    pub fn new(text: &Rope, config: Arc<HighlightConfig>) -> Result<Self> {
+        let tree = parse(text, &config, None)?;
+        Ok(Self { config, tree })
+    }
+
+    /// Returns the syntax tree.
+    pub fn tree(&self) -> &Tree {
+        &self.tree
+    }
+
+    /// Reparses the text after `transaction` turned `old_text` into `text`, reusing the parts of
+    /// the tree that did not change.
+    pub fn update(
+        &mut self,
+        old_text: &Rope,
+        text: &Rope,
+        transaction: &Transaction,
+    ) -> Result<()> {
+        // Back to front, so that the positions of each change are still those of `old_text`.
+        for change in transaction.changes().iter().rev() {
+            let start_position = point(old_text, change.from);
+            let new_end_position = match change.text.rfind('\n') {
+                Some(last) => Point::new(
+                    start_position.row + change.text.matches('\n').count(),
+                    change.text.len() - last - 1,
+                ),
+                None => Point::new(
+                    start_position.row,
+                    start_position.column + change.text.len(),
+                ),
+            };
+            self.tree.edit(&InputEdit {
+                start_byte: change.from,
+                old_end_byte: change.to,
+                new_end_byte: change.from + change.text.len(),
+                start_position,
+                old_end_position: point(old_text, change.to),
+                new_end_position,
+            });
+        }
+
+        self.tree = parse(text, &self.config, Some(&self.tree))?;
+        Ok(())
+    }
+
+    /// Returns the highlighted spans of `text` within `range`, in order, each with its scope.
+    ///
+    /// Where captures overlap, the innermost node wins. Captures of the same node go to the
+    /// pattern that comes first in the query.
+    pub fn highlights(&self, text: &Rope, range: Range<usize>) -> Vec<(Range<usize>, &str)> {
+        let scopes = self.config.scopes();
+        let mut cursor = QueryCursor::new();
+        cursor.set_byte_range(range.clone());
+        let provider = |node: Node| {
+            text.byte_slice(node.byte_range())
+                .chunks()
+                .map(str::as_bytes)
+        };
+
+        let mut captures: Vec<_> = cursor
+            .captures(&self.config.query, self.tree.root_node(), provider)
+            .map(|(m, index)| {
+                let capture = m.captures[index];
+                (
+                    capture.node.byte_range(),
+                    m.pattern_index,
+                    capture.index as usize,
+                )
+            })
+            // Captures starting with an underscore are only used by predicates.
+            .filter(|(_, _, scope)| !scopes[*scope].starts_with('_'))
+            .collect();
+        captures.sort_by_key(|(node, pattern, _)| (node.start, Reverse(node.end), *pattern));
+
+        // Paint the captures over the range, outer nodes first.
+        let mut painted: Vec<Option<usize>> = vec![None; range.len()];
+        for (i, (node, _, _)) in captures.iter().enumerate() {
+            let start = node.start.max(range.start) - range.start;
+            let end = node.end.min(range.end).saturating_sub(range.start);
+            for byte in painted.iter_mut().take(end).skip(start) {
+                match *byte {
+                    Some(j) if captures[j].0 == *node => {}
+                    _ => *byte = Some(i),
+                }
+            }
+        }
+
+        let mut spans: Vec<(Range<usize>, &str)> = Vec::new();
+        for (offset, capture) in painted.into_iter().enumerate() {
+            let Some(capture) = capture else {
+                continue;
+            };
+            let byte = range.start + offset;
+            let scope = scopes[captures[capture].2].as_str();
+            match spans.last_mut() {
+                Some((span, last)) if span.end == byte && *last == scope => span.end += 1,
+                _ => spans.push((byte..byte + 1, scope)),
+            }
+        }
+        spans
+    }
+
+    /// Returns `true` if `pos` is inside a string or a comment of `text`: after its first
+    /// character and before its last, or at the end of a line comment.
+    pub fn in_string_or_comment(&self, text: &Rope, pos: usize) -> bool {
+        let Some(node) = pos
+            .checked_sub(1)
+            .and_then(|start| self.tree.root_node().descendant_for_byte_range(start, pos))
+        else {
+            return false;
+        };
+        let mut node = Some(node);
+        while let Some(current) = node {
+            let kind = current.kind();
+            let range = current.byte_range();
+            let line_comment = kind.contains("comment")
+                && !text.byte_slice(range.clone()).to_string().ends_with("*/");
+            if (kind.contains("string") || kind.contains("comment"))
+                && range.start < pos
+                && (pos < range.end || (line_comment && pos == range.end))
+            {
+                return true;
+            }
+            node = current.parent();
+        }
+        false
+    }
+}
+
+impl fmt::Debug for Syntax {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Syntax")
+            .field("config", &self.config)
+            .finish_non_exhaustive()
+    }
+}
+
+fn parse(text: &Rope, config: &HighlightConfig, old_tree: Option<&Tree>) -> Result<Tree> {
+    let mut parser = Parser::new();
+    parser
+        .set_language(config.language)
+        .map_err(|err| anyhow!("{err}"))?;
+    parser
+        .parse_with(
+            &mut |byte, _| {
+                if byte >= text.len_bytes() {
+                    return &[][..];
+                }
+                let (chunk, start, _, _) = text.chunk_at_byte(byte);
+                &chunk.as_bytes()[byte - start..]
+            },
+            old_tree,
+        )
+        .ok_or_else(|| anyhow!("parsing was cancelled"))
+}
+
+fn point(text: &Rope, byte: usize) -> Point {
+    let line = text.byte_to_line(byte);
+    Point::new(line, byte - text.line_to_byte(line))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HIGHLIGHTS: &str = r#"
+        (function_item name: (identifier) @function)
+        (identifier) @variable
+        "fn" @keyword.function
+        "let" @keyword
+        (string_literal) @string
+        ((identifier) @_self (#eq? @_self "unused"))
+    "#;
+
+    fn config() -> Arc<HighlightConfig> {
+        Arc::new(HighlightConfig::new(tree_sitter_rust::language(), HIGHLIGHTS).unwrap())
+    }
+
+    fn spans(syntax: &Syntax, text: &Rope) -> Vec<(String, String)> {
+        syntax
+            .highlights(text, 0..text.len_bytes())
+            .into_iter()
+            .map(|(range, scope)| (text.byte_slice(range).to_string(), scope.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn captures_map_to_scopes() {
+        let text = Rope::from("fn main() { let s = \"a b\"; }");
+        let syntax = Syntax::new(&text, config()).unwrap();
+
+        let expected = [
+            ("fn", "keyword.function"),
+            ("main", "function"),
+            ("let", "keyword"),
+            ("s", "variable"),
+            ("\"a b\"", "string"),
+        ];
+        assert_eq!(
+            spans(&syntax, &text),
+            expected.map(|(text, scope)| (text.to_string(), scope.to_string()))
+        );
+
+        // Only the captures within the range are returned, clipped to it.
+        let highlights = syntax.highlights(&text, 4..16);
+        assert_eq!(highlights, [(4..7, "function"), (12..15, "keyword")]);
+    }
+
+    #[test]
+    fn finds_strings_and_comments() {
+        let text = Rope::from("let s = \"ab\"; /* c */ // d\n");
+        let syntax = Syntax::new(&text, config()).unwrap();
+
+        let inside: Vec<_> = (0..text.len_bytes())
+            .filter(|&pos| syntax.in_string_or_comment(&text, pos))
+            .collect();
+        assert_eq!(inside, [9, 10, 11, 15, 16, 17, 18, 19, 20, 23, 24, 25, 26]);
+    }
+
+    #[test]
+    fn edits_reparse_incrementally() {
+        let mut text = Rope::from("fn a() {}\nfn b() {}\n");
+        let mut syntax = Syntax::new(&text, config()).unwrap();
+        let transaction = Transaction::change([(3, 4, "first"), (17, 19, "{ let x = \"é\";\n}")]);
+
+        let old_text = text.clone();
+        for change in transaction.changes().iter().rev() {
+            text.remove(text.byte_to_char(change.from)..text.byte_to_char(change.to));
+            text.insert(text.byte_to_char(change.from), &change.text);
+        }
+        syntax.update(&old_text, &text, &transaction).unwrap();
+
+        let fresh = Syntax::new(&text, config()).unwrap();
+        assert_eq!(
+            syntax.tree().root_node().to_sexp(),
+            fresh.tree().root_node().to_sexp()
+        );
+        assert!(spans(&syntax, &text).contains(&("first".into(), "function".into())));
+    }
+
+    #[test]
+    fn languages_without_grammar_have_no_syntax() {
+        let mut loader = Loader::new(std::env::temp_dir().join("tissue-no-runtime"));
+        let mut language = LanguageConfig {
+            name: "rust".into(),
+            ..LanguageConfig::default()
+        };
+        assert!(loader.highlight_config(&language).is_none());
+
+        language.name = "missing".into();
+        language.grammar = Some("missing".into());
+        assert!(loader.highlight_config(&language).is_none());
+    }
+}
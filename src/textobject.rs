@@ -0,0 +1,257 @@
+//! Text objects selected after an operator or in Visual mode (`iw`, `a(`, `ip`, ...).
+
+use crate::{motion, Buffer};
+use std::ops::Range;
+
+/// A text object.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextObject {
+    /// A word (`w`) or, when `big` is set, a WORD (`W`).
+    Word {
+        /// Treat every non-blank character as part of the word.
+        big: bool,
+    },
+    /// A paragraph (`p`).
+    Paragraph,
+    /// A bracket pair such as `(`/`)`.
+    Pair(char, char),
+    /// A quoted string.
+    Quote(char),
+}
+
+impl TextObject {
+    /// Returns the text object named by `c`, as typed after `i` or `a`.
+    pub fn from_char(c: char) -> Option<Self> {
+        Some(match c {
+            'w' => Self::Word { big: false },
+            'W' => Self::Word { big: true },
+            'p' => Self::Paragraph,
+            '(' | ')' | 'b' => Self::Pair('(', ')'),
+            '{' | '}' | 'B' => Self::Pair('{', '}'),
+            '[' | ']' => Self::Pair('[', ']'),
+            '<' | '>' => Self::Pair('<', '>'),
+            '"' | '\'' | '`' => Self::Quote(c),
+            _ => return None,
+        })
+    }
+
+    /// Returns `true` if the object covers whole lines.
+    pub fn is_linewise(&self) -> bool {
+        matches!(self, Self::Paragraph)
+    }
+
+    /// Returns the byte range of the object around `pos`. `inner` excludes surrounding
+    /// whitespace or delimiters.
+    pub fn range(&self, buffer: &Buffer, pos: usize, inner: bool) -> Option<Range<usize>> {
+        match *self {
+            Self::Word { big } => Some(word(buffer, pos, inner, big)),
+            Self::Paragraph => Some(paragraph(buffer, pos, inner)),
+            Self::Pair(open, close) => pair(buffer, pos, open, close, inner),
+            Self::Quote(quote) => quoted(buffer, pos, quote, inner),
+        }
+    }
+}
+
+fn word(buffer: &Buffer, pos: usize, inner: bool, big: bool) -> Range<usize> {
+    let Some(ch) = buffer.char_at(pos) else {
+        return pos..pos;
+    };
+    let same = |&(_, c): &(usize, char)| motion::same_word(c, ch, big) && c != '\n';
+
+    let start = buffer
+        .chars_before(pos)
+        .take_while(same)
+        .last()
+        .map_or(pos, |(i, _)| i);
+    let end = buffer
+        .chars_at(pos)
+        .take_while(same)
+        .last()
+        .map_or(pos, |(i, c)| i + c.len_utf8());
+
+    if inner {
+        return start..end;
+    }
+
+    // `aw` takes the trailing blanks, or the leading ones when there are none.
+    let blank = |&(_, c): &(usize, char)| c == ' ' || c == '\t';
+    let trailing = buffer
+        .chars_at(end)
+        .take_while(blank)
+        .last()
+        .map(|(i, c)| i + c.len_utf8());
+
+    match trailing {
+        Some(trailing) => start..trailing,
+        None => {
+            let leading = buffer
+                .chars_before(start)
+                .take_while(blank)
+                .last()
+                .map_or(start, |(i, _)| i);
+            leading..end
+        }
+    }
+}
+
+fn paragraph(buffer: &Buffer, pos: usize, inner: bool) -> Range<usize> {
+    let last = buffer.len_lines() - 1;
+    let line = buffer.byte_to_pos(pos).line;
+    let blank = motion::is_blank_line(buffer, line);
+
+    let mut first = line;
+    while first > 0 && motion::is_blank_line(buffer, first - 1) == blank {
+        first -= 1;
+    }
+    let mut end = line;
+    while end < last && motion::is_blank_line(buffer, end + 1) == blank {
+        end += 1;
+    }
+    if !inner {
+        while end < last && motion::is_blank_line(buffer, end + 1) != blank {
+            end += 1;
+        }
+    }
+
+    let to = if end < last {
+        buffer.line_start(end + 1)
+    } else {
+        buffer.len_bytes()
+    };
+
+    buffer.line_start(first)..to
+}
+
+fn pair(buffer: &Buffer, pos: usize, open: char, close: char, inner: bool) -> Option<Range<usize>> {
+    let start = match buffer.char_at(pos) {
+        Some(c) if c == open => pos,
+        _ => {
+            let mut depth = 0usize;
+            let from = match buffer.char_at(pos) {
+                Some(c) if c == close => pos,
+                _ => buffer.next_grapheme_boundary(pos),
+            };
+            buffer.chars_before(from).find_map(|(i, c)| {
+                if c == close {
+                    depth += 1;
+                } else if c == open {
+                    if depth == 0 {
+                        return Some(i);
+                    }
+                    depth -= 1;
+                }
+                None
+            })?
+        }
+    };
+
+    let mut depth = 0usize;
+    let end = buffer
+        .chars_at(start + open.len_utf8())
+        .find_map(|(i, c)| {
+            if c == open {
+                depth += 1;
+            } else if c == close {
+                if depth == 0 {
+                    return Some(i);
+                }
+                depth -= 1;
+            }
+            None
+        })?;
+
+    Some(if inner {
+        start + open.len_utf8()..end
+    } else {
+        start..end + close.len_utf8()
+    })
+}
+
+fn quoted(buffer: &Buffer, pos: usize, quote: char, inner: bool) -> Option<Range<usize>> {
+    let line = buffer.byte_to_pos(pos).line;
+    let (start, end) = (buffer.line_start(line), buffer.line_end(line));
+
+    let mut escaped = false;
+    let quotes: Vec<_> = buffer
+        .chars_at(start)
+        .take_while(|&(i, _)| i < end)
+        .filter_map(|(i, c)| {
+            let found = c == quote && !escaped;
+            escaped = c == '\\' && !escaped;
+            found.then_some(i)
+        })
+        .collect();
+
+    // Quotes pair up from the start of the line; the first pair that ends at or after the
+    // cursor is used, like Vim does.
+    let (open, close) = quotes
+        .chunks_exact(2)
+        .map(|pair| (pair[0], pair[1]))
+        .find(|&(_, close)| close >= pos)?;
+
+    if inner {
+        return Some(open + quote.len_utf8()..close);
+    }
+
+    // Like Vim, the outer object takes the whitespace after the closing quote, or the whitespace
+    // before the opening one if there is none after it.
+    let is_blank = |&(i, c): &(usize, char)| i < end && (c == ' ' || c == '\t');
+    let after = close + quote.len_utf8();
+    let trailing = buffer
+        .chars_at(after)
+        .take_while(is_blank)
+        .last()
+        .map_or(after, |(i, c)| i + c.len_utf8());
+
+    Some(if trailing > after {
+        open..trailing
+    } else {
+        let leading = buffer
+            .chars_before(open)
+            .take_while(|&(i, c)| i >= start && (c == ' ' || c == '\t'))
+            .last()
+            .map_or(open, |(i, _)| i);
+        leading..after
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn select(text: &str, pos: usize, object: char, inner: bool) -> Option<&str> {
+        let buffer = Buffer::from(text);
+        let range = TextObject::from_char(object)?.range(&buffer, pos, inner)?;
+        text.get(range)
+    }
+
+    #[test]
+    fn words() {
+        assert_eq!(select("foo bar  baz", 5, 'w', true), Some("bar"));
+        assert_eq!(select("foo bar  baz", 5, 'w', false), Some("bar  "));
+        assert_eq!(select("foo bar", 5, 'w', false), Some(" bar"));
+        assert_eq!(select("a.b-c d", 2, 'W', true), Some("a.b-c"));
+    }
+
+    #[test]
+    fn pairs_and_quotes() {
+        let text = "f(a, (b), c)";
+        assert_eq!(select(text, 3, '(', true), Some("a, (b), c"));
+        assert_eq!(select(text, 6, 'b', false), Some("(b)"));
+        assert_eq!(select(text, 11, ')', true), Some("a, (b), c"));
+        assert_eq!(select("x", 0, '(', true), None);
+
+        let text = r#"say "hi \"there\"" now"#;
+        assert_eq!(select(text, 6, '"', true), Some(r#"hi \"there\""#));
+        assert_eq!(select(text, 0, '"', false), Some(r#""hi \"there\"" "#));
+        assert_eq!(select("a 'b'", 3, '\'', false), Some(" 'b'"));
+    }
+
+    #[test]
+    fn paragraphs() {
+        let text = "a\nb\n\n\nc\n";
+        assert_eq!(select(text, 0, 'p', true), Some("a\nb\n"));
+        assert_eq!(select(text, 0, 'p', false), Some("a\nb\n\n\n"));
+        assert_eq!(select(text, 4, 'p', true), Some("\n\n"));
+    }
+}
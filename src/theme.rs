@@ -0,0 +1,649 @@
+//! Styles for the scopes that highlight captures and the UI map to.
+//!
+//! Themes are TOML files mapping dotted scopes to styles:
+//!
+//! ```toml
+//! inherits = "default"
+//!
+//! "keyword" = "purple"
+//! "ui.statusline" = { fg = "white", bg = "#2c313a", modifiers = ["bold"] }
+//!
+//! [palette]
+//! purple = "#c678dd"
+//! ```
+//!
+//! A style is either a color, used as the foreground, or a table with `fg`, `bg` and `modifiers`.
+//! Colors are palette names, ANSI names such as `light-red`, `#rrggbb` or 256-color indices.
+
+use crate::metadata;
+use anyhow::{anyhow, bail, Context, Result};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+use tissue_tui::{Color, Modifier, Style};
+use toml::{value::Table, Value};
+
+// Themes shipped with the editor, found after the user's theme directories.
+const BUILTIN: &[(&str, &str)] = &[
+    ("default", include_str!("./theme/default.toml")),
+    ("night", include_str!("./theme/night.toml")),
+];
+
+const ANSI_COLORS: &[(&str, Color)] = &[
+    ("reset", Color::Reset),
+    ("black", Color::Black),
+    ("red", Color::Red),
+    ("green", Color::Green),
+    ("yellow", Color::Yellow),
+    ("blue", Color::Blue),
+    ("magenta", Color::Magenta),
+    ("cyan", Color::Cyan),
+    ("gray", Color::Gray),
+    ("light-red", Color::LightRed),
+    ("light-green", Color::LightGreen),
+    ("light-yellow", Color::LightYellow),
+    ("light-blue", Color::LightBlue),
+    ("light-magenta", Color::LightMagenta),
+    ("light-cyan", Color::LightCyan),
+    ("light-gray", Color::LightGray),
+    ("white", Color::White),
+];
+
+const MODIFIERS: &[(&str, Modifier)] = &[
+    ("bold", Modifier::BOLD),
+    ("dim", Modifier::DIM),
+    ("italic", Modifier::ITALIC),
+    ("underlined", Modifier::UNDERLINED),
+    ("slow-blink", Modifier::SLOW_BLINK),
+    ("rapid-blink", Modifier::RAPID_BLINK),
+    ("reversed", Modifier::REVERSED),
+    ("hidden", Modifier::HIDDEN),
+    ("crossed-out", Modifier::CROSSED_OUT),
+];
+
+/// Maps dotted scopes such as `keyword.function` or `ui.statusline` to styles.
+#[derive(Debug, Clone)]
+pub struct Theme {
+    name: String,
+    scopes: HashMap<String, Style>,
+}
+
+impl Theme {
+    /// Loads the theme called `name` from the theme directories, see [`ThemeLoader`].
+    ///
+    /// Returns the theme along with warnings about the parts of it that were ignored.
+    pub fn load(name: &str) -> Result<(Self, Vec<String>)> {
+        ThemeLoader::default().load(name)
+    }
+
+    /// Builds a theme from a parsed theme file whose inheritance has been resolved.
+    pub fn from_table(name: &str, mut table: Table) -> (Self, Vec<String>) {
+        let mut warnings = Vec::new();
+        let palette = match table.remove("palette") {
+            Some(Value::Table(palette)) => palette,
+            Some(_) => {
+                warnings.push("'palette' must be a table".to_string());
+                Table::new()
+            }
+            None => Table::new(),
+        };
+        if table.remove("inherits").is_some() {
+            warnings.push("'inherits' is ignored, load the theme to resolve it".to_string());
+        }
+
+        let mut scopes = HashMap::new();
+        for (scope, value) in table {
+            let mut warn = |message: String| warnings.push(format!("'{scope}': {message}"));
+            if let Some(style) = parse_style(&value, &palette, &mut warn) {
+                scopes.insert(scope, style);
+            }
+        }
+
+        let theme = Self {
+            name: name.to_string(),
+            scopes,
+        };
+        (theme, warnings)
+    }
+
+    /// Returns the name of the theme.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the style of `scope`, falling back to its parents: `keyword.control.return` is
+    /// looked up as `keyword.control.return`, then `keyword.control`, then `keyword`.
+    pub fn get(&self, scope: &str) -> Style {
+        self.try_get(scope).unwrap_or_default()
+    }
+
+    /// Like [`Theme::get`], but returns `None` if neither the scope nor a parent has a style.
+    pub fn try_get(&self, scope: &str) -> Option<Style> {
+        let mut scope = scope;
+        loop {
+            if let Some(&style) = self.scopes.get(scope) {
+                return Some(style);
+            }
+            scope = &scope[..scope.rfind('.')?];
+        }
+    }
+
+    /// Replaces the colors the terminal cannot show with the closest ones it can.
+    pub fn with_color_depth(mut self, depth: ColorDepth) -> Self {
+        for style in self.scopes.values_mut() {
+            style.fg = style.fg.map(|color| depth.convert(color));
+            style.bg = style.bg.map(|color| depth.convert(color));
+        }
+        self
+    }
+}
+
+impl Default for Theme {
+    /// The built-in theme, which sticks to the 16 ANSI colors.
+    fn default() -> Self {
+        let table = toml::from_str(BUILTIN[0].1).expect("unable to parse built-in theme");
+        Self::from_table(BUILTIN[0].0, table).0
+    }
+}
+
+/// Finds themes by name.
+///
+/// Theme files are called `<name>.toml` and looked up in the `themes` directory of each local
+/// configuration directory, nearest first, then of the configuration directory, and finally
+/// among the built-in themes.
+#[derive(Debug, Clone)]
+pub struct ThemeLoader {
+    dirs: Vec<PathBuf>,
+}
+
+impl Default for ThemeLoader {
+    fn default() -> Self {
+        let dirs = metadata::local_config_dirs()
+            .into_iter()
+            .chain([metadata::config_dir()])
+            .map(|dir| dir.join("themes"))
+            .collect();
+        Self::new(dirs)
+    }
+}
+
+impl ThemeLoader {
+    /// Creates a loader that searches `dirs` in order before the built-in themes.
+    pub fn new(dirs: Vec<PathBuf>) -> Self {
+        Self { dirs }
+    }
+
+    /// Loads the theme called `name`, resolving what it inherits.
+    ///
+    /// Returns the theme along with warnings about the parts of it that were ignored.
+    pub fn load(&self, name: &str) -> Result<(Theme, Vec<String>)> {
+        let table = self.load_table(name, 0, &mut Vec::new())?;
+        Ok(Theme::from_table(name, table))
+    }
+
+    /// Returns the names of all themes, sorted and without duplicates.
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<_> = BUILTIN.iter().map(|(name, _)| name.to_string()).collect();
+        for dir in &self.dirs {
+            let Ok(entries) = std::fs::read_dir(dir) else {
+                continue;
+            };
+            names.extend(entries.filter_map(|entry| {
+                let path = entry.ok()?.path();
+                match path.extension()? == "toml" {
+                    true => Some(path.file_stem()?.to_str()?.to_string()),
+                    false => None,
+                }
+            }));
+        }
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    // Loads the first theme called `name` at or after location `from`, and merges it onto the
+    // theme it inherits. A theme inheriting its own name extends the one it shadows.
+    fn load_table(
+        &self,
+        name: &str,
+        from: usize,
+        seen: &mut Vec<(String, usize)>,
+    ) -> Result<Table> {
+        let (location, mut table) = self
+            .find(name, from)?
+            .ok_or_else(|| anyhow!("theme '{name}' not found"))?;
+        if seen.contains(&(name.to_string(), location)) {
+            bail!("theme '{name}' inherits itself");
+        }
+        seen.push((name.to_string(), location));
+
+        let parent = match table.remove("inherits") {
+            Some(Value::String(parent)) => parent,
+            Some(_) => bail!("theme '{name}': 'inherits' must name a theme"),
+            None => return Ok(table),
+        };
+        let from = if parent == name { location + 1 } else { 0 };
+        let parent = self
+            .load_table(&parent, from, seen)
+            .with_context(|| format!("theme '{name}' inherits '{parent}'"))?;
+
+        Ok(merge(parent, table))
+    }
+
+    // Returns where the theme was found, as an index into the directories followed by the
+    // built-in themes, and its contents.
+    fn find(&self, name: &str, from: usize) -> Result<Option<(usize, Table)>> {
+        for (location, dir) in self.dirs.iter().enumerate().skip(from) {
+            let path = dir.join(format!("{name}.toml"));
+            match std::fs::read_to_string(&path) {
+                Ok(s) => return parse(&s, &path).map(|table| Some((location, table))),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(err).context(format!("unable to read {}", path.display())),
+            }
+        }
+        if from > self.dirs.len() {
+            return Ok(None);
+        }
+
+        let builtin = BUILTIN.iter().find(|(builtin, _)| *builtin == name);
+        Ok(builtin.map(|(_, s)| {
+            let table = toml::from_str(s).expect("unable to parse built-in theme");
+            (self.dirs.len(), table)
+        }))
+    }
+}
+
+fn parse(s: &str, path: &Path) -> Result<Table> {
+    toml::from_str(s).with_context(|| format!("unable to parse {}", path.display()))
+}
+
+// Merges `child` onto `parent`: scopes of `child` replace those of `parent` and palettes are
+// merged color by color.
+fn merge(mut parent: Table, mut child: Table) -> Table {
+    match (parent.get_mut("palette"), child.remove("palette")) {
+        (Some(Value::Table(palette)), Some(Value::Table(child_palette))) => {
+            palette.extend(child_palette)
+        }
+        (_, Some(palette)) => {
+            parent.insert("palette".into(), palette);
+        }
+        (_, None) => {}
+    }
+    parent.extend(child);
+    parent
+}
+
+fn parse_style(value: &Value, palette: &Table, warn: &mut impl FnMut(String)) -> Option<Style> {
+    let table = match value {
+        Value::Table(table) => table,
+        color => return parse_color(color, palette, warn).map(|fg| Style::default().fg(fg)),
+    };
+
+    let mut style = Style::default();
+    for (key, value) in table {
+        match key.as_str() {
+            "fg" => style.fg = parse_color(value, palette, warn),
+            "bg" => style.bg = parse_color(value, palette, warn),
+            "modifiers" => {
+                let Some(modifiers) = value.as_array() else {
+                    warn("'modifiers' must be a list".into());
+                    continue;
+                };
+                for modifier in modifiers {
+                    match MODIFIERS
+                        .iter()
+                        .find(|(name, _)| Some(*name) == modifier.as_str())
+                    {
+                        Some(&(_, modifier)) => style = style.add_modifier(modifier),
+                        None => warn(format!(
+                            "unknown modifier {modifier}, expected one of {}",
+                            names(MODIFIERS)
+                        )),
+                    }
+                }
+            }
+            key => warn(format!(
+                "unknown key '{key}', expected one of fg, bg, modifiers \
+                 (dotted scopes must be quoted)"
+            )),
+        }
+    }
+    Some(style)
+}
+
+fn parse_color(value: &Value, palette: &Table, warn: &mut impl FnMut(String)) -> Option<Color> {
+    let color = match value {
+        Value::String(name) => match palette.get(name) {
+            Some(Value::String(color)) => parse_color_name(color),
+            Some(Value::Integer(index)) => u8::try_from(*index).ok().map(Color::Indexed),
+            _ => parse_color_name(name),
+        },
+        Value::Integer(index) => u8::try_from(*index).ok().map(Color::Indexed),
+        _ => None,
+    };
+    if color.is_none() {
+        warn(format!(
+            "unknown color {value}, expected a palette color, one of {}, \
+             #rrggbb or a number from 0 to 255",
+            names(ANSI_COLORS)
+        ));
+    }
+    color
+}
+
+fn parse_color_name(name: &str) -> Option<Color> {
+    if let Some(hex) = name.strip_prefix('#') {
+        let rgb = u32::from_str_radix(hex, 16)
+            .ok()
+            .filter(|_| hex.len() == 6)?;
+        return Some(Color::Rgb((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8));
+    }
+    ANSI_COLORS
+        .iter()
+        .find(|(ansi, _)| *ansi == name)
+        .map(|&(_, color)| color)
+}
+
+fn names<T>(items: &[(&str, T)]) -> String {
+    items
+        .iter()
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// The colors a terminal can show.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorDepth {
+    /// Any 24-bit color.
+    TrueColor,
+    /// The 256-color palette.
+    Ansi256,
+    /// The 16 ANSI colors.
+    Ansi16,
+}
+
+impl ColorDepth {
+    /// Guesses the color depth of the terminal from `COLORTERM` and `TERM`.
+    pub fn detect() -> Self {
+        let colorterm = std::env::var("COLORTERM").ok();
+        let term = std::env::var("TERM").ok();
+        Self::from_env(colorterm.as_deref(), term.as_deref())
+    }
+
+    fn from_env(colorterm: Option<&str>, term: Option<&str>) -> Self {
+        match (colorterm, term) {
+            (Some("truecolor" | "24bit"), _) => Self::TrueColor,
+            (_, Some(term)) if term.contains("256color") => Self::Ansi256,
+            _ => Self::Ansi16,
+        }
+    }
+
+    /// Returns the closest color to `color` that can be shown.
+    pub fn convert(self, color: Color) -> Color {
+        match (self, color) {
+            (Self::Ansi256, Color::Rgb(r, g, b)) => Color::Indexed(rgb_to_indexed(r, g, b)),
+            (Self::Ansi16, Color::Rgb(r, g, b)) => nearest_ansi((r, g, b)),
+            (Self::Ansi16, Color::Indexed(index @ 0..=15)) => ANSI_INDEXED[index as usize],
+            (Self::Ansi16, Color::Indexed(index)) => nearest_ansi(indexed_to_rgb(index)),
+            (_, color) => color,
+        }
+    }
+}
+
+// The ANSI colors in palette order.
+const ANSI_INDEXED: [Color; 16] = [
+    Color::Black,
+    Color::Red,
+    Color::Green,
+    Color::Yellow,
+    Color::Blue,
+    Color::Magenta,
+    Color::Cyan,
+    Color::LightGray,
+    Color::Gray,
+    Color::LightRed,
+    Color::LightGreen,
+    Color::LightYellow,
+    Color::LightBlue,
+    Color::LightMagenta,
+    Color::LightCyan,
+    Color::White,
+];
+
+// Channel values of the 6x6x6 color cube of the 256-color palette.
+const CUBE: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+fn distance((r1, g1, b1): (u8, u8, u8), (r2, g2, b2): (u8, u8, u8)) -> u32 {
+    let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2) as u32;
+    d(r1, r2) + d(g1, g2) + d(b1, b2)
+}
+
+// The actual ANSI colors depend on the terminal, so rather than measuring distances this keeps
+// the hue: grays map to black, gray, light gray or white by brightness and other colors to the
+// ANSI colors made of their dominant channels, light when they are bright.
+fn nearest_ansi((r, g, b): (u8, u8, u8)) -> Color {
+    let (min, max) = (r.min(g).min(b), r.max(g).max(b));
+    if max - min < 32 {
+        return match max {
+            0..=63 => Color::Black,
+            64..=159 => Color::Gray,
+            160..=223 => Color::LightGray,
+            _ => Color::White,
+        };
+    }
+
+    let threshold = min as u32 + (max - min) as u32 * 3 / 5;
+    let bit = |channel: u8, bit: usize| ((channel as u32 >= threshold) as usize) << bit;
+    let index = bit(r, 0) | bit(g, 1) | bit(b, 2);
+    ANSI_INDEXED[index + if max >= 200 { 8 } else { 0 }]
+}
+
+fn indexed_to_rgb(index: u8) -> (u8, u8, u8) {
+    match index {
+        0..=15 => unreachable!("the ANSI colors have no fixed value"),
+        16..=231 => {
+            let index = index - 16;
+            let channel = |i: u8| CUBE[i as usize];
+            (
+                channel(index / 36),
+                channel(index / 6 % 6),
+                channel(index % 6),
+            )
+        }
+        _ => {
+            let level = 8 + (index - 232) * 10;
+            (level, level, level)
+        }
+    }
+}
+
+// Picks the closer of the nearest color of the cube and the nearest gray of the 256-color
+// palette. The first 16 colors are skipped since terminals are free to change them.
+fn rgb_to_indexed(r: u8, g: u8, b: u8) -> u8 {
+    let nearest = |value: u8| {
+        (0..CUBE.len())
+            .min_by_key(|&i| (CUBE[i] as i32 - value as i32).abs())
+            .unwrap() as u8
+    };
+    let cube = 16 + 36 * nearest(r) + 6 * nearest(g) + nearest(b);
+
+    let average = (r as u32 + g as u32 + b as u32) / 3;
+    let gray = 232 + (average.saturating_sub(8) / 10).min(23) as u8;
+
+    match distance((r, g, b), indexed_to_rgb(gray)) < distance((r, g, b), indexed_to_rgb(cube)) {
+        true => gray,
+        false => cube,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(dir: &Path, name: &str, contents: &str) {
+        std::fs::create_dir_all(dir).unwrap();
+        std::fs::write(dir.join(format!("{name}.toml")), contents).unwrap();
+    }
+
+    #[test]
+    fn scopes_fall_back_to_parents() {
+        let theme = Theme::default();
+
+        assert_eq!(theme.get("keyword.control.return"), theme.get("keyword"));
+        assert_eq!(theme.get("function.macro").fg, Some(Color::LightBlue));
+        assert_eq!(theme.try_get("punctuation.bracket"), None);
+    }
+
+    #[test]
+    fn builtin_themes_parse_without_warnings() {
+        let loader = ThemeLoader::new(Vec::new());
+
+        for name in loader.names() {
+            let (theme, warnings) = loader.load(&name).unwrap();
+            assert_eq!(warnings, Vec::<String>::new(), "{name}");
+            assert_eq!(theme.name(), name);
+        }
+    }
+
+    #[test]
+    fn themes_inherit_and_use_palettes() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let (local, global) = (dir.join("local"), dir.join("global"));
+        write(
+            &global,
+            "base",
+            r##"
+            "keyword" = { fg = "accent", modifiers = ["bold"] }
+            "string" = "green"
+            "ui.statusline" = { fg = 244, bg = "bg" }
+
+            [palette]
+            accent = "#ff0000"
+            bg = "black"
+            "##,
+        );
+        write(
+            &global,
+            "child",
+            r##"
+            inherits = "base"
+            "string" = "accent"
+
+            [palette]
+            accent = "#00ff00"
+            "##,
+        );
+        // Shadows the global theme while extending it.
+        write(
+            &local,
+            "child",
+            "inherits = \"child\"\n\"type\" = \"yellow\"\n",
+        );
+        let loader = ThemeLoader::new(vec![local, global]);
+
+        let (theme, warnings) = loader.load("child").unwrap();
+        assert!(warnings.is_empty());
+        assert_eq!(
+            theme.get("keyword.control"),
+            Style::default()
+                .fg(Color::Rgb(0, 255, 0))
+                .add_modifier(Modifier::BOLD)
+        );
+        assert_eq!(theme.get("string").fg, Some(Color::Rgb(0, 255, 0)));
+        assert_eq!(theme.get("type").fg, Some(Color::Yellow));
+        assert_eq!(
+            theme.get("ui.statusline"),
+            Style::default().fg(Color::Indexed(244)).bg(Color::Black)
+        );
+        assert_eq!(
+            loader.names(),
+            ["base", "child", "default", "night"].map(String::from)
+        );
+
+        write(&dir.join("global"), "base", "inherits = \"child\"");
+        let err = loader.load("child").unwrap_err();
+        assert!(format!("{err:#}").ends_with("theme 'child' inherits itself"));
+        assert_eq!(
+            loader.load("missing").unwrap_err().to_string(),
+            "theme 'missing' not found"
+        );
+    }
+
+    #[test]
+    fn unknown_keys_are_reported() {
+        let table = toml::from_str(
+            r##"
+            "keyword" = { fgg = "red", modifiers = ["bold", "blinking"] }
+            "string" = "#12345"
+            ui = { statusline = "red" }
+            "##,
+        )
+        .unwrap();
+
+        let (theme, mut warnings) = Theme::from_table("broken", table);
+        warnings.sort();
+        assert_eq!(warnings.len(), 4);
+        assert!(warnings[0].starts_with("'keyword': unknown key 'fgg', expected one of fg, bg"));
+        assert!(warnings[1]
+            .starts_with("'keyword': unknown modifier \"blinking\", expected one of bold"));
+        assert!(warnings[2].starts_with("'string': unknown color \"#12345\""));
+        assert!(warnings[3].starts_with("'ui': unknown key 'statusline'"));
+        assert_eq!(theme.get("keyword").add_modifier, Modifier::BOLD);
+        assert_eq!(theme.try_get("string"), None);
+    }
+
+    #[test]
+    fn colors_fall_back_to_the_terminal_depth() {
+        assert_eq!(
+            ColorDepth::from_env(Some("truecolor"), Some("xterm-256color")),
+            ColorDepth::TrueColor
+        );
+        assert_eq!(
+            ColorDepth::from_env(None, Some("xterm-256color")),
+            ColorDepth::Ansi256
+        );
+        assert_eq!(
+            ColorDepth::from_env(None, Some("linux")),
+            ColorDepth::Ansi16
+        );
+
+        let convert = |depth: ColorDepth, color| depth.convert(color);
+        assert_eq!(
+            convert(ColorDepth::TrueColor, Color::Rgb(1, 2, 3)),
+            Color::Rgb(1, 2, 3)
+        );
+        assert_eq!(
+            convert(ColorDepth::Ansi256, Color::Rgb(255, 0, 0)),
+            Color::Indexed(196)
+        );
+        assert_eq!(
+            convert(ColorDepth::Ansi256, Color::Rgb(0x1e, 0x21, 0x27)),
+            Color::Indexed(234)
+        );
+        assert_eq!(
+            convert(ColorDepth::Ansi16, Color::Rgb(0xe0, 0x6c, 0x75)),
+            Color::LightRed
+        );
+        assert_eq!(
+            convert(ColorDepth::Ansi16, Color::Indexed(22)),
+            Color::Green
+        );
+        assert_eq!(
+            convert(ColorDepth::Ansi16, Color::Indexed(9)),
+            Color::LightRed
+        );
+        assert_eq!(
+            convert(ColorDepth::Ansi16, Color::Rgb(0x1e, 0x21, 0x27)),
+            Color::Black
+        );
+        assert_eq!(convert(ColorDepth::Ansi16, Color::Cyan), Color::Cyan);
+
+        let (theme, _) = ThemeLoader::new(Vec::new()).load("night").unwrap();
+        let theme = theme.with_color_depth(ColorDepth::Ansi16);
+        assert_eq!(theme.get("keyword").fg, Some(Color::LightMagenta));
+        assert_eq!(theme.get("string").fg, Some(Color::Green));
+    }
+}
@@ -0,0 +1,158 @@
+use crate::Buffer;
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+/// A single replacement of the bytes `from..to` with `text`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Change {
+    /// Start of the replaced bytes.
+    pub from: usize,
+    /// End of the replaced bytes.
+    pub to: usize,
+    /// The text inserted in their place.
+    pub text: String,
+}
+
+/// Which side of an insertion a mapped position sticks to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Assoc {
+    /// Stay before text inserted at the position.
+    Before,
+    /// Move after text inserted at the position.
+    After,
+}
+
+/// A set of non-overlapping changes applied to a buffer at once.
+///
+/// All offsets refer to the text before any of the changes are applied, so that each selection
+/// range can contribute its own change without accounting for the others.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Transaction {
+    changes: Vec<Change>,
+}
+
+impl Transaction {
+    /// Creates a transaction from `(from, to, text)` changes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the changes overlap or are not sorted.
+    pub fn change<I, S>(changes: I) -> Self
+    where
+        I: IntoIterator<Item = (usize, usize, S)>,
+        S: Into<String>,
+    {
+        let changes: Vec<_> = changes
+            .into_iter()
+            .map(|(from, to, text)| Change {
+                from,
+                to,
+                text: text.into(),
+            })
+            .filter(|change| change.from != change.to || !change.text.is_empty())
+            .collect();
+
+        assert!(
+            changes.iter().all(|change| change.from <= change.to)
+                && changes.windows(2).all(|w| w[0].to <= w[1].from),
+            "changes must be sorted and must not overlap"
+        );
+
+        Self { changes }
+    }
+
+    /// Creates a transaction inserting `text` at `pos`.
+    pub fn insert(pos: usize, text: impl Into<String>) -> Self {
+        Self::change([(pos, pos, text)])
+    }
+
+    /// Creates a transaction deleting the bytes in `ranges`.
+    pub fn delete<I: IntoIterator<Item = Range<usize>>>(ranges: I) -> Self {
+        Self::change(ranges.into_iter().map(|range| (range.start, range.end, "")))
+    }
+
+    /// Returns the changes.
+    pub fn changes(&self) -> &[Change] {
+        &self.changes
+    }
+
+    /// Returns `true` if the transaction changes nothing.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Returns the transaction that undoes `self` once it has been applied to `buffer`.
+    ///
+    /// Must be called before `self` is applied.
+    pub fn invert(&self, buffer: &Buffer) -> Self {
+        let mut delta = 0isize;
+        let changes = self
+            .changes
+            .iter()
+            .map(|change| {
+                let from = (change.from as isize + delta) as usize;
+                delta += change.text.len() as isize - (change.to - change.from) as isize;
+                Change {
+                    from,
+                    to: from + change.text.len(),
+                    text: buffer.slice(change.from..change.to).to_string(),
+                }
+            })
+            .collect();
+
+        Self { changes }
+    }
+
+    /// Maps a position in the text before the transaction to the text after it.
+    pub fn map_pos(&self, pos: usize, assoc: Assoc) -> usize {
+        let mut delta = 0isize;
+
+        for change in &self.changes {
+            if pos < change.from || (pos == change.from && assoc == Assoc::Before) {
+                break;
+            }
+
+            let new_from = (change.from as isize + delta) as usize;
+            if pos <= change.to {
+                // Positions inside a replaced span collapse onto its start or end.
+                return match assoc {
+                    Assoc::Before if pos < change.to => new_from,
+                    _ => new_from + change.text.len(),
+                };
+            }
+
+            delta += change.text.len() as isize - (change.to - change.from) as isize;
+        }
+
+        (pos as isize + delta) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn apply_and_invert() {
+        let mut buffer = Buffer::from("hello world");
+        let transaction = Transaction::change([(0, 1, "J"), (5, 5, ","), (6, 11, "there")]);
+
+        let inverse = transaction.invert(&buffer);
+        buffer.apply(&transaction);
+        assert_eq!(buffer.text(), "Jello, there");
+
+        buffer.apply(&inverse);
+        assert_eq!(buffer.text(), "hello world");
+    }
+
+    #[test]
+    fn map_positions() {
+        let transaction = Transaction::change([(2, 2, "xx"), (4, 6, "")]);
+
+        assert_eq!(transaction.map_pos(1, Assoc::After), 1);
+        assert_eq!(transaction.map_pos(2, Assoc::Before), 2);
+        assert_eq!(transaction.map_pos(2, Assoc::After), 4);
+        assert_eq!(transaction.map_pos(5, Assoc::After), 6);
+        assert_eq!(transaction.map_pos(7, Assoc::After), 7);
+    }
+}
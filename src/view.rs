@@ -0,0 +1,148 @@
+//! Views onto buffers, arranged on screen by a [`Layout`](tissue_tui::Layout).
+
+use crate::Selection;
+use serde::{Deserialize, Serialize};
+
+// The most places a jumplist remembers.
+const MAX_JUMPS: usize = 100;
+
+/// A view showing a buffer.
+///
+/// While a view has focus, the buffer it shows is [`App::focus`](crate::App::focus) and its
+/// cursors are the selection of that buffer. The other views keep their buffer here and their
+/// selection in the buffer, with [`Buffer::view_selection`](crate::Buffer::view_selection).
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct View {
+    /// The index of the buffer shown, unless the view has focus.
+    pub buffer: usize,
+    /// The first line of the buffer shown.
+    pub scroll: usize,
+    /// The places the view jumped from.
+    pub jumps: JumpList,
+}
+
+impl View {
+    /// Creates a view of the buffer at `buffer`, scrolled to its top.
+    pub fn new(buffer: usize) -> Self {
+        Self {
+            buffer,
+            scroll: 0,
+            jumps: JumpList::default(),
+        }
+    }
+
+    /// Scrolls the view as little as needed to show `line` in its `height` lines.
+    pub fn scroll_to(&mut self, line: usize, height: usize) {
+        if line < self.scroll {
+            self.scroll = line;
+        } else if height > 0 && line >= self.scroll + height {
+            self.scroll = line + 1 - height;
+        }
+    }
+}
+
+/// A place in a buffer: the buffer and the cursors in it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct JumpPoint {
+    /// The index of the buffer.
+    pub buffer: usize,
+    /// The cursors.
+    pub selection: Selection,
+}
+
+/// The places a view jumped from, such as with a search or `gd`, walked back with `C-o` and
+/// forward again with `C-i`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct JumpList {
+    points: Vec<JumpPoint>,
+    // The index of the place in `points` the view is at, or the length of `points` if it moved on
+    // since the last jump.
+    current: usize,
+}
+
+impl JumpList {
+    /// Creates a jumplist of `points`, the most recent last, none of which the view is at.
+    pub fn new(points: Vec<JumpPoint>) -> Self {
+        let mut points = points;
+        points.drain(..points.len().saturating_sub(MAX_JUMPS));
+        let current = points.len();
+        Self { points, current }
+    }
+
+    /// Returns the places, the most recent last.
+    pub fn points(&self) -> &[JumpPoint] {
+        &self.points
+    }
+
+    /// Records `point` as the place jumped from, forgetting the places jumped back past.
+    pub fn push(&mut self, point: JumpPoint) {
+        self.points.truncate(self.current);
+        if self.points.last() != Some(&point) {
+            self.points.push(point);
+        }
+        if self.points.len() > MAX_JUMPS {
+            self.points.remove(0);
+        }
+        self.current = self.points.len();
+    }
+
+    /// Goes `count` places back from `here`, which is recorded so that [`JumpList::forward`]
+    /// can return to it.
+    pub fn backward(&mut self, here: JumpPoint, count: usize) -> Option<&JumpPoint> {
+        if self.current == self.points.len() {
+            self.push(here);
+            self.current = self.points.len() - 1;
+        }
+        self.current = self.current.checked_sub(count)?;
+        self.points.get(self.current)
+    }
+
+    /// Goes `count` places forward, after going back.
+    pub fn forward(&mut self, count: usize) -> Option<&JumpPoint> {
+        let target = self.current + count;
+        if target >= self.points.len() {
+            return None;
+        }
+        self.current = target;
+        self.points.get(target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scrolls_to_lines_out_of_view() {
+        let mut view = View::new(0);
+        view.scroll_to(12, 10);
+        assert_eq!(view.scroll, 3);
+        view.scroll_to(5, 10);
+        assert_eq!(view.scroll, 3);
+        view.scroll_to(1, 10);
+        assert_eq!(view.scroll, 1);
+    }
+
+    #[test]
+    fn jumps_go_back_and_forth() {
+        let point = |buffer| JumpPoint {
+            buffer,
+            selection: Selection::point(0),
+        };
+        let mut jumps = JumpList::default();
+        assert_eq!(jumps.backward(point(0), 1), None);
+
+        let mut jumps = JumpList::new(vec![point(0), point(1)]);
+        assert_eq!(jumps.backward(point(2), 1), Some(&point(1)));
+        assert_eq!(jumps.backward(point(1), 1), Some(&point(0)));
+        assert_eq!(jumps.backward(point(0), 1), None);
+        assert_eq!(jumps.forward(2), Some(&point(2)));
+        assert_eq!(jumps.forward(1), None);
+
+        // Jumping from a place jumped back to replaces it and forgets the newer ones.
+        jumps.backward(point(2), 2);
+        jumps.push(point(3));
+        assert_eq!(jumps.points(), [point(3)]);
+    }
+}
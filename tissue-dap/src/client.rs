@@ -0,0 +1,294 @@
+use crate::{
+    transport::Transport,
+    types::{
+        Breakpoint, BreakpointEvent, Capabilities, ContinuedEvent, ExitedEvent, OutputEvent, Scope,
+        Source, SourceBreakpoint, StackFrame, StoppedEvent, Thread, Variable,
+    },
+};
+use anyhow::{Context, Result};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use std::{path::Path, process::Stdio};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    process::{Child, Command},
+    sync::{
+        mpsc::{self, UnboundedReceiver},
+        OnceCell,
+    },
+};
+
+/// An event sent by the adapter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// The adapter is ready for breakpoints, followed by `configurationDone`.
+    Initialized,
+    /// A thread stopped, e.g. at a breakpoint or after a step.
+    Stopped(StoppedEvent),
+    /// A thread resumed.
+    Continued(ContinuedEvent),
+    /// The debuggee exited.
+    Exited(ExitedEvent),
+    /// The debug session ended.
+    Terminated,
+    /// The debuggee or the adapter printed something.
+    Output(OutputEvent),
+    /// The adapter changed a breakpoint.
+    Breakpoint(BreakpointEvent),
+}
+
+impl Event {
+    fn parse(event: &str, body: Value) -> Result<Option<Self>> {
+        Ok(Some(match event {
+            "initialized" => Self::Initialized,
+            "stopped" => Self::Stopped(serde_json::from_value(body)?),
+            "continued" => Self::Continued(serde_json::from_value(body)?),
+            "exited" => Self::Exited(serde_json::from_value(body)?),
+            "terminated" => Self::Terminated,
+            "output" => Self::Output(serde_json::from_value(body)?),
+            "breakpoint" => Self::Breakpoint(serde_json::from_value(body)?),
+            _ => return Ok(None),
+        }))
+    }
+}
+
+/// A connection to a debug adapter process.
+#[derive(Debug)]
+pub struct Client {
+    name: String,
+    transport: Transport,
+    capabilities: OnceCell<Capabilities>,
+    // Killed when the client is dropped.
+    _process: Option<Child>,
+}
+
+impl Client {
+    /// Spawns `command` with `args` in `cwd` and connects to it over stdio.
+    ///
+    /// The adapter must be initialized with [`Client::initialize`] before anything else is sent.
+    pub fn start(
+        command: &str,
+        args: &[String],
+        cwd: &Path,
+    ) -> Result<(Self, UnboundedReceiver<Event>)> {
+        let mut process = Command::new(command)
+            .args(args)
+            .current_dir(cwd)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| format!("unable to start debug adapter '{command}'"))?;
+
+        // Unwrap OK since both streams are piped
+        let stdin = process.stdin.take().unwrap();
+        let stdout = process.stdout.take().unwrap();
+        Ok(Self::connect(command, stdout, stdin, Some(process)))
+    }
+
+    // Talks to the adapter called `name` over `reader` and `writer`.
+    pub(crate) fn connect<R, W>(
+        name: &str,
+        reader: R,
+        writer: W,
+        process: Option<Child>,
+    ) -> (Self, UnboundedReceiver<Event>)
+    where
+        R: AsyncRead + Unpin + Send + 'static,
+        W: AsyncWrite + Unpin + Send + 'static,
+    {
+        let (transport, mut incoming) = Transport::start(name, reader, writer);
+
+        let (events, receiver) = mpsc::unbounded_channel();
+        let adapter = name.to_string();
+        tokio::spawn(async move {
+            while let Some((event, body)) = incoming.recv().await {
+                match Event::parse(&event, body) {
+                    Ok(Some(event)) => {
+                        if events.send(event).is_err() {
+                            break;
+                        }
+                    }
+                    Ok(None) => log::debug!("{adapter} sent unhandled event '{event}'"),
+                    Err(err) => log::warn!("{adapter} sent invalid '{event}': {err}"),
+                }
+            }
+        });
+
+        let client = Self {
+            name: name.to_string(),
+            transport,
+            capabilities: OnceCell::new(),
+            _process: process,
+        };
+
+        (client, receiver)
+    }
+
+    /// Returns the name of the adapter binary.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the capabilities of the adapter once it has been initialized.
+    pub fn capabilities(&self) -> Option<&Capabilities> {
+        self.capabilities.get()
+    }
+
+    /// Performs the `initialize` handshake. `adapter_id` names the kind of adapter, e.g. `lldb`.
+    pub async fn initialize(&self, adapter_id: &str) -> Result<&Capabilities> {
+        self.capabilities
+            .get_or_try_init(|| async {
+                let arguments = json!({
+                    "clientID": "tissue",
+                    "clientName": "tissue",
+                    "adapterID": adapter_id,
+                    "locale": "en-us",
+                    "linesStartAt1": true,
+                    "columnsStartAt1": true,
+                    "pathFormat": "path",
+                    "supportsRunInTerminalRequest": false,
+                });
+                self.request("initialize", arguments).await
+            })
+            .await
+    }
+
+    /// Starts the debuggee. `arguments` are specific to the adapter.
+    pub async fn launch(&self, arguments: Value) -> Result<()> {
+        self.transport.request("launch", arguments).await?;
+        Ok(())
+    }
+
+    /// Attaches to a running debuggee. `arguments` are specific to the adapter.
+    pub async fn attach(&self, arguments: Value) -> Result<()> {
+        self.transport.request("attach", arguments).await?;
+        Ok(())
+    }
+
+    /// Replaces the breakpoints of the file at `path`. An empty list clears them.
+    pub async fn set_breakpoints(
+        &self,
+        path: &Path,
+        breakpoints: Vec<SourceBreakpoint>,
+    ) -> Result<Vec<Breakpoint>> {
+        let source = Source {
+            name: path.file_name().map(|name| name.to_string_lossy().into()),
+            path: Some(path.to_path_buf()),
+        };
+        let body: Value = self
+            .request(
+                "setBreakpoints",
+                json!({ "source": source, "breakpoints": breakpoints }),
+            )
+            .await?;
+        field(body, "breakpoints")
+    }
+
+    /// Tells the adapter that all breakpoints are set.
+    ///
+    /// Does nothing if the adapter does not expect it.
+    pub async fn configuration_done(&self) -> Result<()> {
+        if self
+            .capabilities()
+            .map_or(false, |caps| caps.supports_configuration_done_request)
+        {
+            self.transport
+                .request("configurationDone", json!({}))
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Resumes `thread`.
+    pub async fn continue_thread(&self, thread: i64) -> Result<()> {
+        self.transport
+            .request("continue", json!({ "threadId": thread }))
+            .await?;
+        Ok(())
+    }
+
+    /// Steps `thread` over the current line.
+    pub async fn next(&self, thread: i64) -> Result<()> {
+        self.transport
+            .request("next", json!({ "threadId": thread }))
+            .await?;
+        Ok(())
+    }
+
+    /// Steps `thread` into the function called on the current line.
+    pub async fn step_in(&self, thread: i64) -> Result<()> {
+        self.transport
+            .request("stepIn", json!({ "threadId": thread }))
+            .await?;
+        Ok(())
+    }
+
+    /// Steps `thread` out of the current function.
+    pub async fn step_out(&self, thread: i64) -> Result<()> {
+        self.transport
+            .request("stepOut", json!({ "threadId": thread }))
+            .await?;
+        Ok(())
+    }
+
+    /// Suspends `thread`.
+    pub async fn pause(&self, thread: i64) -> Result<()> {
+        self.transport
+            .request("pause", json!({ "threadId": thread }))
+            .await?;
+        Ok(())
+    }
+
+    /// Returns the threads of the debuggee.
+    pub async fn threads(&self) -> Result<Vec<Thread>> {
+        let body = self.request("threads", json!({})).await?;
+        field(body, "threads")
+    }
+
+    /// Returns the stack of `thread`, innermost frame first.
+    pub async fn stack_trace(&self, thread: i64) -> Result<Vec<StackFrame>> {
+        let body = self
+            .request("stackTrace", json!({ "threadId": thread }))
+            .await?;
+        field(body, "stackFrames")
+    }
+
+    /// Returns the scopes of the stack frame `frame`.
+    pub async fn scopes(&self, frame: i64) -> Result<Vec<Scope>> {
+        let body = self.request("scopes", json!({ "frameId": frame })).await?;
+        field(body, "scopes")
+    }
+
+    /// Returns the variables of a scope or of a structured variable.
+    pub async fn variables(&self, reference: i64) -> Result<Vec<Variable>> {
+        let body = self
+            .request("variables", json!({ "variablesReference": reference }))
+            .await?;
+        field(body, "variables")
+    }
+
+    /// Ends the session. If `terminate` is set, the debuggee is terminated as well.
+    pub async fn disconnect(&self, terminate: bool) -> Result<()> {
+        self.transport
+            .request("disconnect", json!({ "terminateDebuggee": terminate }))
+            .await?;
+        Ok(())
+    }
+
+    async fn request<T: DeserializeOwned>(&self, command: &str, arguments: Value) -> Result<T> {
+        let body = match self.transport.request(command, arguments).await? {
+            // Some adapters leave out the body of responses that carry nothing.
+            Value::Null => json!({}),
+            body => body,
+        };
+        serde_json::from_value(body)
+            .with_context(|| format!("{} sent an invalid '{command}' response", self.name))
+    }
+}
+
+fn field<T: DeserializeOwned>(mut body: Value, name: &str) -> Result<T> {
+    let value = body.get_mut(name).map(Value::take).unwrap_or_default();
+    serde_json::from_value(value).with_context(|| format!("invalid '{name}' in response"))
+}
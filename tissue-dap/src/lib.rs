@@ -0,0 +1,14 @@
+//! Debug Adapter Protocol client for tissue.
+//!
+//! A [`Client`] spawns a debug adapter and talks to it over stdio. Events sent by the adapter,
+//! such as a thread stopping at a breakpoint, arrive on the channel returned by [`Client::start`].
+
+#![warn(missing_debug_implementations, missing_docs)]
+
+mod client;
+#[cfg(feature = "test-support")]
+pub mod mock;
+mod transport;
+pub mod types;
+
+pub use self::client::{Client, Event};
@@ -0,0 +1,271 @@
+//! A debug adapter for tests, run in the same process as its client.
+//!
+//! The "program" it debugs is a text file that is executed one line at a time. A line of the
+//! form `name = value` assigns a local variable and `print text` writes `text` to stdout; any
+//! other line does nothing. There is a single thread with a single frame named `main`.
+
+use crate::{Client, Event};
+use serde_json::{json, Value};
+use std::{collections::HashMap, io, path::PathBuf};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader},
+    sync::mpsc::UnboundedReceiver,
+};
+
+const THREAD: i64 = 1;
+const FRAME: i64 = 1;
+const LOCALS: i64 = 1;
+
+struct Adapter<W> {
+    writer: W,
+    seq: u64,
+    program: PathBuf,
+    lines: Vec<String>,
+    stop_on_entry: bool,
+    // The next line to execute, starting at 1.
+    line: usize,
+    breakpoints: HashMap<PathBuf, Vec<usize>>,
+    variables: Vec<(String, String)>,
+}
+
+/// Starts the mock adapter and connects a client to it, like [`Client::start`].
+pub fn start() -> (Client, UnboundedReceiver<Event>) {
+    let (client, adapter) = tokio::io::duplex(64 * 1024);
+    let (reader, writer) = tokio::io::split(adapter);
+    tokio::spawn(async move {
+        if let Err(err) = serve(reader, writer).await {
+            log::error!("mock debug adapter: {err}");
+        }
+    });
+
+    let (reader, writer) = tokio::io::split(client);
+    Client::connect("mock-debug-adapter", reader, writer, None)
+}
+
+async fn serve<R, W>(reader: R, writer: W) -> io::Result<()>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut reader = BufReader::new(reader);
+    let mut adapter = Adapter {
+        writer,
+        seq: 0,
+        program: PathBuf::new(),
+        lines: Vec::new(),
+        stop_on_entry: false,
+        line: 0,
+        breakpoints: HashMap::new(),
+        variables: Vec::new(),
+    };
+
+    while let Some(message) = read_message(&mut reader).await? {
+        if message["type"] != "request" {
+            continue;
+        }
+        let command = message["command"].as_str().unwrap_or_default();
+        let arguments = &message["arguments"];
+
+        let result = match command {
+            "initialize" => Ok(json!({
+                "supportsConfigurationDoneRequest": true,
+                "supportsTerminateRequest": true,
+            })),
+            "launch" | "attach" => adapter.load(arguments),
+            "setBreakpoints" => Ok(adapter.set_breakpoints(arguments)),
+            "configurationDone" => Ok(json!({})),
+            "continue" => Ok(json!({ "allThreadsContinued": true })),
+            "next" | "stepIn" | "stepOut" | "pause" => Ok(json!({})),
+            "threads" => Ok(json!({ "threads": [{ "id": THREAD, "name": "main" }] })),
+            "stackTrace" => Ok(json!({
+                "stackFrames": [{
+                    "id": FRAME,
+                    "name": "main",
+                    "source": { "name": "program", "path": adapter.program },
+                    "line": adapter.line,
+                    "column": 1,
+                }],
+                "totalFrames": 1,
+            })),
+            "scopes" => Ok(json!({
+                "scopes": [{ "name": "Locals", "variablesReference": LOCALS, "expensive": false }],
+            })),
+            "variables" => Ok(json!({
+                "variables": adapter
+                    .variables
+                    .iter()
+                    .map(|(name, value)| json!({ "name": name, "value": value, "variablesReference": 0 }))
+                    .collect::<Vec<_>>(),
+            })),
+            "disconnect" => Ok(json!({})),
+            _ => Err(format!("unknown command '{command}'")),
+        };
+
+        let response = match result {
+            Ok(body) => json!({ "success": true, "body": body }),
+            Err(error) => json!({ "success": false, "message": error }),
+        };
+        adapter
+            .send(json!({
+                "type": "response",
+                "request_seq": message["seq"],
+                "command": command,
+                "success": response["success"],
+                "message": response["message"],
+                "body": response["body"],
+            }))
+            .await?;
+
+        // Events that follow a request are sent after its response, like real adapters do.
+        match command {
+            "launch" | "attach" if response["success"] == true => {
+                adapter.event("initialized", json!({})).await?
+            }
+            "configurationDone" if adapter.stop_on_entry => adapter.stop("entry").await?,
+            "configurationDone" => adapter.run(false).await?,
+            "continue" => adapter.run(true).await?,
+            "next" | "stepIn" | "stepOut" => adapter.step().await?,
+            "pause" => adapter.stop("pause").await?,
+            "disconnect" => break,
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+impl<W: AsyncWrite + Unpin> Adapter<W> {
+    fn load(&mut self, arguments: &Value) -> Result<Value, String> {
+        let program = arguments["program"].as_str().ok_or("missing 'program'")?;
+        let text = std::fs::read_to_string(program)
+            .map_err(|err| format!("unable to read '{program}': {err}"))?;
+
+        self.program = program.into();
+        self.lines = text.lines().map(String::from).collect();
+        self.stop_on_entry = arguments["stopOnEntry"].as_bool().unwrap_or(false);
+        self.line = 1;
+        Ok(json!({}))
+    }
+
+    fn set_breakpoints(&mut self, arguments: &Value) -> Value {
+        let path = PathBuf::from(arguments["source"]["path"].as_str().unwrap_or_default());
+        let lines: Vec<usize> = arguments["breakpoints"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|breakpoint| breakpoint["line"].as_u64())
+            .map(|line| line as usize)
+            .collect();
+
+        let breakpoints: Vec<_> = lines
+            .iter()
+            .enumerate()
+            .map(|(id, &line)| {
+                let verified = path == self.program && line <= self.lines.len();
+                json!({ "id": id + 1, "verified": verified, "line": line })
+            })
+            .collect();
+        self.breakpoints.insert(path, lines);
+
+        json!({ "breakpoints": breakpoints })
+    }
+
+    // Runs until a breakpoint or the end of the program. If `resume` is set, a breakpoint on the
+    // current line is not hit again.
+    async fn run(&mut self, mut resume: bool) -> io::Result<()> {
+        loop {
+            if self.line > self.lines.len() {
+                return self.exit().await;
+            }
+            let hit = self
+                .breakpoints
+                .get(&self.program)
+                .map_or(false, |lines| lines.contains(&self.line));
+            if hit && !resume {
+                return self.stop("breakpoint").await;
+            }
+            resume = false;
+            self.execute().await?;
+        }
+    }
+
+    async fn step(&mut self) -> io::Result<()> {
+        self.execute().await?;
+        if self.line > self.lines.len() {
+            self.exit().await
+        } else {
+            self.stop("step").await
+        }
+    }
+
+    async fn execute(&mut self) -> io::Result<()> {
+        let line = self.lines[self.line - 1].trim().to_string();
+        self.line += 1;
+
+        if let Some(text) = line.strip_prefix("print ") {
+            self.event(
+                "output",
+                json!({ "category": "stdout", "output": format!("{text}\n") }),
+            )
+            .await?;
+        } else if let Some((name, value)) = line.split_once(" = ") {
+            match self.variables.iter_mut().find(|(n, _)| n == name) {
+                Some((_, old)) => *old = value.to_string(),
+                None => self.variables.push((name.to_string(), value.to_string())),
+            }
+        }
+        Ok(())
+    }
+
+    async fn stop(&mut self, reason: &str) -> io::Result<()> {
+        self.event(
+            "stopped",
+            json!({ "reason": reason, "threadId": THREAD, "allThreadsStopped": true }),
+        )
+        .await
+    }
+
+    async fn exit(&mut self) -> io::Result<()> {
+        self.event("exited", json!({ "exitCode": 0 })).await?;
+        self.event("terminated", json!({})).await
+    }
+
+    async fn event(&mut self, event: &str, body: Value) -> io::Result<()> {
+        self.send(json!({ "type": "event", "event": event, "body": body }))
+            .await
+    }
+
+    async fn send(&mut self, mut message: Value) -> io::Result<()> {
+        self.seq += 1;
+        message["seq"] = self.seq.into();
+        write_message(&mut self.writer, &message).await
+    }
+}
+
+async fn read_message(reader: &mut (impl AsyncBufReadExt + Unpin)) -> io::Result<Option<Value>> {
+    let mut length = 0;
+    let mut line = String::new();
+
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).await? == 0 {
+            return Ok(None);
+        }
+        match line.trim_end().split_once(": ") {
+            Some(("Content-Length", value)) => length = value.parse().unwrap(),
+            Some(_) => {}
+            None => break,
+        }
+    }
+
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).await?;
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+async fn write_message(writer: &mut (impl AsyncWrite + Unpin), message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    let message = format!("Content-Length: {}\r\n\r\n{body}", body.len());
+    writer.write_all(message.as_bytes()).await?;
+    writer.flush().await
+}
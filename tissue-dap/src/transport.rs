@@ -0,0 +1,238 @@
+use anyhow::{anyhow, bail, Context, Result};
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+use tokio::{
+    io::{
+        AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt,
+        BufReader,
+    },
+    sync::{
+        mpsc::{self, UnboundedReceiver, UnboundedSender},
+        oneshot,
+    },
+};
+
+// How long to wait for the response to a request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(20);
+
+// Requests waiting for a response, or `None` once the adapter has gone away.
+type Pending = Arc<Mutex<Option<HashMap<u64, oneshot::Sender<Result<Value>>>>>>;
+
+/// Protocol messages over a pair of byte streams, framed with `Content-Length` headers.
+#[derive(Debug)]
+pub(crate) struct Transport {
+    name: String,
+    outgoing: UnboundedSender<Value>,
+    pending: Pending,
+    seq: Arc<AtomicU64>,
+}
+
+impl Transport {
+    /// Starts reading from and writing to the adapter. Events from the adapter are sent to the
+    /// returned channel as `(event, body)`.
+    pub fn start<R, W>(
+        name: &str,
+        reader: R,
+        writer: W,
+    ) -> (Self, UnboundedReceiver<(String, Value)>)
+    where
+        R: AsyncRead + Unpin + Send + 'static,
+        W: AsyncWrite + Unpin + Send + 'static,
+    {
+        let (outgoing, outgoing_rx) = mpsc::unbounded_channel();
+        let (events, events_rx) = mpsc::unbounded_channel();
+        let pending = Arc::new(Mutex::new(Some(HashMap::new())));
+        let seq = Arc::new(AtomicU64::new(1));
+
+        tokio::spawn(send(name.to_string(), writer, outgoing_rx));
+        tokio::spawn(recv(
+            name.to_string(),
+            BufReader::new(reader),
+            outgoing.clone(),
+            events,
+            pending.clone(),
+            seq.clone(),
+        ));
+
+        let transport = Self {
+            name: name.to_string(),
+            outgoing,
+            pending,
+            seq,
+        };
+
+        (transport, events_rx)
+    }
+
+    /// Sends a request and waits for the body of its response.
+    pub async fn request(&self, command: &str, arguments: Value) -> Result<Value> {
+        let seq = self.seq.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = oneshot::channel();
+        match self.pending.lock().unwrap().as_mut() {
+            Some(pending) => pending.insert(seq, tx),
+            None => bail!("{} is not running", self.name),
+        };
+
+        let message = json!({
+            "seq": seq,
+            "type": "request",
+            "command": command,
+            "arguments": arguments,
+        });
+        if self.outgoing.send(message).is_err() {
+            self.forget(seq);
+            bail!("{} is not running", self.name);
+        }
+
+        match tokio::time::timeout(REQUEST_TIMEOUT, rx).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(_)) => bail!("{} exited before answering '{command}'", self.name),
+            Err(_) => {
+                self.forget(seq);
+                bail!("{} timed out answering '{command}'", self.name)
+            }
+        }
+    }
+
+    fn forget(&self, seq: u64) {
+        if let Some(pending) = self.pending.lock().unwrap().as_mut() {
+            pending.remove(&seq);
+        }
+    }
+}
+
+async fn read_message<R: AsyncBufRead + Unpin>(reader: &mut R) -> Result<Option<Value>> {
+    let mut content_length = None;
+    let mut line = String::new();
+
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).await? == 0 {
+            return Ok(None);
+        }
+
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = Some(value.trim().parse::<usize>()?);
+            }
+        }
+    }
+
+    let length = content_length.context("message without a Content-Length header")?;
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).await?;
+
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+async fn write_message<W: AsyncWrite + Unpin>(writer: &mut W, message: &Value) -> Result<()> {
+    let body = serde_json::to_string(message)?;
+    writer
+        .write_all(format!("Content-Length: {}\r\n\r\n{body}", body.len()).as_bytes())
+        .await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+async fn send<W: AsyncWrite + Unpin>(
+    name: String,
+    mut writer: W,
+    mut outgoing: UnboundedReceiver<Value>,
+) {
+    while let Some(message) = outgoing.recv().await {
+        log::trace!("{name} <- {message}");
+        if let Err(err) = write_message(&mut writer, &message).await {
+            log::error!("failed to write to {name}: {err}");
+            break;
+        }
+    }
+}
+
+async fn recv<R: AsyncBufRead + Unpin>(
+    name: String,
+    mut reader: R,
+    outgoing: UnboundedSender<Value>,
+    events: UnboundedSender<(String, Value)>,
+    pending: Pending,
+    seq: Arc<AtomicU64>,
+) {
+    loop {
+        let message = match read_message(&mut reader).await {
+            Ok(Some(message)) => message,
+            Ok(None) => break,
+            Err(err) => {
+                log::error!("failed to read from {name}: {err}");
+                break;
+            }
+        };
+        log::trace!("{name} -> {message}");
+
+        let body = message.get("body").cloned().unwrap_or(Value::Null);
+
+        match message.get("type").and_then(Value::as_str) {
+            Some("event") => {
+                let event = message
+                    .get("event")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default();
+                events.send((event.to_string(), body)).ok();
+            }
+            Some("response") => {
+                let sender =
+                    message
+                        .get("request_seq")
+                        .and_then(Value::as_u64)
+                        .and_then(|request_seq| {
+                            pending
+                                .lock()
+                                .unwrap()
+                                .as_mut()
+                                .and_then(|pending| pending.remove(&request_seq))
+                        });
+                let Some(sender) = sender else {
+                    log::warn!("{name} answered an unknown request: {message}");
+                    continue;
+                };
+                let result = match message.get("success").and_then(Value::as_bool) {
+                    Some(true) => Ok(body),
+                    _ => Err(anyhow!(
+                        "{name}: {}",
+                        message
+                            .get("message")
+                            .and_then(Value::as_str)
+                            .unwrap_or("request failed")
+                    )),
+                };
+                sender.send(result).ok();
+            }
+            // Reverse requests such as `runInTerminal` are not supported.
+            Some("request") => {
+                let command = message.get("command").cloned().unwrap_or(Value::Null);
+                let response = json!({
+                    "seq": seq.fetch_add(1, Ordering::Relaxed),
+                    "type": "response",
+                    "request_seq": message.get("seq"),
+                    "success": false,
+                    "command": command,
+                    "message": "not supported",
+                });
+                outgoing.send(response).ok();
+            }
+            _ => log::warn!("{name} sent an invalid message: {message}"),
+        }
+    }
+
+    // Dropping the senders fails the requests that are still waiting.
+    pending.lock().unwrap().take();
+}
@@ -0,0 +1,160 @@
+//! The parts of the protocol the client uses.
+//!
+//! Lines and columns are 1-based, which the client asks the adapter for during initialization.
+
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Features supported by the adapter.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Capabilities {
+    /// The adapter expects `configurationDone` once breakpoints are set.
+    pub supports_configuration_done_request: bool,
+    /// Breakpoints may have conditions.
+    pub supports_conditional_breakpoints: bool,
+    /// The debuggee can be terminated without ending the session.
+    pub supports_terminate_request: bool,
+}
+
+/// A source file.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Source {
+    /// The name shown to the user.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// The path of the file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+}
+
+/// A breakpoint requested by the client.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceBreakpoint {
+    /// The line of the breakpoint.
+    pub line: usize,
+    /// An expression that must hold for the breakpoint to stop.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub condition: Option<String>,
+}
+
+/// A breakpoint as set by the adapter.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Breakpoint {
+    /// Identifies the breakpoint in `breakpoint` events.
+    pub id: Option<i64>,
+    /// Whether the adapter could set the breakpoint.
+    pub verified: bool,
+    /// Why the breakpoint could not be set.
+    pub message: Option<String>,
+    /// The line the breakpoint ended up on.
+    pub line: Option<usize>,
+}
+
+/// A thread of the debuggee.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Thread {
+    /// Identifies the thread in requests.
+    pub id: i64,
+    /// The name of the thread.
+    pub name: String,
+}
+
+/// A frame of a thread's stack.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StackFrame {
+    /// Identifies the frame in requests.
+    pub id: i64,
+    /// The name of the function.
+    pub name: String,
+    /// The file the frame is in.
+    pub source: Option<Source>,
+    /// The current line.
+    pub line: usize,
+    /// The current column.
+    pub column: usize,
+}
+
+/// A group of variables, such as locals or globals.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Scope {
+    /// The name of the scope.
+    pub name: String,
+    /// Pass to [`Client::variables`](crate::Client::variables) to read the variables.
+    pub variables_reference: i64,
+    /// Reading the variables is slow.
+    #[serde(default)]
+    pub expensive: bool,
+}
+
+/// A variable.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Variable {
+    /// The name of the variable.
+    pub name: String,
+    /// The value, formatted by the adapter.
+    pub value: String,
+    /// The type of the value.
+    #[serde(rename = "type")]
+    pub ty: Option<String>,
+    /// If not 0, pass to [`Client::variables`](crate::Client::variables) to read the children.
+    #[serde(default)]
+    pub variables_reference: i64,
+}
+
+/// Body of the `stopped` event.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StoppedEvent {
+    /// Why the debuggee stopped, such as `breakpoint` or `step`.
+    pub reason: String,
+    /// Additional information to show to the user.
+    pub description: Option<String>,
+    /// The thread that stopped.
+    pub thread_id: Option<i64>,
+    /// Whether every thread stopped.
+    #[serde(default)]
+    pub all_threads_stopped: bool,
+}
+
+/// Body of the `continued` event.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContinuedEvent {
+    /// The thread that continued.
+    pub thread_id: i64,
+}
+
+/// Body of the `exited` event.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExitedEvent {
+    /// The exit code of the debuggee.
+    pub exit_code: i64,
+}
+
+/// Body of the `output` event.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OutputEvent {
+    /// Where the output went, such as `stdout` or `console`.
+    pub category: Option<String>,
+    /// The output.
+    pub output: String,
+}
+
+/// Body of the `breakpoint` event.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BreakpointEvent {
+    /// Whether the breakpoint was added, changed or removed.
+    pub reason: String,
+    /// The breakpoint.
+    pub breakpoint: Breakpoint,
+}
@@ -0,0 +1,157 @@
+use serde_json::json;
+use std::{path::PathBuf, time::Duration};
+use tempfile::TempDir;
+use tissue_dap::{
+    mock,
+    types::{SourceBreakpoint, StoppedEvent, Variable},
+    Client, Event,
+};
+use tokio::{sync::mpsc::UnboundedReceiver, time::timeout};
+
+// Writes a program for the mock adapter to a file in a directory of its own, which is removed
+// when the returned `TempDir` is dropped.
+fn program(text: &str) -> (TempDir, PathBuf) {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("program");
+    std::fs::write(&path, text).unwrap();
+    (dir, path)
+}
+
+async fn start() -> (Client, UnboundedReceiver<Event>) {
+    let (client, events) = mock::start();
+    client.initialize("mock").await.unwrap();
+    (client, events)
+}
+
+async fn next_event(events: &mut UnboundedReceiver<Event>) -> Event {
+    timeout(Duration::from_secs(5), events.recv())
+        .await
+        .expect("no event was sent")
+        .expect("adapter exited")
+}
+
+async fn stopped(events: &mut UnboundedReceiver<Event>) -> StoppedEvent {
+    loop {
+        match next_event(events).await {
+            Event::Stopped(stopped) => return stopped,
+            Event::Terminated => panic!("debuggee terminated"),
+            _ => {}
+        }
+    }
+}
+
+fn breakpoints(lines: &[usize]) -> Vec<SourceBreakpoint> {
+    lines
+        .iter()
+        .map(|&line| SourceBreakpoint {
+            line,
+            condition: None,
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn launch_stops_at_breakpoints() {
+    let (_dir, path) = program("a = 1\nb = 2\nc = 3\nd = 4\n");
+    let (client, mut events) = start().await;
+    assert!(
+        client
+            .capabilities()
+            .unwrap()
+            .supports_configuration_done_request
+    );
+
+    client.launch(json!({ "program": path })).await.unwrap();
+    assert_eq!(next_event(&mut events).await, Event::Initialized);
+
+    let set = client
+        .set_breakpoints(&path, breakpoints(&[2, 4, 9]))
+        .await
+        .unwrap();
+    let verified: Vec<_> = set.iter().map(|breakpoint| breakpoint.verified).collect();
+    assert_eq!(verified, [true, true, false]);
+    client.configuration_done().await.unwrap();
+
+    let stop = stopped(&mut events).await;
+    assert_eq!(stop.reason, "breakpoint");
+    let frames = client.stack_trace(stop.thread_id.unwrap()).await.unwrap();
+    assert_eq!(frames[0].line, 2);
+    assert_eq!(
+        frames[0].source.as_ref().unwrap().path.as_ref(),
+        Some(&path)
+    );
+
+    client.continue_thread(1).await.unwrap();
+    stopped(&mut events).await;
+    assert_eq!(client.stack_trace(1).await.unwrap()[0].line, 4);
+
+    // Clearing the breakpoints lets the program run to the end.
+    client.set_breakpoints(&path, Vec::new()).await.unwrap();
+    client.continue_thread(1).await.unwrap();
+    assert!(
+        matches!(next_event(&mut events).await, Event::Exited(exited) if exited.exit_code == 0)
+    );
+    assert_eq!(next_event(&mut events).await, Event::Terminated);
+}
+
+#[tokio::test]
+async fn stepping_updates_variables() {
+    let (_dir, path) = program("x = 1\nprint hello\nx = 2\nx = 3\n");
+    let (client, mut events) = start().await;
+
+    client
+        .launch(json!({ "program": path, "stopOnEntry": true }))
+        .await
+        .unwrap();
+    assert_eq!(next_event(&mut events).await, Event::Initialized);
+    client.configuration_done().await.unwrap();
+    assert_eq!(stopped(&mut events).await.reason, "entry");
+
+    let threads = client.threads().await.unwrap();
+    assert_eq!(threads.len(), 1);
+    let thread = threads[0].id;
+
+    client.next(thread).await.unwrap();
+    assert_eq!(stopped(&mut events).await.reason, "step");
+    client.step_in(thread).await.unwrap();
+    let Event::Output(output) = next_event(&mut events).await else {
+        panic!("expected output");
+    };
+    assert_eq!(output.output, "hello\n");
+    stopped(&mut events).await;
+    client.step_out(thread).await.unwrap();
+    stopped(&mut events).await;
+
+    let frame = client.stack_trace(thread).await.unwrap()[0].id;
+    let scopes = client.scopes(frame).await.unwrap();
+    assert_eq!(scopes[0].name, "Locals");
+    let variables = client
+        .variables(scopes[0].variables_reference)
+        .await
+        .unwrap();
+    assert_eq!(
+        variables,
+        [Variable {
+            name: "x".into(),
+            value: "2".into(),
+            ty: None,
+            variables_reference: 0,
+        }]
+    );
+
+    client.disconnect(true).await.unwrap();
+}
+
+#[tokio::test]
+async fn failed_requests_are_errors() {
+    let (client, _) = start().await;
+
+    let err = client
+        .attach(json!({ "program": "/nonexistent/program" }))
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("unable to read"), "{err}");
+
+    client.disconnect(false).await.unwrap();
+    assert!(client.threads().await.is_err());
+}
@@ -0,0 +1,492 @@
+use crate::{transport::Transport, OffsetEncoding};
+use anyhow::{anyhow, bail, Context, Result};
+use lsp_types::{
+    self as lsp,
+    notification::{self, Notification as _},
+    request::{self, Request},
+};
+use serde_json::Value;
+use std::{
+    path::{Path, PathBuf},
+    process::Stdio,
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    process::{Child, Command},
+    sync::{
+        mpsc::{self, UnboundedReceiver},
+        OnceCell,
+    },
+};
+
+/// A notification sent by the server.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Notification {
+    /// Diagnostics for a document replace the ones published before.
+    PublishDiagnostics(lsp::PublishDiagnosticsParams),
+    /// A message to show to the user.
+    ShowMessage(lsp::ShowMessageParams),
+    /// A message to log.
+    LogMessage(lsp::LogMessageParams),
+    /// Progress of a long running operation, such as indexing the workspace.
+    Progress(lsp::ProgressParams),
+}
+
+impl Notification {
+    fn parse(method: &str, params: Value) -> Result<Option<Self>> {
+        Ok(Some(match method {
+            notification::PublishDiagnostics::METHOD => {
+                Self::PublishDiagnostics(serde_json::from_value(params)?)
+            }
+            notification::ShowMessage::METHOD => Self::ShowMessage(serde_json::from_value(params)?),
+            notification::LogMessage::METHOD => Self::LogMessage(serde_json::from_value(params)?),
+            notification::Progress::METHOD => Self::Progress(serde_json::from_value(params)?),
+            _ => return Ok(None),
+        }))
+    }
+}
+
+/// A connection to a language server process.
+#[derive(Debug)]
+pub struct Client {
+    name: String,
+    root: PathBuf,
+    transport: Transport,
+    initialized: OnceCell<(lsp::ServerCapabilities, OffsetEncoding)>,
+    // Killed when the client is dropped, unless the server runs in this process.
+    _process: Option<Child>,
+}
+
+impl Client {
+    /// Spawns `command` with `args` in `root` and connects to it over stdio.
+    ///
+    /// The server must be initialized with [`Client::initialize`] before anything else is sent.
+    pub fn start(
+        command: &str,
+        args: &[String],
+        root: &Path,
+    ) -> Result<(Self, UnboundedReceiver<Notification>)> {
+        let mut process = Command::new(command)
+            .args(args)
+            .current_dir(root)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| format!("unable to start language server '{command}'"))?;
+
+        // Unwrap OK since both streams are piped
+        let stdin = process.stdin.take().unwrap();
+        let stdout = process.stdout.take().unwrap();
+        Ok(Self::connect(command, root, stdout, stdin, Some(process)))
+    }
+
+    // Talks to the server called `name` over `reader` and `writer`.
+    pub(crate) fn connect<R, W>(
+        name: &str,
+        root: &Path,
+        reader: R,
+        writer: W,
+        process: Option<Child>,
+    ) -> (Self, UnboundedReceiver<Notification>)
+    where
+        R: AsyncRead + Unpin + Send + 'static,
+        W: AsyncWrite + Unpin + Send + 'static,
+    {
+        let (transport, mut incoming) = Transport::start(name, reader, writer);
+
+        let (notifications, receiver) = mpsc::unbounded_channel();
+        let server = name.to_string();
+        tokio::spawn(async move {
+            while let Some((method, params)) = incoming.recv().await {
+                match Notification::parse(&method, params) {
+                    Ok(Some(notification)) => {
+                        if notifications.send(notification).is_err() {
+                            break;
+                        }
+                    }
+                    Ok(None) => log::debug!("{server} sent unhandled notification '{method}'"),
+                    Err(err) => log::warn!("{server} sent invalid '{method}': {err}"),
+                }
+            }
+        });
+
+        let client = Self {
+            name: name.to_string(),
+            root: root.to_path_buf(),
+            transport,
+            initialized: OnceCell::new(),
+            _process: process,
+        };
+
+        (client, receiver)
+    }
+
+    /// Returns the name of the server binary.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the workspace root the server was started in.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Returns the capabilities of the server once it has been initialized.
+    pub fn capabilities(&self) -> Option<&lsp::ServerCapabilities> {
+        self.initialized.get().map(|(capabilities, _)| capabilities)
+    }
+
+    /// Returns how the server counts columns.
+    pub fn offset_encoding(&self) -> OffsetEncoding {
+        self.initialized
+            .get()
+            .map(|&(_, encoding)| encoding)
+            .unwrap_or_default()
+    }
+
+    /// Returns how the server wants to be told about document changes.
+    pub fn sync_kind(&self) -> lsp::TextDocumentSyncKind {
+        match self
+            .capabilities()
+            .and_then(|caps| caps.text_document_sync.as_ref())
+        {
+            Some(lsp::TextDocumentSyncCapability::Kind(kind)) => *kind,
+            Some(lsp::TextDocumentSyncCapability::Options(options)) => {
+                options.change.unwrap_or(lsp::TextDocumentSyncKind::NONE)
+            }
+            None => lsp::TextDocumentSyncKind::NONE,
+        }
+    }
+
+    /// Performs the `initialize` handshake.
+    pub async fn initialize(&self) -> Result<()> {
+        let root_uri = lsp::Url::from_directory_path(&self.root)
+            .map_err(|_| anyhow!("invalid workspace root '{}'", self.root.display()))?;
+
+        #[allow(deprecated)]
+        let params = lsp::InitializeParams {
+            process_id: Some(std::process::id()),
+            root_path: Some(self.root.display().to_string()),
+            root_uri: Some(root_uri.clone()),
+            workspace_folders: Some(vec![lsp::WorkspaceFolder {
+                name: self
+                    .root
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+                uri: root_uri,
+            }]),
+            capabilities: client_capabilities(),
+            client_info: Some(lsp::ClientInfo {
+                name: "tissue".into(),
+                version: Some(env!("CARGO_PKG_VERSION").into()),
+            }),
+            ..Default::default()
+        };
+
+        let result = self.request::<request::Initialize>(params).await?;
+        let encoding = match &result.capabilities.position_encoding {
+            Some(kind) => OffsetEncoding::from_kind(kind)
+                .with_context(|| format!("{} chose unknown position encoding", self.name))?,
+            None => OffsetEncoding::Utf16,
+        };
+
+        self.initialized
+            .set((result.capabilities, encoding))
+            .map_err(|_| anyhow!("{} is already initialized", self.name))?;
+        self.notify::<notification::Initialized>(lsp::InitializedParams {})
+    }
+
+    /// Asks the server to shut down and exit.
+    pub async fn shutdown(&self) -> Result<()> {
+        self.request::<request::Shutdown>(()).await?;
+        self.notify::<notification::Exit>(())
+    }
+
+    /// Tells the server that a document was opened.
+    pub fn text_document_did_open(
+        &self,
+        uri: lsp::Url,
+        language_id: &str,
+        version: i32,
+        text: String,
+    ) -> Result<()> {
+        self.notify::<notification::DidOpenTextDocument>(lsp::DidOpenTextDocumentParams {
+            text_document: lsp::TextDocumentItem::new(uri, language_id.into(), version, text),
+        })
+    }
+
+    /// Tells the server that a document changed.
+    ///
+    /// `changes` must match [`Client::sync_kind`]: ranges are only allowed for incremental sync.
+    pub fn text_document_did_change(
+        &self,
+        uri: lsp::Url,
+        version: i32,
+        changes: Vec<lsp::TextDocumentContentChangeEvent>,
+    ) -> Result<()> {
+        if self.sync_kind() == lsp::TextDocumentSyncKind::NONE {
+            return Ok(());
+        }
+
+        self.notify::<notification::DidChangeTextDocument>(lsp::DidChangeTextDocumentParams {
+            text_document: lsp::VersionedTextDocumentIdentifier::new(uri, version),
+            content_changes: changes,
+        })
+    }
+
+    /// Tells the server that a document was saved.
+    pub fn text_document_did_save(&self, uri: lsp::Url) -> Result<()> {
+        self.notify::<notification::DidSaveTextDocument>(lsp::DidSaveTextDocumentParams {
+            text_document: lsp::TextDocumentIdentifier::new(uri),
+            text: None,
+        })
+    }
+
+    /// Tells the server that a document was closed.
+    pub fn text_document_did_close(&self, uri: lsp::Url) -> Result<()> {
+        self.notify::<notification::DidCloseTextDocument>(lsp::DidCloseTextDocumentParams {
+            text_document: lsp::TextDocumentIdentifier::new(uri),
+        })
+    }
+
+    /// Requests completions at `pos`.
+    pub async fn completion(
+        &self,
+        uri: lsp::Url,
+        pos: lsp::Position,
+    ) -> Result<Vec<lsp::CompletionItem>> {
+        self.require("completion", |caps| caps.completion_provider.is_some())?;
+
+        let params = lsp::CompletionParams {
+            text_document_position: position_params(uri, pos),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+            context: Some(lsp::CompletionContext {
+                trigger_kind: lsp::CompletionTriggerKind::INVOKED,
+                trigger_character: None,
+            }),
+        };
+
+        Ok(match self.request::<request::Completion>(params).await? {
+            Some(lsp::CompletionResponse::Array(items)) => items,
+            Some(lsp::CompletionResponse::List(list)) => list.items,
+            None => Vec::new(),
+        })
+    }
+
+    /// Requests hover information at `pos`.
+    pub async fn hover(&self, uri: lsp::Url, pos: lsp::Position) -> Result<Option<lsp::Hover>> {
+        self.require("hover", |caps| {
+            !matches!(
+                caps.hover_provider,
+                None | Some(lsp::HoverProviderCapability::Simple(false))
+            )
+        })?;
+
+        self.request::<request::HoverRequest>(lsp::HoverParams {
+            text_document_position_params: position_params(uri, pos),
+            work_done_progress_params: Default::default(),
+        })
+        .await
+    }
+
+    /// Requests the definition of the symbol at `pos`.
+    pub async fn goto_definition(
+        &self,
+        uri: lsp::Url,
+        pos: lsp::Position,
+    ) -> Result<Vec<lsp::Location>> {
+        self.require("goto definition", |caps| {
+            !matches!(
+                caps.definition_provider,
+                None | Some(lsp::OneOf::Left(false))
+            )
+        })?;
+
+        let params = lsp::GotoDefinitionParams {
+            text_document_position_params: position_params(uri, pos),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        };
+
+        Ok(
+            match self.request::<request::GotoDefinition>(params).await? {
+                Some(lsp::GotoDefinitionResponse::Scalar(location)) => vec![location],
+                Some(lsp::GotoDefinitionResponse::Array(locations)) => locations,
+                Some(lsp::GotoDefinitionResponse::Link(links)) => links
+                    .into_iter()
+                    .map(|link| lsp::Location::new(link.target_uri, link.target_selection_range))
+                    .collect(),
+                None => Vec::new(),
+            },
+        )
+    }
+
+    /// Requests the references to the symbol at `pos`, including its declaration.
+    pub async fn references(
+        &self,
+        uri: lsp::Url,
+        pos: lsp::Position,
+    ) -> Result<Vec<lsp::Location>> {
+        self.require("references", |caps| {
+            !matches!(
+                caps.references_provider,
+                None | Some(lsp::OneOf::Left(false))
+            )
+        })?;
+
+        let params = lsp::ReferenceParams {
+            text_document_position: position_params(uri, pos),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+            context: lsp::ReferenceContext {
+                include_declaration: true,
+            },
+        };
+
+        Ok(self
+            .request::<request::References>(params)
+            .await?
+            .unwrap_or_default())
+    }
+
+    /// Requests the edits that rename the symbol at `pos` to `new_name`.
+    pub async fn rename(
+        &self,
+        uri: lsp::Url,
+        pos: lsp::Position,
+        new_name: String,
+    ) -> Result<Option<lsp::WorkspaceEdit>> {
+        self.require("rename", |caps| {
+            !matches!(caps.rename_provider, None | Some(lsp::OneOf::Left(false)))
+        })?;
+
+        self.request::<request::Rename>(lsp::RenameParams {
+            text_document_position: position_params(uri, pos),
+            new_name,
+            work_done_progress_params: Default::default(),
+        })
+        .await
+    }
+
+    /// Requests the symbols defined in the document.
+    pub async fn document_symbols(
+        &self,
+        uri: lsp::Url,
+    ) -> Result<Option<lsp::DocumentSymbolResponse>> {
+        self.require("document symbols", |caps| {
+            !matches!(
+                caps.document_symbol_provider,
+                None | Some(lsp::OneOf::Left(false))
+            )
+        })?;
+
+        self.request::<request::DocumentSymbolRequest>(lsp::DocumentSymbolParams {
+            text_document: lsp::TextDocumentIdentifier::new(uri),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        })
+        .await
+    }
+
+    /// Requests the symbols of the workspace matching `query`.
+    pub async fn workspace_symbols(
+        &self,
+        query: String,
+    ) -> Result<Option<lsp::WorkspaceSymbolResponse>> {
+        self.require("workspace symbols", |caps| {
+            !matches!(
+                caps.workspace_symbol_provider,
+                None | Some(lsp::OneOf::Left(false))
+            )
+        })?;
+
+        self.request::<request::WorkspaceSymbolRequest>(lsp::WorkspaceSymbolParams {
+            query,
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        })
+        .await
+    }
+
+    // Fails unless the server is initialized and `supported` accepts its capabilities.
+    fn require<F>(&self, feature: &str, supported: F) -> Result<()>
+    where
+        F: FnOnce(&lsp::ServerCapabilities) -> bool,
+    {
+        match self.capabilities() {
+            Some(capabilities) if supported(capabilities) => Ok(()),
+            Some(_) => bail!("{} does not support {feature}", self.name),
+            None => bail!("{} is not initialized", self.name),
+        }
+    }
+
+    async fn request<R: Request>(&self, params: R::Params) -> Result<R::Result> {
+        let result = self
+            .transport
+            .request(R::METHOD, serde_json::to_value(params)?)
+            .await?;
+        serde_json::from_value(result)
+            .with_context(|| format!("{} sent an invalid '{}' response", self.name, R::METHOD))
+    }
+
+    fn notify<N: notification::Notification>(&self, params: N::Params) -> Result<()> {
+        self.transport
+            .notify(N::METHOD, serde_json::to_value(params)?)
+    }
+}
+
+fn position_params(uri: lsp::Url, pos: lsp::Position) -> lsp::TextDocumentPositionParams {
+    lsp::TextDocumentPositionParams::new(lsp::TextDocumentIdentifier::new(uri), pos)
+}
+
+fn client_capabilities() -> lsp::ClientCapabilities {
+    lsp::ClientCapabilities {
+        general: Some(lsp::GeneralClientCapabilities {
+            position_encodings: Some(vec![
+                lsp::PositionEncodingKind::UTF8,
+                lsp::PositionEncodingKind::UTF32,
+                lsp::PositionEncodingKind::UTF16,
+            ]),
+            ..Default::default()
+        }),
+        text_document: Some(lsp::TextDocumentClientCapabilities {
+            synchronization: Some(lsp::TextDocumentSyncClientCapabilities {
+                did_save: Some(true),
+                ..Default::default()
+            }),
+            completion: Some(lsp::CompletionClientCapabilities {
+                completion_item: Some(lsp::CompletionItemCapability {
+                    snippet_support: Some(false),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+            hover: Some(lsp::HoverClientCapabilities {
+                content_format: Some(vec![lsp::MarkupKind::PlainText, lsp::MarkupKind::Markdown]),
+                ..Default::default()
+            }),
+            definition: Some(lsp::GotoCapability::default()),
+            references: Some(lsp::ReferenceClientCapabilities::default()),
+            rename: Some(lsp::RenameClientCapabilities::default()),
+            document_symbol: Some(lsp::DocumentSymbolClientCapabilities {
+                hierarchical_document_symbol_support: Some(true),
+                ..Default::default()
+            }),
+            publish_diagnostics: Some(lsp::PublishDiagnosticsClientCapabilities::default()),
+            ..Default::default()
+        }),
+        workspace: Some(lsp::WorkspaceClientCapabilities {
+            symbol: Some(lsp::WorkspaceSymbolClientCapabilities::default()),
+            ..Default::default()
+        }),
+        window: Some(lsp::WindowClientCapabilities {
+            work_done_progress: Some(true),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
@@ -0,0 +1,18 @@
+//! Language Server Protocol client for tissue.
+//!
+//! A [`Client`] spawns a language server and talks JSON-RPC to it over stdio. Notifications sent
+//! by the server, such as diagnostics, arrive on the channel returned by [`Client::start`].
+
+#![warn(missing_debug_implementations, missing_docs)]
+
+mod client;
+#[cfg(feature = "test-support")]
+pub mod mock;
+mod position;
+mod transport;
+
+pub use self::{
+    client::{Client, Notification},
+    position::{lsp_pos_to_pos, pos_to_lsp_pos, OffsetEncoding},
+};
+pub use lsp_types as lsp;
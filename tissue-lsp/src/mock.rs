@@ -0,0 +1,303 @@
+//! A language server for tests, run in the same process as its client.
+//!
+//! It keeps the text of open documents and answers requests by looking at the word under the
+//! cursor: definitions are the first occurrence of the word, references are all of them, and
+//! every `TODO` is reported as a warning. Changes to documents that are not open make it stop, as a
+//! real server would get out of sync.
+
+use crate::{Client, Notification};
+use serde_json::{json, Value};
+use std::{collections::HashMap, io, path::Path};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader},
+    sync::mpsc::UnboundedReceiver,
+};
+
+/// Starts the mock server and connects a client to it, like [`Client::start`] with a server
+/// that runs in `root`.
+pub fn start(root: &Path) -> (Client, UnboundedReceiver<Notification>) {
+    let (client, server) = tokio::io::duplex(64 * 1024);
+    let (reader, writer) = tokio::io::split(server);
+    tokio::spawn(async move {
+        if let Err(err) = serve(reader, writer).await {
+            log::error!("mock language server: {err}");
+        }
+    });
+
+    let (reader, writer) = tokio::io::split(client);
+    Client::connect("mock-language-server", root, reader, writer, None)
+}
+
+async fn serve<R, W>(reader: R, mut writer: W) -> io::Result<()>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut reader = BufReader::new(reader);
+    let mut documents: HashMap<String, String> = HashMap::new();
+
+    while let Some(message) = read_message(&mut reader).await? {
+        // Responses to our own requests carry no method.
+        let Some(method) = message["method"].as_str() else {
+            continue;
+        };
+        let params = &message["params"];
+        let id = message.get("id").cloned();
+
+        let result = match method {
+            "initialize" => json!({
+                "capabilities": {
+                    "textDocumentSync": 2,
+                    "completionProvider": {},
+                    "hoverProvider": true,
+                    "definitionProvider": true,
+                    "referencesProvider": true,
+                    "renameProvider": true,
+                },
+            }),
+            "initialized" => {
+                // Servers may ask the client for settings at any time.
+                write_message(
+                    &mut writer,
+                    &json!({
+                        "jsonrpc": "2.0",
+                        "id": "configuration",
+                        "method": "workspace/configuration",
+                        "params": { "items": [{ "section": "mock" }] },
+                    }),
+                )
+                .await?;
+                continue;
+            }
+            "textDocument/didOpen" => {
+                let uri = params["textDocument"]["uri"].as_str().unwrap().to_string();
+                let text = params["textDocument"]["text"].as_str().unwrap().to_string();
+                publish_diagnostics(&mut writer, &uri, &text).await?;
+                documents.insert(uri, text);
+                continue;
+            }
+            "textDocument/didClose" => {
+                // Diagnostics of closed documents are cleared, like most servers do.
+                let uri = params["textDocument"]["uri"].as_str().unwrap();
+                documents.remove(uri);
+                publish_diagnostics(&mut writer, uri, "").await?;
+                continue;
+            }
+            "textDocument/didChange" => {
+                let uri = params["textDocument"]["uri"].as_str().unwrap();
+                let Some(text) = documents.get_mut(uri) else {
+                    let message = format!("change to unopened document {uri}");
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+                };
+                for change in params["contentChanges"].as_array().unwrap() {
+                    let new_text = change["text"].as_str().unwrap();
+                    match change.get("range") {
+                        Some(range) => {
+                            let start = offset(text, &range["start"]);
+                            let end = offset(text, &range["end"]);
+                            text.replace_range(start..end, new_text);
+                        }
+                        None => *text = new_text.to_string(),
+                    }
+                }
+                publish_diagnostics(&mut writer, uri, text).await?;
+                continue;
+            }
+            "textDocument/completion" => {
+                let (_, text, pos) = document_position(&documents, params);
+                let start = word_start(text, pos);
+                let prefix = &text[start..pos];
+                let mut words: Vec<_> = words(text)
+                    .map(|(_, word)| word)
+                    .filter(|word| word.starts_with(prefix) && *word != prefix)
+                    .collect();
+                words.sort_unstable();
+                words.dedup();
+                Value::Array(
+                    words
+                        .into_iter()
+                        .map(|word| json!({ "label": word }))
+                        .collect(),
+                )
+            }
+            "textDocument/hover" => {
+                let (_, text, pos) = document_position(&documents, params);
+                match word_at(text, pos) {
+                    Some(word) => {
+                        let count = words(text).filter(|&(_, w)| w == word).count();
+                        json!({ "contents": { "kind": "plaintext", "value": format!("{word}: {count} occurrence(s)") } })
+                    }
+                    None => Value::Null,
+                }
+            }
+            "textDocument/definition" | "textDocument/references" | "textDocument/rename" => {
+                let (uri, text, pos) = document_position(&documents, params);
+                let word = word_at(text, pos).unwrap_or_default();
+                let ranges: Vec<_> = words(text)
+                    .filter(|&(_, w)| w == word)
+                    .map(|(start, _)| json!({ "start": position(text, start), "end": position(text, start + word.len()) }))
+                    .collect();
+
+                match method {
+                    "textDocument/definition" => ranges
+                        .first()
+                        .map_or(Value::Null, |range| json!({ "uri": uri, "range": range })),
+                    "textDocument/references" => Value::Array(
+                        ranges
+                            .iter()
+                            .map(|range| json!({ "uri": uri, "range": range }))
+                            .collect(),
+                    ),
+                    _ => {
+                        let new_name = params["newName"].as_str().unwrap();
+                        let edits: Vec<_> = ranges
+                            .iter()
+                            .map(|range| json!({ "range": range, "newText": new_name }))
+                            .collect();
+                        json!({ "changes": { uri: edits } })
+                    }
+                }
+            }
+            "shutdown" => Value::Null,
+            "exit" => break,
+            _ if id.is_none() => continue,
+            _ => {
+                let error =
+                    json!({ "code": -32601, "message": format!("unhandled method '{method}'") });
+                write_message(
+                    &mut writer,
+                    &json!({ "jsonrpc": "2.0", "id": id, "error": error }),
+                )
+                .await?;
+                continue;
+            }
+        };
+
+        if let Some(id) = id {
+            let response = json!({ "jsonrpc": "2.0", "id": id, "result": result });
+            write_message(&mut writer, &response).await?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn read_message(reader: &mut (impl AsyncBufReadExt + Unpin)) -> io::Result<Option<Value>> {
+    let mut length = 0;
+    let mut line = String::new();
+
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).await? == 0 {
+            return Ok(None);
+        }
+        match line.trim_end().split_once(": ") {
+            Some(("Content-Length", value)) => length = value.parse().unwrap(),
+            Some(_) => {}
+            None => break,
+        }
+    }
+
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).await?;
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+async fn write_message(writer: &mut (impl AsyncWrite + Unpin), message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    let message = format!("Content-Length: {}\r\n\r\n{body}", body.len());
+    writer.write_all(message.as_bytes()).await?;
+    writer.flush().await
+}
+
+async fn publish_diagnostics(
+    writer: &mut (impl AsyncWrite + Unpin),
+    uri: &str,
+    text: &str,
+) -> io::Result<()> {
+    let diagnostics: Vec<_> = text
+        .match_indices("TODO")
+        .map(|(start, _)| {
+            json!({
+                "range": { "start": position(text, start), "end": position(text, start + 4) },
+                "severity": 2,
+                "message": "found TODO",
+            })
+        })
+        .collect();
+
+    let params = json!({ "uri": uri, "diagnostics": diagnostics });
+    write_message(
+        writer,
+        &json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": params,
+        }),
+    )
+    .await
+}
+
+fn document_position<'a>(
+    documents: &'a HashMap<String, String>,
+    params: &Value,
+) -> (&'a str, &'a str, usize) {
+    let uri = params["textDocument"]["uri"].as_str().unwrap();
+    let (uri, text) = documents
+        .get_key_value(uri)
+        .expect("request for unopened document");
+    (uri, text, offset(text, &params["position"]))
+}
+
+// Converts a UTF-16 position to a byte offset.
+fn offset(text: &str, pos: &Value) -> usize {
+    let line = pos["line"].as_u64().unwrap() as usize;
+    let character = pos["character"].as_u64().unwrap() as usize;
+    let line_start: usize = text.split_inclusive('\n').take(line).map(str::len).sum();
+
+    let mut units = 0;
+    for (i, c) in text[line_start..].char_indices() {
+        if units >= character || c == '\n' {
+            return line_start + i;
+        }
+        units += c.len_utf16();
+    }
+    text.len()
+}
+
+// Converts a byte offset to a UTF-16 position.
+fn position(text: &str, offset: usize) -> Value {
+    let before = &text[..offset];
+    let line = before.matches('\n').count();
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    let character: usize = before[line_start..].chars().map(char::len_utf16).sum();
+    json!({ "line": line, "character": character })
+}
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn words(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    text.split(|c: char| !is_word(c))
+        .filter(|word| !word.is_empty())
+        .map(move |word| (word.as_ptr() as usize - text.as_ptr() as usize, word))
+}
+
+fn word_start(text: &str, pos: usize) -> usize {
+    text[..pos]
+        .char_indices()
+        .rev()
+        .take_while(|&(_, c)| is_word(c))
+        .last()
+        .map_or(pos, |(i, _)| i)
+}
+
+fn word_at(text: &str, pos: usize) -> Option<&str> {
+    let start = word_start(text, pos);
+    let end = text[pos..]
+        .char_indices()
+        .find(|&(_, c)| !is_word(c))
+        .map_or(text.len(), |(i, _)| pos + i);
+    (end > start).then(|| &text[start..end])
+}
@@ -0,0 +1,122 @@
+use lsp_types::{Position, PositionEncodingKind};
+use ropey::Rope;
+
+/// The unit in which a language server counts columns.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum OffsetEncoding {
+    /// UTF-8 bytes.
+    Utf8,
+    /// UTF-16 code units, which every server supports.
+    #[default]
+    Utf16,
+    /// Unicode scalar values.
+    Utf32,
+}
+
+impl OffsetEncoding {
+    /// Returns the encoding negotiated with a server, or `None` if it is not supported.
+    pub fn from_kind(kind: &PositionEncodingKind) -> Option<Self> {
+        match kind.as_str() {
+            "utf-8" => Some(Self::Utf8),
+            "utf-16" => Some(Self::Utf16),
+            "utf-32" => Some(Self::Utf32),
+            _ => None,
+        }
+    }
+
+    /// Returns the name of the encoding in the protocol.
+    pub fn kind(self) -> PositionEncodingKind {
+        match self {
+            Self::Utf8 => PositionEncodingKind::UTF8,
+            Self::Utf16 => PositionEncodingKind::UTF16,
+            Self::Utf32 => PositionEncodingKind::UTF32,
+        }
+    }
+}
+
+/// Converts a byte offset into `text` to a protocol position.
+pub fn pos_to_lsp_pos(text: &Rope, byte_idx: usize, encoding: OffsetEncoding) -> Position {
+    let byte_idx = byte_idx.min(text.len_bytes());
+    let line = text.byte_to_line(byte_idx);
+    let line_start = text.line_to_byte(line);
+    let column = match encoding {
+        OffsetEncoding::Utf8 => byte_idx - line_start,
+        OffsetEncoding::Utf16 => {
+            text.char_to_utf16_cu(text.byte_to_char(byte_idx))
+                - text.char_to_utf16_cu(text.byte_to_char(line_start))
+        }
+        OffsetEncoding::Utf32 => text.byte_to_char(byte_idx) - text.byte_to_char(line_start),
+    };
+
+    Position::new(line as u32, column as u32)
+}
+
+/// Converts a protocol position to a byte offset into `text`.
+///
+/// Columns past the end of the line are clamped to it. Returns `None` if the line does not exist.
+pub fn lsp_pos_to_pos(text: &Rope, pos: Position, encoding: OffsetEncoding) -> Option<usize> {
+    let line = pos.line as usize;
+    if line >= text.len_lines() {
+        return None;
+    }
+
+    let slice = text.line(line);
+    let line_start = text.line_to_byte(line);
+    let content = slice.len_chars()
+        - slice
+            .chars_at(slice.len_chars())
+            .reversed()
+            .take_while(|&c| c == '\n' || c == '\r')
+            .count();
+    let column = pos.character as usize;
+
+    let chars = match encoding {
+        OffsetEncoding::Utf8 => {
+            let byte = column.min(slice.char_to_byte(content));
+            // Round down to a character boundary.
+            slice.byte_to_char(byte)
+        }
+        OffsetEncoding::Utf16 => {
+            let units = column.min(slice.char_to_utf16_cu(content));
+            slice.utf16_cu_to_char(units)
+        }
+        OffsetEncoding::Utf32 => column.min(content),
+    };
+
+    Some(line_start + slice.char_to_byte(chars))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip_through_every_encoding() {
+        let text = Rope::from("a😀b\nxé\n");
+        // Byte 5 is `b`, after the four-byte emoji.
+        let expected = [
+            (OffsetEncoding::Utf8, 5),
+            (OffsetEncoding::Utf16, 3),
+            (OffsetEncoding::Utf32, 2),
+        ];
+
+        for (encoding, column) in expected {
+            let pos = pos_to_lsp_pos(&text, 5, encoding);
+            assert_eq!(pos, Position::new(0, column), "{encoding:?}");
+            assert_eq!(
+                lsp_pos_to_pos(&text, pos, encoding),
+                Some(5),
+                "{encoding:?}"
+            );
+        }
+
+        assert_eq!(
+            lsp_pos_to_pos(&text, Position::new(1, 99), OffsetEncoding::Utf16),
+            Some(10)
+        );
+        assert_eq!(
+            lsp_pos_to_pos(&text, Position::new(9, 0), OffsetEncoding::Utf16),
+            None
+        );
+    }
+}
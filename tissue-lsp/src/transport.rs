@@ -0,0 +1,278 @@
+use anyhow::{anyhow, bail, Context, Result};
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+use tokio::{
+    io::{
+        AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt,
+        BufReader,
+    },
+    sync::{
+        mpsc::{self, UnboundedReceiver, UnboundedSender},
+        oneshot,
+    },
+};
+
+// How long to wait for the response to a request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(20);
+
+// Requests waiting for a response, or `None` once the server has gone away.
+type Pending = Arc<Mutex<Option<HashMap<u64, oneshot::Sender<Result<Value>>>>>>;
+
+/// JSON-RPC over a pair of byte streams, framed with `Content-Length` headers.
+#[derive(Debug)]
+pub(crate) struct Transport {
+    name: String,
+    outgoing: UnboundedSender<Value>,
+    pending: Pending,
+    next_id: AtomicU64,
+}
+
+impl Transport {
+    /// Starts reading from and writing to the server. Notifications from the server are sent to
+    /// the returned channel as `(method, params)`.
+    pub fn start<R, W>(
+        name: &str,
+        reader: R,
+        writer: W,
+    ) -> (Self, UnboundedReceiver<(String, Value)>)
+    where
+        R: AsyncRead + Unpin + Send + 'static,
+        W: AsyncWrite + Unpin + Send + 'static,
+    {
+        let (outgoing, outgoing_rx) = mpsc::unbounded_channel();
+        let (notifications, notifications_rx) = mpsc::unbounded_channel();
+        let pending = Arc::new(Mutex::new(Some(HashMap::new())));
+
+        tokio::spawn(send(name.to_string(), writer, outgoing_rx));
+        tokio::spawn(recv(
+            name.to_string(),
+            BufReader::new(reader),
+            outgoing.clone(),
+            notifications,
+            pending.clone(),
+        ));
+
+        let transport = Self {
+            name: name.to_string(),
+            outgoing,
+            pending,
+            next_id: AtomicU64::new(0),
+        };
+
+        (transport, notifications_rx)
+    }
+
+    /// Sends a request and waits for its result.
+    pub async fn request(&self, method: &str, params: Value) -> Result<Value> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = oneshot::channel();
+        match self.pending.lock().unwrap().as_mut() {
+            Some(pending) => pending.insert(id, tx),
+            None => bail!("{} is not running", self.name),
+        };
+
+        let message = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        if self.outgoing.send(message).is_err() {
+            self.forget(id);
+            bail!("{} is not running", self.name);
+        }
+
+        match tokio::time::timeout(REQUEST_TIMEOUT, rx).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(_)) => bail!("{} exited before answering '{method}'", self.name),
+            Err(_) => {
+                self.forget(id);
+                bail!("{} timed out answering '{method}'", self.name)
+            }
+        }
+    }
+
+    fn forget(&self, id: u64) {
+        if let Some(pending) = self.pending.lock().unwrap().as_mut() {
+            pending.remove(&id);
+        }
+    }
+
+    /// Sends a notification.
+    pub fn notify(&self, method: &str, params: Value) -> Result<()> {
+        let message = json!({ "jsonrpc": "2.0", "method": method, "params": params });
+        self.outgoing
+            .send(message)
+            .map_err(|_| anyhow!("{} is not running", self.name))
+    }
+}
+
+/// Reads one message. Returns `None` at the end of the stream.
+async fn read_message<R: AsyncBufRead + Unpin>(reader: &mut R) -> Result<Option<Value>> {
+    let mut content_length = None;
+    let mut line = String::new();
+
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).await? == 0 {
+            return Ok(None);
+        }
+
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = Some(value.trim().parse::<usize>()?);
+            }
+        }
+    }
+
+    let length = content_length.context("message without a Content-Length header")?;
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).await?;
+
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+/// Writes one message.
+async fn write_message<W: AsyncWrite + Unpin>(writer: &mut W, message: &Value) -> Result<()> {
+    let body = serde_json::to_string(message)?;
+    writer
+        .write_all(format!("Content-Length: {}\r\n\r\n{body}", body.len()).as_bytes())
+        .await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+async fn send<W: AsyncWrite + Unpin>(
+    name: String,
+    mut writer: W,
+    mut outgoing: UnboundedReceiver<Value>,
+) {
+    while let Some(message) = outgoing.recv().await {
+        log::trace!("{name} <- {message}");
+        if let Err(err) = write_message(&mut writer, &message).await {
+            log::error!("failed to write to {name}: {err}");
+            break;
+        }
+    }
+}
+
+async fn recv<R: AsyncBufRead + Unpin>(
+    name: String,
+    mut reader: R,
+    outgoing: UnboundedSender<Value>,
+    notifications: UnboundedSender<(String, Value)>,
+    pending: Pending,
+) {
+    loop {
+        let message = match read_message(&mut reader).await {
+            Ok(Some(message)) => message,
+            Ok(None) => break,
+            Err(err) => {
+                log::error!("failed to read from {name}: {err}");
+                break;
+            }
+        };
+        log::trace!("{name} -> {message}");
+
+        let method = message.get("method").and_then(Value::as_str);
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+
+        match (message.get("id").cloned(), method) {
+            // A request from the server.
+            (Some(id), Some(method)) => {
+                let response = match server_request_result(method, &params) {
+                    Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                    Err(error) => {
+                        log::debug!("{name} sent unhandled request '{method}'");
+                        json!({ "jsonrpc": "2.0", "id": id, "error": error })
+                    }
+                };
+                outgoing.send(response).ok();
+            }
+            (None, Some(method)) => {
+                notifications.send((method.to_string(), params)).ok();
+            }
+            (Some(id), None) => {
+                let sender = id.as_u64().and_then(|id| {
+                    pending
+                        .lock()
+                        .unwrap()
+                        .as_mut()
+                        .and_then(|pending| pending.remove(&id))
+                });
+                let Some(sender) = sender else {
+                    log::warn!("{name} answered unknown request {id}");
+                    continue;
+                };
+                let result = match message.get("error") {
+                    Some(error) => Err(anyhow!(
+                        "{name}: {}",
+                        error
+                            .get("message")
+                            .and_then(Value::as_str)
+                            .unwrap_or("unknown error")
+                    )),
+                    None => Ok(message.get("result").cloned().unwrap_or(Value::Null)),
+                };
+                sender.send(result).ok();
+            }
+            (None, None) => log::warn!("{name} sent an invalid message: {message}"),
+        }
+    }
+
+    // Dropping the senders fails the requests that are still waiting.
+    pending.lock().unwrap().take();
+}
+
+// The JSON-RPC error code for requests of unknown methods.
+const METHOD_NOT_FOUND: i64 = -32601;
+
+// Answers requests the server sends to the client, or returns the error object for requests the
+// client does not handle. Nothing is configurable yet, so every configuration item is `null`.
+fn server_request_result(method: &str, params: &Value) -> Result<Value, Value> {
+    match method {
+        "workspace/configuration" => {
+            let items = params
+                .get("items")
+                .and_then(Value::as_array)
+                .map_or(0, Vec::len);
+            Ok(Value::Array(vec![Value::Null; items]))
+        }
+        // Progress is reported through `$/progress` notifications, whatever the token.
+        "window/workDoneProgress/create" => Ok(Value::Null),
+        // Edits are only applied when the user asks for them, as with renames.
+        "workspace/applyEdit" => Ok(json!({
+            "applied": false,
+            "failureReason": "edits requested by the server are not supported",
+        })),
+        _ => Err(json!({
+            "code": METHOD_NOT_FOUND,
+            "message": format!("unhandled method '{method}'"),
+        })),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unknown_server_requests_are_errors() {
+        let params = json!({ "items": [{ "section": "a" }, { "section": "b" }] });
+        assert_eq!(
+            server_request_result("workspace/configuration", &params),
+            Ok(json!([null, null]))
+        );
+        let edit = server_request_result("workspace/applyEdit", &json!({ "edit": {} }));
+        assert_eq!(edit.unwrap()["applied"], false);
+
+        let error = server_request_result("workspace/unknown", &Value::Null).unwrap_err();
+        assert_eq!(error["code"], METHOD_NOT_FOUND);
+    }
+}
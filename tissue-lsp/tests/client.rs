@@ -0,0 +1,137 @@
+use std::time::Duration;
+use tissue_lsp::{
+    lsp::{self, DiagnosticSeverity, Position, Range, TextDocumentContentChangeEvent, Url},
+    mock, Client, Notification, OffsetEncoding,
+};
+use tokio::{sync::mpsc::UnboundedReceiver, time::timeout};
+
+async fn start() -> (Client, UnboundedReceiver<Notification>) {
+    let (client, notifications) = mock::start(&std::env::temp_dir());
+    client.initialize().await.unwrap();
+    (client, notifications)
+}
+
+async fn diagnostics(notifications: &mut UnboundedReceiver<Notification>) -> Vec<lsp::Diagnostic> {
+    loop {
+        let notification = timeout(Duration::from_secs(5), notifications.recv())
+            .await
+            .expect("no diagnostics were published")
+            .expect("server exited");
+        if let Notification::PublishDiagnostics(params) = notification {
+            return params.diagnostics;
+        }
+    }
+}
+
+fn uri() -> Url {
+    Url::from_file_path(std::env::temp_dir().join("main.txt")).unwrap()
+}
+
+fn range(start: (u32, u32), end: (u32, u32)) -> Range {
+    Range::new(Position::new(start.0, start.1), Position::new(end.0, end.1))
+}
+
+#[tokio::test]
+async fn initialize_negotiates_capabilities() {
+    let (client, _) = start().await;
+
+    assert_eq!(client.sync_kind(), lsp::TextDocumentSyncKind::INCREMENTAL);
+    assert_eq!(client.offset_encoding(), OffsetEncoding::Utf16);
+    assert!(client.capabilities().unwrap().rename_provider.is_some());
+
+    client.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn incremental_changes_update_diagnostics() {
+    let (client, mut notifications) = start().await;
+
+    client
+        .text_document_did_open(uri(), "text", 0, "let x = 1;\n".into())
+        .unwrap();
+    assert!(diagnostics(&mut notifications).await.is_empty());
+
+    // Insert a comment after the first line, then replace `x` with a wide character.
+    let changes = vec![
+        TextDocumentContentChangeEvent {
+            range: Some(range((1, 0), (1, 0))),
+            range_length: None,
+            text: "// TODO\n".into(),
+        },
+        TextDocumentContentChangeEvent {
+            range: Some(range((0, 4), (0, 5))),
+            range_length: None,
+            text: "😀".into(),
+        },
+    ];
+    client.text_document_did_change(uri(), 1, changes).unwrap();
+
+    let diagnostics = diagnostics(&mut notifications).await;
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].range, range((1, 3), (1, 7)));
+    assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::WARNING));
+
+    let hover = client.hover(uri(), Position::new(0, 10)).await.unwrap();
+    assert_eq!(
+        hover.unwrap().contents,
+        lsp::HoverContents::Markup(lsp::MarkupContent {
+            kind: lsp::MarkupKind::PlainText,
+            value: "1: 1 occurrence(s)".into(),
+        })
+    );
+}
+
+#[tokio::test]
+async fn navigation_and_editing_requests() {
+    let (client, _) = start().await;
+    let text = "fn foo() {}\nfn bar() { foo(); fo }\n";
+    client
+        .text_document_did_open(uri(), "text", 0, text.into())
+        .unwrap();
+
+    let completions = client
+        .completion(uri(), Position::new(1, 20))
+        .await
+        .unwrap();
+    let labels: Vec<_> = completions.iter().map(|item| item.label.as_str()).collect();
+    assert_eq!(labels, ["foo"]);
+
+    let definition = client
+        .goto_definition(uri(), Position::new(1, 12))
+        .await
+        .unwrap();
+    assert_eq!(
+        definition,
+        [lsp::Location::new(uri(), range((0, 3), (0, 6)))]
+    );
+
+    let references = client.references(uri(), Position::new(0, 4)).await.unwrap();
+    assert_eq!(references.len(), 2);
+    assert_eq!(references[1].range, range((1, 11), (1, 14)));
+
+    let edit = client
+        .rename(uri(), Position::new(0, 4), "baz".into())
+        .await
+        .unwrap()
+        .unwrap();
+    let edits = &edit.changes.unwrap()[&uri()];
+    assert_eq!(edits.len(), 2);
+    assert!(edits.iter().all(|edit| edit.new_text == "baz"));
+}
+
+#[tokio::test]
+async fn failures_are_reported() {
+    assert!(Client::start("tissue-no-such-server", &[], &std::env::temp_dir()).is_err());
+
+    let (client, _) = mock::start(&std::env::temp_dir());
+    let err = client.hover(uri(), Position::new(0, 0)).await.unwrap_err();
+    assert!(err.to_string().contains("not initialized"), "{err}");
+
+    client.initialize().await.unwrap();
+    client.shutdown().await.unwrap();
+    let err = client.hover(uri(), Position::new(0, 0)).await.unwrap_err();
+    assert!(
+        err.to_string().contains("exited") || err.to_string().contains("not running"),
+        "{err}"
+    );
+}
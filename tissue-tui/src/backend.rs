@@ -0,0 +1,33 @@
+mod crossterm;
+mod test;
+
+pub use self::{crossterm::CrosstermBackend, test::TestBackend};
+
+use crate::{Cell, Rect};
+use std::io;
+
+/// A sink for rendered cells.
+pub trait Backend {
+    /// Draws the given cells at their `(x, y)` positions.
+    fn draw<'a, I>(&mut self, content: I) -> io::Result<()>
+    where
+        I: Iterator<Item = (u16, u16, &'a Cell)>;
+
+    /// Hides the cursor.
+    fn hide_cursor(&mut self) -> io::Result<()>;
+
+    /// Shows the cursor.
+    fn show_cursor(&mut self) -> io::Result<()>;
+
+    /// Moves the cursor to `(x, y)`.
+    fn set_cursor(&mut self, x: u16, y: u16) -> io::Result<()>;
+
+    /// Clears the whole screen.
+    fn clear(&mut self) -> io::Result<()>;
+
+    /// Returns the size of the screen.
+    fn size(&self) -> io::Result<Rect>;
+
+    /// Flushes pending output.
+    fn flush(&mut self) -> io::Result<()>;
+}
@@ -0,0 +1,149 @@
+use crate::{Backend, Cell, Color, Modifier, Rect};
+use crossterm::{
+    cursor::{Hide, MoveTo, Show},
+    queue,
+    style::{Attribute, Print, SetAttribute, SetBackgroundColor, SetForegroundColor},
+    terminal::{self, Clear, ClearType},
+};
+use std::io::{self, Write};
+
+/// A backend that writes escape sequences through crossterm.
+#[derive(Debug)]
+pub struct CrosstermBackend<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> CrosstermBackend<W> {
+    /// Creates a backend writing to `writer`.
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+}
+
+impl<W: Write> Backend for CrosstermBackend<W> {
+    fn draw<'a, I>(&mut self, content: I) -> io::Result<()>
+    where
+        I: Iterator<Item = (u16, u16, &'a Cell)>,
+    {
+        let mut fg = Color::Reset;
+        let mut bg = Color::Reset;
+        let mut modifier = Modifier::empty();
+        let mut last_pos: Option<(u16, u16)> = None;
+
+        for (x, y, cell) in content {
+            // Skip the cursor movement when the cell directly follows the previous one.
+            if !matches!(last_pos, Some((lx, ly)) if lx + 1 == x && ly == y) {
+                queue!(self.writer, MoveTo(x, y))?;
+            }
+            last_pos = Some((x, y));
+
+            if cell.modifier != modifier {
+                queue_modifier_diff(&mut self.writer, modifier, cell.modifier)?;
+                modifier = cell.modifier;
+            }
+            if cell.fg != fg {
+                queue!(self.writer, SetForegroundColor(cell.fg.into()))?;
+                fg = cell.fg;
+            }
+            if cell.bg != bg {
+                queue!(self.writer, SetBackgroundColor(cell.bg.into()))?;
+                bg = cell.bg;
+            }
+
+            queue!(self.writer, Print(&cell.symbol))?;
+        }
+
+        queue!(
+            self.writer,
+            SetForegroundColor(Color::Reset.into()),
+            SetBackgroundColor(Color::Reset.into()),
+            SetAttribute(Attribute::Reset),
+        )
+    }
+
+    fn hide_cursor(&mut self) -> io::Result<()> {
+        queue!(self.writer, Hide)
+    }
+
+    fn show_cursor(&mut self) -> io::Result<()> {
+        queue!(self.writer, Show)
+    }
+
+    fn set_cursor(&mut self, x: u16, y: u16) -> io::Result<()> {
+        queue!(self.writer, MoveTo(x, y))
+    }
+
+    fn clear(&mut self) -> io::Result<()> {
+        queue!(self.writer, Clear(ClearType::All))
+    }
+
+    fn size(&self) -> io::Result<Rect> {
+        let (width, height) = terminal::size()?;
+        Ok(Rect::new(0, 0, width, height))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+// Queues the attribute changes needed to go from `from` to `to`.
+fn queue_modifier_diff<W: Write>(writer: &mut W, from: Modifier, to: Modifier) -> io::Result<()> {
+    let removed = from - to;
+    let added = to - from;
+
+    if removed.intersects(Modifier::BOLD | Modifier::DIM) {
+        queue!(writer, SetAttribute(Attribute::NormalIntensity))?;
+        // `NormalIntensity` clears both, so re-apply whichever one is kept.
+        if to.contains(Modifier::BOLD) {
+            queue!(writer, SetAttribute(Attribute::Bold))?;
+        }
+        if to.contains(Modifier::DIM) {
+            queue!(writer, SetAttribute(Attribute::Dim))?;
+        }
+    }
+
+    let attributes = [
+        (Modifier::ITALIC, Attribute::Italic, Attribute::NoItalic),
+        (
+            Modifier::UNDERLINED,
+            Attribute::Underlined,
+            Attribute::NoUnderline,
+        ),
+        (
+            Modifier::SLOW_BLINK,
+            Attribute::SlowBlink,
+            Attribute::NoBlink,
+        ),
+        (
+            Modifier::RAPID_BLINK,
+            Attribute::RapidBlink,
+            Attribute::NoBlink,
+        ),
+        (Modifier::REVERSED, Attribute::Reverse, Attribute::NoReverse),
+        (Modifier::HIDDEN, Attribute::Hidden, Attribute::NoHidden),
+        (
+            Modifier::CROSSED_OUT,
+            Attribute::CrossedOut,
+            Attribute::NotCrossedOut,
+        ),
+    ];
+
+    for (modifier, on, off) in attributes {
+        if removed.contains(modifier) {
+            queue!(writer, SetAttribute(off))?;
+        }
+        if added.contains(modifier) {
+            queue!(writer, SetAttribute(on))?;
+        }
+    }
+
+    if added.contains(Modifier::BOLD) {
+        queue!(writer, SetAttribute(Attribute::Bold))?;
+    }
+    if added.contains(Modifier::DIM) {
+        queue!(writer, SetAttribute(Attribute::Dim))?;
+    }
+
+    Ok(())
+}
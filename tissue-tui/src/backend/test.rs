@@ -0,0 +1,123 @@
+use crate::{Backend, Cell, Rect, Surface};
+use std::{fmt, io};
+
+/// A headless backend that renders into an in-memory [`Surface`].
+///
+/// Used to snapshot-test rendering without a terminal.
+#[derive(Debug)]
+pub struct TestBackend {
+    surface: Surface,
+    cursor: (u16, u16),
+    cursor_visible: bool,
+    cells_drawn: usize,
+}
+
+impl TestBackend {
+    /// Creates a backend with a screen of `width` by `height` cells.
+    pub fn new(width: u16, height: u16) -> Self {
+        Self {
+            surface: Surface::empty(Rect::new(0, 0, width, height)),
+            cursor: (0, 0),
+            cursor_visible: true,
+            cells_drawn: 0,
+        }
+    }
+
+    /// Returns the screen contents.
+    pub fn surface(&self) -> &Surface {
+        &self.surface
+    }
+
+    /// Returns the cursor position.
+    pub fn cursor(&self) -> (u16, u16) {
+        self.cursor
+    }
+
+    /// Returns `true` if the cursor is shown.
+    pub fn is_cursor_visible(&self) -> bool {
+        self.cursor_visible
+    }
+
+    /// Returns the number of cells written by the last call to [`Backend::draw`].
+    pub fn cells_drawn(&self) -> usize {
+        self.cells_drawn
+    }
+
+    /// Resizes the screen.
+    pub fn resize(&mut self, width: u16, height: u16) {
+        self.surface.resize(Rect::new(0, 0, width, height));
+    }
+
+    /// Asserts that the screen shows `lines`, ignoring styles.
+    #[track_caller]
+    pub fn assert_lines<S: AsRef<str>>(&self, lines: &[S]) {
+        let expected: Vec<_> = lines.iter().map(|line| line.as_ref()).collect();
+        let snapshot = self.to_string();
+        let actual: Vec<_> = snapshot.lines().collect();
+
+        assert_eq!(actual, expected, "\n{snapshot}");
+    }
+}
+
+impl fmt::Display for TestBackend {
+    /// Writes the screen contents, one line per row.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let area = self.surface.area();
+
+        for y in area.y..area.bottom() {
+            for x in area.x..area.right() {
+                // Unwrap OK since we are iterating within the area
+                f.write_str(&self.surface.get(x, y).unwrap().symbol)?;
+            }
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Backend for TestBackend {
+    fn draw<'a, I>(&mut self, content: I) -> io::Result<()>
+    where
+        I: Iterator<Item = (u16, u16, &'a Cell)>,
+    {
+        self.cells_drawn = 0;
+
+        for (x, y, cell) in content {
+            if let Some(target) = self.surface.get_mut(x, y) {
+                *target = cell.clone();
+                self.cells_drawn += 1;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn hide_cursor(&mut self) -> io::Result<()> {
+        self.cursor_visible = false;
+        Ok(())
+    }
+
+    fn show_cursor(&mut self) -> io::Result<()> {
+        self.cursor_visible = true;
+        Ok(())
+    }
+
+    fn set_cursor(&mut self, x: u16, y: u16) -> io::Result<()> {
+        self.cursor = (x, y);
+        Ok(())
+    }
+
+    fn clear(&mut self) -> io::Result<()> {
+        self.surface.reset();
+        Ok(())
+    }
+
+    fn size(&self) -> io::Result<Rect> {
+        Ok(self.surface.area())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,192 @@
+use bitflags::bitflags;
+
+/// A terminal color.
+#[allow(missing_docs)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Color {
+    /// The terminal's default color.
+    #[default]
+    Reset,
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    Gray,
+    LightRed,
+    LightGreen,
+    LightYellow,
+    LightBlue,
+    LightMagenta,
+    LightCyan,
+    LightGray,
+    White,
+    /// A 24-bit color.
+    Rgb(u8, u8, u8),
+    /// A color from the 256-color palette.
+    Indexed(u8),
+}
+
+impl From<Color> for crossterm::style::Color {
+    fn from(color: Color) -> Self {
+        use crossterm::style::Color as C;
+
+        match color {
+            Color::Reset => C::Reset,
+            Color::Black => C::Black,
+            Color::Red => C::DarkRed,
+            Color::Green => C::DarkGreen,
+            Color::Yellow => C::DarkYellow,
+            Color::Blue => C::DarkBlue,
+            Color::Magenta => C::DarkMagenta,
+            Color::Cyan => C::DarkCyan,
+            Color::Gray => C::DarkGrey,
+            Color::LightRed => C::Red,
+            Color::LightGreen => C::Green,
+            Color::LightYellow => C::Yellow,
+            Color::LightBlue => C::Blue,
+            Color::LightMagenta => C::Magenta,
+            Color::LightCyan => C::Cyan,
+            Color::LightGray => C::Grey,
+            Color::White => C::White,
+            Color::Rgb(r, g, b) => C::Rgb { r, g, b },
+            Color::Indexed(i) => C::AnsiValue(i),
+        }
+    }
+}
+
+bitflags! {
+    /// Text attributes of a cell.
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct Modifier: u16 {
+        /// Bold or increased intensity.
+        const BOLD = 1 << 0;
+        /// Faint or decreased intensity.
+        const DIM = 1 << 1;
+        /// Italic.
+        const ITALIC = 1 << 2;
+        /// Underlined.
+        const UNDERLINED = 1 << 3;
+        /// Blinking at less than 150 times per minute.
+        const SLOW_BLINK = 1 << 4;
+        /// Blinking at 150 times per minute or more.
+        const RAPID_BLINK = 1 << 5;
+        /// Swapped foreground and background colors.
+        const REVERSED = 1 << 6;
+        /// Invisible text.
+        const HIDDEN = 1 << 7;
+        /// Struck through.
+        const CROSSED_OUT = 1 << 8;
+    }
+}
+
+/// A set of style changes applied on top of a cell.
+///
+/// Unset colors leave the cell's colors untouched, so styles can be layered with
+/// [`Style::patch`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Style {
+    /// Foreground color.
+    pub fg: Option<Color>,
+    /// Background color.
+    pub bg: Option<Color>,
+    /// Modifiers to turn on.
+    pub add_modifier: Modifier,
+    /// Modifiers to turn off.
+    pub sub_modifier: Modifier,
+}
+
+impl Style {
+    /// Sets the foreground color.
+    pub const fn fg(mut self, color: Color) -> Self {
+        self.fg = Some(color);
+        self
+    }
+
+    /// Sets the background color.
+    pub const fn bg(mut self, color: Color) -> Self {
+        self.bg = Some(color);
+        self
+    }
+
+    /// Turns on `modifier`.
+    pub fn add_modifier(mut self, modifier: Modifier) -> Self {
+        self.sub_modifier.remove(modifier);
+        self.add_modifier.insert(modifier);
+        self
+    }
+
+    /// Turns off `modifier`.
+    pub fn remove_modifier(mut self, modifier: Modifier) -> Self {
+        self.add_modifier.remove(modifier);
+        self.sub_modifier.insert(modifier);
+        self
+    }
+
+    /// Layers `other` on top of this style.
+    pub fn patch(mut self, other: Style) -> Self {
+        self.fg = other.fg.or(self.fg);
+        self.bg = other.bg.or(self.bg);
+
+        self.add_modifier.remove(other.sub_modifier);
+        self.add_modifier.insert(other.add_modifier);
+        self.sub_modifier.remove(other.add_modifier);
+        self.sub_modifier.insert(other.sub_modifier);
+
+        self
+    }
+}
+
+/// A rectangular area of the screen.
+#[allow(missing_docs)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Rect {
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+}
+
+impl Rect {
+    /// Creates a new rectangle.
+    pub const fn new(x: u16, y: u16, width: u16, height: u16) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Returns the number of cells in the rectangle.
+    pub const fn area(self) -> usize {
+        self.width as usize * self.height as usize
+    }
+
+    /// Returns the x coordinate one past the right edge.
+    pub const fn right(self) -> u16 {
+        self.x.saturating_add(self.width)
+    }
+
+    /// Returns the y coordinate one past the bottom edge.
+    pub const fn bottom(self) -> u16 {
+        self.y.saturating_add(self.height)
+    }
+
+    /// Returns `true` if `(x, y)` lies inside the rectangle.
+    pub const fn contains(self, x: u16, y: u16) -> bool {
+        x >= self.x && x < self.right() && y >= self.y && y < self.bottom()
+    }
+
+    /// Returns the overlapping part of two rectangles.
+    pub fn intersection(self, other: Rect) -> Rect {
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        let right = self.right().min(other.right());
+        let bottom = self.bottom().min(other.bottom());
+
+        Rect::new(x, y, right.saturating_sub(x), bottom.saturating_sub(y))
+    }
+}
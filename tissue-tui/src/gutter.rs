@@ -0,0 +1,186 @@
+use crate::{Rect, Style, Surface};
+use std::collections::BTreeMap;
+
+// Line numbers take at least this many columns, so that the text does not shift as the first
+// lines are typed.
+const MIN_LINE_NUMBER_WIDTH: usize = 3;
+
+/// How a [`GutterColumn::LineNumbers`] column numbers lines.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum LineNumbers {
+    /// The number of every line, counting from 1.
+    #[default]
+    Absolute,
+    /// The distance of every line from the cursor line, which shows 0.
+    Relative,
+    /// The distance of every line from the cursor line, which shows its own number.
+    Hybrid,
+}
+
+/// A column of a [`Gutter`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GutterColumn {
+    /// Line numbers, right-aligned and as wide as the number of the last line.
+    LineNumbers {
+        /// How lines are numbered.
+        mode: LineNumbers,
+        /// The style of the numbers.
+        style: Style,
+        /// The style of the number of the cursor line.
+        cursor_style: Style,
+    },
+    /// One column of signs, such as diagnostics or breakpoints, keyed by line.
+    Signs(BTreeMap<usize, (char, Style)>),
+    /// An empty column.
+    Spacer,
+}
+
+impl GutterColumn {
+    fn width(&self, line_count: usize) -> usize {
+        match self {
+            Self::LineNumbers { .. } => line_count.to_string().len().max(MIN_LINE_NUMBER_WIDTH),
+            Self::Signs(_) | Self::Spacer => 1,
+        }
+    }
+}
+
+/// The columns drawn left of the lines of a buffer, such as line numbers and signs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Gutter {
+    columns: Vec<GutterColumn>,
+    line_count: usize,
+    cursor_line: usize,
+}
+
+impl Gutter {
+    /// Creates an empty gutter for a buffer of `line_count` lines with the cursor on
+    /// `cursor_line`.
+    pub fn new(line_count: usize, cursor_line: usize) -> Self {
+        Self {
+            columns: Vec::new(),
+            line_count,
+            cursor_line,
+        }
+    }
+
+    /// Adds `column` right of the columns added so far.
+    pub fn push(&mut self, column: GutterColumn) {
+        self.columns.push(column);
+    }
+
+    /// Returns how many columns the gutter takes.
+    pub fn width(&self) -> u16 {
+        let widths = self
+            .columns
+            .iter()
+            .map(|column| column.width(self.line_count));
+        widths.sum::<usize>() as u16
+    }
+
+    /// Draws the gutter of the lines from `first_line` on into `area`, one line per row. Rows past
+    /// the last line are left empty.
+    pub fn render(&self, surface: &mut Surface, area: Rect, first_line: usize) {
+        let lines = (first_line..self.line_count).take(area.height as usize);
+        for (y, line) in lines.enumerate() {
+            let y = area.y + y as u16;
+            let mut x = area.x;
+            for column in &self.columns {
+                let width = column.width(self.line_count);
+                let cell = match column {
+                    GutterColumn::LineNumbers {
+                        mode,
+                        style,
+                        cursor_style,
+                    } => {
+                        let number = match mode {
+                            LineNumbers::Relative => line.abs_diff(self.cursor_line),
+                            LineNumbers::Hybrid if line != self.cursor_line => {
+                                line.abs_diff(self.cursor_line)
+                            }
+                            _ => line + 1,
+                        };
+                        let style = match line == self.cursor_line {
+                            true => cursor_style,
+                            false => style,
+                        };
+                        Some((format!("{number:>width$}"), *style))
+                    }
+                    GutterColumn::Signs(signs) => signs
+                        .get(&line)
+                        .map(|(sign, style)| (sign.to_string(), *style)),
+                    GutterColumn::Spacer => None,
+                };
+                if let Some((symbol, style)) = cell {
+                    let width = width.min(area.right().saturating_sub(x) as usize);
+                    surface.set_stringn(x, y, &symbol, width, style);
+                }
+                x += width as u16;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Color, Terminal, TestBackend};
+
+    fn gutter(mode: LineNumbers, line_count: usize) -> Gutter {
+        let mut gutter = Gutter::new(line_count, 1);
+        let red = Style::default().fg(Color::Red);
+        gutter.push(GutterColumn::Signs(BTreeMap::from([(2, ('●', red))])));
+        gutter.push(GutterColumn::LineNumbers {
+            mode,
+            style: Style::default(),
+            cursor_style: Style::default().fg(Color::Yellow),
+        });
+        gutter.push(GutterColumn::Spacer);
+        gutter
+    }
+
+    #[test]
+    fn numbers_lines_next_to_signs() {
+        let mut terminal = Terminal::new(TestBackend::new(6, 4)).unwrap();
+        for (mode, numbers) in [
+            (LineNumbers::Absolute, ["  1", "  2", "  3"]),
+            (LineNumbers::Relative, ["  1", "  0", "  1"]),
+            (LineNumbers::Hybrid, ["  1", "  2", "  1"]),
+        ] {
+            let gutter = gutter(mode, 3);
+            assert_eq!(gutter.width(), 5);
+
+            terminal
+                .draw(|surface| {
+                    gutter.render(surface, surface.area(), 0);
+                    None
+                })
+                .unwrap();
+
+            let backend = terminal.backend();
+            backend.assert_lines(&[
+                format!(" {}  ", numbers[0]),
+                format!(" {}  ", numbers[1]),
+                format!("●{}  ", numbers[2]),
+                "      ".to_string(),
+            ]);
+            let surface = backend.surface();
+            assert_eq!(surface.get(3, 1).unwrap().fg, Color::Yellow);
+            assert_eq!(surface.get(0, 2).unwrap().fg, Color::Red);
+        }
+    }
+
+    #[test]
+    fn line_numbers_widen_with_the_line_count() {
+        let gutter = gutter(LineNumbers::Absolute, 12345);
+        assert_eq!(gutter.width(), 7);
+
+        let mut terminal = Terminal::new(TestBackend::new(8, 2)).unwrap();
+        terminal
+            .draw(|surface| {
+                gutter.render(surface, surface.area(), 9998);
+                None
+            })
+            .unwrap();
+        terminal.backend().assert_lines(&["  9999  ", " 10000  "]);
+    }
+}
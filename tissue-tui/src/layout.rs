@@ -0,0 +1,509 @@
+use crate::Rect;
+
+/// Identifies a view of a [`Layout`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ViewId(usize);
+
+/// How a split arranges its views.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Split {
+    /// Views stacked above each other.
+    Horizontal,
+    /// Views side by side, with a column between them for a separator.
+    Vertical,
+}
+
+/// A side of a view, to look for a neighboring view on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    /// Left of the view.
+    Left,
+    /// Right of the view.
+    Right,
+    /// Above the view.
+    Up,
+    /// Below the view.
+    Down,
+}
+
+/// The shape of a [`Layout`] without its view ids, to save a layout and build it again.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LayoutTree {
+    /// A view.
+    View,
+    /// Children split the same way, with their share of the area relative to each other.
+    Split(Split, Vec<(LayoutTree, f32)>),
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    View(ViewId),
+    // Children with their share of the area, relative to the other children.
+    Container(Split, Vec<(Node, f32)>),
+}
+
+impl Node {
+    // Builds the nodes of `tree`, numbering its views from `next_id` on. Splits of less than two
+    // children are left out.
+    fn from_tree(tree: &LayoutTree, next_id: &mut usize) -> Self {
+        match tree {
+            LayoutTree::Split(split, children) if children.len() > 1 => {
+                let children = children.iter().map(|(child, weight)| {
+                    let weight = match weight.is_finite() && *weight > 0.0 {
+                        true => *weight,
+                        false => 1.0,
+                    };
+                    (Node::from_tree(child, next_id), weight)
+                });
+                Node::Container(*split, children.collect())
+            }
+            LayoutTree::Split(_, children) if children.len() == 1 => {
+                Node::from_tree(&children[0].0, next_id)
+            }
+            _ => {
+                *next_id += 1;
+                Node::View(ViewId(*next_id - 1))
+            }
+        }
+    }
+
+    fn tree(&self) -> LayoutTree {
+        match self {
+            Node::View(_) => LayoutTree::View,
+            Node::Container(split, children) => {
+                let children = children
+                    .iter()
+                    .map(|(child, weight)| (child.tree(), *weight));
+                LayoutTree::Split(*split, children.collect())
+            }
+        }
+    }
+
+    fn first_view(&self) -> ViewId {
+        match self {
+            Node::View(id) => *id,
+            Node::Container(_, children) => children[0].0.first_view(),
+        }
+    }
+
+    fn collect(&self, area: Rect, views: &mut Vec<(ViewId, Rect)>, separators: &mut Vec<Rect>) {
+        match self {
+            Node::View(id) => views.push((*id, area)),
+            Node::Container(split, children) => {
+                let weights: Vec<_> = children.iter().map(|(_, weight)| *weight).collect();
+                let areas = split_area(*split, &weights, area);
+                for ((child, _), &child_area) in children.iter().zip(&areas) {
+                    child.collect(child_area, views, separators);
+                }
+                if *split == Split::Vertical {
+                    let gaps = areas
+                        .iter()
+                        .skip(1)
+                        .map(|child| Rect::new(child.x.saturating_sub(1), area.y, 1, area.height));
+                    separators.extend(gaps);
+                }
+            }
+        }
+    }
+}
+
+// Divides `area` between children weighing `weights`, leaving a column between side by side
+// children.
+fn split_area(split: Split, weights: &[f32], area: Rect) -> Vec<Rect> {
+    let gaps = match split {
+        Split::Horizontal => 0,
+        Split::Vertical => weights.len().saturating_sub(1) as u16,
+    };
+    let total = match split {
+        Split::Horizontal => area.height,
+        Split::Vertical => area.width.saturating_sub(gaps),
+    };
+    let sum: f32 = weights.iter().sum();
+
+    let mut areas = Vec::with_capacity(weights.len());
+    let (mut weight, mut start) = (0.0, 0);
+    for (i, w) in weights.iter().enumerate() {
+        weight += w;
+        let end = (total as f32 * weight / sum).round() as u16;
+        let size = end.saturating_sub(start);
+        areas.push(match split {
+            Split::Horizontal => Rect::new(area.x, area.y + start, area.width, size),
+            Split::Vertical => Rect::new(area.x + start + i as u16, area.y, size, area.height),
+        });
+        start = end.max(start);
+    }
+    areas
+}
+
+/// A tree of views, split horizontally or vertically, that divides an area between them.
+///
+/// The layout only arranges views: what a view shows is kept by its owner, keyed by its
+/// [`ViewId`].
+#[derive(Debug, Clone)]
+pub struct Layout {
+    root: Node,
+    next_id: usize,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Layout {
+    /// Creates a layout of a single view.
+    pub fn new() -> Self {
+        Self {
+            root: Node::View(ViewId(0)),
+            next_id: 1,
+        }
+    }
+
+    /// Builds the layout shaped like `tree`. Its views, from top left to bottom right, are
+    /// numbered in order, so they match the views of the layout `tree` was taken from.
+    pub fn from_tree(tree: &LayoutTree) -> Self {
+        let mut next_id = 0;
+        let root = Node::from_tree(tree, &mut next_id);
+        Self { root, next_id }
+    }
+
+    /// Returns the shape of the layout.
+    pub fn tree(&self) -> LayoutTree {
+        self.root.tree()
+    }
+
+    /// Returns the views, from top left to bottom right.
+    pub fn views(&self) -> Vec<ViewId> {
+        let views = self.areas(Rect::default()).into_iter();
+        views.map(|(id, _)| id).collect()
+    }
+
+    /// Returns whether `view` is part of the layout.
+    pub fn contains(&self, view: ViewId) -> bool {
+        self.path(view).is_some()
+    }
+
+    /// Returns the area of each view when the layout fills `area`.
+    pub fn areas(&self, area: Rect) -> Vec<(ViewId, Rect)> {
+        let mut views = Vec::new();
+        self.root.collect(area, &mut views, &mut Vec::new());
+        views
+    }
+
+    /// Returns the columns between side by side views when the layout fills `area`.
+    pub fn separators(&self, area: Rect) -> Vec<Rect> {
+        let mut separators = Vec::new();
+        self.root.collect(area, &mut Vec::new(), &mut separators);
+        separators
+    }
+
+    /// Returns the view drawn at `(x, y)` when the layout fills `area`.
+    pub fn view_at(&self, area: Rect, x: u16, y: u16) -> Option<ViewId> {
+        let mut views = self.areas(area).into_iter();
+        views
+            .find(|(_, area)| area.contains(x, y))
+            .map(|(id, _)| id)
+    }
+
+    /// Splits `view` in two and returns the new view, placed below or right of it.
+    pub fn split(&mut self, view: ViewId, split: Split) -> Option<ViewId> {
+        let path = self.path(view)?;
+        let id = ViewId(self.next_id);
+        self.next_id += 1;
+
+        // Splitting a view the way its container splits adds a sibling sharing its space.
+        if let Some((&index, parent)) = path.split_last() {
+            if let Node::Container(kind, children) = self.node_mut(parent) {
+                if *kind == split {
+                    children[index].1 /= 2.0;
+                    let weight = children[index].1;
+                    children.insert(index + 1, (Node::View(id), weight));
+                    return Some(id);
+                }
+            }
+        }
+        let node = self.node_mut(&path);
+        *node = Node::Container(split, vec![(Node::View(view), 1.0), (Node::View(id), 1.0)]);
+        Some(id)
+    }
+
+    /// Removes `view` and returns the view that took its space, or `None` if `view` is the last
+    /// view or is not part of the layout.
+    pub fn close(&mut self, view: ViewId) -> Option<ViewId> {
+        let path = self.path(view)?;
+        let (&index, parent) = path.split_last()?;
+
+        let Node::Container(_, children) = self.node_mut(parent) else {
+            unreachable!("views are only nested in containers");
+        };
+        let (_, weight) = children.remove(index);
+        let neighbor = index.saturating_sub(1);
+        children[neighbor].1 += weight;
+        let focus = children[neighbor].0.first_view();
+
+        if children.len() == 1 {
+            let (child, _) = children.pop().expect("one child is left");
+            *self.node_mut(parent) = child;
+        }
+        Some(focus)
+    }
+
+    /// Removes every view but `view`.
+    pub fn only(&mut self, view: ViewId) {
+        if self.contains(view) {
+            self.root = Node::View(view);
+        }
+    }
+
+    /// Returns the view next to `view` on `side` when the layout fills `area`, the one closest to
+    /// the top or left edge of `view` if several are.
+    pub fn neighbor(&self, area: Rect, view: ViewId, side: Side) -> Option<ViewId> {
+        let areas = self.areas(area);
+        let (_, from) = areas.iter().find(|(id, _)| *id == view)?;
+        let overlaps = |start: u16, end: u16, other_start: u16, other_end: u16| {
+            start < other_end && other_start < end
+        };
+
+        let candidates = areas.iter().filter_map(|&(id, to)| {
+            let (gap, offset) = match side {
+                Side::Left if to.right() <= from.x => (from.x - to.right(), to.y.abs_diff(from.y)),
+                Side::Right if to.x >= from.right() => (to.x - from.right(), to.y.abs_diff(from.y)),
+                Side::Up if to.bottom() <= from.y => (from.y - to.bottom(), to.x.abs_diff(from.x)),
+                Side::Down if to.y >= from.bottom() => {
+                    (to.y - from.bottom(), to.x.abs_diff(from.x))
+                }
+                _ => return None,
+            };
+            let adjacent = match side {
+                Side::Left | Side::Right => overlaps(from.y, from.bottom(), to.y, to.bottom()),
+                Side::Up | Side::Down => overlaps(from.x, from.right(), to.x, to.right()),
+            };
+            adjacent.then_some((gap, offset, id))
+        });
+        candidates.min().map(|(_, _, id)| id)
+    }
+
+    /// Swaps the places of two views.
+    pub fn swap(&mut self, view: ViewId, other: ViewId) {
+        let (Some(path), Some(other_path)) = (self.path(view), self.path(other)) else {
+            return;
+        };
+        *self.node_mut(&path) = Node::View(other);
+        *self.node_mut(&other_path) = Node::View(view);
+    }
+
+    /// Grows `view` by `delta` rows or columns, or shrinks it if `delta` is negative, taking the
+    /// space from the view after it in the innermost `split` containing it, or the one before it
+    /// if it is the last one. Views keep at least one row or column.
+    ///
+    /// Returns `false` if no `split` contains `view`.
+    pub fn resize(&mut self, area: Rect, view: ViewId, split: Split, delta: i32) -> bool {
+        let Some(path) = self.path(view) else {
+            return false;
+        };
+
+        // Find the innermost container splitting the right way, and its area.
+        let mut node = &self.root;
+        let mut node_area = area;
+        let mut found = None;
+        for (depth, &index) in path.iter().enumerate() {
+            let Node::Container(kind, children) = node else {
+                break;
+            };
+            let weights: Vec<_> = children.iter().map(|(_, weight)| *weight).collect();
+            if *kind == split {
+                found = Some((depth, node_area));
+            }
+            node_area = split_area(*kind, &weights, node_area)[index];
+            node = &children[index].0;
+        }
+        let Some((depth, container_area)) = found else {
+            return false;
+        };
+
+        let index = path[depth];
+        let Node::Container(_, children) = self.node_mut(&path[..depth]) else {
+            unreachable!("the container was found on the path");
+        };
+        let weights: Vec<_> = children.iter().map(|(_, weight)| *weight).collect();
+        let sizes: Vec<i32> = split_area(split, &weights, container_area)
+            .iter()
+            .map(|area| match split {
+                Split::Horizontal => area.height as i32,
+                Split::Vertical => area.width as i32,
+            })
+            .collect();
+        let other = match index + 1 < children.len() {
+            true => index + 1,
+            false => index - 1,
+        };
+        // Without room for a row or column each, as before the area is known, there is nothing
+        // to resize.
+        if sizes[index] + sizes[other] < 2 {
+            return true;
+        }
+        let delta = delta.clamp(1 - sizes[index], sizes[other] - 1);
+
+        for (i, (_, weight)) in children.iter_mut().enumerate() {
+            *weight = match i {
+                _ if i == index => sizes[i] + delta,
+                _ if i == other => sizes[i] - delta,
+                _ => sizes[i],
+            }
+            .max(1) as f32;
+        }
+        true
+    }
+
+    /// Gives the views of every split an equal share of its area.
+    pub fn equalize(&mut self) {
+        fn equalize(node: &mut Node) {
+            if let Node::Container(_, children) = node {
+                for (child, weight) in children {
+                    *weight = 1.0;
+                    equalize(child);
+                }
+            }
+        }
+        equalize(&mut self.root);
+    }
+
+    // Returns the indices of the children leading from the root to `view`.
+    fn path(&self, view: ViewId) -> Option<Vec<usize>> {
+        fn find(node: &Node, view: ViewId, path: &mut Vec<usize>) -> bool {
+            match node {
+                Node::View(id) => *id == view,
+                Node::Container(_, children) => {
+                    children.iter().enumerate().any(|(i, (child, _))| {
+                        path.push(i);
+                        find(child, view, path) || path.pop().is_none()
+                    })
+                }
+            }
+        }
+        let mut path = Vec::new();
+        find(&self.root, view, &mut path).then_some(path)
+    }
+
+    fn node_mut(&mut self, path: &[usize]) -> &mut Node {
+        path.iter().fold(&mut self.root, |node, &index| match node {
+            Node::Container(_, children) => &mut children[index].0,
+            Node::View(_) => unreachable!("paths lead through containers"),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AREA: Rect = Rect::new(0, 0, 81, 20);
+
+    #[test]
+    fn splits_and_closes_views() {
+        let mut layout = Layout::new();
+        let [first] = layout.views()[..] else {
+            panic!("a new layout has one view");
+        };
+        let right = layout.split(first, Split::Vertical).unwrap();
+        let below = layout.split(right, Split::Horizontal).unwrap();
+
+        assert_eq!(
+            layout.areas(AREA),
+            [
+                (first, Rect::new(0, 0, 40, 20)),
+                (right, Rect::new(41, 0, 40, 10)),
+                (below, Rect::new(41, 10, 40, 10)),
+            ]
+        );
+        assert_eq!(layout.separators(AREA), [Rect::new(40, 0, 1, 20)]);
+        assert_eq!(layout.view_at(AREA, 50, 15), Some(below));
+        assert_eq!(layout.view_at(AREA, 40, 15), None);
+
+        // A split the same way as its container shares the space of the split view.
+        let third = layout.split(below, Split::Horizontal).unwrap();
+        let heights: Vec<_> = layout.areas(AREA).iter().map(|(_, a)| a.height).collect();
+        assert_eq!(heights, [20, 10, 5, 5]);
+
+        assert_eq!(layout.close(below), Some(right));
+        assert_eq!(layout.close(third), Some(right));
+        assert_eq!(layout.views(), [first, right]);
+        assert_eq!(layout.close(first), Some(right));
+        assert_eq!(layout.areas(AREA), [(right, AREA)]);
+        assert_eq!(layout.close(right), None);
+    }
+
+    #[test]
+    fn moves_between_neighbors() {
+        let mut layout = Layout::new();
+        let left = layout.views()[0];
+        let right = layout.split(left, Split::Vertical).unwrap();
+        let top = layout.split(left, Split::Horizontal).unwrap();
+        layout.swap(left, top);
+        let bottom = left;
+        let right_bottom = layout.split(right, Split::Horizontal).unwrap();
+
+        assert_eq!(layout.views(), [top, bottom, right, right_bottom]);
+        assert_eq!(layout.neighbor(AREA, top, Side::Down), Some(bottom));
+        assert_eq!(layout.neighbor(AREA, top, Side::Right), Some(right));
+        assert_eq!(
+            layout.neighbor(AREA, bottom, Side::Right),
+            Some(right_bottom)
+        );
+        assert_eq!(layout.neighbor(AREA, right_bottom, Side::Up), Some(right));
+        assert_eq!(layout.neighbor(AREA, right, Side::Left), Some(top));
+        assert_eq!(layout.neighbor(AREA, right, Side::Right), None);
+
+        layout.only(right);
+        assert_eq!(layout.views(), [right]);
+    }
+
+    #[test]
+    fn resizes_views() {
+        let mut layout = Layout::new();
+        let first = layout.views()[0];
+        let second = layout.split(first, Split::Horizontal).unwrap();
+
+        assert!(layout.resize(AREA, first, Split::Horizontal, 3));
+        let heights: Vec<_> = layout.areas(AREA).iter().map(|(_, a)| a.height).collect();
+        assert_eq!(heights, [13, 7]);
+
+        // The last view takes its space from the one before it.
+        assert!(layout.resize(AREA, second, Split::Horizontal, 100));
+        let heights: Vec<_> = layout.areas(AREA).iter().map(|(_, a)| a.height).collect();
+        assert_eq!(heights, [1, 19]);
+
+        assert!(!layout.resize(AREA, first, Split::Vertical, 1));
+        assert!(layout.resize(Rect::default(), first, Split::Horizontal, 1));
+        layout.equalize();
+        let heights: Vec<_> = layout.areas(AREA).iter().map(|(_, a)| a.height).collect();
+        assert_eq!(heights, [10, 10]);
+    }
+
+    #[test]
+    fn rebuilds_layouts_from_their_tree() {
+        let mut layout = Layout::new();
+        let first = layout.views()[0];
+        let second = layout.split(first, Split::Vertical).unwrap();
+        layout.split(second, Split::Horizontal).unwrap();
+        layout.resize(AREA, first, Split::Vertical, 10);
+        layout.swap(first, second);
+
+        let rebuilt = Layout::from_tree(&layout.tree());
+        assert_eq!(rebuilt.tree(), layout.tree());
+        let areas = |layout: &Layout| -> Vec<_> {
+            layout
+                .areas(AREA)
+                .into_iter()
+                .map(|(_, area)| area)
+                .collect()
+        };
+        assert_eq!(areas(&rebuilt), areas(&layout));
+        assert_eq!(rebuilt.views(), [ViewId(0), ViewId(1), ViewId(2)]);
+
+        // Splits of a single child are left out.
+        let tree = LayoutTree::Split(Split::Horizontal, vec![(LayoutTree::View, 1.0)]);
+        assert_eq!(Layout::from_tree(&tree).tree(), LayoutTree::View);
+    }
+}
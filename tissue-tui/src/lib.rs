@@ -1,14 +1,26 @@
-pub fn add(left: usize, right: usize) -> usize {
-    left + right
-}
+//! Terminal rendering for tissue.
+//!
+//! Widgets draw into a [`Surface`], a grid of [`Cell`]s. The [`Terminal`] keeps the surface of the
+//! previous frame around and hands only the cells that changed to its [`Backend`].
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+#![warn(missing_debug_implementations, missing_docs)]
 
-    #[test]
-    fn it_works() {
-        let result = add(2, 2);
-        assert_eq!(result, 4);
-    }
-}
+mod backend;
+mod graphics;
+mod gutter;
+mod layout;
+mod picker;
+mod statusline;
+mod surface;
+mod terminal;
+
+pub use self::{
+    backend::{Backend, CrosstermBackend, TestBackend},
+    graphics::{Color, Modifier, Rect, Style},
+    gutter::{Gutter, GutterColumn, LineNumbers},
+    layout::{Layout, LayoutTree, Side, Split, ViewId},
+    picker::{fuzzy_match, Picker, PickerEvent, PickerStyle, Preview},
+    statusline::{BufferLine, Span, StatusLine},
+    surface::{Cell, Surface},
+    terminal::Terminal,
+};
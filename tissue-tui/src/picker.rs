@@ -0,0 +1,441 @@
+mod fuzzy;
+
+pub use fuzzy::fuzzy_match;
+
+use crate::{Rect, Style, Surface};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+// How wide a picker must be to show the preview next to the items.
+const MIN_PREVIEW_WIDTH: u16 = 72;
+
+/// What a key did to a [`Picker`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PickerEvent {
+    /// The query or the selected item changed.
+    Update,
+    /// The selected item was picked with enter.
+    Submit,
+    /// The picker was closed without picking an item.
+    Abort,
+    /// The key did nothing.
+    Ignored,
+}
+
+/// Styles a [`Picker`] is drawn with.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PickerStyle {
+    /// The query line.
+    pub prompt: Style,
+    /// The items.
+    pub text: Style,
+    /// The selected item.
+    pub selected: Style,
+    /// The characters of an item matching the query.
+    pub matched: Style,
+}
+
+/// Lines of a file shown next to the items of a [`Picker`], such as the lines around a match.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Preview {
+    /// The lines, which must not hold tabs or line breaks.
+    pub lines: Vec<String>,
+    /// The line to highlight and scroll to, as an index into `lines`.
+    pub line: Option<usize>,
+}
+
+/// A list of items filtered by a typed query, one of which is picked.
+///
+/// Items are fuzzy matched against the query and sorted by how well they match, best first. Items
+/// may keep arriving while the picker is open, and are shown as soon as they match.
+#[derive(Debug)]
+pub struct Picker<T> {
+    items: Vec<(String, T)>,
+    query: String,
+    // The scores and indices of the items matching the query, best first.
+    matches: Vec<(i64, usize)>,
+    selected: usize,
+}
+
+impl<T> Default for Picker<T> {
+    fn default() -> Self {
+        Self {
+            items: Vec::new(),
+            query: String::new(),
+            matches: Vec::new(),
+            selected: 0,
+        }
+    }
+}
+
+impl<T> Picker<T> {
+    /// Creates a picker over items, each shown as its label.
+    pub fn new(items: impl IntoIterator<Item = (String, T)>) -> Self {
+        let mut picker = Self::default();
+        picker.extend(items);
+        picker
+    }
+
+    /// Adds items to the list, keeping the selected item selected.
+    pub fn extend(&mut self, items: impl IntoIterator<Item = (String, T)>) {
+        let selected = self.matches.get(self.selected).copied();
+        let start = self.items.len();
+        self.items.extend(items);
+
+        let query = &self.query;
+        let found = self.items[start..]
+            .iter()
+            .enumerate()
+            .filter_map(|(i, (label, _))| Some((fuzzy_match(query, label)?.0, start + i)));
+        self.matches.extend(found);
+        if !query.is_empty() {
+            sort(&mut self.matches);
+        }
+        if let Some(selected) = selected {
+            self.selected = self
+                .matches
+                .iter()
+                .position(|&m| m == selected)
+                .unwrap_or(0);
+        }
+    }
+
+    /// Returns the typed query.
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    /// Replaces the query and selects the best match.
+    pub fn set_query(&mut self, query: impl Into<String>) {
+        let query = query.into();
+        // Typing more of the query can only narrow the matches, so only those are scored again.
+        let narrowed = !self.query.is_empty() && query.starts_with(&self.query);
+        let candidates: Vec<usize> = match narrowed {
+            true => self.matches.iter().map(|&(_, i)| i).collect(),
+            false => (0..self.items.len()).collect(),
+        };
+        self.matches = candidates
+            .into_iter()
+            .filter_map(|i| Some((fuzzy_match(&query, &self.items[i].0)?.0, i)))
+            .collect();
+        match query.is_empty() {
+            true => self.matches.sort_by_key(|&(_, i)| i),
+            false => sort(&mut self.matches),
+        }
+        self.query = query;
+        self.selected = 0;
+    }
+
+    /// Returns the number of items.
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Returns `true` if there are no items.
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Returns the labels of the items matching the query, in order.
+    pub fn matches(&self) -> impl Iterator<Item = &str> {
+        self.matches.iter().map(|&(_, i)| self.items[i].0.as_str())
+    }
+
+    /// Returns the selected item, if any item matches.
+    pub fn selected(&self) -> Option<&T> {
+        let &(_, index) = self.matches.get(self.selected)?;
+        Some(&self.items[index].1)
+    }
+
+    /// Moves the selection by `delta` matches, wrapping around the ends of the list.
+    pub fn move_selection(&mut self, delta: isize) {
+        let len = self.matches.len() as isize;
+        if len > 0 {
+            self.selected = (self.selected as isize + delta).rem_euclid(len) as usize;
+        }
+    }
+
+    /// Edits the query or moves the selection with `event`.
+    pub fn handle_key(&mut self, event: KeyEvent) -> PickerEvent {
+        let ctrl = event.modifiers.contains(KeyModifiers::CONTROL);
+        match event.code {
+            KeyCode::Esc => return PickerEvent::Abort,
+            KeyCode::Char('c') if ctrl => return PickerEvent::Abort,
+            KeyCode::Enter => return PickerEvent::Submit,
+            KeyCode::Up | KeyCode::BackTab => self.move_selection(-1),
+            KeyCode::Down | KeyCode::Tab => self.move_selection(1),
+            KeyCode::Char('p') if ctrl => self.move_selection(-1),
+            KeyCode::Char('n') if ctrl => self.move_selection(1),
+            KeyCode::PageUp => self.move_selection(-10),
+            KeyCode::PageDown => self.move_selection(10),
+            KeyCode::Char('u') if ctrl => self.set_query(""),
+            KeyCode::Backspace => {
+                let mut query = std::mem::take(&mut self.query);
+                query.pop();
+                self.set_query(query);
+            }
+            KeyCode::Char(c) if !ctrl && !event.modifiers.contains(KeyModifiers::ALT) => {
+                let mut query = std::mem::take(&mut self.query);
+                query.push(c);
+                self.set_query(query);
+            }
+            _ => return PickerEvent::Ignored,
+        }
+        PickerEvent::Update
+    }
+
+    /// Draws the query line followed by the matching items into `area`, scrolled so that the
+    /// selected item shows. Returns the position of the cursor on the query line.
+    ///
+    /// The `preview` of the selected item is drawn to the right of the items when the area is
+    /// wide enough.
+    pub fn render(
+        &self,
+        surface: &mut Surface,
+        area: Rect,
+        style: PickerStyle,
+        preview: Option<&Preview>,
+    ) -> (u16, u16) {
+        if area.height == 0 {
+            return (area.x, area.y);
+        }
+        surface.set_style(area, style.text);
+
+        let area = match preview {
+            Some(preview) if area.width >= MIN_PREVIEW_WIDTH => {
+                let width = area.width / 2;
+                let preview_area = Rect::new(
+                    area.x + width + 1,
+                    area.y,
+                    area.width - width - 1,
+                    area.height,
+                );
+                for y in area.y..area.bottom() {
+                    surface.set_string(area.x + width, y, "│", style.prompt);
+                }
+                render_preview(surface, preview_area, style, preview);
+                Rect::new(area.x, area.y, width, area.height)
+            }
+            _ => area,
+        };
+
+        let prompt_area = Rect::new(area.x, area.y, area.width, 1);
+        surface.set_style(prompt_area, style.prompt);
+        let count = format!("{}/{}", self.matches.len(), self.items.len());
+        let count_x = area.right().saturating_sub(count.width() as u16 + 1);
+        surface.set_stringn(count_x, area.y, &count, area.width as usize, style.prompt);
+        let (x, y) = surface.set_stringn(
+            area.x,
+            area.y,
+            &format!("> {}", self.query),
+            count_x.saturating_sub(area.x + 1) as usize,
+            style.prompt,
+        );
+
+        let height = (area.height - 1) as usize;
+        let offset = self.selected.saturating_sub(height.saturating_sub(1));
+        for (row, (i, &(_, index))) in self
+            .matches
+            .iter()
+            .enumerate()
+            .skip(offset)
+            .take(height)
+            .enumerate()
+        {
+            let y = area.y + 1 + row as u16;
+            let item_style = match i == self.selected {
+                true => {
+                    surface.set_style(Rect::new(area.x, y, area.width, 1), style.selected);
+                    style.selected
+                }
+                false => style.text,
+            };
+            let label = &self.items[index].0;
+            let width = area.width.saturating_sub(2);
+            surface.set_stringn(area.x + 1, y, label, width as usize, item_style);
+
+            let Some((_, indices)) = fuzzy_match(&self.query, label) else {
+                continue;
+            };
+            let matched = item_style.patch(style.matched);
+            let mut column = 0;
+            let mut indices = indices.into_iter().peekable();
+            for (i, c) in label.chars().enumerate() {
+                if column >= width {
+                    break;
+                }
+                if indices.next_if_eq(&i).is_some() {
+                    if let Some(cell) = surface.get_mut(area.x + 1 + column, y) {
+                        cell.set_style(matched);
+                    }
+                }
+                column += c.width().unwrap_or(0) as u16;
+            }
+        }
+
+        (x, y)
+    }
+}
+
+// Sorts matches best first, keeping items that score the same in the order they were added.
+fn sort(matches: &mut [(i64, usize)]) {
+    matches.sort_by_key(|&(score, i)| (std::cmp::Reverse(score), i));
+}
+
+// Draws the lines of `preview` into `area`, scrolled so that its highlighted line is centered.
+fn render_preview(surface: &mut Surface, area: Rect, style: PickerStyle, preview: &Preview) {
+    let height = area.height as usize;
+    let offset = preview
+        .line
+        .map_or(0, |line| line.saturating_sub(height / 2));
+    for (row, (i, line)) in preview
+        .lines
+        .iter()
+        .enumerate()
+        .skip(offset)
+        .take(height)
+        .enumerate()
+    {
+        let y = area.y + row as u16;
+        let line_style = match preview.line == Some(i) {
+            true => {
+                surface.set_style(Rect::new(area.x, y, area.width, 1), style.selected);
+                style.selected
+            }
+            false => style.text,
+        };
+        surface.set_stringn(
+            area.x + 1,
+            y,
+            line,
+            area.width.saturating_sub(1) as usize,
+            line_style,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Color, Terminal, TestBackend};
+
+    fn picker() -> Picker<usize> {
+        let labels = ["src/main.rs", "src/app.rs", "Cargo.toml", "README.md"];
+        Picker::new(labels.iter().map(|label| label.to_string()).zip(0..))
+    }
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn filters_and_selects_items() {
+        let mut picker = picker();
+        assert_eq!(
+            picker.handle_key(key(KeyCode::Char('s'))),
+            PickerEvent::Update
+        );
+        assert_eq!(
+            picker.matches().collect::<Vec<_>>(),
+            ["src/main.rs", "src/app.rs"]
+        );
+        picker.handle_key(key(KeyCode::Down));
+        assert_eq!(picker.selected(), Some(&1));
+        picker.handle_key(key(KeyCode::Down));
+        assert_eq!(picker.selected(), Some(&0));
+
+        picker.set_query("rs app");
+        assert_eq!(picker.matches().collect::<Vec<_>>(), ["src/app.rs"]);
+        picker.extend([("tests/app.rs".to_string(), 4)]);
+        assert_eq!(picker.matches().count(), 2);
+        picker.set_query("S");
+        assert_eq!(picker.matches().count(), 0);
+        picker.set_query("nothing");
+        assert_eq!(picker.selected(), None);
+        assert_eq!(picker.handle_key(key(KeyCode::Esc)), PickerEvent::Abort);
+    }
+
+    #[test]
+    fn renders_the_selected_item_in_view() {
+        let mut terminal = Terminal::new(TestBackend::new(20, 3)).unwrap();
+        let mut picker = picker();
+        picker.set_query("r");
+        picker.move_selection(2);
+        let style = PickerStyle {
+            selected: Style::default().bg(Color::Blue),
+            ..PickerStyle::default()
+        };
+
+        terminal
+            .draw(|surface| Some(picker.render(surface, surface.area(), style, None)))
+            .unwrap();
+
+        let backend = terminal.backend();
+        backend.assert_lines(&[
+            "> r             4/4 ",
+            " src/main.rs        ",
+            " src/app.rs         ",
+        ]);
+        assert_eq!(backend.cursor(), (3, 0));
+        assert_eq!(backend.surface().get(0, 2).unwrap().bg, Color::Blue);
+    }
+
+    #[test]
+    fn sorts_the_best_matches_first() {
+        let mut picker = picker();
+        picker.set_query("as");
+        picker.move_selection(1);
+        assert_eq!(
+            picker.matches().collect::<Vec<_>>(),
+            ["src/app.rs", "src/main.rs"]
+        );
+        assert_eq!(picker.selected(), Some(&0));
+
+        // A better match arriving keeps the selected item selected.
+        picker.extend([("as.rs".to_string(), 4)]);
+        assert_eq!(picker.matches().next(), Some("as.rs"));
+        assert_eq!(picker.selected(), Some(&0));
+
+        picker.set_query("");
+        assert_eq!(picker.matches().next(), Some("src/main.rs"));
+    }
+
+    #[test]
+    fn renders_matched_characters_and_the_preview() {
+        let mut terminal = Terminal::new(TestBackend::new(MIN_PREVIEW_WIDTH, 3)).unwrap();
+        let mut picker = picker();
+        picker.set_query("cat");
+        let style = PickerStyle {
+            matched: Style::default().fg(Color::Yellow),
+            ..PickerStyle::default()
+        };
+        let preview = Preview {
+            lines: (1..=5).map(|i| format!("line {i}")).collect(),
+            line: Some(3),
+        };
+
+        terminal
+            .draw(|surface| Some(picker.render(surface, surface.area(), style, Some(&preview))))
+            .unwrap();
+
+        let backend = terminal.backend();
+        let lines = backend
+            .surface()
+            .content()
+            .chunks(MIN_PREVIEW_WIDTH as usize);
+        let lines: Vec<String> = lines
+            .map(|line| line.iter().map(|cell| cell.symbol.as_str()).collect())
+            .collect();
+        assert!(lines[0].starts_with("> cat"));
+        assert!(lines[1].starts_with(" Cargo.toml"));
+        assert_eq!(lines[0][36..].trim_end(), "│ line 3");
+        assert_eq!(lines[1][36..].trim_end(), "│ line 4");
+
+        let fg = |x| backend.surface().get(x, 1).unwrap().fg;
+        let yellow = Color::Yellow;
+        assert_eq!(
+            (fg(1), fg(2), fg(3), fg(7)),
+            (yellow, yellow, Color::Reset, yellow)
+        );
+    }
+}
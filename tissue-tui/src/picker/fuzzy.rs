@@ -0,0 +1,176 @@
+//! Fuzzy matching of picker queries against item labels.
+//!
+//! The characters of a query must appear in a label in order. Among the ways they can, the best
+//! scoring one is picked: consecutive characters and characters starting a word, a path component
+//! or a camel case hump score more, and gaps between characters score less. Matches within the
+//! file name of a path score more than matches in its directories.
+
+// Points for every matched character.
+const MATCH: i64 = 16;
+// Points for a character following the previous one.
+const CONSECUTIVE: i64 = 8;
+// Points lost for every character skipped between two matched ones.
+const GAP: i64 = 1;
+// Points for a character starting a path component.
+const PATH_START: i64 = 12;
+// Points for a character starting a word or a camel case hump.
+const WORD_START: i64 = 8;
+// Points for a character in the last path component.
+const FILE_NAME: i64 = 2;
+
+/// Scores how well `query` matches `label`, or returns `None` if it does not.
+///
+/// Each whitespace separated word of the query must match on its own, and the score is the sum of
+/// their scores. Case is ignored unless the word has an uppercase letter. The indices of the
+/// matched characters of `label` are returned along with the score.
+pub fn fuzzy_match(query: &str, label: &str) -> Option<(i64, Vec<usize>)> {
+    let chars: Vec<char> = label.chars().collect();
+    let bonuses = bonuses(&chars);
+    let mut score = 0;
+    let mut indices = Vec::new();
+
+    for word in query.split_whitespace() {
+        let (word_score, word_indices) = match_word(word, &chars, &bonuses)?;
+        score += word_score;
+        indices.extend(word_indices);
+    }
+
+    indices.sort_unstable();
+    indices.dedup();
+    Some((score, indices))
+}
+
+// Returns the points for matching each character of a label.
+fn bonuses(chars: &[char]) -> Vec<i64> {
+    let file_name = chars
+        .iter()
+        .rposition(|&c| c == '/' || c == '\\')
+        .map_or(0, |i| i + 1);
+
+    chars
+        .iter()
+        .enumerate()
+        .map(|(i, &c)| {
+            let prev = i.checked_sub(1).map(|i| chars[i]);
+            let bonus = match prev {
+                None | Some('/' | '\\') => PATH_START,
+                Some(prev) if !prev.is_alphanumeric() && c.is_alphanumeric() => WORD_START,
+                Some(prev) if prev.is_lowercase() && c.is_uppercase() => WORD_START,
+                Some(_) => 0,
+            };
+            match i >= file_name {
+                true => bonus + FILE_NAME,
+                false => bonus,
+            }
+        })
+        .collect()
+}
+
+// Finds the best scoring way for the characters of `word` to appear in order in `chars`.
+fn match_word(word: &str, chars: &[char], bonuses: &[i64]) -> Option<(i64, Vec<usize>)> {
+    let ignore_case = !word.chars().any(char::is_uppercase);
+    let eq = |a: char, b: char| match ignore_case {
+        true => a == b || a.to_lowercase().eq(b.to_lowercase()),
+        false => a == b,
+    };
+    let word: Vec<char> = word.chars().collect();
+    let n = chars.len();
+    if word.len() > n {
+        return None;
+    }
+
+    // `scores[i][j]` is the best score of the first `i + 1` characters of `word` with the last one
+    // matched at `j`, and `from[i][j]` is where the character before it was matched.
+    let mut scores = vec![vec![None; n]; word.len()];
+    let mut from = vec![vec![0; n]; word.len()];
+
+    for (j, &c) in chars.iter().enumerate() {
+        if eq(word[0], c) {
+            scores[0][j] = Some(MATCH + bonuses[j]);
+        }
+    }
+    for i in 1..word.len() {
+        // The best score of the previous character matched before `j - 1`, minus the gap.
+        let mut best: Option<(i64, usize)> = None;
+        for j in i..n {
+            if j >= 2 {
+                if let Some(score) = scores[i - 1][j - 2] {
+                    if best.map_or(true, |(best, _)| score > best) {
+                        best = Some((score, j - 2));
+                    }
+                }
+            }
+            if !eq(word[i], chars[j]) {
+                best = best.map(|(score, k)| (score - GAP, k));
+                continue;
+            }
+
+            let consecutive = scores[i - 1][j - 1].map(|score| (score + CONSECUTIVE, j - 1));
+            let gapped = best.map(|(score, k)| (score - GAP, k));
+            let previous = match (consecutive, gapped) {
+                (Some(a), Some(b)) => Some(if a.0 >= b.0 { a } else { b }),
+                (a, b) => a.or(b),
+            };
+            if let Some((score, k)) = previous {
+                scores[i][j] = Some(score + MATCH + bonuses[j]);
+                from[i][j] = k;
+            }
+            best = best.map(|(score, k)| (score - GAP, k));
+        }
+    }
+
+    let last = word.len() - 1;
+    let (mut j, score) = scores[last]
+        .iter()
+        .enumerate()
+        .filter_map(|(j, score)| Some((j, (*score)?)))
+        .max_by_key(|&(j, score)| (score, std::cmp::Reverse(j)))?;
+
+    let mut indices = vec![j; word.len()];
+    for i in (1..word.len()).rev() {
+        j = from[i][j];
+        indices[i - 1] = j;
+    }
+    Some((score, indices))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn score(query: &str, label: &str) -> i64 {
+        fuzzy_match(query, label).unwrap().0
+    }
+
+    #[test]
+    fn matches_characters_in_order() {
+        assert_eq!(
+            fuzzy_match("ab", "a_b"),
+            Some((score("ab", "a_b"), vec![0, 2]))
+        );
+        assert_eq!(fuzzy_match("ba", "a_b"), None);
+        assert_eq!(fuzzy_match("abc", "ab"), None);
+        assert_eq!(fuzzy_match("", "anything"), Some((0, Vec::new())));
+        assert_eq!(
+            fuzzy_match("main app", "src/app/main.rs").unwrap().1.len(),
+            7
+        );
+    }
+
+    #[test]
+    fn smart_case() {
+        assert!(fuzzy_match("readme", "README.md").is_some());
+        assert!(fuzzy_match("Readme", "readme.md").is_none());
+        assert!(fuzzy_match("README", "README.md").is_some());
+    }
+
+    #[test]
+    fn prefers_word_starts_and_file_names() {
+        // The best alignment skips the earlier character that starts no word.
+        assert_eq!(fuzzy_match("fb", "xf_foo_bar").unwrap().1, vec![3, 7]);
+        assert!(score("app", "src/app.rs") > score("app", "src/happy.rs"));
+        assert!(score("main", "src/main.rs") > score("main", "main/src.rs"));
+        assert!(score("fb", "FooBar") > score("fb", "fooba"));
+        assert!(score("abc", "abc") > score("abc", "a_b_c"));
+    }
+}
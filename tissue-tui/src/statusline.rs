@@ -0,0 +1,183 @@
+use crate::{Rect, Style, Surface};
+use unicode_width::UnicodeWidthStr;
+
+/// Text drawn in one style.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Span {
+    /// The text.
+    pub text: String,
+    /// The style of the text, patching the style of the line.
+    pub style: Style,
+}
+
+impl Span {
+    /// Creates a span of `text` in `style`.
+    pub fn new(text: impl Into<String>, style: Style) -> Self {
+        Self {
+            text: text.into(),
+            style,
+        }
+    }
+
+    /// Creates a span of `text` in the style of the line.
+    pub fn raw(text: impl Into<String>) -> Self {
+        Self::new(text, Style::default())
+    }
+}
+
+// Returns the width of `spans` separated by a space, leaving out the empty ones.
+fn width(spans: &[Span]) -> u16 {
+    let spans = spans.iter().filter(|span| !span.text.is_empty());
+    let (count, width) = spans.fold((0, 0), |(count, width), span| {
+        (count + 1, width + span.text.width())
+    });
+    (width + count.max(1) - 1) as u16
+}
+
+// Draws `spans` separated by a space from `x` on, up to `right`, and returns where they end.
+fn render_spans(surface: &mut Surface, spans: &[Span], x: u16, y: u16, right: u16) -> u16 {
+    let mut x = x;
+    for span in spans.iter().filter(|span| !span.text.is_empty()) {
+        if x >= right {
+            break;
+        }
+        let width = (right - x) as usize;
+        x = surface.set_stringn(x, y, &span.text, width, span.style).0 + 1;
+    }
+    x.saturating_sub(1)
+}
+
+/// A line of three groups of spans, aligned left, centered and aligned right, such as the status
+/// line of a view.
+///
+/// Spans are separated by a space, and the line is padded by a space on both ends. The left group
+/// comes first: the other groups are left out if they do not fit next to it.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct StatusLine {
+    /// The spans aligned left.
+    pub left: Vec<Span>,
+    /// The spans centered.
+    pub center: Vec<Span>,
+    /// The spans aligned right.
+    pub right: Vec<Span>,
+}
+
+impl StatusLine {
+    /// Draws the line on the first row of `area`, in `style`.
+    pub fn render(&self, surface: &mut Surface, area: Rect, style: Style) {
+        if area.height == 0 {
+            return;
+        }
+        let area = Rect::new(area.x, area.y, area.width, 1);
+        surface.set_style(area, style);
+        let (start, end) = (area.x + 1, area.right().saturating_sub(1));
+
+        let left_end = render_spans(surface, &self.left, start, area.y, end);
+        let free = match left_end > start {
+            true => left_end + 1,
+            false => start,
+        };
+        let right_width = width(&self.right);
+        let right = end.saturating_sub(right_width);
+        let right = match right >= free && right_width > 0 {
+            true => {
+                render_spans(surface, &self.right, right, area.y, end);
+                right.saturating_sub(1)
+            }
+            false => end,
+        };
+        let center_width = width(&self.center);
+        let center = area.x + area.width.saturating_sub(center_width) / 2;
+        if center >= free && center + center_width <= right {
+            render_spans(surface, &self.center, center, area.y, right);
+        }
+    }
+}
+
+/// A line of tabs, such as the open buffers, scrolled so that the active tab shows.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct BufferLine {
+    tabs: Vec<Span>,
+    active: usize,
+}
+
+impl BufferLine {
+    /// Creates a line of `tabs`, the one at `active` being active.
+    pub fn new(tabs: Vec<Span>, active: usize) -> Self {
+        Self { tabs, active }
+    }
+
+    /// Draws the tabs on the first row of `area`, in `style`, each padded by a space on both
+    /// ends.
+    pub fn render(&self, surface: &mut Surface, area: Rect, style: Style) {
+        if area.height == 0 {
+            return;
+        }
+        let area = Rect::new(area.x, area.y, area.width, 1);
+        surface.set_style(area, style);
+
+        let widths: Vec<_> = self.tabs.iter().map(|tab| tab.text.width() + 2).collect();
+        let active = self.active.min(self.tabs.len().saturating_sub(1));
+        let mut first = 0;
+        while first < active && widths[first..=active].iter().sum::<usize>() > area.width as usize {
+            first += 1;
+        }
+
+        let mut x = area.x;
+        for tab in &self.tabs[first..] {
+            if x >= area.right() {
+                break;
+            }
+            let width = (area.right() - x) as usize;
+            x = surface
+                .set_stringn(x, area.y, &format!(" {} ", tab.text), width, tab.style)
+                .0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Color, Terminal, TestBackend};
+
+    fn render(width: u16, draw: impl Fn(&mut Surface, Rect)) -> String {
+        let mut terminal = Terminal::new(TestBackend::new(width, 1)).unwrap();
+        terminal
+            .draw(|surface| {
+                draw(surface, surface.area());
+                None
+            })
+            .unwrap();
+        let surface = terminal.backend().surface();
+        let cells = surface.content().iter().map(|cell| cell.symbol.as_str());
+        cells.collect()
+    }
+
+    #[test]
+    fn aligns_groups_and_drops_those_that_do_not_fit() {
+        let red = Style::default().fg(Color::Red);
+        let line = StatusLine {
+            left: vec![Span::new("NOR", red), Span::raw(""), Span::raw("a.rs")],
+            center: vec![Span::raw("mid")],
+            right: vec![Span::raw("rust"), Span::raw("1:1")],
+        };
+
+        let draw = |surface: &mut Surface, area| line.render(surface, area, Style::default());
+        assert_eq!(render(30, draw), " NOR a.rs    mid     rust 1:1 ");
+        assert_eq!(render(20, draw), " NOR a.rs  rust 1:1 ");
+        assert_eq!(render(12, draw), " NOR a.rs   ");
+        assert_eq!(render(6, draw), " NOR  ");
+    }
+
+    #[test]
+    fn scrolls_to_the_active_tab() {
+        let tabs: Vec<_> = ["one", "two", "three"].map(Span::raw).into();
+        let draw = |active| {
+            let line = BufferLine::new(tabs.clone(), active);
+            move |surface: &mut Surface, area| line.render(surface, area, Style::default())
+        };
+        assert_eq!(render(14, draw(0)), " one  two  thr");
+        assert_eq!(render(14, draw(2)), " two  three   ");
+    }
+}
@@ -0,0 +1,282 @@
+use crate::{Color, Modifier, Rect, Style};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// A single terminal cell.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cell {
+    /// The grapheme displayed in the cell. Cells covered by a preceding wide grapheme hold an
+    /// empty string.
+    pub symbol: String,
+    /// Foreground color.
+    pub fg: Color,
+    /// Background color.
+    pub bg: Color,
+    /// Text attributes.
+    pub modifier: Modifier,
+}
+
+impl Cell {
+    /// Sets the grapheme displayed in the cell.
+    pub fn set_symbol(&mut self, symbol: &str) -> &mut Self {
+        self.symbol.clear();
+        self.symbol.push_str(symbol);
+        self
+    }
+
+    /// Applies `style` to the cell.
+    pub fn set_style(&mut self, style: Style) -> &mut Self {
+        if let Some(fg) = style.fg {
+            self.fg = fg;
+        }
+        if let Some(bg) = style.bg {
+            self.bg = bg;
+        }
+        self.modifier.insert(style.add_modifier);
+        self.modifier.remove(style.sub_modifier);
+        self
+    }
+
+    /// Returns the style of the cell.
+    pub fn style(&self) -> Style {
+        Style::default()
+            .fg(self.fg)
+            .bg(self.bg)
+            .add_modifier(self.modifier)
+    }
+
+    /// Resets the cell to a blank space with default colors.
+    pub fn reset(&mut self) {
+        self.symbol.clear();
+        self.symbol.push(' ');
+        self.fg = Color::Reset;
+        self.bg = Color::Reset;
+        self.modifier = Modifier::empty();
+    }
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Self {
+            symbol: " ".into(),
+            fg: Color::Reset,
+            bg: Color::Reset,
+            modifier: Modifier::empty(),
+        }
+    }
+}
+
+/// A grid of cells covering an area of the terminal.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Surface {
+    area: Rect,
+    content: Vec<Cell>,
+}
+
+impl Surface {
+    /// Creates a surface of blank cells.
+    pub fn empty(area: Rect) -> Self {
+        Self::filled(area, &Cell::default())
+    }
+
+    /// Creates a surface with every cell set to `cell`.
+    pub fn filled(area: Rect, cell: &Cell) -> Self {
+        Self {
+            area,
+            content: vec![cell.clone(); area.area()],
+        }
+    }
+
+    /// Creates a surface from lines of text, one row per line. Handy in tests.
+    pub fn with_lines<S: AsRef<str>>(lines: &[S]) -> Self {
+        let height = lines.len() as u16;
+        let width = lines
+            .iter()
+            .map(|line| line.as_ref().width())
+            .max()
+            .unwrap_or_default() as u16;
+        let mut surface = Self::empty(Rect::new(0, 0, width, height));
+
+        for (y, line) in lines.iter().enumerate() {
+            surface.set_string(0, y as u16, line.as_ref(), Style::default());
+        }
+
+        surface
+    }
+
+    /// Returns the area covered by the surface.
+    pub fn area(&self) -> Rect {
+        self.area
+    }
+
+    /// Returns all cells in row-major order.
+    pub fn content(&self) -> &[Cell] {
+        &self.content
+    }
+
+    /// Returns the cell at `(x, y)`.
+    pub fn get(&self, x: u16, y: u16) -> Option<&Cell> {
+        self.index_of(x, y).map(|i| &self.content[i])
+    }
+
+    /// Returns the cell at `(x, y)` mutably.
+    pub fn get_mut(&mut self, x: u16, y: u16) -> Option<&mut Cell> {
+        self.index_of(x, y).map(|i| &mut self.content[i])
+    }
+
+    fn index_of(&self, x: u16, y: u16) -> Option<usize> {
+        self.area.contains(x, y).then(|| {
+            (y - self.area.y) as usize * self.area.width as usize + (x - self.area.x) as usize
+        })
+    }
+
+    fn pos_of(&self, i: usize) -> (u16, u16) {
+        let width = self.area.width as usize;
+        (
+            self.area.x + (i % width) as u16,
+            self.area.y + (i / width) as u16,
+        )
+    }
+
+    /// Resets every cell.
+    pub fn reset(&mut self) {
+        self.content.iter_mut().for_each(Cell::reset);
+    }
+
+    /// Resizes the surface to `area`. All cells are reset.
+    pub fn resize(&mut self, area: Rect) {
+        self.area = area;
+        self.content.resize(area.area(), Cell::default());
+        self.reset();
+    }
+
+    /// Applies `style` to every cell inside `area`.
+    pub fn set_style(&mut self, area: Rect, style: Style) {
+        let area = self.area.intersection(area);
+
+        for y in area.y..area.bottom() {
+            for x in area.x..area.right() {
+                if let Some(cell) = self.get_mut(x, y) {
+                    cell.set_style(style);
+                }
+            }
+        }
+    }
+
+    /// Writes `string` starting at `(x, y)`. Returns the position after the last grapheme written.
+    pub fn set_string(&mut self, x: u16, y: u16, string: &str, style: Style) -> (u16, u16) {
+        self.set_stringn(x, y, string, usize::MAX, style)
+    }
+
+    /// Writes at most `width` columns of `string` starting at `(x, y)`, clipped to the right edge
+    /// of the surface. Returns the position after the last grapheme written.
+    pub fn set_stringn(
+        &mut self,
+        x: u16,
+        y: u16,
+        string: &str,
+        width: usize,
+        style: Style,
+    ) -> (u16, u16) {
+        if y < self.area.y || y >= self.area.bottom() {
+            return (x, y);
+        }
+
+        let max = (self.area.right().saturating_sub(x) as usize).min(width);
+        let mut offset = 0;
+
+        for grapheme in string.graphemes(true) {
+            let grapheme_width = grapheme.width();
+            if grapheme_width == 0 {
+                continue;
+            }
+            if offset + grapheme_width > max {
+                break;
+            }
+
+            let cx = x + offset as u16;
+            if let Some(cell) = self.get_mut(cx, y) {
+                cell.set_symbol(grapheme).set_style(style);
+            }
+            // Cells covered by a wide grapheme are left empty so that diffing skips them.
+            for i in 1..grapheme_width {
+                if let Some(cell) = self.get_mut(cx + i as u16, y) {
+                    cell.set_symbol("").set_style(style);
+                }
+            }
+
+            offset += grapheme_width;
+        }
+
+        (x + offset as u16, y)
+    }
+
+    /// Returns the cells that must be redrawn to turn `self` into `next`.
+    ///
+    /// Both surfaces must cover the same area.
+    pub fn diff<'a>(&self, next: &'a Surface) -> Vec<(u16, u16, &'a Cell)> {
+        debug_assert_eq!(self.area, next.area);
+
+        let mut updates = Vec::new();
+        // Cells hidden behind a wide grapheme in `next` need no update, while those hidden in
+        // `self` must be redrawn even if they compare equal.
+        let mut invalidated = 0;
+        let mut to_skip = 0;
+
+        for (i, (current, next_cell)) in self.content.iter().zip(&next.content).enumerate() {
+            if (current != next_cell || invalidated > 0) && to_skip == 0 {
+                let (x, y) = self.pos_of(i);
+                updates.push((x, y, next_cell));
+            }
+
+            to_skip = next_cell.symbol.width().saturating_sub(1);
+            let affected = current.symbol.width().max(next_cell.symbol.width());
+            invalidated = affected.max(invalidated).saturating_sub(1);
+        }
+
+        updates
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_string_clips_and_handles_wide_graphemes() {
+        let mut surface = Surface::empty(Rect::new(0, 0, 6, 1));
+        let end = surface.set_string(1, 0, "a한b🦀", Style::default());
+
+        assert_eq!(end, (5, 0));
+        assert_eq!(surface, Surface::with_lines(&[" a한b "]));
+        assert_eq!(surface.get(3, 0).unwrap().symbol, "");
+    }
+
+    #[test]
+    fn diff_reports_only_changed_cells() {
+        let prev = Surface::with_lines(&["hello", "world"]);
+        let next = Surface::with_lines(&["hallo", "world"]);
+
+        let diff: Vec<_> = prev
+            .diff(&next)
+            .into_iter()
+            .map(|(x, y, cell)| (x, y, cell.symbol.as_str()))
+            .collect();
+
+        assert_eq!(diff, vec![(1, 0, "a")]);
+    }
+
+    #[test]
+    fn diff_redraws_cells_uncovered_by_wide_graphemes() {
+        let prev = Surface::with_lines(&["한 x"]);
+        let next = Surface::with_lines(&["a  x"]);
+
+        let diff: Vec<_> = prev
+            .diff(&next)
+            .into_iter()
+            .map(|(x, y, cell)| (x, y, cell.symbol.as_str()))
+            .collect();
+
+        assert_eq!(diff, vec![(0, 0, "a"), (1, 0, " ")]);
+    }
+}
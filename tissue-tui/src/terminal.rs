@@ -0,0 +1,183 @@
+use crate::{Backend, Rect, Surface};
+use std::io;
+
+/// A double-buffered terminal.
+///
+/// Each frame is drawn into a fresh surface and compared against the previous one, so only the
+/// cells that changed are written to the backend.
+#[derive(Debug)]
+pub struct Terminal<B: Backend> {
+    backend: B,
+    surfaces: [Surface; 2],
+    current: usize,
+    cursor_hidden: bool,
+}
+
+impl<B: Backend> Terminal<B> {
+    /// Creates a terminal covering the whole screen of `backend`.
+    pub fn new(backend: B) -> io::Result<Self> {
+        let area = backend.size()?;
+
+        Ok(Self {
+            backend,
+            surfaces: [Surface::empty(area), Surface::empty(area)],
+            current: 0,
+            cursor_hidden: false,
+        })
+    }
+
+    /// Returns the backend.
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
+
+    /// Returns the backend mutably.
+    pub fn backend_mut(&mut self) -> &mut B {
+        &mut self.backend
+    }
+
+    /// Returns the area covered by the terminal.
+    pub fn size(&self) -> Rect {
+        self.surfaces[self.current].area()
+    }
+
+    /// Returns the surface of the frame being drawn.
+    pub fn current_surface_mut(&mut self) -> &mut Surface {
+        &mut self.surfaces[self.current]
+    }
+
+    /// Resizes the terminal and forces a full redraw on the next frame.
+    pub fn resize(&mut self, area: Rect) -> io::Result<()> {
+        self.surfaces[self.current].resize(area);
+        self.surfaces[1 - self.current].resize(area);
+        self.clear()
+    }
+
+    /// Resizes the terminal if the backend reports a new size.
+    pub fn autoresize(&mut self) -> io::Result<()> {
+        let area = self.backend.size()?;
+        if area != self.size() {
+            self.resize(area)?;
+        }
+        Ok(())
+    }
+
+    /// Clears the screen and forces a full redraw on the next frame.
+    pub fn clear(&mut self) -> io::Result<()> {
+        self.backend.clear()?;
+        self.surfaces[1 - self.current].reset();
+        Ok(())
+    }
+
+    /// Draws a frame.
+    ///
+    /// `render` fills the frame's surface and returns where the cursor should be placed, or
+    /// `None` to hide it.
+    pub fn draw<F>(&mut self, render: F) -> io::Result<()>
+    where
+        F: FnOnce(&mut Surface) -> Option<(u16, u16)>,
+    {
+        let cursor = render(&mut self.surfaces[self.current]);
+        self.flush()?;
+
+        match cursor {
+            Some((x, y)) => {
+                self.backend.set_cursor(x, y)?;
+                if self.cursor_hidden {
+                    self.backend.show_cursor()?;
+                    self.cursor_hidden = false;
+                }
+            }
+            None if !self.cursor_hidden => {
+                self.backend.hide_cursor()?;
+                self.cursor_hidden = true;
+            }
+            None => {}
+        }
+
+        self.swap_surfaces();
+        self.backend.flush()
+    }
+
+    // Writes the difference between the previous and the current surface to the backend.
+    fn flush(&mut self) -> io::Result<()> {
+        let previous = &self.surfaces[1 - self.current];
+        let current = &self.surfaces[self.current];
+        let updates = previous.diff(current);
+
+        self.backend.draw(updates.into_iter())
+    }
+
+    fn swap_surfaces(&mut self) {
+        self.surfaces[1 - self.current].reset();
+        self.current = 1 - self.current;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Color, Style, TestBackend};
+
+    #[test]
+    fn draws_frames_into_test_backend() {
+        let mut terminal = Terminal::new(TestBackend::new(8, 2)).unwrap();
+
+        terminal
+            .draw(|surface| {
+                surface.set_string(0, 0, "tissue", Style::default().fg(Color::Green));
+                surface.set_string(0, 1, "~", Style::default());
+                Some((2, 1))
+            })
+            .unwrap();
+
+        let backend = terminal.backend();
+        backend.assert_lines(&["tissue  ", "~       "]);
+        assert_eq!(backend.cursor(), (2, 1));
+        assert_eq!(backend.surface().get(0, 0).unwrap().fg, Color::Green);
+    }
+
+    #[test]
+    fn redraws_only_changed_cells() {
+        let mut terminal = Terminal::new(TestBackend::new(5, 1)).unwrap();
+        let render = |text: &'static str| {
+            move |surface: &mut Surface| {
+                surface.set_string(0, 0, text, Style::default());
+                None
+            }
+        };
+
+        terminal.draw(render("hello")).unwrap();
+        assert_eq!(terminal.backend().cells_drawn(), 5);
+
+        terminal.draw(render("help!")).unwrap();
+        assert_eq!(terminal.backend().cells_drawn(), 2);
+        terminal.backend().assert_lines(&["help!"]);
+        assert!(!terminal.backend().is_cursor_visible());
+
+        terminal.draw(render("help!")).unwrap();
+        assert_eq!(terminal.backend().cells_drawn(), 0);
+    }
+
+    #[test]
+    fn resize_forces_full_redraw() {
+        let mut terminal = Terminal::new(TestBackend::new(3, 1)).unwrap();
+        terminal
+            .draw(|surface| {
+                surface.set_string(0, 0, "abc", Style::default());
+                None
+            })
+            .unwrap();
+
+        terminal.backend_mut().resize(4, 2);
+        terminal.autoresize().unwrap();
+        terminal
+            .draw(|surface| {
+                surface.set_string(0, 1, "abcd", Style::default());
+                None
+            })
+            .unwrap();
+
+        terminal.backend().assert_lines(&["    ", "abcd"]);
+    }
+}